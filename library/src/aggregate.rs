@@ -0,0 +1,169 @@
+//! Rolling the daily availability up into weeks or months.
+
+use crate::AvailabilityData;
+use chrono::{Datelike, Duration, NaiveDate};
+use std::collections::HashMap;
+
+/// A period the days are rolled up into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Period {
+    /// A week starting on Monday.
+    Week,
+    /// A calendar month.
+    Month,
+}
+
+impl Period {
+    /// Returns the first day of the period a date belongs to.
+    pub fn start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Week => date - Duration::days(date.weekday().num_days_from_monday().into()),
+            Period::Month => date.with_day(1).expect("Every month has the first day"),
+        }
+    }
+
+    /// Returns the first day of the period before the one starting on `start`.
+    pub fn previous(self, start: NaiveDate) -> NaiveDate {
+        self.start(start - Duration::days(1))
+    }
+}
+
+/// Availability of a package over a period.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[non_exhaustive]
+pub struct PeriodAvailability {
+    /// The first day of the period.
+    pub start: NaiveDate,
+    /// Days of the period the package was available on.
+    pub available_days: usize,
+    /// Days of the period there is a manifest for.
+    pub known_days: usize,
+    /// Percentage of the known days the package was available on, rounded to one decimal place,
+    /// or `None` if none of the days is known.
+    pub percentage: Option<f64>,
+}
+
+impl AvailabilityData {
+    /// Rolls the availability of a package on a target up into the last `periods` periods up to
+    /// the latest of the `dates`, the latest period first.
+    ///
+    /// Only the given `dates` are taken into account, the days without a manifest or without the
+    /// target in it are skipped.
+    pub fn aggregate(
+        &self,
+        target: &str,
+        pkg: &str,
+        dates: &[NaiveDate],
+        period: Period,
+        periods: usize,
+    ) -> Option<Vec<PeriodAvailability>> {
+        let row = self.get_availability_row(target, pkg, dates)?;
+        let mut days: HashMap<NaiveDate, (usize, usize)> = HashMap::new();
+        for (&date, &available) in dates.iter().zip(&row.availability_list) {
+            if self.is_manifest_missing(date) || !self.is_target_listed(target, date) {
+                continue;
+            }
+            let (available_days, known_days) = days.entry(period.start(date)).or_default();
+            *available_days += usize::from(available);
+            *known_days += 1;
+        }
+        let mut start = match dates.iter().max() {
+            Some(&latest) => period.start(latest),
+            None => return Some(Vec::new()),
+        };
+        let mut result = Vec::with_capacity(periods);
+        for _ in 0..periods {
+            let (available_days, known_days) = days.get(&start).copied().unwrap_or_default();
+            let percentage = if known_days == 0 {
+                None
+            } else {
+                Some((available_days as f64 * 1000. / known_days as f64).round() / 10.)
+            };
+            result.push(PeriodAvailability {
+                start,
+                available_days,
+                known_days,
+                percentage,
+            });
+            start = period.previous(start);
+        }
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::Manifest;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn manifest(date: NaiveDate, available: bool) -> Manifest {
+        let data = format!(
+            r#"date = "{}"
+[pkg.cargo.target.x86_64-unknown-linux-gnu]
+available = {}
+"#,
+            date, available
+        );
+        toml::from_str(&data).unwrap()
+    }
+
+    #[test]
+    fn period_starts() {
+        // A Wednesday.
+        let wednesday = date("2024-05-01");
+        assert_eq!(date("2024-04-29"), Period::Week.start(wednesday));
+        assert_eq!(date("2024-05-01"), Period::Month.start(wednesday));
+        assert_eq!(
+            date("2024-04-22"),
+            Period::Week.previous(date("2024-04-29"))
+        );
+        assert_eq!(
+            date("2024-04-01"),
+            Period::Month.previous(date("2024-05-01"))
+        );
+    }
+
+    #[test]
+    fn aggregate_by_month() {
+        let mut data = AvailabilityData::default();
+        let dates = [
+            date("2024-05-02"),
+            date("2024-05-01"),
+            date("2024-04-30"),
+            date("2024-04-29"),
+            date("2024-04-28"),
+        ];
+        for (idx, &day) in dates.iter().enumerate() {
+            data.add_manifest(manifest(day, idx != 3));
+        }
+        let months = data
+            .aggregate(
+                "x86_64-unknown-linux-gnu",
+                "cargo",
+                &dates,
+                Period::Month,
+                3,
+            )
+            .unwrap();
+        assert_eq!(3, months.len());
+        assert_eq!(date("2024-05-01"), months[0].start);
+        assert_eq!(
+            (2, 2, Some(100.)),
+            (
+                months[0].available_days,
+                months[0].known_days,
+                months[0].percentage
+            )
+        );
+        assert_eq!(date("2024-04-01"), months[1].start);
+        assert_eq!(Some(66.7), months[1].percentage);
+        assert_eq!(None, months[2].percentage);
+        assert!(data
+            .aggregate("x86_64-unknown-linux-gnu", "rustc", &dates, Period::Week, 1)
+            .is_none());
+    }
+}
@@ -1,51 +1,227 @@
 //! Availability evaluation tools.
 
-use crate::manifest::Manifest;
+use crate::manifest::{Artifact, Manifest};
 use chrono::NaiveDate;
 use std::{
     borrow::Borrow,
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
 };
 
 type PackageName = String;
 type TargetTriple = String;
 type DatesSet = HashSet<NaiveDate>;
 type PackagesAvailability = HashMap<PackageName, DatesSet>;
+type PackageVersions = HashMap<NaiveDate, String>;
+type PackageArtifacts = HashMap<PackageName, HashMap<NaiveDate, Artifact>>;
+type ProfileName = String;
+type ProfilesAvailability = HashMap<ProfileName, HashMap<NaiveDate, bool>>;
 
 /// Data about packages availability in rust builds.
 #[derive(Debug, Default)]
 pub struct AvailabilityData {
     data: HashMap<TargetTriple, PackagesAvailability>,
+    versions: HashMap<PackageName, PackageVersions>,
+    artifacts: HashMap<TargetTriple, PackageArtifacts>,
+    /// Package renames found in the manifests, from an old name to a new one.
+    renames: HashMap<PackageName, PackageName>,
+    /// Packages of the installation profiles in any of the manifests.
+    profiles: BTreeMap<ProfileName, BTreeSet<PackageName>>,
+    profiles_availability: HashMap<TargetTriple, ProfilesAvailability>,
+    /// Commits `rustc` was built from.
+    rustc_commits: HashMap<NaiveDate, String>,
+    /// Days without a manifest, as opposed to days when packages were missing from one.
+    missing_manifests: BTreeSet<NaiveDate>,
+    /// Days on which the manifests listed a target, whether its packages were available or not.
+    listed_targets: HashMap<TargetTriple, DatesSet>,
 }
 
 /// A single row in an availability table.
 #[derive(Debug, serde::Serialize)]
+#[non_exhaustive]
 pub struct AvailabilityRow<'a> {
     /// Name of the package.
     pub package_name: &'a str,
     /// List of "availabilities".
     pub availability_list: Vec<bool>,
+    /// Versions of the package on the same dates as the `availability_list`. A version is only
+    /// provided if the package is available.
+    pub version_list: Vec<Option<String>>,
+    /// Download locations of the package on the same dates as the `availability_list`, if the
+    /// package is available and the manifest specifies them.
+    pub artifact_list: Vec<Option<Artifact>>,
+    /// Names the package has been renamed to by the manifests, the most recent one last. The
+    /// package goes by its original name regardless.
+    pub renamed_to: Vec<String>,
+    /// Installation profiles the package is a part of, the smallest one first.
+    pub profiles: Vec<String>,
     /// Date when the component has been available for the last time.
     pub last_available: Option<NaiveDate>,
-    /// A hidden field to improve compatibility.
-    _hidden: (),
+    /// Statistics over the `availability_list`.
+    pub stats: AvailabilityStats,
+    /// The `availability_list` as runs of days with the same availability, for drawing
+    /// sparklines.
+    pub runs: Vec<Run>,
+    /// Whether the package has appeared or disappeared on the most recent date, compared to the
+    /// previous date with a manifest.
+    pub changed_recently: bool,
+}
+
+/// Consecutive days on which a package was equally available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[non_exhaustive]
+pub struct Run {
+    /// Whether the package was available, or `None` if there were no manifests on these days or
+    /// they didn't list the target.
+    pub available: Option<bool>,
+    /// Number of days in the run.
+    pub days: usize,
+}
+
+impl Run {
+    /// Encodes a list of "availabilities" as runs, keeping the order of the list.
+    pub fn encode(availability_list: impl IntoIterator<Item = Option<bool>>) -> Vec<Run> {
+        let mut runs: Vec<Run> = Vec::new();
+        for available in availability_list {
+            match runs.last_mut() {
+                Some(run) if run.available == available => run.days += 1,
+                _ => runs.push(Run { available, days: 1 }),
+            }
+        }
+        runs
+    }
+}
+
+/// Whether a target has been listed by the manifests throughout a range of dates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum TargetLifecycle {
+    /// The target has been listed since before the range, and still is.
+    Established,
+    /// The target has been added within the range, and listed since `since`.
+    New {
+        /// The first date of the range the target has been listed on since.
+        since: NaiveDate,
+    },
+    /// The target isn't listed on the most recent date anymore.
+    Retired {
+        /// The last date the target was listed on.
+        until: NaiveDate,
+    },
+}
+
+/// Availability of an installation profile on a target.
+#[derive(Debug, serde::Serialize)]
+#[non_exhaustive]
+pub struct ProfileRow<'a> {
+    /// Name of the profile.
+    pub name: &'a str,
+    /// Whether all the packages of the profile that the target has were available, if the
+    /// manifest of the date defines the profile.
+    pub availability_list: Vec<Option<bool>>,
+}
+
+/// Statistics over a list of "availabilities", which is expected to start with the most recent
+/// date.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[non_exhaustive]
+pub struct AvailabilityStats {
+    /// For how many days in a row the package has been available up to the most recent date.
+    pub current_streak: usize,
+    /// The longest number of days in a row the package has been missing.
+    pub longest_outage: usize,
+    /// Percentage of the days the package has been available, rounded to one decimal place.
+    pub percentage: f64,
+}
+
+/// A package that has appeared on or disappeared from a target, compared to the previous date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+#[non_exhaustive]
+pub struct Change<'a> {
+    /// The date of the change.
+    pub date: NaiveDate,
+    /// The target triple.
+    pub target: &'a str,
+    /// Name of the package.
+    pub package: &'a str,
+    /// Whether the package has appeared (`true`) or disappeared (`false`).
+    pub available: bool,
+}
+
+impl AvailabilityStats {
+    /// Calculates statistics over a list of "availabilities".
+    pub fn new(availability_list: &[bool]) -> Self {
+        let current_streak = availability_list
+            .iter()
+            .take_while(|&&available| available)
+            .count();
+        let longest_outage = availability_list
+            .split(|&available| available)
+            .map(<[bool]>::len)
+            .max()
+            .unwrap_or(0);
+        let percentage = if availability_list.is_empty() {
+            0.
+        } else {
+            let available = availability_list.iter().filter(|&&a| a).count();
+            (available as f64 * 1000. / availability_list.len() as f64).round() / 10.
+        };
+        AvailabilityStats {
+            current_streak,
+            longest_outage,
+            percentage,
+        }
+    }
 }
 
 impl AvailabilityData {
     /// Adds an availability data from a given [`Manifest`].
     pub fn add_manifest(&mut self, manifest: Manifest) {
+        self.add_manifest_filtered(manifest, |_, _| true)
+    }
+
+    /// Adds an availability data from a given [`Manifest`], skipping the packages that `keep`
+    /// rejects. It is called with a target and a package name, after the package renames are
+    /// applied.
+    pub fn add_manifest_filtered(&mut self, manifest: Manifest, keep: impl Fn(&str, &str) -> bool) {
         let reverse_renames: HashMap<_, _> = manifest
             .renames
             .iter()
-            .map(|(key, value)| (&value.to, key))
+            .map(|(key, value)| (value.to.as_str(), key.as_str()))
             .collect();
+        self.add_profiles(&manifest, &reverse_renames);
+        if let Some(commit) = manifest
+            .packages
+            .get("rustc")
+            .and_then(|info| info.git_commit_hash.clone())
+        {
+            self.rustc_commits.insert(manifest.date, commit);
+        }
         for (package_name, info) in manifest.packages {
-            let package_name = reverse_renames
-                .get(&package_name)
-                .map(|name| String::clone(name))
-                .unwrap_or(package_name);
+            let package_name = original_name(&reverse_renames, &package_name).to_string();
+            if let Some(version) = info.version {
+                self.versions
+                    .entry(package_name.clone())
+                    .or_default()
+                    .insert(manifest.date, version);
+            }
             for (target_triple, target_info) in info.targets {
+                if !keep(&target_triple, &package_name) {
+                    continue;
+                }
+                self.listed_targets
+                    .entry(target_triple.clone())
+                    .or_default()
+                    .insert(manifest.date);
                 if target_info.available {
+                    if !target_info.artifact.is_empty() {
+                        self.artifacts
+                            .entry(target_triple.clone())
+                            .or_default()
+                            .entry(package_name.clone())
+                            .or_default()
+                            .insert(manifest.date, target_info.artifact);
+                    }
                     self.data
                         .entry(target_triple.clone())
                         .or_default()
@@ -55,6 +231,106 @@ impl AvailabilityData {
                 }
             }
         }
+        self.renames.extend(
+            manifest
+                .renames
+                .into_iter()
+                .map(|(old, rename)| (old, rename.to)),
+        );
+    }
+
+    /// Records the packages of the manifest's profiles and whether the profiles were available on
+    /// every target of the manifest.
+    ///
+    /// Like rustup, a profile is considered available if all of its packages that a target has
+    /// are available, so the packages a target doesn't have at all are skipped.
+    fn add_profiles(&mut self, manifest: &Manifest, reverse_renames: &HashMap<&str, &str>) {
+        let targets: HashSet<&str> = manifest
+            .packages
+            .values()
+            .flat_map(|info| info.targets.keys())
+            .map(String::as_str)
+            .filter(|&target| target != "*")
+            .collect();
+        for (profile, packages) in &manifest.profiles {
+            self.profiles.entry(profile.clone()).or_default().extend(
+                packages
+                    .iter()
+                    .map(|package| original_name(reverse_renames, package).to_string()),
+            );
+            for &target in &targets {
+                let available = packages
+                    .iter()
+                    .filter_map(|package| {
+                        let targets = &manifest.packages.get(package)?.targets;
+                        targets.get(target).or_else(|| targets.get("*"))
+                    })
+                    .all(|info| info.available);
+                self.profiles_availability
+                    .entry(target.to_string())
+                    .or_default()
+                    .entry(profile.clone())
+                    .or_default()
+                    .insert(manifest.date, available);
+            }
+        }
+    }
+
+    /// Records that there's no manifest for a given date, e.g. because it hasn't been published or
+    /// couldn't be fetched. Such days are skipped by the statistics and the changes.
+    pub fn add_missing_manifest(&mut self, date: NaiveDate) {
+        self.missing_manifests.insert(date);
+    }
+
+    /// Checks whether there's no manifest for a given date, see
+    /// [`add_missing_manifest`](Self::add_missing_manifest).
+    pub fn is_manifest_missing(&self, date: NaiveDate) -> bool {
+        self.missing_manifests.contains(&date)
+    }
+
+    /// Checks whether the manifest of a given date lists a given target. Days the target isn't
+    /// listed on, e.g. before it has been added, say nothing about its packages, like the days
+    /// without a manifest.
+    pub fn is_target_listed(&self, target: &str, date: NaiveDate) -> bool {
+        self.listed_targets
+            .get(target)
+            .is_some_and(|dates| dates.contains(&date))
+    }
+
+    /// Tells whether a target has been added or removed within the given `dates`, judging by the
+    /// most recent and the oldest of them that have a manifest.
+    pub fn target_lifecycle(&self, target: &str, dates: &[NaiveDate]) -> TargetLifecycle {
+        let mut known: Vec<_> = dates
+            .iter()
+            .copied()
+            .filter(|&date| !self.is_manifest_missing(date))
+            .collect();
+        known.sort_unstable_by(|a, b| b.cmp(a));
+        let latest = match known.first() {
+            Some(&latest) => latest,
+            None => return TargetLifecycle::Established,
+        };
+        if !self.is_target_listed(target, latest) {
+            let until = self
+                .listed_targets
+                .get(target)
+                .and_then(|listed| listed.iter().copied().filter(|&date| date < latest).max());
+            return match until {
+                Some(until) => TargetLifecycle::Retired { until },
+                None => TargetLifecycle::Established,
+            };
+        }
+        let listed = known
+            .iter()
+            .take_while(|&&date| self.is_target_listed(target, date))
+            .count();
+        if listed == known.len() {
+            TargetLifecycle::Established
+        } else {
+            TargetLifecycle::New {
+                since: known[listed - 1],
+            }
+        }
     }
 
     /// Adds multiple [`Manifest`]s at once.
@@ -74,11 +350,30 @@ impl AvailabilityData {
             .collect()
     }
 
+    /// Returns the packages that are only published for the '*' target, e.g. `rust-src`, since
+    /// they are the same on every target.
+    pub fn get_target_independent_packages(&self) -> HashSet<&'_ str> {
+        let on_targets: HashSet<&str> = self
+            .data
+            .iter()
+            .filter(|(target, _)| target.as_str() != "*")
+            .flat_map(|(_, packages)| packages.keys())
+            .map(AsRef::as_ref)
+            .collect();
+        self.data
+            .get("*")
+            .into_iter()
+            .flat_map(|packages| packages.keys())
+            .map(AsRef::as_ref)
+            .filter(|package| !on_targets.contains(package))
+            .collect()
+    }
+
     /// Returns all available packages throughout all the targets and all the times.
-    pub fn get_available_packages<'a>(&'a self) -> HashSet<&'a str> {
+    pub fn get_available_packages(&self) -> HashSet<&'_ str> {
         self.data
-            .iter()
-            .flat_map(|(_, per_target)| per_target.keys())
+            .values()
+            .flat_map(|per_target| per_target.keys())
             .map(AsRef::as_ref)
             .collect()
     }
@@ -97,19 +392,55 @@ impl AvailabilityData {
         I: IntoIterator,
         I::Item: Borrow<NaiveDate>,
     {
-        if self.data.get(target).and_then(|t| t.get(pkg)).is_none() {
-            return None;
-        }
+        self.data.get(target).and_then(|t| t.get(pkg))?;
         let available_dates = self.available_dates(target, pkg);
-        let availability_list = dates
-            .into_iter()
-            .map(|date| available_dates.contains(date.borrow()))
-            .collect();
+        let mut availability_list = Vec::new();
+        let mut version_list = Vec::new();
+        let mut artifact_list = Vec::new();
+        // Days without a manifest or without the target in it say nothing about the package.
+        let mut known_list = Vec::new();
+        let mut marked_list = Vec::new();
+        for date in dates {
+            let date = *date.borrow();
+            let available = available_dates.contains(&date);
+            availability_list.push(available);
+            if self.is_manifest_missing(date) || !self.is_target_listed(target, date) {
+                marked_list.push(None);
+            } else {
+                known_list.push(available);
+                marked_list.push(Some(available));
+            }
+            if available {
+                version_list.push(self.version(pkg, date).map(String::from));
+                artifact_list.push(self.artifact(target, pkg, date).cloned());
+            } else {
+                version_list.push(None);
+                artifact_list.push(None);
+            }
+        }
+        let changed_recently = match marked_list.first() {
+            Some(Some(_)) => known_list.get(..2).is_some_and(|pair| pair[0] != pair[1]),
+            _ => false,
+        };
         Some(AvailabilityRow {
             package_name: pkg,
+            stats: AvailabilityStats::new(&known_list),
+            runs: Run::encode(marked_list),
+            changed_recently,
             availability_list,
+            version_list,
+            artifact_list,
+            renamed_to: self
+                .rename_chain(pkg)
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            profiles: self
+                .package_profiles(pkg)
+                .into_iter()
+                .map(String::from)
+                .collect(),
             last_available: available_dates.into_iter().max(),
-            _hidden: (),
         })
     }
 
@@ -125,10 +456,163 @@ impl AvailabilityData {
         }
     }
 
+    /// Returns the version of a given package on a given date, if the manifest specifies it.
+    pub fn version(&self, pkg: &str, date: NaiveDate) -> Option<&str> {
+        self.versions
+            .get(pkg)
+            .and_then(|versions| versions.get(&date))
+            .map(String::as_str)
+    }
+
+    /// Returns the names a given package has been renamed to by the manifests, following the
+    /// renames of the renamed package as well. The most recent name goes last.
+    pub fn rename_chain(&self, pkg: &str) -> Vec<&str> {
+        let mut chain: Vec<&str> = Vec::new();
+        let mut current = pkg;
+        while let Some(next) = self.renames.get(current) {
+            if next == pkg || chain.contains(&next.as_str()) {
+                break;
+            }
+            chain.push(next);
+            current = next;
+        }
+        chain
+    }
+
+    /// Returns the names of the installation profiles found in the manifests, the smallest one
+    /// first.
+    pub fn get_profiles(&self) -> Vec<&str> {
+        let mut profiles: Vec<_> = self.profiles.iter().collect();
+        profiles.sort_by_key(|(_, packages)| packages.len());
+        profiles
+            .into_iter()
+            .map(|(profile, _)| profile.as_str())
+            .collect()
+    }
+
+    /// Returns the installation profiles a given package is a part of, the smallest one first.
+    pub fn package_profiles(&self, pkg: &str) -> Vec<&str> {
+        let mut profiles = self.get_profiles();
+        profiles.retain(|profile| self.profiles[*profile].contains(pkg));
+        profiles
+    }
+
+    /// Makes a row of whether a given installation profile was available on a given target on the
+    /// given dates.
+    pub fn get_profile_row<'a, I>(&self, target: &str, profile: &'a str, dates: I) -> ProfileRow<'a>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<NaiveDate>,
+    {
+        let availability = self
+            .profiles_availability
+            .get(target)
+            .and_then(|profiles| profiles.get(profile));
+        ProfileRow {
+            name: profile,
+            availability_list: dates
+                .into_iter()
+                .map(|date| availability.and_then(|dates| dates.get(date.borrow()).copied()))
+                .collect(),
+        }
+    }
+
+    /// Returns the download locations of a given package on a given target and date, if the
+    /// manifest specifies them. The `*` target is checked as well.
+    pub fn artifact(&self, target: &str, pkg: &str, date: NaiveDate) -> Option<&Artifact> {
+        [target, "*"].iter().find_map(|target| {
+            self.artifacts
+                .get(*target)
+                .and_then(|packages| packages.get(pkg))
+                .and_then(|artifacts| artifacts.get(&date))
+        })
+    }
+
+    /// Finds the packages that appeared on or disappeared from a target on the given `dates`, which
+    /// must be sorted in descending order. The oldest date is only compared against, and the days
+    /// without a manifest or without the target in it are skipped.
+    ///
+    /// Changes are ordered by package and then by date, the most recent first.
+    pub fn target_changes<'a>(&'a self, target: &'a str, dates: &[NaiveDate]) -> Vec<Change<'a>> {
+        let dates: Vec<_> = dates
+            .iter()
+            .copied()
+            .filter(|&date| !self.is_manifest_missing(date) && self.is_target_listed(target, date))
+            .collect();
+        let mut packages: Vec<_> = self.get_available_packages().into_iter().collect();
+        packages.sort_unstable();
+        let mut changes = Vec::new();
+        for package in packages {
+            let row = match self.get_availability_row(target, package, &dates) {
+                Some(row) => row,
+                None => continue,
+            };
+            // Pairs of (newer, older) days.
+            let days = dates.iter().zip(row.availability_list.iter());
+            let previous_days = row.availability_list.iter().skip(1);
+            for ((&date, &now), &before) in days.zip(previous_days) {
+                if now != before {
+                    changes.push(Change {
+                        date,
+                        target,
+                        package,
+                        available: now,
+                    });
+                }
+            }
+        }
+        changes
+    }
+
+    /// Finds the changes on all the targets, see [`target_changes`](Self::target_changes).
+    ///
+    /// Changes are ordered by date, the most recent first, and then by target and package.
+    pub fn changes(&self, dates: &[NaiveDate]) -> Vec<Change<'_>> {
+        let mut changes: Vec<_> = self
+            .get_available_targets()
+            .into_iter()
+            .flat_map(|target| self.target_changes(target, dates))
+            .collect();
+        changes.sort_unstable_by(|a, b| {
+            (b.date, a.target, a.package).cmp(&(a.date, b.target, b.package))
+        });
+        changes
+    }
+
+    /// Returns the commit of the `rust-lang/rust` repository `rustc` was built from on a given
+    /// date, if the manifest specifies it.
+    pub fn rustc_commit(&self, date: NaiveDate) -> Option<&str> {
+        self.rustc_commits.get(&date).map(String::as_str)
+    }
+
     /// Finds when a given package was last available on a given target.
     pub fn last_available(&self, target: &str, pkg: &str) -> Option<NaiveDate> {
         self.available_dates(target, pkg).into_iter().max()
     }
+
+    /// Finds when all the given packages were last available on a given target at the same time.
+    /// Without any packages, there's no such date.
+    pub fn last_available_together(&self, target: &str, pkgs: &[&str]) -> Option<NaiveDate> {
+        let (first, rest) = pkgs.split_first()?;
+        let mut dates = self.available_dates(target, first);
+        for pkg in rest {
+            let available = self.available_dates(target, pkg);
+            dates.retain(|date| available.contains(date));
+        }
+        dates.into_iter().max()
+    }
+}
+
+/// Follows the renames back to the original name of a package.
+fn original_name<'a>(reverse_renames: &HashMap<&str, &'a str>, mut name: &'a str) -> &'a str {
+    let new_name = name;
+    while let Some(&old) = reverse_renames.get(name) {
+        if old == new_name {
+            break;
+        }
+        name = old;
+    }
+    name
 }
 
 #[cfg(test)]
@@ -156,26 +640,170 @@ available = true
         // The *wildcard* target is ignored here.
         assert_eq!(1, all_targets.len());
         assert!(all_targets.contains("lol"));
+        let target_independent = availability.get_target_independent_packages();
+        assert_eq!(
+            target_independent.into_iter().collect::<Vec<_>>(),
+            ["rust-src"]
+        );
 
         let package_exists = availability
-            .get_availability_row("*", "rust-src", vec![NaiveDate::from_ymd(2018, 9, 3)])
+            .get_availability_row(
+                "*",
+                "rust-src",
+                vec![NaiveDate::from_ymd_opt(2018, 9, 3).unwrap()],
+            )
             .unwrap();
         assert_eq!("rust-src", package_exists.package_name);
         assert_eq!(vec!(true), package_exists.availability_list);
         let package_exists = availability.get_availability_row(
             "lol",
             "rust-src",
-            vec![NaiveDate::from_ymd(2018, 9, 3)],
+            vec![NaiveDate::from_ymd_opt(2018, 9, 3).unwrap()],
         );
         // rust-src is not present in lol target
         assert!(package_exists.is_none());
         let package_exists = availability
-            .get_availability_row("lol", "ahaha", vec![NaiveDate::from_ymd(2018, 9, 3)])
+            .get_availability_row(
+                "lol",
+                "ahaha",
+                vec![NaiveDate::from_ymd_opt(2018, 9, 3).unwrap()],
+            )
             .unwrap();
         assert_eq!("ahaha", package_exists.package_name);
         assert_eq!(vec!(true), package_exists.availability_list);
     }
 
+    #[test]
+    fn versions() {
+        let data = r#"date = "2018-09-03"
+[pkg.ahaha]
+version = "0.1.0"
+[pkg.ahaha.target.lol]
+available = true
+[pkg.ahaha.target.kek]
+available = false
+"#;
+        let mut availability: AvailabilityData = Default::default();
+        availability.add_manifest(toml::from_str(data).unwrap());
+        let date = NaiveDate::from_ymd_opt(2018, 9, 3).unwrap();
+        let dates = [date, date.succ_opt().unwrap()];
+        assert_eq!(Some("0.1.0"), availability.version("ahaha", date));
+        let row = availability
+            .get_availability_row("lol", "ahaha", dates)
+            .unwrap();
+        assert_eq!(vec![Some("0.1.0".to_string()), None], row.version_list);
+    }
+
+    #[test]
+    fn rustc_commits() {
+        let data = r#"date = "2018-09-03"
+[pkg.rustc]
+git_commit_hash = "b36fe3a8e8fa9e8e8df8d0c4ba3a3c9d4e3c1c4a"
+[pkg.rustc.target.lol]
+available = true
+[pkg.cargo]
+git_commit_hash = "0000000000000000000000000000000000000000"
+[pkg.cargo.target.lol]
+available = true
+"#;
+        let mut availability: AvailabilityData = Default::default();
+        availability.add_manifest(toml::from_str(data).unwrap());
+        let date = NaiveDate::from_ymd_opt(2018, 9, 3).unwrap();
+        assert_eq!(
+            Some("b36fe3a8e8fa9e8e8df8d0c4ba3a3c9d4e3c1c4a"),
+            availability.rustc_commit(date)
+        );
+        assert_eq!(None, availability.rustc_commit(date.succ_opt().unwrap()));
+    }
+
+    #[test]
+    fn changes() {
+        let manifests = [
+            ("2018-09-01", "available = true", "available = true"),
+            ("2018-09-02", "available = false", "available = true"),
+            ("2018-09-03", "available = true", "available = false"),
+        ];
+        let mut availability: AvailabilityData = Default::default();
+        for (date, ahaha, ohoho) in &manifests {
+            let data = format!(
+                "date = \"{}\"\n[pkg.ahaha.target.lol]\n{}\n[pkg.ohoho.target.lol]\n{}\n",
+                date, ahaha, ohoho
+            );
+            availability.add_manifest(toml::from_str(&data).unwrap());
+        }
+        let day = |day| NaiveDate::from_ymd_opt(2018, 9, day).unwrap();
+        let change = |date, package, available| Change {
+            date,
+            target: "lol",
+            package,
+            available,
+        };
+        assert_eq!(
+            vec![
+                change(day(3), "ahaha", true),
+                change(day(3), "ohoho", false),
+                change(day(2), "ahaha", false),
+            ],
+            availability.changes(&[day(3), day(2), day(1)])
+        );
+        // The oldest date is only compared against.
+        assert_eq!(
+            vec![
+                change(day(3), "ahaha", true),
+                change(day(3), "ohoho", false)
+            ],
+            availability.changes(&[day(3), day(2)])
+        );
+    }
+
+    #[test]
+    fn last_available_together() {
+        let manifests = [
+            ("2018-09-01", "true", "true"),
+            ("2018-09-02", "true", "false"),
+            ("2018-09-03", "false", "true"),
+        ];
+        let mut availability: AvailabilityData = Default::default();
+        for (date, ahaha, ohoho) in &manifests {
+            let data = format!(
+                "date = \"{}\"\n[pkg.ahaha.target.lol]\navailable = {}\n[pkg.ohoho.target.lol]\navailable = {}\n",
+                date, ahaha, ohoho
+            );
+            availability.add_manifest(toml::from_str(&data).unwrap());
+        }
+        let day = |day| NaiveDate::from_ymd_opt(2018, 9, day);
+        assert_eq!(
+            day(2),
+            availability.last_available_together("lol", &["ahaha"])
+        );
+        assert_eq!(
+            day(1),
+            availability.last_available_together("lol", &["ahaha", "ohoho"])
+        );
+        assert_eq!(
+            None,
+            availability.last_available_together("lol", &["ahaha", "kek"])
+        );
+        assert_eq!(None, availability.last_available_together("lol", &[]));
+    }
+
+    #[test]
+    fn stats() {
+        let stats = AvailabilityStats::new(&[true, true, false, true, false, false, false]);
+        assert_eq!(2, stats.current_streak);
+        assert_eq!(3, stats.longest_outage);
+        assert_eq!(42.9, stats.percentage);
+
+        let stats = AvailabilityStats::new(&[false, true]);
+        assert_eq!(0, stats.current_streak);
+        assert_eq!(1, stats.longest_outage);
+        assert_eq!(50., stats.percentage);
+
+        let stats = AvailabilityStats::new(&[]);
+        assert_eq!(0, stats.longest_outage);
+        assert_eq!(0., stats.percentage);
+    }
+
     #[test]
     fn check_rename() {
         let data = r#"date = "2018-09-03"
@@ -191,4 +819,258 @@ to = "ahaha"
         assert_eq!(1, all_packages.len());
         assert!(all_packages.contains("kek"));
     }
+
+    #[test]
+    fn profiles() {
+        let data = r#"date = "2018-09-03"
+[pkg.rustc.target.lol]
+available = true
+[pkg.rustc.target.kek]
+available = true
+[pkg.docs.target.lol]
+available = false
+[pkg.src.target."*"]
+available = true
+[profiles]
+minimal = ["rustc"]
+default = ["rustc", "docs", "src"]
+"#;
+        let mut availability: AvailabilityData = Default::default();
+        availability.add_manifest(toml::from_str(data).unwrap());
+        let date = NaiveDate::from_ymd_opt(2018, 9, 3).unwrap();
+        let other = NaiveDate::from_ymd_opt(2018, 9, 2).unwrap();
+        assert_eq!(availability.get_profiles(), ["minimal", "default"]);
+        assert_eq!(
+            availability.package_profiles("rustc"),
+            ["minimal", "default"]
+        );
+        assert_eq!(availability.package_profiles("src"), ["default"]);
+        let row = availability.get_profile_row("lol", "default", [date, other]);
+        assert_eq!(row.availability_list, [Some(false), None]);
+        // The `docs` are skipped, since the target doesn't have them.
+        let row = availability.get_profile_row("kek", "default", [date]);
+        assert_eq!(row.availability_list, [Some(true)]);
+        let row = availability
+            .get_availability_row("lol", "rustc", [date])
+            .unwrap();
+        assert_eq!(row.profiles, ["minimal", "default"]);
+    }
+
+    #[test]
+    fn runs() {
+        let mut availability: AvailabilityData = Default::default();
+        for (date, available) in [
+            ("2018-09-04", true),
+            ("2018-09-02", false),
+            ("2018-09-01", false),
+        ] {
+            let data = format!(
+                "date = \"{}\"\n[pkg.ahaha.target.lol]\navailable = {}\n",
+                date, available
+            );
+            availability.add_manifest(toml::from_str(&data).unwrap());
+        }
+        let dates: Vec<_> = (1..=4)
+            .rev()
+            .map(|day| NaiveDate::from_ymd_opt(2018, 9, day).unwrap())
+            .collect();
+        availability.add_missing_manifest(dates[1]);
+
+        let row = availability
+            .get_availability_row("lol", "ahaha", &dates)
+            .unwrap();
+        let run = |available, days| Run { available, days };
+        assert_eq!(
+            row.runs,
+            [run(Some(true), 1), run(None, 1), run(Some(false), 2)]
+        );
+        assert!(row.changed_recently);
+        let row = availability
+            .get_availability_row("lol", "ahaha", &dates[2..])
+            .unwrap();
+        assert_eq!(row.runs, [run(Some(false), 2)]);
+        assert!(!row.changed_recently);
+        // Nothing is known about a day without a manifest.
+        let row = availability
+            .get_availability_row("lol", "ahaha", &dates[1..])
+            .unwrap();
+        assert!(!row.changed_recently);
+    }
+
+    #[test]
+    fn rename_chain() {
+        let data = r#"date = "2018-09-03"
+[pkg.ahaha.target.lol]
+available = true
+[renames.kek]
+to = "ohoho"
+[renames.ohoho]
+to = "ahaha"
+"#;
+        let mut availability: AvailabilityData = Default::default();
+        availability.add_manifest(toml::from_str(data).unwrap());
+        let date = NaiveDate::from_ymd_opt(2018, 9, 3).unwrap();
+        let row = availability
+            .get_availability_row("lol", "kek", [date])
+            .unwrap();
+        assert_eq!(row.availability_list, [true]);
+        assert_eq!(row.renamed_to, ["ohoho", "ahaha"]);
+        assert_eq!(availability.rename_chain("ohoho"), ["ahaha"]);
+        assert!(availability.rename_chain("ahaha").is_empty());
+    }
+
+    #[test]
+    fn filtered() {
+        let data = r#"date = "2018-09-03"
+[pkg.ahaha.target.lol]
+available = true
+[pkg.ahaha.target.kek]
+available = true
+[pkg.ohoho.target.lol]
+available = true
+"#;
+        let mut availability: AvailabilityData = Default::default();
+        availability.add_manifest_filtered(toml::from_str(data).unwrap(), |target, package| {
+            target == "lol" || package != "ahaha"
+        });
+        let date = NaiveDate::from_ymd_opt(2018, 9, 3).unwrap();
+        assert!(availability
+            .get_availability_row("lol", "ahaha", [date])
+            .is_some());
+        assert!(availability
+            .get_availability_row("kek", "ahaha", [date])
+            .is_none());
+        assert!(!availability.get_available_targets().contains("kek"));
+    }
+
+    #[test]
+    fn artifacts() {
+        let data = r#"date = "2018-09-03"
+[pkg.ahaha.target.lol]
+available = true
+url = "https://example.com/ahaha.tar.gz"
+hash = "abc"
+[pkg.ahaha.target.kek]
+available = true
+[pkg.ohoho.target."*"]
+available = true
+xz_url = "https://example.com/ohoho.tar.xz"
+xz_hash = "def"
+"#;
+        let mut availability: AvailabilityData = Default::default();
+        availability.add_manifest(toml::from_str(data).unwrap());
+        let date = NaiveDate::from_ymd_opt(2018, 9, 3).unwrap();
+        let other = NaiveDate::from_ymd_opt(2018, 9, 2).unwrap();
+
+        let row = availability
+            .get_availability_row("lol", "ahaha", [date, other])
+            .unwrap();
+        let artifact = row.artifact_list[0].as_ref().unwrap();
+        assert_eq!(
+            artifact.url.as_deref(),
+            Some("https://example.com/ahaha.tar.gz")
+        );
+        assert_eq!(artifact.hash.as_deref(), Some("abc"));
+        assert_eq!(row.artifact_list[1], None);
+        assert_eq!(availability.artifact("kek", "ahaha", date), None);
+        assert_eq!(
+            availability
+                .artifact("kek", "ohoho", date)
+                .and_then(|artifact| artifact.xz_hash.as_deref()),
+            Some("def")
+        );
+    }
+
+    #[test]
+    fn missing_manifests() {
+        let manifest = |date| {
+            let data = format!(
+                "date = \"{}\"\n[pkg.ahaha.target.lol]\navailable = true\n",
+                date
+            );
+            toml::from_str::<Manifest>(&data).unwrap()
+        };
+        let mut availability: AvailabilityData = Default::default();
+        availability.add_manifest(manifest("2018-09-03"));
+        availability.add_manifest(manifest("2018-09-01"));
+        let missing = NaiveDate::from_ymd_opt(2018, 9, 2).unwrap();
+        availability.add_missing_manifest(missing);
+        let dates: Vec<_> = (1..=3)
+            .rev()
+            .map(|day| NaiveDate::from_ymd_opt(2018, 9, day).unwrap())
+            .collect();
+
+        assert!(availability.is_manifest_missing(missing));
+        assert!(!availability.is_manifest_missing(dates[0]));
+        let row = availability
+            .get_availability_row("lol", "ahaha", &dates)
+            .unwrap();
+        assert_eq!(row.availability_list, [true, false, true]);
+        assert_eq!(row.stats, AvailabilityStats::new(&[true, true]));
+        assert!(availability.target_changes("lol", &dates).is_empty());
+    }
+
+    #[test]
+    fn target_lifecycle() {
+        // "old" is listed until the 2nd, "new" since the 3rd and "kept" on every day, even
+        // though its package is missing on the 2nd.
+        let manifests = [
+            ("2018-09-01", "[pkg.ahaha.target.old]\navailable = true\n[pkg.ahaha.target.kept]\navailable = true\n"),
+            ("2018-09-02", "[pkg.ahaha.target.old]\navailable = true\n[pkg.ahaha.target.kept]\navailable = false\n"),
+            ("2018-09-03", "[pkg.ahaha.target.new]\navailable = true\n[pkg.ahaha.target.kept]\navailable = true\n"),
+            ("2018-09-04", "[pkg.ahaha.target.new]\navailable = true\n[pkg.ahaha.target.kept]\navailable = true\n"),
+        ];
+        let mut availability: AvailabilityData = Default::default();
+        for (date, packages) in manifests.iter() {
+            let data = format!("date = \"{}\"\n{}", date, packages);
+            availability.add_manifest(toml::from_str(&data).unwrap());
+        }
+        let dates: Vec<_> = (1..=4)
+            .rev()
+            .map(|day| NaiveDate::from_ymd_opt(2018, 9, day).unwrap())
+            .collect();
+
+        assert!(availability.is_target_listed("kept", dates[2]));
+        assert!(!availability.is_target_listed("new", dates[2]));
+        assert_eq!(
+            availability.target_lifecycle("kept", &dates),
+            TargetLifecycle::Established
+        );
+        assert_eq!(
+            availability.target_lifecycle("new", &dates),
+            TargetLifecycle::New { since: dates[1] }
+        );
+        assert_eq!(
+            availability.target_lifecycle("old", &dates),
+            TargetLifecycle::Retired { until: dates[2] }
+        );
+        // A target added before the range is established.
+        assert_eq!(
+            availability.target_lifecycle("new", &dates[..2]),
+            TargetLifecycle::Established
+        );
+
+        // The days the target isn't listed on are padded rather than counted as an outage.
+        let row = availability
+            .get_availability_row("new", "ahaha", &dates)
+            .unwrap();
+        assert_eq!(row.availability_list, [true, true, false, false]);
+        assert_eq!(row.stats, AvailabilityStats::new(&[true, true]));
+        assert_eq!(
+            row.runs,
+            [
+                Run {
+                    available: Some(true),
+                    days: 2
+                },
+                Run {
+                    available: None,
+                    days: 2
+                },
+            ]
+        );
+        assert!(availability.target_changes("new", &dates).is_empty());
+        assert!(availability.target_changes("old", &dates).is_empty());
+        assert_eq!(availability.target_changes("kept", &dates).len(), 2);
+    }
 }
@@ -1,17 +1,112 @@
 //! Cache downloaded manifests.
 
-use crate::{manifest::Manifest, Error};
-use chrono::NaiveDate;
+use crate::{manifest::Manifest, verify::sha256, Error};
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
 use std::{
+    collections::{HashMap, VecDeque},
     fs,
     path::{Path, PathBuf},
+    sync::{Arc, Mutex},
 };
 
-/// A cache that stores manifests on a file system.
+/// A storage of downloaded manifests.
+pub trait Cache: Send + Sync {
+    /// Retrieves a manifest for a given date, if it's been stored.
+    fn get(&self, day: NaiveDate) -> Option<Manifest>;
+
+    /// Stores a manifest. Failures are not fatal and are only logged.
+    fn store(&self, manifest: &Manifest);
+
+    /// Retrieves the validators of the manifest last downloaded from a given URL, if they've been
+    /// stored.
+    ///
+    /// Nothing is ever stored by default.
+    fn get_validators(&self, _url: &str) -> Option<Validators> {
+        None
+    }
+
+    /// Stores the validators of a manifest downloaded from a given URL. Failures are not fatal and
+    /// are only logged.
+    fn store_validators(&self, _url: &str, _validators: &Validators) {}
+}
+
+impl<C: Cache + ?Sized> Cache for Arc<C> {
+    fn get(&self, day: NaiveDate) -> Option<Manifest> {
+        (**self).get(day)
+    }
+
+    fn store(&self, manifest: &Manifest) {
+        (**self).store(manifest)
+    }
+
+    fn get_validators(&self, url: &str) -> Option<Validators> {
+        (**self).get_validators(url)
+    }
+
+    fn store_validators(&self, url: &str, validators: &Validators) {
+        (**self).store_validators(url, validators)
+    }
+}
+
+/// What a server has sent along with a manifest to tell later whether it has changed since.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validators {
+    /// Date of the manifest.
+    pub date: NaiveDate,
+    /// The `ETag` header.
+    pub etag: Option<String>,
+    /// The `Last-Modified` header.
+    pub last_modified: Option<String>,
+}
+
+/// Limits of the cache size.
+#[derive(Debug, Clone, Default)]
+pub struct EvictionPolicy {
+    /// Manifests for dates older than this are removed.
+    pub max_age: Option<Duration>,
+    /// The oldest manifests are removed until the total size of the cache (in bytes) fits into
+    /// this limit.
+    pub max_size: Option<u64>,
+}
+
+/// A manifest stored in the cache.
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    /// Date of the manifest.
+    pub date: NaiveDate,
+    /// Path to the stored manifest.
+    pub path: PathBuf,
+    /// Size of the stored manifest in bytes.
+    pub size: u64,
+    /// Beginning of the [content hash](Manifest::content_hash) of the stored manifest, `None` for
+    /// the manifests cached by older versions.
+    pub hash: Option<String>,
+}
+
+/// A cache that stores manifests on a file system, keyed by their dates and content hashes.
+///
+/// Storing a manifest for a date replaces the one cached for it before. If their contents differ,
+/// the manifest has been re-published, see [`FsCache::take_republished`].
 pub struct FsCache {
     storage_path: Option<PathBuf>,
+    compressed: bool,
+    republished: Mutex<Vec<NaiveDate>>,
+    /// The cached manifests by date, listed from the disk on the first lookup.
+    index: Mutex<Option<HashMap<NaiveDate, Vec<CacheEntry>>>>,
 }
 
+const ZSTD_LEVEL: i32 = 19;
+
+/// How many characters of the content hash the file names contain.
+const HASH_LEN: usize = 16;
+
+/// Name of the file the validators are stored in, next to the manifests.
+const VALIDATORS_FILE: &str = "validators.toml";
+
+/// Name of the file whose modification time tells when the cache has been evicted.
+const EVICTED_FILE: &str = ".evicted";
+
 impl FsCache {
     /// Initializes a cache with a given path.
     ///
@@ -24,47 +119,622 @@ impl FsCache {
         }
         Ok(FsCache {
             storage_path: Some(path.into()),
+            compressed: false,
+            republished: Mutex::new(Vec::new()),
+            index: Mutex::new(None),
         })
     }
 
     /// Initializes a no-op cache.
     pub fn noop() -> Self {
-        FsCache { storage_path: None }
+        FsCache {
+            storage_path: None,
+            compressed: false,
+            republished: Mutex::new(Vec::new()),
+            index: Mutex::new(None),
+        }
     }
 
-    fn make_file_name(&self, day: NaiveDate) -> PathBuf {
-        self.storage_path
+    /// Makes the cache store manifests zstd-compressed. Manifests that have been stored
+    /// uncompressed are still read.
+    pub fn compressed(self) -> Self {
+        FsCache {
+            compressed: true,
+            ..self
+        }
+    }
+
+    /// Takes the dates whose manifests have been stored again with different contents since the
+    /// last call, i.e. have been re-published.
+    ///
+    /// Only the manifests that are downloaded again are compared, i.e. the ones the cache misses
+    /// or that are re-validated. A manifest re-published for a date that's served from the cache
+    /// goes unnoticed.
+    pub fn take_republished(&self) -> Vec<NaiveDate> {
+        std::mem::take(&mut *self.republished.lock().unwrap())
+    }
+
+    fn make_file_name(&self, day: NaiveDate, hash: &str, compressed: bool) -> PathBuf {
+        let extension = if compressed { "toml.zst" } else { "toml" };
+        self.storage_path.as_ref().unwrap().join(format!(
+            "{}.{}.{}",
+            day.format("%Y-%m-%d"),
+            hash,
+            extension
+        ))
+    }
+
+    /// Parses a name of a cached manifest, i.e. `YYYY-MM-DD[.<hash>].toml[.zst]`.
+    fn parse_file_name(name: &str) -> Option<(NaiveDate, Option<String>)> {
+        let name = name.strip_suffix(".zst").unwrap_or(name);
+        let name = name.strip_suffix(".toml")?;
+        let (date, hash) = match name.split_once('.') {
+            Some((date, hash)) => (date, Some(hash.to_string())),
+            None => (name, None),
+        };
+        Some((NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?, hash))
+    }
+
+    /// Lists the manifests cached for a given date.
+    fn entries_of(&self, day: NaiveDate) -> Result<Vec<CacheEntry>, Error> {
+        let mut index = self.index.lock().unwrap();
+        if index.is_none() {
+            let mut by_date: HashMap<_, Vec<_>> = HashMap::new();
+            for entry in self.entries()? {
+                by_date.entry(entry.date).or_default().push(entry);
+            }
+            *index = Some(by_date);
+        }
+        Ok(index
             .as_ref()
-            .unwrap()
-            .join(day.format("%Y-%m-%d.toml").to_string())
+            .and_then(|index| index.get(&day))
+            .cloned()
+            .unwrap_or_default())
     }
-}
 
-impl FsCache {
-    pub(crate) fn get(&self, day: NaiveDate) -> Option<Manifest> {
-        if self.storage_path.is_none() {
-            return None;
+    /// Makes the next lookup list the manifests from the disk again.
+    fn invalidate_index(&self) {
+        *self.index.lock().unwrap() = None;
+    }
+
+    fn load(entry: &CacheEntry) -> Result<Manifest, Error> {
+        if entry
+            .path
+            .extension()
+            .is_some_and(|extension| extension == "zst")
+        {
+            Self::load_compressed(&entry.path)
+        } else {
+            Manifest::load_from_fs(&entry.path)
         }
+    }
 
-        let file_name = self.make_file_name(day);
-        if !file_name.exists() {
-            log::debug!("File {:?} doesn't exist", file_name);
-            return None;
+    /// Stores a manifest unless the same one is already cached, replacing the other ones cached
+    /// for its date.
+    ///
+    /// Returns the path the manifest has been stored at, if it had to be stored.
+    fn replace(&self, manifest: &Manifest) -> Result<Option<PathBuf>, Error> {
+        let data = manifest.to_canonical_vec()?;
+        let hash = &sha256(&data)[..HASH_LEN];
+        let cached = self.entries_of(manifest.date)?;
+        if cached
+            .iter()
+            .any(|entry| entry.hash.as_deref() == Some(hash))
+        {
+            return Ok(None);
+        }
+        let path = self.make_file_name(manifest.date, hash, self.compressed);
+        let data = if self.compressed {
+            zstd::encode_all(&data[..], ZSTD_LEVEL)
+                .map_err(|e| Error::Io(e, format!("compressing {}", manifest.date)))?
+        } else {
+            data
+        };
+        let size = data.len() as u64;
+        fs::write(&path, data).map_err(|e| Error::Io(e, format!("writing to {:?}", path)))?;
+        // Manifests cached by older versions can't be told apart from the new one.
+        if cached.iter().any(|entry| entry.hash.is_some()) {
+            log::warn!(
+                manifest_date:% = manifest.date;
+                "The manifest for {} has been re-published",
+                manifest.date
+            );
+            self.republished.lock().unwrap().push(manifest.date);
+        }
+        for entry in &cached {
+            Self::remove(entry)?;
         }
-        Manifest::load_from_fs(&file_name)
-            .map_err(|e| log::warn!("Can't load manifest: {}", e))
+        if let Some(index) = self.index.lock().unwrap().as_mut() {
+            index.insert(
+                manifest.date,
+                vec![CacheEntry {
+                    date: manifest.date,
+                    path: path.clone(),
+                    size,
+                    hash: Some(hash.to_string()),
+                }],
+            );
+        }
+        Ok(Some(path))
+    }
+
+    fn validators_path(&self) -> Option<PathBuf> {
+        Some(self.storage_path.as_ref()?.join(VALIDATORS_FILE))
+    }
+
+    fn load_validators(path: &Path) -> Result<HashMap<String, Validators>, Error> {
+        if !path.exists() {
+            return Ok(HashMap::new());
+        }
+        let data = fs::read(path).map_err(|e| Error::Io(e, format!("reading {:?}", path)))?;
+        toml::from_slice(&data).map_err(|e| Error::TomlDe(e, format!("{:?}", path)))
+    }
+
+    fn save_validators(url: &str, validators: &Validators, path: &Path) -> Result<(), Error> {
+        let mut all = Self::load_validators(path)?;
+        all.insert(url.to_string(), validators.clone());
+        let data = toml::to_vec(&all)
+            .map_err(|e| Error::TomlSer(e, format!("serializing validators of {}", url)))?;
+        fs::write(path, data).map_err(|e| Error::Io(e, format!("writing to {:?}", path)))
+    }
+
+    fn load_compressed(path: &Path) -> Result<Manifest, Error> {
+        let data = fs::read(path).map_err(|e| Error::Io(e, format!("reading {:?}", path)))?;
+        let data = zstd::decode_all(&data[..])
+            .map_err(|e| Error::Io(e, format!("decompressing {:?}", path)))?;
+        toml::from_slice(&data).map_err(|e| Error::TomlDe(e, format!("{:?}", path)))
+    }
+}
+
+impl Cache for FsCache {
+    fn get(&self, day: NaiveDate) -> Option<Manifest> {
+        self.storage_path.as_ref()?;
+
+        let entries = self
+            .entries_of(day)
+            .map_err(|e| log::warn!("Can't list cached manifests: {}", e))
+            .ok()?;
+        let entry = match entries.first() {
+            Some(entry) => entry,
+            None => {
+                log::debug!("No manifest for {} is cached", day);
+                return None;
+            }
+        };
+        Self::load(entry)
+            .map_err(|e| {
+                // The file might have been removed behind our back.
+                self.invalidate_index();
+                log::warn!("Can't load manifest: {}", e)
+            })
             .ok()
     }
 
-    pub(crate) fn store(&self, manifest: &Manifest) {
+    fn store(&self, manifest: &Manifest) {
         if self.storage_path.is_none() {
             return;
         }
 
-        let file_name = self.make_file_name(manifest.date);
-        match manifest.save_to_file(&file_name) {
-            Ok(_) => log::debug!("Manifest stored at {:?}", file_name),
+        match self.replace(manifest) {
+            Ok(Some(path)) => log::debug!("Manifest stored at {:?}", path),
+            Ok(None) => log::debug!("Manifest for {} is already cached", manifest.date),
             Err(e) => log::warn!("Can't save a manifest to the disk: {}", e),
         }
     }
+
+    fn get_validators(&self, url: &str) -> Option<Validators> {
+        Self::load_validators(&self.validators_path()?)
+            .map_err(|e| log::warn!("Can't load validators: {}", e))
+            .ok()?
+            .remove(url)
+    }
+
+    fn store_validators(&self, url: &str, validators: &Validators) {
+        let path = match self.validators_path() {
+            Some(path) => path,
+            None => return,
+        };
+        if let Err(e) = Self::save_validators(url, validators, &path) {
+            log::warn!("Can't save validators to the disk: {}", e);
+        }
+    }
+}
+
+/// A cache that keeps a bounded number of the most recently used manifests in memory in front of
+/// another cache.
+pub struct MemoryCache<C> {
+    inner: C,
+    capacity: usize,
+    /// The most recently used manifests are at the back.
+    entries: Mutex<VecDeque<Manifest>>,
+}
+
+impl<C> MemoryCache<C> {
+    /// Wraps a cache keeping up to `capacity` manifests in memory. A zero capacity disables the
+    /// in-memory tier altogether.
+    pub fn new(inner: C, capacity: usize) -> Self {
+        MemoryCache {
+            inner,
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Returns the wrapped cache.
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    fn remember(&self, manifest: Manifest) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|entry| entry.date != manifest.date);
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(manifest);
+    }
+}
+
+impl<C: Cache> Cache for MemoryCache<C> {
+    fn get(&self, day: NaiveDate) -> Option<Manifest> {
+        {
+            let mut entries = self.entries.lock().unwrap();
+            if let Some(idx) = entries.iter().position(|entry| entry.date == day) {
+                let manifest = entries.remove(idx).expect("Index is valid");
+                let result = manifest.clone();
+                entries.push_back(manifest);
+                return Some(result);
+            }
+        }
+        let manifest = self.inner.get(day)?;
+        self.remember(manifest.clone());
+        Some(manifest)
+    }
+
+    fn store(&self, manifest: &Manifest) {
+        self.inner.store(manifest);
+        self.remember(manifest.clone());
+    }
+
+    fn get_validators(&self, url: &str) -> Option<Validators> {
+        self.inner.get_validators(url)
+    }
+
+    fn store_validators(&self, url: &str, validators: &Validators) {
+        self.inner.store_validators(url, validators)
+    }
+}
+
+impl FsCache {
+    /// Lists all the cached manifests sorted by date in ascending order.
+    pub fn entries(&self) -> Result<Vec<CacheEntry>, Error> {
+        let storage_path = match &self.storage_path {
+            Some(path) => path,
+            None => return Ok(Vec::new()),
+        };
+        let dir = fs::read_dir(storage_path)
+            .map_err(|e| Error::Io(e, format!("reading {:?}", storage_path)))?;
+        let mut entries = Vec::new();
+        for entry in dir {
+            let entry = entry.map_err(|e| Error::Io(e, format!("reading {:?}", storage_path)))?;
+            let path = entry.path();
+            let (date, hash) = match path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(Self::parse_file_name)
+            {
+                Some(parsed) => parsed,
+                None => continue,
+            };
+            let size = entry
+                .metadata()
+                .map_err(|e| Error::Io(e, format!("reading metadata of {:?}", path)))?
+                .len();
+            entries.push(CacheEntry {
+                date,
+                path,
+                size,
+                hash,
+            });
+        }
+        entries.sort_unstable_by_key(|entry| entry.date);
+        Ok(entries)
+    }
+
+    /// Removes manifests that don't fit into a given policy. The age of manifests is calculated
+    /// relative to the `today` date.
+    ///
+    /// Returns the removed entries.
+    pub fn evict(
+        &self,
+        policy: &EvictionPolicy,
+        today: NaiveDate,
+    ) -> Result<Vec<CacheEntry>, Error> {
+        let entries = self.entries()?;
+        let mut total_size: u64 = entries.iter().map(|entry| entry.size).sum();
+        let mut removed = Vec::new();
+        for entry in entries {
+            let too_old = policy
+                .max_age
+                .is_some_and(|max_age| today - entry.date > max_age);
+            let too_big = policy
+                .max_size
+                .is_some_and(|max_size| total_size > max_size);
+            if !too_old && !too_big {
+                // Entries are sorted by date, so the rest are newer.
+                break;
+            }
+            Self::remove(&entry)?;
+            total_size -= entry.size;
+            removed.push(entry);
+        }
+        self.invalidate_index();
+        Ok(removed)
+    }
+
+    /// Same as [`FsCache::evict`], but does nothing if the cache has already been evicted within
+    /// a given `interval`, so frequent runs don't list the whole cache every time.
+    pub fn evict_every(
+        &self,
+        interval: std::time::Duration,
+        policy: &EvictionPolicy,
+        today: NaiveDate,
+    ) -> Result<Vec<CacheEntry>, Error> {
+        let stamp = match &self.storage_path {
+            Some(path) => path.join(EVICTED_FILE),
+            None => return Ok(Vec::new()),
+        };
+        let evicted = fs::metadata(&stamp).and_then(|metadata| metadata.modified());
+        if evicted.is_ok_and(|evicted| evicted.elapsed().is_ok_and(|elapsed| elapsed < interval)) {
+            return Ok(Vec::new());
+        }
+        let removed = self.evict(policy, today)?;
+        fs::write(&stamp, "").map_err(|e| Error::Io(e, format!("writing to {:?}", stamp)))?;
+        Ok(removed)
+    }
+
+    /// Removes all the cached manifests.
+    ///
+    /// Returns the removed entries.
+    pub fn clear(&self) -> Result<Vec<CacheEntry>, Error> {
+        let entries = self.entries()?;
+        for entry in &entries {
+            Self::remove(entry)?;
+        }
+        self.invalidate_index();
+        Ok(entries)
+    }
+
+    /// Checks that a cached manifest can be loaded and, unless it's been cached by an older
+    /// version, that its contents still match the hash it's been stored with.
+    pub fn verify(entry: &CacheEntry) -> Result<(), Error> {
+        let manifest = Self::load(entry)?;
+        let expected = match &entry.hash {
+            Some(hash) => hash,
+            None => return Ok(()),
+        };
+        let actual = manifest.content_hash()?;
+        if actual.starts_with(expected.as_str()) {
+            Ok(())
+        } else {
+            Err(Error::Verification(
+                format!(
+                    "content hash {} doesn't match {}",
+                    &actual[..HASH_LEN],
+                    expected
+                ),
+                format!("{:?}", entry.path),
+            ))
+        }
+    }
+
+    /// Removes a cached manifest.
+    ///
+    /// Caches that have already listed it notice it's gone on the next lookup of its date.
+    pub fn remove(entry: &CacheEntry) -> Result<(), Error> {
+        log::debug!("Removing {:?} from the cache", entry.path);
+        fs::remove_file(&entry.path).map_err(|e| Error::Io(e, format!("removing {:?}", entry.path)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::Rename;
+    use std::collections::HashMap;
+
+    fn manifest(date: NaiveDate) -> Manifest {
+        Manifest {
+            date,
+            packages: HashMap::new(),
+            renames: HashMap::new(),
+            profiles: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn evict() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path();
+        let cache = FsCache::new(path).unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2019, 1, d).unwrap();
+        for d in 1..=5 {
+            cache.store(&manifest(day(d)));
+        }
+        let size = cache.entries().unwrap()[0].size;
+
+        let removed = cache
+            .evict(
+                &EvictionPolicy {
+                    max_age: Some(Duration::days(8)),
+                    max_size: None,
+                },
+                day(10),
+            )
+            .unwrap();
+        assert_eq!(
+            vec![day(1)],
+            removed.iter().map(|e| e.date).collect::<Vec<_>>()
+        );
+
+        let removed = cache
+            .evict(
+                &EvictionPolicy {
+                    max_age: None,
+                    max_size: Some(size * 2),
+                },
+                day(10),
+            )
+            .unwrap();
+        assert_eq!(
+            vec![day(2), day(3)],
+            removed.iter().map(|e| e.date).collect::<Vec<_>>()
+        );
+        assert!(cache.get(day(4)).is_some());
+
+        assert_eq!(2, cache.clear().unwrap().len());
+        assert!(cache.entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn evict_every() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path();
+        let cache = FsCache::new(path).unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2019, 1, d).unwrap();
+        let policy = EvictionPolicy {
+            max_age: Some(Duration::days(1)),
+            max_size: None,
+        };
+        let hour = std::time::Duration::from_secs(3600);
+        cache.store(&manifest(day(1)));
+        assert_eq!(1, cache.evict_every(hour, &policy, day(10)).unwrap().len());
+
+        // Too soon for another eviction.
+        cache.store(&manifest(day(2)));
+        assert!(cache
+            .evict_every(hour, &policy, day(10))
+            .unwrap()
+            .is_empty());
+        assert!(cache.get(day(2)).is_some());
+
+        let removed = cache.evict_every(std::time::Duration::ZERO, &policy, day(10));
+        assert_eq!(1, removed.unwrap().len());
+    }
+
+    #[test]
+    fn compressed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path();
+        let day = NaiveDate::from_ymd_opt(2019, 1, 1).unwrap();
+        let cache = FsCache::new(path).unwrap().compressed();
+        // Cached uncompressed by an older version.
+        manifest(day)
+            .save_to_file(path.join("2019-01-01.toml"))
+            .unwrap();
+
+        assert_eq!(Some(manifest(day)), cache.get(day));
+        let next_day = day.succ_opt().unwrap();
+        cache.store(&manifest(next_day));
+        assert_eq!(Some(manifest(next_day)), cache.get(next_day));
+        let entries = cache.entries().unwrap();
+        assert_eq!(2, entries.len());
+        assert_eq!(None, entries[0].hash);
+        assert!(entries[1].path.to_str().unwrap().ends_with(".toml.zst"));
+    }
+
+    #[test]
+    fn republished() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path();
+        let day = NaiveDate::from_ymd_opt(2019, 1, 1).unwrap();
+        let original = manifest(day);
+        let cache = FsCache::new(path).unwrap();
+        original.save_to_file(path.join("2019-01-01.toml")).unwrap();
+        // Manifests cached by older versions are replaced silently.
+        cache.store(&original);
+        cache.store(&original);
+        assert_eq!(1, cache.entries().unwrap().len());
+        assert!(cache.take_republished().is_empty());
+
+        let mut republished = original.clone();
+        republished.renames.insert(
+            "rls".into(),
+            Rename {
+                to: "rls-preview".into(),
+            },
+        );
+        cache.store(&republished);
+        assert_eq!(vec![day], cache.take_republished());
+        assert!(cache.take_republished().is_empty());
+        let entries = cache.entries().unwrap();
+        assert_eq!(1, entries.len());
+        let hash = republished.content_hash().unwrap();
+        assert_eq!(Some(&hash[..HASH_LEN]), entries[0].hash.as_deref());
+        assert_eq!(Some(republished), cache.get(day));
+
+        assert!(FsCache::verify(&entries[0]).is_ok());
+        // Written over with the original contents.
+        let data = manifest(day).to_canonical_vec().unwrap();
+        fs::write(&entries[0].path, data).unwrap();
+        assert!(matches!(
+            FsCache::verify(&entries[0]),
+            Err(Error::Verification(..))
+        ));
+    }
+
+    #[test]
+    fn index() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path();
+        let day = NaiveDate::from_ymd_opt(2019, 1, 1).unwrap();
+        let cache = FsCache::new(path).unwrap();
+        assert_eq!(None, cache.get(day));
+        // Stored after the index has been listed.
+        cache.store(&manifest(day));
+        assert_eq!(Some(manifest(day)), cache.get(day));
+        let entries = cache.entries().unwrap();
+        assert_eq!(1, entries.len());
+        FsCache::remove(&entries[0]).unwrap();
+        assert_eq!(None, cache.get(day));
+        cache.store(&manifest(day));
+        assert_eq!(Some(manifest(day)), cache.get(day));
+    }
+
+    #[test]
+    fn validators() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path();
+        let cache = FsCache::new(path).unwrap();
+        let url = "https://example.com/dist/channel-rust-nightly.toml";
+        assert_eq!(None, cache.get_validators(url));
+        let validators = Validators {
+            date: NaiveDate::from_ymd_opt(2019, 1, 1).unwrap(),
+            etag: Some("\"abc\"".into()),
+            last_modified: None,
+        };
+        cache.store_validators(url, &validators);
+        cache.store_validators("https://example.com/other.toml", &validators);
+        assert_eq!(Some(validators), cache.get_validators(url));
+        // Only manifests are listed.
+        assert!(cache.entries().unwrap().is_empty());
+        assert_eq!(None, FsCache::noop().get_validators(url));
+    }
+
+    #[test]
+    fn memory() {
+        let day = |d| NaiveDate::from_ymd_opt(2019, 1, d).unwrap();
+        let cache = MemoryCache::new(FsCache::noop(), 2);
+        cache.store(&manifest(day(1)));
+        cache.store(&manifest(day(2)));
+        assert!(cache.get(day(1)).is_some());
+        // The second day is the least recently used one now.
+        cache.store(&manifest(day(3)));
+        assert!(cache.get(day(1)).is_some());
+        assert!(cache.get(day(2)).is_none());
+        assert!(cache.get(day(3)).is_some());
+    }
 }
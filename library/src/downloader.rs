@@ -1,19 +1,34 @@
-use super::skip_errors::SkipMissingExt;
+use super::skip_errors::{MissingDays, SkipMissingExt};
 use crate::{
-    cache::FsCache,
+    cache::{Cache, FsCache, Validators},
     manifest::Manifest,
-    source::{DefaultSource, SourceInfo},
+    parallel::parallel_map,
+    retry::RetryPolicy,
+    source::{DefaultSource, SourceInfo, FILE_SCHEME},
+    verify::Verification,
     Error,
 };
 use chrono::{Duration, NaiveDate};
-use std::{io, iter};
+use reqwest::{
+    blocking::Response,
+    header::{HeaderName, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    StatusCode,
+};
+use std::{collections::BTreeSet, fs, io, iter, sync::Mutex};
 
 /// Manifests downloader and parser.
 pub struct Downloader<S> {
     client: reqwest::blocking::Client,
     source: S,
-    cache: FsCache,
-    skip_missing_days: usize,
+    cache: Box<dyn Cache>,
+    missing_days: MissingDays,
+    tolerate_failed_days: usize,
+    concurrency: usize,
+    retry_policy: RetryPolicy,
+    verification: Option<Verification>,
+    /// Days whose manifests have failed to download for another reason than
+    /// being missing.
+    failed_days: Mutex<BTreeSet<NaiveDate>>,
 }
 
 impl<'a> Downloader<DefaultSource<'a>> {
@@ -29,8 +44,13 @@ impl<S> Downloader<S> {
         Downloader {
             client: reqwest::blocking::Client::new(),
             source,
-            cache: FsCache::noop(),
-            skip_missing_days: 0,
+            cache: Box::new(FsCache::noop()),
+            missing_days: MissingDays::default(),
+            tolerate_failed_days: 0,
+            concurrency: 1,
+            retry_policy: RetryPolicy::default(),
+            verification: None,
+            failed_days: Default::default(),
         }
     }
 }
@@ -39,19 +59,24 @@ impl<S> Downloader<S>
 where
     S: SourceInfo,
 {
-    /// Sets a cache for the downloader. By default a [`NoopCache`] is used.
-    pub fn set_cache(self, c: FsCache) -> Downloader<S> {
+    /// Sets a cache for the downloader. By default a no-op [`FsCache`] is used.
+    pub fn set_cache(self, c: impl Cache + 'static) -> Downloader<S> {
         Downloader {
             client: self.client,
             source: self.source,
-            cache: c,
-            skip_missing_days: self.skip_missing_days,
+            cache: Box::new(c),
+            missing_days: self.missing_days,
+            tolerate_failed_days: self.tolerate_failed_days,
+            concurrency: self.concurrency,
+            retry_policy: self.retry_policy,
+            verification: self.verification.clone(),
+            failed_days: self.failed_days,
         }
     }
 
     /// Set to non zero if you want to silently skip days for which manifest files are missing.
     /// Not more than `skip` days will be skipped.
-    /// Please not that this setting only affects the [`get_last_manifests`] method.
+    /// Please note that this setting only affects the [`get_last_manifests`] method.
     ///
     /// Off (zero) by default.
     pub fn skip_missing_days(self, skip: usize) -> Downloader<S> {
@@ -59,7 +84,143 @@ where
             client: self.client,
             source: self.source,
             cache: self.cache,
-            skip_missing_days: skip,
+            missing_days: MissingDays {
+                limit: skip,
+                ..self.missing_days
+            },
+            tolerate_failed_days: self.tolerate_failed_days,
+            concurrency: self.concurrency,
+            retry_policy: self.retry_policy,
+            verification: self.verification.clone(),
+            failed_days: self.failed_days,
+        }
+    }
+
+    /// Makes the [`skip_missing_days`] limit apply to the days missing in a row rather than to all
+    /// the missing days.
+    ///
+    /// Off by default.
+    pub fn count_missing_days_in_a_row(self, in_a_row: bool) -> Downloader<S> {
+        Downloader {
+            client: self.client,
+            source: self.source,
+            cache: self.cache,
+            missing_days: MissingDays {
+                in_a_row,
+                ..self.missing_days
+            },
+            tolerate_failed_days: self.tolerate_failed_days,
+            concurrency: self.concurrency,
+            retry_policy: self.retry_policy,
+            verification: self.verification.clone(),
+            failed_days: self.failed_days,
+        }
+    }
+
+    /// Makes the downloader stop looking further back, rather than fail, once more days than the
+    /// [`skip_missing_days`] limit are missing. The manifests found by then are returned.
+    ///
+    /// Off by default.
+    pub fn stop_at_missing_days(self, stop: bool) -> Downloader<S> {
+        Downloader {
+            client: self.client,
+            source: self.source,
+            cache: self.cache,
+            missing_days: MissingDays {
+                stop,
+                ..self.missing_days
+            },
+            tolerate_failed_days: self.tolerate_failed_days,
+            concurrency: self.concurrency,
+            retry_policy: self.retry_policy,
+            verification: self.verification.clone(),
+            failed_days: self.failed_days,
+        }
+    }
+
+    /// Sets how many manifests might be downloaded and parsed simultaneously by the
+    /// [`get_last_manifests`] method.
+    ///
+    /// The manifests are downloaded in batches of this size, so a stop on missing days only
+    /// wastes the rest of the current batch. One (i.e. sequential downloading) by default.
+    pub fn concurrency(self, concurrency: usize) -> Downloader<S> {
+        Downloader {
+            client: self.client,
+            source: self.source,
+            cache: self.cache,
+            missing_days: self.missing_days,
+            tolerate_failed_days: self.tolerate_failed_days,
+            concurrency: concurrency.max(1),
+            retry_policy: self.retry_policy,
+            verification: self.verification.clone(),
+            failed_days: self.failed_days,
+        }
+    }
+
+    /// Set to non zero if you want to skip days for which manifest files couldn't be fetched
+    /// for any reason (after all the retries), logging a warning instead of failing. Not more than
+    /// `tolerate` days will be skipped. Missing manifests are counted here only when they exceed
+    /// the [`skip_missing_days`] limit.
+    /// Please note that this setting doesn't affect the latest manifest.
+    ///
+    /// Off (zero) by default.
+    pub fn tolerate_failed_days(self, tolerate: usize) -> Downloader<S> {
+        Downloader {
+            client: self.client,
+            source: self.source,
+            cache: self.cache,
+            missing_days: self.missing_days,
+            tolerate_failed_days: tolerate,
+            concurrency: self.concurrency,
+            retry_policy: self.retry_policy,
+            verification: self.verification.clone(),
+            failed_days: self.failed_days,
+        }
+    }
+
+    /// Sets an HTTP client to download manifests with, e.g. to configure default headers, a proxy
+    /// or timeouts.
+    pub fn set_client(self, client: reqwest::blocking::Client) -> Downloader<S> {
+        Downloader {
+            client,
+            source: self.source,
+            cache: self.cache,
+            missing_days: self.missing_days,
+            tolerate_failed_days: self.tolerate_failed_days,
+            concurrency: self.concurrency,
+            retry_policy: self.retry_policy,
+            verification: self.verification.clone(),
+            failed_days: self.failed_days,
+        }
+    }
+
+    /// Sets a policy of retrying failed downloads. By default nothing is retried.
+    pub fn retry_policy(self, retry_policy: RetryPolicy) -> Downloader<S> {
+        Downloader {
+            client: self.client,
+            source: self.source,
+            cache: self.cache,
+            missing_days: self.missing_days,
+            tolerate_failed_days: self.tolerate_failed_days,
+            concurrency: self.concurrency,
+            retry_policy,
+            verification: self.verification.clone(),
+            failed_days: self.failed_days,
+        }
+    }
+
+    /// Makes the downloader verify every manifest it downloads. By default nothing is verified.
+    pub fn verification(self, verification: Verification) -> Downloader<S> {
+        Downloader {
+            client: self.client,
+            source: self.source,
+            cache: self.cache,
+            missing_days: self.missing_days,
+            tolerate_failed_days: self.tolerate_failed_days,
+            concurrency: self.concurrency,
+            retry_policy: self.retry_policy,
+            verification: Some(verification),
+            failed_days: self.failed_days,
         }
     }
 
@@ -67,20 +228,66 @@ where
     /// manifest is fetched.
     ///
     /// The returned vector is sorted in descending order of dates.
-    pub fn get_last_manifests(&self, days: usize) -> Result<Vec<Manifest>, Error> {
+    pub fn get_last_manifests(&self, days: usize) -> Result<Vec<Manifest>, Error>
+    where
+        S: Sync,
+    {
         let latest = self.get_latest_manifest()?;
         let latest_day = latest.date;
-        log::info!("Latest manifest is for {}", latest_day);
-        let rest = (1..days)
-            .filter_map(|day| latest_day.checked_sub_signed(Duration::days(day as i64)))
-            .map(|date| self.get_manifest(date))
-            .skip_missing(self.skip_missing_days);
+        log::info!(manifest_date:% = latest_day; "Latest manifest is for {}", latest_day);
+        let rest = self.get_manifests_for_days(latest_day, 1..days);
         iter::once(Ok(latest)).chain(rest).collect()
     }
 
+    /// Get manifests for given `days` ending at the `end` date (inclusive).
+    ///
+    /// The returned vector is sorted in descending order of dates. Missing manifests are skipped
+    /// according to the [`skip_missing_days`] setting.
+    pub fn get_manifests_until(&self, end: NaiveDate, days: usize) -> Result<Vec<Manifest>, Error>
+    where
+        S: Sync,
+    {
+        self.get_manifests_for_days(end, 0..days).collect()
+    }
+
+    /// Fetches manifests for days which are `days_back` days before the `from` date.
+    fn get_manifests_for_days(
+        &self,
+        from: NaiveDate,
+        days_back: impl Iterator<Item = usize>,
+    ) -> impl Iterator<Item = Result<Manifest, Error>> + '_
+    where
+        S: Sync,
+    {
+        let dates: Vec<_> = days_back
+            .filter_map(|day| from.checked_sub_signed(Duration::days(day as i64)))
+            .collect();
+        parallel_map(dates, self.concurrency, move |&date| {
+            let result = self.get_manifest(date);
+            match &result {
+                Err(Error::BadResponse(StatusCode::NOT_FOUND, _)) | Ok(_) => {}
+                Err(_) => {
+                    self.failed_days.lock().unwrap().insert(date);
+                }
+            }
+            result
+        })
+        .skip_missing_with(self.missing_days)
+        .skip_failed(self.tolerate_failed_days)
+    }
+
+    /// Days whose manifests have failed to download so far for another reason than being
+    /// missing, e.g. the ones skipped according to the [`tolerate_failed_days`] setting.
+    ///
+    /// [`tolerate_failed_days`]: Self::tolerate_failed_days
+    pub fn failed_days(&self) -> Vec<NaiveDate> {
+        self.failed_days.lock().unwrap().iter().copied().collect()
+    }
+
     /// Gets manifest for a given date.
     pub fn get_manifest(&self, day: NaiveDate) -> Result<Manifest, Error> {
         if let Some(cached) = self.cache.get(day) {
+            log::debug!(manifest_date:% = day; "Found the manifest for {} in the cache", day);
             return Ok(cached);
         }
         let manifest = self.get_manifest_by_url(self.source.make_manifest_url(day))?;
@@ -88,12 +295,70 @@ where
         Ok(manifest)
     }
 
-    /// Gets manifest for a given date. If the `date` is `None`, the latest available manifest is
-    /// requested.
+    /// Gets the latest available manifest.
     ///
-    /// This call is never cached.
+    /// The latest manifest might be re-published, so it's always requested. However, if the server
+    /// has sent an `ETag` or a `Last-Modified` header along with it before, the request is
+    /// conditional and the manifest is read from the cache unless it has changed.
     pub fn get_latest_manifest(&self) -> Result<Manifest, Error> {
-        self.get_manifest_by_url(self.source.make_latest_manifest_url())
+        let url = self.source.make_latest_manifest_url();
+        let url = url.as_ref();
+        if url.starts_with(FILE_SCHEME) {
+            return self.get_manifest_by_url(url);
+        }
+        let validators = self.cache.get_validators(url);
+        self.retry_policy
+            .run(|| self.fetch_latest_manifest(url, validators.as_ref()))
+    }
+
+    /// Makes a single attempt to fetch the latest manifest, unless it hasn't changed since the
+    /// `validators` have been received.
+    fn fetch_latest_manifest(
+        &self,
+        url: &str,
+        validators: Option<&Validators>,
+    ) -> Result<Manifest, Error> {
+        log::info!("Fetching a manifest from {}", url);
+        let mut request = self.client.get(url);
+        if let Some(validators) = validators {
+            if let Some(etag) = &validators.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &validators.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let response = request.send().map_err(|e| Error::Reqwest(e, url.into()))?;
+        if let (StatusCode::NOT_MODIFIED, Some(validators)) = (response.status(), validators) {
+            let date = validators.date;
+            if let Some(manifest) = self.cache.get(date) {
+                log::info!(
+                    manifest_date:% = date;
+                    "The latest manifest hasn't changed since {} has been downloaded",
+                    date
+                );
+                return Ok(manifest);
+            }
+            log::debug!("The latest manifest for {} is no longer cached", date);
+            return self.fetch_latest_manifest(url, None);
+        }
+        let header = |name: HeaderName| {
+            let value = response.headers().get(name)?.to_str().ok()?;
+            Some(value.to_string())
+        };
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+        let manifest = self.parse_manifest(url, &Self::read_body(url, response)?)?;
+        if etag.is_some() || last_modified.is_some() {
+            self.cache.store(&manifest);
+            let validators = Validators {
+                date: manifest.date,
+                etag,
+                last_modified,
+            };
+            self.cache.store_validators(url, &validators);
+        }
+        Ok(manifest)
     }
 
     /// Fetches a manifest from a given url.
@@ -102,17 +367,73 @@ where
     pub fn get_manifest_by_url(&self, url: impl AsRef<str>) -> Result<Manifest, Error> {
         let url = url.as_ref();
         log::info!("Fetching a manifest from {}", url);
-        let mut response = self
+        // The companion files are retried on their own while verifying.
+        let bytes = self.retry_policy.run(|| self.fetch(url))?;
+        self.parse_manifest(url, &bytes)
+    }
+
+    /// Verifies and parses a fetched manifest.
+    fn parse_manifest(&self, url: &str, bytes: &[u8]) -> Result<Manifest, Error> {
+        if let Some(verification) = &self.verification {
+            self.verify(verification, url, bytes)?;
+        }
+
+        toml::from_slice(bytes).map_err(|e| Error::TomlDe(e, url.to_string()))
+    }
+
+    /// Verifies a manifest against the companion files published next to it.
+    fn verify(&self, verification: &Verification, url: &str, data: &[u8]) -> Result<(), Error> {
+        match self.fetch_companion(&format!("{}.sha256", url))? {
+            Some(checksum) => {
+                verification.check_checksum(data, &String::from_utf8_lossy(&checksum), url)?
+            }
+            None => verification.missing("checksum", url)?,
+        }
+        if verification.keyring.is_some() {
+            match self.fetch_companion(&format!("{}.asc", url))? {
+                Some(signature) => verification.check_signature(data, &signature, url)?,
+                None => verification.missing("signature", url)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Fetches a file published next to a manifest, if there's one, retrying just like the
+    /// manifests are.
+    fn fetch_companion(&self, url: &str) -> Result<Option<Vec<u8>>, Error> {
+        match self.retry_policy.run(|| self.fetch(url)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(Error::BadResponse(reqwest::StatusCode::NOT_FOUND, _)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Fetches a file over HTTP, or reads it if it's a `file://` URL.
+    fn fetch(&self, url: &str) -> Result<Vec<u8>, Error> {
+        if let Some(path) = url.strip_prefix(FILE_SCHEME) {
+            return fs::read(path).map_err(|e| match e.kind() {
+                // Missing files are treated like missing manifests on a server.
+                io::ErrorKind::NotFound => {
+                    Error::BadResponse(reqwest::StatusCode::NOT_FOUND, url.into())
+                }
+                _ => Error::Io(e, url.into()),
+            });
+        }
+        let response = self
             .client
             .get(url)
             .send()
             .map_err(|e| Error::Reqwest(e, url.into()))?;
+        Self::read_body(url, response)
+    }
+
+    /// Reads the body of a successful response.
+    fn read_body(url: &str, mut response: Response) -> Result<Vec<u8>, Error> {
         if !response.status().is_success() {
             return Err(Error::BadResponse(response.status(), url.into()));
         }
         let mut bytes = Vec::new();
         io::copy(&mut response, &mut bytes).map_err(|e| Error::Io(e, url.into()))?;
-
-        toml::from_slice(&bytes).map_err(|e| Error::TomlDe(e, url.to_string()))
+        Ok(bytes)
     }
 }
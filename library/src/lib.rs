@@ -11,18 +11,21 @@
 
 #![deny(missing_docs)]
 
+pub mod aggregate;
 pub mod availability;
 pub mod cache;
 mod downloader;
 pub mod manifest;
+mod parallel;
+pub mod retry;
 mod skip_errors;
 mod source;
 pub mod table;
-mod types;
+pub mod verify;
 
 pub use availability::AvailabilityData;
 pub use downloader::Downloader;
-pub use source::{DefaultSource, SourceInfo};
+pub use source::{DefaultSource, LocalSource, SourceInfo};
 use std::io;
 
 /// An error that might happen inside the library.
@@ -47,4 +50,9 @@ pub enum Error {
     /// I/O error.
     #[error("I/O error {0} at {1}")]
     Io(#[source] io::Error, String),
+
+    /// A manifest doesn't match its checksum or signature, or can't be verified while
+    /// verification is required.
+    #[error("Verification of manifest {1} failed: {0}")]
+    Verification(String, String),
 }
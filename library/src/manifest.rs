@@ -7,14 +7,14 @@ use crate::Error;
 use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
     fs::File,
     io::{Read, Write},
     path::Path,
 };
 
 /// A rustup manifest.
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "kebab-case")]
 pub struct Manifest {
     /// A date for which the manifest is generated.
@@ -25,28 +25,64 @@ pub struct Manifest {
     /// A map of package "renames".
     #[serde(default)]
     pub renames: HashMap<String, Rename>,
+    /// Installation profiles, like `minimal` or `default`, and the packages they consist of.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub profiles: HashMap<String, Vec<String>>,
 }
 
 /// Package renaming
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Rename {
     /// New name of the package.
     pub to: String,
 }
 
 /// Package info.
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct PackageTargets {
+    /// Version of the package.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// The commit of the `rust-lang/rust` repository the package was built from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_commit_hash: Option<String>,
     /// Maps targets onto package availability info.
     #[serde(rename = "target")]
     pub targets: HashMap<String, PackageInfo>,
 }
 
 /// A per-target package information.
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct PackageInfo {
     /// If a package is available for a specific target.
     pub available: bool,
+    /// Where the package can be downloaded from, if it is available.
+    #[serde(flatten)]
+    pub artifact: Artifact,
+}
+
+/// Download locations of a package.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Artifact {
+    /// URL of a gzip-compressed tarball.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// SHA-256 hash of the gzip-compressed tarball.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// URL of an xz-compressed tarball.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xz_url: Option<String>,
+    /// SHA-256 hash of the xz-compressed tarball.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xz_hash: Option<String>,
+}
+
+impl Artifact {
+    /// Checks whether no download location is known.
+    pub fn is_empty(&self) -> bool {
+        self.url.is_none() && self.xz_url.is_none()
+    }
 }
 
 impl Manifest {
@@ -60,6 +96,34 @@ impl Manifest {
         toml::from_str(&data).map_err(|e| Error::TomlDe(e, format!("{:?}", path)))
     }
 
+    /// Returns `(target, package)` pairs of all the packages available in the manifest.
+    pub fn available_packages(&self) -> BTreeSet<(&str, &str)> {
+        self.packages
+            .iter()
+            .flat_map(|(package, targets)| {
+                targets
+                    .targets
+                    .iter()
+                    .filter(|(_, info)| info.available)
+                    .map(move |(target, _)| (target.as_str(), package.as_str()))
+            })
+            .collect()
+    }
+
+    /// Serializes the `Manifest` with everything sorted by name, so the same contents are always
+    /// serialized the same way.
+    pub(crate) fn to_canonical_vec(&self) -> Result<Vec<u8>, Error> {
+        let err = |e| Error::TomlSer(e, format!("serializing {}", self.date));
+        let value = toml::Value::try_from(self).map_err(err)?;
+        toml::to_vec(&value).map_err(err)
+    }
+
+    /// Makes a lowercase hex SHA-256 digest of the manifest's contents, which doesn't depend on the
+    /// order of the packages, the targets or the renames.
+    pub fn content_hash(&self) -> Result<String, Error> {
+        Ok(crate::verify::sha256(&self.to_canonical_vec()?))
+    }
+
     /// Serializes the `Manifest` to a given path.
     pub fn save_to_file(&self, path: impl AsRef<Path>) -> Result<(), Error> {
         let path = path.as_ref();
@@ -102,26 +166,41 @@ to = "rls-preview"
 
 [renames.rustfmt]
 to = "rustfmt-preview"
+
+[profiles]
+minimal = ["cargo"]
+default = ["cargo", "rustfmt-preview"]
 "#;
         let parsed_manifest: Manifest = toml::from_str(data).unwrap();
         let reference_manifest = Manifest {
-            date: NaiveDate::from_ymd(2018, 9, 3),
+            date: NaiveDate::from_ymd_opt(2018, 9, 3).unwrap(),
             packages: vec![
                 (
                     "cargo".to_string(),
                     PackageTargets {
+                        version: None,
+                        git_commit_hash: None,
                         targets: vec![
                             (
                                 "aarch64-unknown-linux-gnu".to_string(),
-                                PackageInfo { available: true },
+                                PackageInfo {
+                                    available: true,
+                                    ..Default::default()
+                                },
                             ),
                             (
                                 "arm-unknown-linux-gnueabi".to_string(),
-                                PackageInfo { available: true },
+                                PackageInfo {
+                                    available: true,
+                                    ..Default::default()
+                                },
                             ),
                             (
                                 "arm-unknown-linux-gnueabihf".to_string(),
-                                PackageInfo { available: false },
+                                PackageInfo {
+                                    available: false,
+                                    ..Default::default()
+                                },
                             ),
                         ]
                         .into_iter()
@@ -131,18 +210,29 @@ to = "rustfmt-preview"
                 (
                     "rustfmt-preview".to_string(),
                     PackageTargets {
+                        version: None,
+                        git_commit_hash: None,
                         targets: vec![
                             (
                                 "aarch64-unknown-linux-gnu".to_string(),
-                                PackageInfo { available: true },
+                                PackageInfo {
+                                    available: true,
+                                    ..Default::default()
+                                },
                             ),
                             (
                                 "x86_64-unknown-freebsd".to_string(),
-                                PackageInfo { available: false },
+                                PackageInfo {
+                                    available: false,
+                                    ..Default::default()
+                                },
                             ),
                             (
                                 "x86_64-unknown-linux-gnu".to_string(),
-                                PackageInfo { available: true },
+                                PackageInfo {
+                                    available: true,
+                                    ..Default::default()
+                                },
                             ),
                         ]
                         .into_iter()
@@ -168,8 +258,68 @@ to = "rustfmt-preview"
             ]
             .into_iter()
             .collect(),
+            profiles: vec![
+                ("minimal".to_string(), vec!["cargo".to_string()]),
+                (
+                    "default".to_string(),
+                    vec!["cargo".to_string(), "rustfmt-preview".to_string()],
+                ),
+            ]
+            .into_iter()
+            .collect(),
         };
         assert_eq!(reference_manifest, parsed_manifest);
+        assert_eq!(
+            vec![
+                ("aarch64-unknown-linux-gnu", "cargo"),
+                ("aarch64-unknown-linux-gnu", "rustfmt-preview"),
+                ("arm-unknown-linux-gnueabi", "cargo"),
+                ("x86_64-unknown-linux-gnu", "rustfmt-preview"),
+            ],
+            parsed_manifest
+                .available_packages()
+                .into_iter()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn content_hash() {
+        let manifest = |data: &str| toml::from_str::<Manifest>(data).unwrap();
+        let original = manifest(
+            r#"date = "2024-05-01"
+[pkg.cargo.target.x86_64-unknown-linux-gnu]
+available = true
+[pkg.cargo.target.aarch64-unknown-linux-gnu]
+available = true
+[pkg.rustc.target.x86_64-unknown-linux-gnu]
+available = true
+"#,
+        );
+        let reordered = manifest(
+            r#"date = "2024-05-01"
+[pkg.rustc.target.x86_64-unknown-linux-gnu]
+available = true
+[pkg.cargo.target.aarch64-unknown-linux-gnu]
+available = true
+[pkg.cargo.target.x86_64-unknown-linux-gnu]
+available = true
+"#,
+        );
+        let republished = manifest(
+            r#"date = "2024-05-01"
+[pkg.cargo.target.x86_64-unknown-linux-gnu]
+available = true
+[pkg.cargo.target.aarch64-unknown-linux-gnu]
+available = false
+[pkg.rustc.target.x86_64-unknown-linux-gnu]
+available = true
+"#,
+        );
+        let hash = original.content_hash().unwrap();
+        assert_eq!(64, hash.len());
+        assert_eq!(hash, reordered.content_hash().unwrap());
+        assert_ne!(hash, republished.content_hash().unwrap());
     }
 
     #[test]
@@ -184,11 +334,24 @@ xz_hash = "dbb913da0a207ae80c53bc6a42074b637920c2a80121420416579fed3e7f2499"
 "#;
         let parsed_manifest: Manifest = toml::from_str(data).unwrap();
         let reference_manifest = Manifest {
-            date: NaiveDate::from_ymd(2018, 9, 3),
+            date: NaiveDate::from_ymd_opt(2018, 9, 3).unwrap(),
             packages: vec![(
                 "rust-src".to_string(),
                 PackageTargets {
-                    targets: vec![("*".to_string(), PackageInfo { available: true })]
+                    version: None,
+                    git_commit_hash: None,
+                    targets: vec![(
+                        "*".to_string(),
+                        PackageInfo {
+                            available: true,
+                            artifact: Artifact {
+                                url: Some("https://static.rust-lang.org/dist/2018-09-03/rust-src-nightly.tar.gz".into()),
+                                hash: Some("79f524d39ccd7fc28be473d1ec35e77ff18322488d455e046c7fe850f2a56b67".into()),
+                                xz_url: Some("https://static.rust-lang.org/dist/2018-09-03/rust-src-nightly.tar.xz".into()),
+                                xz_hash: Some("dbb913da0a207ae80c53bc6a42074b637920c2a80121420416579fed3e7f2499".into()),
+                            },
+                        },
+                    )]
                         .into_iter()
                         .collect(),
                 },
@@ -196,8 +359,11 @@ xz_hash = "dbb913da0a207ae80c53bc6a42074b637920c2a80121420416579fed3e7f2499"
             .into_iter()
             .collect(),
             renames: vec![].into_iter().collect(),
+            profiles: HashMap::new(),
         };
         assert_eq!(reference_manifest, parsed_manifest);
+        let serialized = toml::to_string(&parsed_manifest).unwrap();
+        assert_eq!(parsed_manifest, toml::from_str(&serialized).unwrap());
     }
 
     #[test]
@@ -250,6 +416,10 @@ hash = "74b5d5ff8434c15359eccd15dfc097a1d9c7a3ac44a21718b2998e8cccc347a9"
 xz_url = "https://static.rust-lang.org/dist/2018-09-03/rustfmt-nightly-x86_64-unknown-linux-gnu.tar.xz"
 xz_hash = "85c786cfd3f7531a26e004819651da00540e24f83f5d8de0e3ab991730b4cc0d"
 "#;
-        let _manifest: Manifest = toml::from_str(data).unwrap();
+        let manifest: Manifest = toml::from_str(data).unwrap();
+        assert_eq!(
+            Some("0.30.0-nightly (0ec7281b9 2018-08-20)"),
+            manifest.packages["cargo"].version.as_deref()
+        );
     }
 }
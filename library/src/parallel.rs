@@ -0,0 +1,113 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread, vec,
+};
+
+/// Lazily applies `f` to the items using up to `threads` threads. The order of the results
+/// matches the order of the items.
+///
+/// The items are processed in batches of `threads`, and the next batch only starts once the
+/// results of the previous one are consumed, so dropping the iterator stops the processing.
+pub fn parallel_map<T, R, F>(items: Vec<T>, threads: usize, f: F) -> ParallelMap<T, R, F>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    ParallelMap {
+        items: items.into_iter(),
+        threads: threads.max(1),
+        f,
+        ready: Vec::new().into_iter(),
+    }
+}
+
+/// An iterator returned by [`parallel_map`].
+pub struct ParallelMap<T, R, F> {
+    items: vec::IntoIter<T>,
+    threads: usize,
+    f: F,
+    ready: vec::IntoIter<R>,
+}
+
+impl<T, R, F> Iterator for ParallelMap<T, R, F>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    type Item = R;
+
+    fn next(&mut self) -> Option<R> {
+        if let Some(result) = self.ready.next() {
+            return Some(result);
+        }
+        let batch: Vec<T> = self.items.by_ref().take(self.threads).collect();
+        if batch.is_empty() {
+            return None;
+        }
+        self.ready = map_batch(&batch, &self.f).into_iter();
+        self.ready.next()
+    }
+}
+
+/// Applies `f` to every item, one thread per item.
+fn map_batch<T, R, F>(items: &[T], f: &F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    if items.len() == 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..items.len()).map(|_| None).collect::<Vec<_>>());
+    thread::scope(|scope| {
+        for _ in 0..items.len() {
+            scope.spawn(|| {
+                let idx = next.fetch_add(1, Ordering::Relaxed);
+                let result = f(&items[idx]);
+                results.lock().unwrap()[idx] = Some(result);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("Every item is processed"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_order() {
+        let items: Vec<u64> = (0..100).collect();
+        let expected: Vec<u64> = items.iter().map(|x| x * 2).collect();
+        for threads in &[0, 1, 3, 8, 200] {
+            let actual: Vec<u64> = parallel_map(items.clone(), *threads, |x| x * 2).collect();
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn stops_when_dropped() {
+        let processed = AtomicUsize::new(0);
+        let first: Vec<u64> = parallel_map((0..100).collect(), 4, |x: &u64| {
+            processed.fetch_add(1, Ordering::Relaxed);
+            *x
+        })
+        .take(5)
+        .collect();
+        assert_eq!(vec![0, 1, 2, 3, 4], first);
+        assert_eq!(8, processed.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,145 @@
+//! Retrying of failed downloads.
+
+use crate::Error;
+use rand::Rng;
+use std::{thread, time::Duration};
+
+/// Describes how failed downloads are retried.
+///
+/// Only transient errors, i.e. network errors and `5xx` / `429` HTTP responses, are retried. The
+/// delay between attempts grows exponentially starting with `initial_backoff` up to
+/// `max_backoff`.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one.
+    pub max_attempts: u32,
+    /// A delay before the first retry.
+    pub initial_backoff: Duration,
+    /// Maximum delay between attempts.
+    pub max_backoff: Duration,
+    /// A fraction (from 0 to 1) of a delay that is randomly added or subtracted to it, so multiple
+    /// simultaneous downloads don't retry at the very same time.
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    /// No retries at all.
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            jitter: 0.,
+        }
+    }
+}
+
+/// Checks if an error might go away if a request is repeated.
+fn is_transient(e: &Error) -> bool {
+    match e {
+        Error::Reqwest(..) => true,
+        Error::BadResponse(status, _) => {
+            status.is_server_error() || *status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        }
+        _ => false,
+    }
+}
+
+/// A random number in `[-1, 1]` to spread retries.
+fn random_unit() -> f64 {
+    rand::thread_rng().gen_range(-1.0..=1.0)
+}
+
+impl RetryPolicy {
+    /// Calculates a delay before a given retry (starting from zero).
+    fn backoff(&self, retry: u32) -> Duration {
+        let delay = self
+            .initial_backoff
+            .checked_mul(2u32.saturating_pow(retry))
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+        let jitter = self.jitter.clamp(0., 1.) * random_unit();
+        delay.mul_f64(1. + jitter)
+    }
+
+    /// Runs a given function until it succeeds, fails with a non-transient error or runs out of
+    /// attempts.
+    pub fn run<T>(&self, mut f: impl FnMut() -> Result<T, Error>) -> Result<T, Error> {
+        let mut retry = 0;
+        loop {
+            match f() {
+                Err(e) if retry + 1 < self.max_attempts && is_transient(&e) => {
+                    let delay = self.backoff(retry);
+                    log::warn!("{}, retrying in {:?}", e, delay);
+                    thread::sleep(delay);
+                    retry += 1;
+                }
+                result => break result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_exponentially() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
+            jitter: 0.,
+        };
+        assert_eq!(Duration::from_millis(100), policy.backoff(0));
+        assert_eq!(Duration::from_millis(200), policy.backoff(1));
+        assert_eq!(Duration::from_millis(400), policy.backoff(2));
+        assert_eq!(Duration::from_millis(500), policy.backoff(3));
+        assert_eq!(Duration::from_millis(500), policy.backoff(40));
+    }
+
+    #[test]
+    fn jitter_spreads_backoff() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_millis(1000),
+            max_backoff: Duration::from_millis(1000),
+            jitter: 0.5,
+        };
+        let delays: Vec<_> = (0..20).map(|_| policy.backoff(0)).collect();
+        assert!(delays.iter().all(|d| (500..=1500).contains(&d.as_millis())));
+        assert!(delays.iter().any(|d| *d != delays[0]));
+    }
+
+    #[test]
+    fn retries_transient_errors_only() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            ..Default::default()
+        };
+
+        let mut attempts = 0;
+        let result: Result<(), _> = policy.run(|| {
+            attempts += 1;
+            Err(Error::BadResponse(
+                reqwest::StatusCode::BAD_GATEWAY,
+                "url".into(),
+            ))
+        });
+        assert!(result.is_err());
+        assert_eq!(3, attempts);
+
+        let mut attempts = 0;
+        let result: Result<(), _> = policy.run(|| {
+            attempts += 1;
+            Err(Error::BadResponse(
+                reqwest::StatusCode::NOT_FOUND,
+                "url".into(),
+            ))
+        });
+        assert!(result.is_err());
+        assert_eq!(1, attempts);
+    }
+}
@@ -1,17 +1,32 @@
 use crate::Error;
 
+/// How missing manifests are skipped.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MissingDays {
+    /// How many missing manifests are skipped.
+    pub limit: usize,
+    /// Whether the `limit` applies to the manifests missing in a row rather than to all of them.
+    pub in_a_row: bool,
+    /// Whether to stop, rather than to fail, once more manifests than the `limit` are missing.
+    pub stop: bool,
+}
+
 /// An iterator wrapper to skip missing manifests.
 pub struct SkipMissing<I: IntoIterator> {
     inner: I::IntoIter,
-    to_skip: usize,
+    policy: MissingDays,
+    missing: usize,
+    stopped: bool,
 }
 
 impl<I: IntoIterator> SkipMissing<I> {
-    /// Create a wrapper.
-    pub fn new(inner: I, to_skip: usize) -> Self {
+    /// Create a wrapper that skips missing manifests according to the `policy`.
+    pub fn with_policy(inner: I, policy: MissingDays) -> Self {
         SkipMissing {
             inner: inner.into_iter(),
-            to_skip,
+            policy,
+            missing: 0,
+            stopped: false,
         }
     }
 }
@@ -20,33 +35,151 @@ impl<I: IntoIterator<Item = Result<T, Error>>, T> Iterator for SkipMissing<I> {
     type Item = Result<T, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.stopped {
+            return None;
+        }
         loop {
-            let next = self.inner.next()?;
-            if self.to_skip == 0 {
-                break Some(next);
-            } else {
-                match next {
-                    Ok(x) => break Some(Ok(x)),
-                    Err(Error::BadResponse(reqwest::StatusCode::NOT_FOUND, url)) => {
-                        log::warn!("Missing a manifest: {}", url);
-                        self.to_skip -= 1;
+            match self.inner.next()? {
+                Err(Error::BadResponse(reqwest::StatusCode::NOT_FOUND, url))
+                    if self.missing < self.policy.limit =>
+                {
+                    log::warn!("Missing a manifest: {}", url);
+                    self.missing += 1;
+                }
+                Err(Error::BadResponse(reqwest::StatusCode::NOT_FOUND, url))
+                    if self.policy.stop =>
+                {
+                    log::warn!(
+                        "Stopped looking for manifests at {}: more than {} are missing{}",
+                        url,
+                        self.policy.limit,
+                        if self.policy.in_a_row {
+                            " in a row"
+                        } else {
+                            ""
+                        }
+                    );
+                    self.stopped = true;
+                    return None;
+                }
+                Ok(x) => {
+                    if self.policy.in_a_row {
+                        self.missing = 0;
                     }
-                    Err(e) => break Some(Err(e)),
+                    break Some(Ok(x));
                 }
+                next => break Some(next),
             }
         }
     }
 }
 
-/// An extension trait that adds a `skip_missing` method for iterators.
+/// An iterator wrapper to skip any errors.
+pub struct SkipFailed<I: IntoIterator> {
+    inner: I::IntoIter,
+    to_skip: usize,
+}
+
+impl<I: IntoIterator<Item = Result<T, Error>>, T> Iterator for SkipFailed<I> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.inner.next()? {
+                Err(e) if self.to_skip != 0 => {
+                    log::warn!("Skipping a failed manifest: {}", e);
+                    self.to_skip -= 1;
+                }
+                next => break Some(next),
+            }
+        }
+    }
+}
+
+/// An extension trait that adds `skip_missing_with` and `skip_failed` methods for iterators.
 pub trait SkipMissingExt: Iterator {
-    /// Skips 404 HTTP errors, but not more than `days` times.
-    fn skip_missing<T>(self, days: usize) -> SkipMissing<Self>
+    /// Skips 404 HTTP errors according to the `policy`.
+    fn skip_missing_with<T>(self, policy: MissingDays) -> SkipMissing<Self>
     where
         Self: Iterator<Item = Result<T, Error>> + Sized,
     {
-        SkipMissing::new(self, days)
+        SkipMissing::with_policy(self, policy)
+    }
+
+    /// Skips any errors, but not more than `days` times.
+    fn skip_failed<T>(self, days: usize) -> SkipFailed<Self>
+    where
+        Self: Iterator<Item = Result<T, Error>> + Sized,
+    {
+        SkipFailed {
+            inner: self,
+            to_skip: days,
+        }
     }
 }
 
 impl<I: Iterator> SkipMissingExt for I {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+
+    fn error(status: StatusCode) -> Result<u32, Error> {
+        Err(Error::BadResponse(status, "url".into()))
+    }
+
+    #[test]
+    fn skip_missing_and_failed() {
+        let items = vec![
+            Ok(1),
+            error(StatusCode::NOT_FOUND),
+            error(StatusCode::BAD_GATEWAY),
+            Ok(2),
+            error(StatusCode::BAD_GATEWAY),
+        ];
+        let policy = MissingDays {
+            limit: 1,
+            ..Default::default()
+        };
+        let skipped: Vec<_> = items
+            .into_iter()
+            .skip_missing_with(policy)
+            .skip_failed(1)
+            .collect();
+        assert_eq!(3, skipped.len());
+        assert_eq!(1, *skipped[0].as_ref().unwrap());
+        assert_eq!(2, *skipped[1].as_ref().unwrap());
+        assert!(skipped[2].is_err());
+    }
+
+    #[test]
+    fn skip_missing_in_a_row() {
+        let items = || {
+            vec![
+                Ok(1),
+                error(StatusCode::NOT_FOUND),
+                Ok(2),
+                error(StatusCode::NOT_FOUND),
+                error(StatusCode::NOT_FOUND),
+                Ok(3),
+            ]
+        };
+        let policy = MissingDays {
+            limit: 1,
+            in_a_row: true,
+            stop: false,
+        };
+        let skipped: Vec<_> = items().into_iter().skip_missing_with(policy).collect();
+        assert_eq!(4, skipped.len());
+        assert!(skipped[2].is_err());
+
+        let policy = MissingDays {
+            stop: true,
+            ..policy
+        };
+        let skipped: Vec<_> = items().into_iter().skip_missing_with(policy).collect();
+        let skipped: Vec<_> = skipped.into_iter().map(Result::unwrap).collect();
+        assert_eq!(vec![1, 2], skipped);
+    }
+}
@@ -1,5 +1,9 @@
 use chrono::NaiveDate;
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    fs,
+    path::{Path, PathBuf},
+};
 
 /// A set of methods that we need to retrieve manifest from a source.
 pub trait SourceInfo {
@@ -13,6 +17,18 @@ pub trait SourceInfo {
     fn make_latest_manifest_url(&self) -> Self::Url;
 }
 
+impl<T: SourceInfo + ?Sized> SourceInfo for Box<T> {
+    type Url = T::Url;
+
+    fn make_manifest_url(&self, date: NaiveDate) -> Self::Url {
+        (**self).make_manifest_url(date)
+    }
+
+    fn make_latest_manifest_url(&self) -> Self::Url {
+        (**self).make_latest_manifest_url()
+    }
+}
+
 /// Default source, i.e. `https://static.rust-lang.org/...`.
 pub struct DefaultSource<'a> {
     channel: &'a str,
@@ -51,3 +67,93 @@ impl<'a> SourceInfo for DefaultSource<'a> {
         format!("{}/channel-rust-{}.toml", self.base_url, self.channel)
     }
 }
+
+/// A scheme of the URLs the [`Downloader`](crate::Downloader) reads from the local file system
+/// instead of downloading.
+pub(crate) const FILE_SCHEME: &str = "file://";
+
+/// A local directory laid out like `https://static.rust-lang.org/dist`, i.e. with the manifests
+/// of every day in a `YYYY-MM-DD` subdirectory, e.g. a mirror for offline runs.
+pub struct LocalSource<'a> {
+    channel: &'a str,
+    dir: PathBuf,
+}
+
+impl<'a> LocalSource<'a> {
+    /// Initializes a new local source of a channel.
+    pub fn new(channel: &'a str, dir: impl Into<PathBuf>) -> Self {
+        LocalSource {
+            channel,
+            dir: dir.into(),
+        }
+    }
+
+    fn file_name(&self) -> String {
+        format!("channel-rust-{}.toml", self.channel)
+    }
+
+    fn url(path: &Path) -> String {
+        format!("{}{}", FILE_SCHEME, path.display())
+    }
+}
+
+impl<'a> SourceInfo for LocalSource<'a> {
+    type Url = String;
+
+    fn make_manifest_url(&self, date: NaiveDate) -> Self::Url {
+        Self::url(&self.dir.join(date.to_string()).join(self.file_name()))
+    }
+
+    /// The latest manifest is the one at the top of the directory, if there's one. Otherwise it's
+    /// the one of the most recent day.
+    fn make_latest_manifest_url(&self) -> Self::Url {
+        let latest = self.dir.join(self.file_name());
+        if latest.exists() {
+            return Self::url(&latest);
+        }
+        let most_recent = fs::read_dir(&self.dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<NaiveDate>().ok())
+            .filter(|date| {
+                self.dir
+                    .join(date.to_string())
+                    .join(self.file_name())
+                    .exists()
+            })
+            .max();
+        match most_recent {
+            Some(date) => self.make_manifest_url(date),
+            // Reported as a missing manifest.
+            None => Self::url(&latest),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_latest_manifest() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        for day in &["2024-05-01", "2024-05-03", "2024-05-02"] {
+            fs::create_dir_all(dir.join(day)).unwrap();
+            fs::write(dir.join(day).join("channel-rust-nightly.toml"), "").unwrap();
+        }
+        fs::create_dir_all(dir.join("2024-05-04")).unwrap();
+        let source = LocalSource::new("nightly", dir);
+        let date = NaiveDate::from_ymd_opt(2024, 5, 3).unwrap();
+        assert_eq!(
+            source.make_manifest_url(date),
+            source.make_latest_manifest_url()
+        );
+
+        fs::write(dir.join("channel-rust-nightly.toml"), "").unwrap();
+        assert_eq!(
+            format!("file://{}", dir.join("channel-rust-nightly.toml").display()),
+            source.make_latest_manifest_url()
+        );
+    }
+}
@@ -1,6 +1,6 @@
 //! A table of statuses.
 
-use crate::availability::{AvailabilityData, AvailabilityRow};
+use crate::availability::{AvailabilityData, AvailabilityRow, ProfileRow, TargetLifecycle};
 use chrono::NaiveDate;
 use std::{borrow::Borrow, iter};
 
@@ -11,8 +11,27 @@ pub struct Table<'a, Additional: 'a = ()> {
     pub current_target: &'a str,
     /// Table's title.
     pub title: Vec<String>,
+    /// Dates of the table's columns, so they can be formatted in a template.
+    pub dates: Vec<NaiveDate>,
+    /// Commits `rustc` was built from on the same dates as the `dates`, if the manifests specify
+    /// them.
+    pub rustc_commits: Vec<Option<&'a str>>,
+    /// Whether there's no manifest for the same dates as the `dates`, so the packages' availability
+    /// on those days is unknown rather than missing.
+    pub manifest_missing: Vec<bool>,
+    /// Whether the manifests of the same dates as the `dates` don't list the target, e.g. before
+    /// it has been added, so its packages are unknown rather than missing. Days without a manifest
+    /// aren't counted.
+    pub target_missing: Vec<bool>,
+    /// Whether the target has been added or removed within the `dates`.
+    pub lifecycle: TargetLifecycle,
     /// A list of packages and their availabilities sorted by package name in an ascending order.
+    /// The target-independent packages aren't listed.
     pub packages_availability: Vec<AvailabilityRow<'a>>,
+    /// The packages that are the same on every target, e.g. `rust-src`, sorted by package name.
+    pub target_independent: Vec<AvailabilityRow<'a>>,
+    /// Availability of the installation profiles, the smallest one first.
+    pub profiles: Vec<ProfileRow<'a>>,
     /// Additional data to render.
     pub additional: Additional,
 }
@@ -163,16 +182,77 @@ impl<'a, Additional> Table<'a, Additional> {
                     .map(|date| date.borrow().format(date_fmt).to_string()),
             )
             .collect();
+        let target_independent = data.get_target_independent_packages();
         let packages = sort(data.get_available_packages());
         let availability = packages
-            .into_iter()
+            .iter()
+            .filter(|pkg| !target_independent.contains(*pkg))
             .filter_map(|pkg| data.get_availability_row(target, pkg, dates.clone()))
             .collect();
+        let target_independent = sort(target_independent)
+            .into_iter()
+            .filter_map(|pkg| data.get_availability_row("*", pkg, dates.clone()))
+            .collect();
+        let profiles = data
+            .get_profiles()
+            .into_iter()
+            .map(|profile| data.get_profile_row(target, profile, dates.clone()))
+            .collect();
+        let days: Vec<_> = dates.clone().map(|date| *date.borrow()).collect();
+        let lifecycle = data.target_lifecycle(target, &days);
         Table {
             current_target: target,
             title,
+            dates: days,
+            rustc_commits: dates
+                .clone()
+                .map(|date| data.rustc_commit(*date.borrow()))
+                .collect(),
+            manifest_missing: dates
+                .clone()
+                .map(|date| data.is_manifest_missing(*date.borrow()))
+                .collect(),
+            target_missing: dates
+                .clone()
+                .map(|date| {
+                    let date = *date.borrow();
+                    !data.is_manifest_missing(date) && !data.is_target_listed(target, date)
+                })
+                .collect(),
+            lifecycle,
             packages_availability: availability,
+            target_independent,
+            profiles,
             additional: additional_data,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_independent() {
+        let data = r#"date = "2018-09-03"
+[pkg.rust-src.target."*"]
+available = true
+[pkg.rustc.target.lol]
+available = true
+"#;
+        let mut availability: AvailabilityData = Default::default();
+        availability.add_manifest(toml::from_str(data).unwrap());
+        let date = NaiveDate::from_ymd_opt(2018, 9, 3).unwrap();
+        let table = Table::builder(&availability, "lol").dates([date]).build();
+        let names = |rows: &[AvailabilityRow]| -> Vec<String> {
+            rows.iter()
+                .map(|row| row.package_name.to_string())
+                .collect()
+        };
+        assert_eq!(names(&table.packages_availability), ["rustc"]);
+        assert_eq!(names(&table.target_independent), ["rust-src"]);
+        assert_eq!(table.target_independent[0].availability_list, [true]);
+        assert_eq!(table.target_missing, [false]);
+        assert_eq!(table.lifecycle, TargetLifecycle::Established);
+    }
+}
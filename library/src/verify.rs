@@ -0,0 +1,169 @@
+//! Verification of downloaded manifests.
+
+use crate::Error;
+use sha2::{Digest, Sha256};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+/// Describes how downloaded manifests are verified against the `.sha256` checksums (and,
+/// optionally, the `.asc` signatures) published next to them.
+///
+/// A manifest that doesn't match its checksum or signature is always rejected. Manifests that
+/// can't be verified because the companion files are missing are only rejected if verification is
+/// `required`, otherwise they are accepted with a warning.
+#[derive(Debug, Clone, Default)]
+pub struct Verification {
+    /// Reject manifests that can't be verified.
+    pub required: bool,
+    /// A keyring with the Rust release key. If set, the `.asc` signatures are checked with
+    /// `gpgv`, which has to be installed.
+    pub keyring: Option<PathBuf>,
+}
+
+/// Makes a lowercase hex SHA-256 digest of the data.
+pub fn sha256(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Extracts the digest from the contents of a `.sha256` file, i.e. `<digest>  <file name>`.
+pub(crate) fn parse_checksum(contents: &str) -> Option<String> {
+    let digest = contents.split_whitespace().next()?;
+    if digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(digest.to_ascii_lowercase())
+    } else {
+        None
+    }
+}
+
+impl Verification {
+    /// Handles a missing companion file.
+    pub(crate) fn missing(&self, what: &str, url: &str) -> Result<(), Error> {
+        if self.required {
+            Err(Error::Verification(
+                format!("no {} found", what),
+                url.into(),
+            ))
+        } else {
+            log::warn!("Can't verify {}: no {} found", url, what);
+            Ok(())
+        }
+    }
+
+    /// Checks the manifest's data against the contents of its `.sha256` file.
+    pub(crate) fn check_checksum(
+        &self,
+        data: &[u8],
+        checksum: &str,
+        url: &str,
+    ) -> Result<(), Error> {
+        let expected = parse_checksum(checksum)
+            .ok_or_else(|| Error::Verification("malformed checksum".into(), url.into()))?;
+        let actual = sha256(data);
+        if actual == expected {
+            log::debug!("Checksum of {} matches", url);
+            Ok(())
+        } else {
+            Err(Error::Verification(
+                format!("checksum mismatch: expected {}, got {}", expected, actual),
+                url.into(),
+            ))
+        }
+    }
+
+    /// Checks the manifest's data against its detached signature.
+    pub(crate) fn check_signature(
+        &self,
+        data: &[u8],
+        signature: &[u8],
+        url: &str,
+    ) -> Result<(), Error> {
+        let keyring = match &self.keyring {
+            Some(keyring) => keyring,
+            None => return Ok(()),
+        };
+        // `gpgv` reads the data from the stdin, but wants the signature in a file, which is
+        // created exclusively and removed once it's dropped.
+        let mut signature_file = tempfile::Builder::new()
+            .suffix(".asc")
+            .tempfile()
+            .map_err(|e| Error::Io(e, "creating a temporary signature file".into()))?;
+        signature_file
+            .write_all(signature)
+            .map_err(|e| Error::Io(e, signature_file.path().display().to_string()))?;
+        let output = run_gpgv(keyring, signature_file.path(), data)
+            .map_err(|e| Error::Io(e, "gpgv".into()))?;
+        if output.status.success() {
+            log::debug!("Signature of {} is valid", url);
+            Ok(())
+        } else {
+            Err(Error::Verification(
+                format!(
+                    "bad signature: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+                url.into(),
+            ))
+        }
+    }
+}
+
+fn run_gpgv(
+    keyring: &Path,
+    signature: &Path,
+    data: &[u8],
+) -> std::io::Result<std::process::Output> {
+    let mut child = Command::new("gpgv")
+        .arg("--keyring")
+        .arg(keyring)
+        .arg(signature)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // `gpgv` might bail out before reading everything, the exit code tells why.
+    let _ = child.stdin.take().expect("Piped stdin").write_all(data);
+    child.wait_with_output()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums() {
+        let digest = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert_eq!(digest, sha256(b"abc"));
+        let contents = format!("{}  channel-rust-nightly.toml\n", digest.to_uppercase());
+        assert_eq!(Some(digest.to_string()), parse_checksum(&contents));
+        assert_eq!(None, parse_checksum("<html>Not found</html>"));
+
+        let verification = Verification::default();
+        assert!(verification
+            .check_checksum(b"abc", &contents, "url")
+            .is_ok());
+        assert!(matches!(
+            verification.check_checksum(b"abd", &contents, "url"),
+            Err(Error::Verification(..))
+        ));
+    }
+
+    #[test]
+    fn missing_companions() {
+        assert!(Verification::default().missing("checksum", "url").is_ok());
+        let required = Verification {
+            required: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            required.missing("checksum", "url"),
+            Err(Error::Verification(..))
+        ));
+    }
+}
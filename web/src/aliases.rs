@@ -0,0 +1,97 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use rustup_available_packages::manifest::{Manifest, PackageTargets};
+
+use crate::opts;
+
+/// Makes the packages and targets of a manifest go by their aliases, merging
+/// the ones that end up under the same name: a package is available if it is
+/// available under any of its names.
+pub fn apply(manifest: &mut Manifest, aliases: &opts::Aliases) {
+    if aliases.packages.is_empty() && aliases.targets.is_empty() {
+        return;
+    }
+    // Packages renamed by the manifest itself go by their old names.
+    let old_names: HashMap<_, _> = manifest
+        .renames
+        .iter()
+        .map(|(old, rename)| (rename.to.clone(), old.clone()))
+        .collect();
+    let mut aliased = HashSet::new();
+    // Merged in the order of the names, so the same package wins every time.
+    let packages: BTreeMap<_, _> = std::mem::take(&mut manifest.packages).into_iter().collect();
+    for (name, info) in packages {
+        let old_name = old_names.get(&name).unwrap_or(&name);
+        let name = match aliases.packages.get(old_name) {
+            Some(alias) => {
+                aliased.insert(name);
+                alias.clone()
+            }
+            None => name,
+        };
+        let merged = manifest
+            .packages
+            .entry(name)
+            .or_insert_with(|| PackageTargets {
+                version: None,
+                git_commit_hash: None,
+                targets: HashMap::new(),
+            });
+        merged.version = merged.version.take().or(info.version);
+        merged.git_commit_hash = merged.git_commit_hash.take().or(info.git_commit_hash);
+        let targets: BTreeMap<_, _> = info.targets.into_iter().collect();
+        for (target, target_info) in targets {
+            let target = aliases.targets.get(&target).cloned().unwrap_or(target);
+            merged
+                .targets
+                .entry(target)
+                .and_modify(|merged| merged.available |= target_info.available)
+                .or_insert(target_info);
+        }
+    }
+    for packages in manifest.profiles.values_mut() {
+        for name in packages {
+            let old_name = old_names.get(name).unwrap_or(name);
+            if let Some(alias) = aliases.packages.get(old_name) {
+                *name = alias.clone();
+            }
+        }
+    }
+    // Otherwise the aliased packages would go by their old names again.
+    manifest
+        .renames
+        .retain(|_, rename| !aliased.contains(&rename.to));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_in_the_order_of_the_names() {
+        let manifest = r#"date = "2024-01-01"
+[pkg.rust-analyzer]
+version = "1.0"
+[pkg.rust-analyzer.target.x86_64-unknown-linux-gnu]
+available = false
+[pkg.rust-analyzer-preview]
+version = "0.9"
+[pkg.rust-analyzer-preview.target.x86_64-unknown-linux-gnu]
+available = true
+"#;
+        let aliases = opts::Aliases {
+            packages: vec![("rust-analyzer-preview".into(), "rust-analyzer".into())]
+                .into_iter()
+                .collect(),
+            targets: BTreeMap::new(),
+        };
+        for _ in 0..10 {
+            let mut manifest: Manifest = toml::from_str(manifest).unwrap();
+            apply(&mut manifest, &aliases);
+            assert_eq!(1, manifest.packages.len());
+            let merged = &manifest.packages["rust-analyzer"];
+            assert_eq!(Some("1.0"), merged.version.as_deref());
+            assert!(merged.targets["x86_64-unknown-linux-gnu"].available);
+        }
+    }
+}
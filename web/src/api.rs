@@ -0,0 +1,260 @@
+//! A JSON API over the data of the latest rendering, served under `/api/v1/`.
+
+use chrono::NaiveDate;
+use hyper::{header::CONTENT_TYPE, Body, Response, StatusCode};
+use percent_encoding::percent_decode_str;
+use rustup_available_packages::availability::{AvailabilityStats, Change};
+use rustup_status::LoadedChannel;
+use serde::Serialize;
+use std::borrow::Cow;
+
+use crate::serve::status;
+
+pub const PREFIX: &str = "/api/v1/";
+
+#[derive(Serialize)]
+struct TargetInfo<'a> {
+    name: &'a str,
+    /// Packages available on the latest date.
+    available: Vec<&'a str>,
+    /// Packages that have been available before, but not on the latest date.
+    missing: Vec<&'a str>,
+}
+
+#[derive(Serialize)]
+struct Targets<'a> {
+    channel: &'a str,
+    /// The latest date with a manifest.
+    date: Option<NaiveDate>,
+    targets: Vec<TargetInfo<'a>>,
+}
+
+#[derive(Serialize)]
+struct Day {
+    date: NaiveDate,
+    /// `null` if there is no manifest for the date.
+    available: Option<bool>,
+    version: Option<String>,
+}
+
+#[derive(Serialize)]
+struct PackageHistory<'a> {
+    channel: &'a str,
+    target: &'a str,
+    package: &'a str,
+    /// The latest day first.
+    days: Vec<Day>,
+    last_available: Option<NaiveDate>,
+    stats: AvailabilityStats,
+}
+
+#[derive(Serialize)]
+struct Changes<'a> {
+    channel: &'a str,
+    since: Option<NaiveDate>,
+    /// The latest change first.
+    changes: Vec<Change<'a>>,
+}
+
+/// Answers a request to the API. `path` is relative to the [`PREFIX`].
+///
+/// Both the `path` and the `query` are percent-encoded.
+pub fn respond(channels: &[LoadedChannel], path: &str, query: Option<&str>) -> Response<Body> {
+    let params: Vec<(Cow<str>, Cow<str>)> =
+        form_urlencoded::parse(query.unwrap_or_default().as_bytes()).collect();
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_ref())
+    };
+    let channel = match param("channel") {
+        Some(name) => channels.iter().find(|channel| channel.name == name),
+        None => channels.first(),
+    };
+    let channel = match channel {
+        Some(channel) => channel,
+        None => return error(StatusCode::NOT_FOUND, "Unknown channel"),
+    };
+    // Decoded one by one, so an encoded slash doesn't split a segment.
+    let segments: Result<Vec<_>, _> = path
+        .trim_end_matches('/')
+        .split('/')
+        .map(|segment| percent_decode_str(segment).decode_utf8())
+        .collect();
+    let segments = match segments {
+        Ok(segments) => segments,
+        Err(_) => return error(StatusCode::BAD_REQUEST, "The path must be UTF-8"),
+    };
+    let segments: Vec<&str> = segments.iter().map(AsRef::as_ref).collect();
+    match segments.as_slice() {
+        ["targets"] => json(&targets(channel)),
+        ["targets", target, "packages", package] => match package_history(channel, target, package)
+        {
+            Some(history) => json(&history),
+            None => error(StatusCode::NOT_FOUND, "Unknown target or package"),
+        },
+        ["changes"] => {
+            let since = match param("since").map(str::parse::<NaiveDate>).transpose() {
+                Ok(since) => since,
+                Err(_) => {
+                    return error(StatusCode::BAD_REQUEST, "`since` must be a YYYY-MM-DD date")
+                }
+            };
+            json(&changes(channel, since))
+        }
+        _ => status(StatusCode::NOT_FOUND),
+    }
+}
+
+/// Dates that have a manifest, the latest first.
+fn known_dates(channel: &LoadedChannel) -> Vec<NaiveDate> {
+    channel
+        .all_dates
+        .iter()
+        .copied()
+        .filter(|&date| !channel.data.is_manifest_missing(date))
+        .collect()
+}
+
+fn targets(channel: &LoadedChannel) -> Targets<'_> {
+    let date = known_dates(channel).first().copied();
+    let mut packages: Vec<_> = channel.data.get_available_packages().into_iter().collect();
+    packages.sort_unstable();
+    let mut targets: Vec<_> = channel.data.get_available_targets().into_iter().collect();
+    targets.sort_unstable();
+    let targets = targets
+        .into_iter()
+        .map(|target| {
+            let (available, missing) = packages
+                .iter()
+                .filter_map(|&package| {
+                    let row = channel.data.get_availability_row(target, package, date)?;
+                    Some((package, row.availability_list.first() == Some(&true)))
+                })
+                .partition::<Vec<_>, _>(|&(_, available)| available);
+            TargetInfo {
+                name: target,
+                available: available.into_iter().map(|(package, _)| package).collect(),
+                missing: missing.into_iter().map(|(package, _)| package).collect(),
+            }
+        })
+        .collect();
+    Targets {
+        channel: &channel.name,
+        date,
+        targets,
+    }
+}
+
+fn package_history<'a>(
+    channel: &'a LoadedChannel,
+    target: &'a str,
+    package: &'a str,
+) -> Option<PackageHistory<'a>> {
+    let data = &channel.data;
+    let row = data.get_availability_row(target, package, &channel.all_dates)?;
+    let days = channel
+        .all_dates
+        .iter()
+        .zip(&row.availability_list)
+        .zip(&row.version_list)
+        .map(|((&date, &available), version)| Day {
+            date,
+            available: Some(available)
+                .filter(|_| !data.is_manifest_missing(date) && data.is_target_listed(target, date)),
+            version: version.clone(),
+        })
+        .collect();
+    Some(PackageHistory {
+        channel: &channel.name,
+        target,
+        package,
+        days,
+        last_available: row.last_available,
+        stats: row.stats,
+    })
+}
+
+fn changes(channel: &LoadedChannel, since: Option<NaiveDate>) -> Changes<'_> {
+    let dates = known_dates(channel);
+    // The day before `since` is needed to tell what has changed on `since`.
+    let dates = match since {
+        Some(since) => {
+            let newer = dates.iter().take_while(|&&date| date >= since).count();
+            &dates[..(newer + 1).min(dates.len())]
+        }
+        None => &dates[..],
+    };
+    Changes {
+        channel: &channel.name,
+        since,
+        changes: channel.data.changes(dates),
+    }
+}
+
+fn json<T: Serialize>(value: &T) -> Response<Body> {
+    match serde_json::to_vec(value) {
+        Ok(body) => Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .expect("Valid response"),
+        Err(e) => {
+            log::error!("Can't serialize an API response: {}", e);
+            status(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+fn error(code: StatusCode, message: &str) -> Response<Body> {
+    let body = serde_json::json!({ "error": message });
+    Response::builder()
+        .status(code)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .expect("Valid response")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::manifest;
+    use rustup_available_packages::AvailabilityData;
+
+    fn channel(name: &str) -> LoadedChannel {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let mut data = AvailabilityData::default();
+        data.add_manifest(manifest(
+            date,
+            &[("rust-src", "x86_64-unknown-linux-gnu", true)],
+        ));
+        LoadedChannel {
+            name: name.to_string(),
+            data,
+            all_dates: vec![date],
+            rendered: 1,
+            manifests: Default::default(),
+            phases: Default::default(),
+        }
+    }
+
+    #[test]
+    fn decodes_the_path_and_the_query() {
+        let channels = [channel("nightly"), channel("1.75 beta")];
+        let path = "targets/x86_64%2Dunknown-linux-gnu/packages/rust%2Dsrc";
+        let response = respond(&channels, path, Some("channel=1.75+beta"));
+        assert_eq!(StatusCode::OK, response.status());
+
+        let response = respond(&channels, path, Some("channel=1.75%20beta"));
+        assert_eq!(StatusCode::OK, response.status());
+
+        let response = respond(&channels, "targets/a%2Fb/packages/rust-src", None);
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+
+        let response = respond(&channels, "targets/%FF/packages/rust-src", None);
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+
+        let response = respond(&channels, "changes", Some("since=2024%2D01%2D01"));
+        assert_eq!(StatusCode::OK, response.status());
+    }
+}
@@ -0,0 +1,118 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Context;
+use chrono::NaiveDate;
+use rustup_available_packages::AvailabilityData;
+
+use crate::{
+    fs_tree::DayStatus,
+    opts,
+    output::{Output, Purpose},
+    ChannelInfo,
+};
+
+/// Name of the link to the latest snapshot.
+const LATEST: &str = "latest";
+
+/// Stores the status of every package on every target on a given date under
+/// `$archive/$date/availability.json`, points `$archive/latest` at it and
+/// removes the snapshots that are too old to be kept.
+pub fn generate_archive(
+    data: &AvailabilityData,
+    date: NaiveDate,
+    archive: &opts::Archive,
+    channel: ChannelInfo,
+    out: &mut dyn Output,
+) -> anyhow::Result<()> {
+    let root = channel.dir(&archive.path);
+    let mut packages: Vec<_> = data.get_available_packages().into_iter().collect();
+    packages.sort_unstable();
+    let snapshot: BTreeMap<_, BTreeMap<_, _>> = data
+        .get_available_targets()
+        .into_iter()
+        // A day's snapshot only has the targets of the day's manifest.
+        .filter(|target| data.is_target_listed(target, date))
+        .map(|target| {
+            let statuses = packages
+                .iter()
+                .filter_map(|&package| {
+                    let mut row = data.get_availability_row(target, package, [date])?;
+                    let status = DayStatus {
+                        available: row.availability_list[0],
+                        version: row.version_list.pop().flatten(),
+                        artifact: row.artifact_list.pop().flatten(),
+                        // The same for all the packages of the day.
+                        rustc_commit: None,
+                        target_missing: false,
+                        manifest_missing: false,
+                    };
+                    Some((package, status))
+                })
+                .collect();
+            (target, statuses)
+        })
+        .collect();
+
+    let day = date.format("%Y-%m-%d").to_string();
+    log::info!("Archiving {} to {}", day, root.display());
+    out.set_purpose(Purpose::Archive);
+    out.write(
+        &root.join(&day).join("availability.json"),
+        &serde_json::to_vec(&snapshot)?,
+    )?;
+    out.symlink(Path::new(&day), &root.join(LATEST))?;
+
+    if let Some(keep_days) = archive.keep_days {
+        for name in out.list_dir(&root)? {
+            let old = match name.parse::<NaiveDate>() {
+                Ok(old) => old,
+                // Not a snapshot, e.g. a subdirectory of another channel.
+                Err(_) => continue,
+            };
+            // The snapshot that has just been written is always kept, so
+            // `latest` never dangles.
+            if old < date && (date - old).num_days() >= keep_days as i64 {
+                log::info!("Removing archived {}", old);
+                out.remove_dir(&root.join(&name))
+                    .with_context(|| format!("Can't remove archived {}", old))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fake_data::test_data, output::MemoryOutput};
+
+    #[test]
+    fn keeps_the_latest_snapshot() {
+        let (data, dates) = test_data(3);
+        let channels = vec!["nightly".to_string()];
+        let channel = ChannelInfo::new("nightly", &channels);
+        let root = Path::new("archive");
+        for keep_days in [0, 2] {
+            let archive = opts::Archive {
+                path: root.into(),
+                keep_days: Some(keep_days),
+            };
+            let mut out = MemoryOutput::default();
+            for &date in dates.iter().rev() {
+                generate_archive(&data, date, &archive, channel, &mut out).unwrap();
+            }
+            let mut kept = out.list_dir(root).unwrap();
+            kept.retain(|name| name != LATEST);
+            let expected: Vec<_> = dates[..keep_days.max(1)]
+                .iter()
+                .rev()
+                .map(|date| date.to_string())
+                .collect();
+            assert_eq!(kept, expected, "keep_days: {}", keep_days);
+            let latest = out.into_tree(root);
+            let snapshot: serde_json::Value =
+                serde_json::from_slice(&latest["latest/availability.json"]).unwrap();
+            assert!(snapshot["x86_64-unknown-linux-gnu"]["rustc"]["available"].is_boolean());
+        }
+    }
+}
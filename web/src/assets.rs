@@ -0,0 +1,243 @@
+//! Static assets, copied into the output under names with a hash of their
+//! contents, so browsers never use a stale copy of a changed file.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use rustup_available_packages::verify::sha256;
+
+use crate::{
+    opts,
+    output::{Output, Purpose},
+};
+
+/// How many hex digits of the hash are added to the names.
+const HASH_LEN: usize = 10;
+
+struct Asset {
+    contents: Vec<u8>,
+    /// The hashed path relative to the output directory, `/`-separated.
+    hashed: String,
+}
+
+/// All the assets, keyed by their `/`-separated paths relative to their
+/// directories.
+pub struct Assets<'a> {
+    config: &'a opts::Assets,
+    files: BTreeMap<String, Asset>,
+}
+
+/// Recursively collects all the files of a directory.
+fn find_files(dir: &Path, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Can't read {:?}", dir))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Adds a hash to a file name before its extension, e.g. `css/style.css`
+/// becomes `css/style.0123456789.css`.
+fn hashed_name(name: &str, hash: &str) -> String {
+    let (dir, file) = match name.rsplit_once('/') {
+        Some((dir, file)) => (format!("{}/", dir), file),
+        None => (String::new(), name),
+    };
+    match file.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => {
+            format!("{}{}.{}.{}", dir, stem, hash, extension)
+        }
+        _ => format!("{}{}.{}", dir, file, hash),
+    }
+}
+
+impl<'a> Assets<'a> {
+    /// Reads the assets of all the directories. A file of a later directory
+    /// takes precedence over the one with the same path in an earlier one.
+    pub fn load(config: &'a opts::Assets) -> anyhow::Result<Self> {
+        let mut files = BTreeMap::new();
+        for dir in &config.dirs {
+            let mut paths = Vec::new();
+            find_files(dir, &mut paths)?;
+            for path in paths {
+                let name = path
+                    .strip_prefix(dir)
+                    .expect("Found under the directory")
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                let contents =
+                    fs::read(&path).with_context(|| format!("Can't read asset {:?}", path))?;
+                let hash = sha256(&contents);
+                let hashed = hashed_name(&name, &hash[..HASH_LEN]);
+                files.insert(name, Asset { contents, hashed });
+            }
+        }
+        Ok(Assets { config, files })
+    }
+
+    /// Where the hashed assets are, keyed by their original paths: their URLs
+    /// if the `url` is configured, or their paths in the output otherwise.
+    pub fn links(&self) -> BTreeMap<String, String> {
+        self.files
+            .iter()
+            .map(|(name, asset)| {
+                let link = match &self.config.url {
+                    Some(url) => format!("{}/{}", url.trim_end_matches('/'), asset.hashed),
+                    None => self.output_path(asset).to_string_lossy().into_owned(),
+                };
+                (name.clone(), link)
+            })
+            .collect()
+    }
+
+    /// Whether the links of the assets are relative to the pages.
+    pub fn relative(&self) -> bool {
+        self.config.url.is_none()
+    }
+
+    /// Changes whenever any of the assets changes.
+    pub fn fingerprint(&self) -> String {
+        let hashed: Vec<_> = self.files.values().map(|asset| &*asset.hashed).collect();
+        sha256(hashed.join("\n").as_bytes())[..HASH_LEN].to_string()
+    }
+
+    fn output_path(&self, asset: &Asset) -> PathBuf {
+        self.config.output_dir.join(&asset.hashed)
+    }
+
+    /// Copies the assets into the output directory, and removes the previous
+    /// copies of them.
+    pub fn write(&self, out: &mut dyn Output) -> anyhow::Result<()> {
+        out.set_purpose(Purpose::Asset);
+        for asset in self.files.values() {
+            let path = self.output_path(asset);
+            log::info!("Writing asset to {:?}", path);
+            out.write(&path, &asset.contents)?;
+        }
+        self.remove_stale(out)
+    }
+
+    /// Removes the copies of the assets with other hashes from the
+    /// directories the assets are written to.
+    fn remove_stale(&self, out: &mut dyn Output) -> anyhow::Result<()> {
+        let mut dirs: BTreeMap<PathBuf, Vec<(&str, &str)>> = BTreeMap::new();
+        for (name, asset) in &self.files {
+            let path = self.output_path(asset);
+            let dir = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+            dirs.entry(dir)
+                .or_default()
+                .push((file_name(name), file_name(&asset.hashed)));
+        }
+        for (dir, assets) in dirs {
+            for file in out.list_dir(&dir)? {
+                let original = match unhashed_name(&file) {
+                    Some(original) => original,
+                    None => continue,
+                };
+                let stale = assets
+                    .iter()
+                    .any(|&(name, hashed)| name == original && hashed != file);
+                if stale {
+                    log::info!("Removing a previous copy {:?}", dir.join(&file));
+                    out.remove_file(&dir.join(&file))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The last component of a `/`-separated path.
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// Reverts [`hashed_name`] for a file name, if it has a hash.
+fn unhashed_name(file: &str) -> Option<String> {
+    let is_hash = |part: &str| {
+        part.len() == HASH_LEN
+            && part
+                .chars()
+                .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+    };
+    let mut parts: Vec<_> = file.split('.').collect();
+    if parts.len() < 2 {
+        return None;
+    }
+    let hash = if parts.len() > 2 && is_hash(parts[parts.len() - 2]) {
+        parts.len() - 2
+    } else if is_hash(parts[parts.len() - 1]) {
+        parts.len() - 1
+    } else {
+        return None;
+    };
+    parts.remove(hash);
+    Some(parts.join("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::output::MemoryOutput;
+
+    #[test]
+    fn hashed_names() {
+        assert_eq!(
+            hashed_name("css/style.css", "0123456789"),
+            "css/style.0123456789.css"
+        );
+        assert_eq!(hashed_name("LICENSE", "0123456789"), "LICENSE.0123456789");
+        assert_eq!(
+            unhashed_name("style.0123456789.css").as_deref(),
+            Some("style.css")
+        );
+        assert_eq!(
+            unhashed_name("LICENSE.0123456789").as_deref(),
+            Some("LICENSE")
+        );
+        assert_eq!(unhashed_name("style.css"), None);
+        assert_eq!(unhashed_name("style.0123456789.css.gz"), None);
+    }
+
+    #[test]
+    fn stale_copies_are_removed() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("css")).unwrap();
+        fs::write(dir.join("css/style.css"), "body {}").unwrap();
+        let config = opts::Assets {
+            dirs: vec![dir.into()],
+            output_dir: "site/assets".into(),
+            url: None,
+        };
+        let assets = Assets::load(&config).unwrap();
+        let link = assets.links()["css/style.css"].clone();
+
+        let mut out = MemoryOutput::default();
+        out.write(Path::new("site/assets/css/style.0123456789.css"), b"old")
+            .unwrap();
+        out.write(Path::new("site/assets/css/other.0123456789.css"), b"kept")
+            .unwrap();
+        assets.write(&mut out).unwrap();
+        let mut paths = out.paths();
+        paths.sort_unstable();
+        assert_eq!(
+            paths,
+            [
+                PathBuf::from("site/assets/css/other.0123456789.css"),
+                PathBuf::from(&link),
+            ]
+        );
+    }
+}
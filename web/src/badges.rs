@@ -0,0 +1,109 @@
+use std::path::Path;
+
+use anyhow::Context;
+use chrono::NaiveDate;
+use handlebars::Handlebars;
+use rustup_available_packages::AvailabilityData;
+
+use crate::{
+    escape_xml, opts,
+    output::{Output, Purpose},
+    ChannelInfo, PathRenderData,
+};
+
+const AVAILABLE_COLOR: &str = "#4c1";
+const MISSING_COLOR: &str = "#e05d44";
+
+/// Roughly estimates a width of a text in pixels (Verdana, 11px).
+fn text_width(text: &str) -> usize {
+    text.chars().count() * 7 + 10
+}
+
+/// Renders a flat shields.io-style badge.
+fn render_badge(label: &str, message: &str, color: &str) -> String {
+    let label_width = text_width(label);
+    let message_width = text_width(message);
+    let width = label_width + message_width;
+    let (label, message) = (escape_xml(label), escape_xml(message));
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {message}">
+  <title>{label}: {message}</title>
+  <linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient>
+  <clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath>
+  <g clip-path="url(#r)">
+    <rect width="{label_width}" height="20" fill="#555"/>
+    <rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/>
+    <rect width="{width}" height="20" fill="url(#s)"/>
+  </g>
+  <g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">
+    <text x="{label_x}" y="14">{label}</text>
+    <text x="{message_x}" y="14">{message}</text>
+  </g>
+</svg>
+"##,
+        label_x = label_width / 2,
+        message_x = label_width + message_width / 2,
+    )
+}
+
+/// Makes a badge message for a package given its availability on the `dates`
+/// (sorted in descending order).
+fn status_message(dates: &[NaiveDate], availability: &[bool]) -> (String, &'static str) {
+    if availability.first() == Some(&true) {
+        return ("available".into(), AVAILABLE_COLOR);
+    }
+    // The package went missing right after the last day it was available.
+    let missing_since = availability
+        .iter()
+        .position(|&available| available)
+        .and_then(|idx| idx.checked_sub(1))
+        .map(|idx| dates[idx]);
+    match missing_since {
+        Some(date) => (
+            format!("missing since {}", date.format("%Y-%m-%d")),
+            MISSING_COLOR,
+        ),
+        None => ("missing".into(), MISSING_COLOR),
+    }
+}
+
+/// Generates an SVG badge with the current status of every package on every
+/// target.
+///
+/// `dates` are all the known dates in descending order, not only the rendered
+/// ones.
+pub fn generate_badges(
+    data: &AvailabilityData,
+    dates: &[NaiveDate],
+    config: &opts::Badges,
+    channel: ChannelInfo,
+    out: &mut dyn Output,
+) -> anyhow::Result<()> {
+    let handlebars = Handlebars::new();
+    let pkgs = data.get_available_packages();
+    for target in data.get_available_targets() {
+        log::info!("Generating badges for {}", target);
+        for pkg in &pkgs {
+            let row = match data.get_availability_row(target, pkg, dates) {
+                Some(row) => row,
+                None => continue,
+            };
+            let output_path = handlebars
+                .render_template(
+                    &config.output_pattern,
+                    &PathRenderData {
+                        package: Some(pkg),
+                        ..PathRenderData::target(target)
+                    },
+                )
+                .with_context(|| {
+                    format!("Invalid badge output pattern: {}", config.output_pattern)
+                })?;
+            let (message, color) = status_message(dates, &row.availability_list);
+            let badge = render_badge(pkg, &message, color);
+            out.set_purpose(Purpose::Badge);
+            out.write(&channel.relocate(Path::new(&output_path)), badge.as_bytes())?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,193 @@
+use anyhow::Context;
+use chrono::{Duration, NaiveDate};
+use rustup_available_packages::manifest::Manifest;
+use rustup_status::{channel_cache, downloader};
+use structopt::StructOpt;
+
+use crate::ConfigOpt;
+
+/// How many days are fetched at once while walking back.
+const BATCH_DAYS: usize = 7;
+
+#[derive(StructOpt)]
+pub struct BisectOpt {
+    #[structopt(flatten)]
+    config: ConfigOpt,
+    #[structopt(short = "t", long = "target", help = "A target to look into")]
+    target: String,
+    #[structopt(
+        short = "p",
+        long = "package",
+        help = "A package that has gone missing"
+    )]
+    package: String,
+    #[structopt(
+        long = "max-days",
+        help = "How many days to look back at most",
+        default_value = "365"
+    )]
+    max_days: usize,
+}
+
+fn is_available(manifest: &Manifest, target: &str, package: &str) -> bool {
+    manifest
+        .packages
+        .get(package)
+        .and_then(|info| info.targets.get(target).or_else(|| info.targets.get("*")))
+        .is_some_and(|info| info.available)
+}
+
+fn rustc_commit(manifest: &Manifest) -> Option<&str> {
+    manifest.packages.get("rustc")?.git_commit_hash.as_deref()
+}
+
+fn describe(manifest: &Manifest) -> String {
+    match rustc_commit(manifest) {
+        Some(commit) => format!("{} (rustc {})", manifest.date, commit),
+        None => manifest.date.to_string(),
+    }
+}
+
+/// Walks back from the latest manifest, in which a package is missing, until
+/// the package is available, fetching up to `max_days` days in batches with
+/// `fetch(end, days)`, which returns the manifests the latest first.
+///
+/// Returns the last manifest the package is available in and the first one it
+/// is missing from.
+fn bisect(
+    latest: Manifest,
+    target: &str,
+    package: &str,
+    max_days: usize,
+    mut fetch: impl FnMut(NaiveDate, usize) -> anyhow::Result<Vec<Manifest>>,
+) -> anyhow::Result<Option<(Manifest, Manifest)>> {
+    let latest_date = latest.date;
+    let mut first_missing = latest;
+    // Days before the latest one that have been looked into.
+    let mut looked_back = 1;
+    while looked_back < max_days {
+        let days = BATCH_DAYS.min(max_days - looked_back);
+        let end = latest_date - Duration::days(looked_back as i64);
+        for manifest in fetch(end, days)? {
+            if is_available(&manifest, target, package) {
+                return Ok(Some((manifest, first_missing)));
+            }
+            first_missing = manifest;
+        }
+        looked_back += days;
+    }
+    Ok(None)
+}
+
+/// Walks back through the manifests, past the rendered days if needed, to
+/// find the day a package disappeared from a target, and prints the days
+/// around it with the commits `rustc` was built from.
+pub fn run(opts: &BisectOpt) -> anyhow::Result<()> {
+    let (config, channel) = opts.config.load_with_channel()?;
+    // A whole batch may be missing, beta and stable are released rarely.
+    let downloader =
+        downloader(&config, &channel, BATCH_DAYS)?.set_cache(channel_cache(&config, &channel)?);
+    let latest = downloader
+        .get_latest_manifest()
+        .with_context(|| format!("Can't fetch the latest {} manifest", channel))?;
+    if is_available(&latest, &opts.target, &opts.package) {
+        anyhow::bail!(
+            "{} is available on {} in the latest manifest ({})",
+            opts.package,
+            opts.target,
+            latest.date
+        );
+    }
+
+    let found = bisect(
+        latest,
+        &opts.target,
+        &opts.package,
+        opts.max_days,
+        |end, days| {
+            downloader
+                .get_manifests_until(end, days)
+                .with_context(|| format!("Can't fetch the {} manifests", channel))
+        },
+    )?;
+    let (last_available, first_missing) = match found {
+        Some(found) => found,
+        None => anyhow::bail!(
+            "{} hasn't been available on {} in the last {} days",
+            opts.package,
+            opts.target,
+            opts.max_days
+        ),
+    };
+    println!("Last available: {}", describe(&last_available));
+    println!("First missing:  {}", describe(&first_missing));
+    if let (Some(from), Some(to)) = (rustc_commit(&last_available), rustc_commit(&first_missing)) {
+        println!(
+            "Changes: https://github.com/rust-lang/rust/compare/{}...{}",
+            from, to
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustup_status::{fake_data, opts::FakeData};
+
+    #[test]
+    fn finds_the_boundary_across_batches() {
+        let end = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+        let mut manifests = fake_data::manifests(&FakeData::default(), "nightly", end, 30);
+        let target = "x86_64-unknown-linux-gnu";
+        // Missing on the latest 10 days, so the second batch has the boundary.
+        for manifest in &mut manifests[..10] {
+            let info = manifest.packages.get_mut("rustc").unwrap();
+            info.targets.get_mut(target).unwrap().available = false;
+        }
+        for manifest in &mut manifests[10..] {
+            let info = manifest.packages.get_mut("rustc").unwrap();
+            info.targets.get_mut(target).unwrap().available = true;
+        }
+        let mut fetched = Vec::new();
+        let fetch = |end: NaiveDate, days| {
+            fetched.push((end, days));
+            let index = (manifests[0].date - end).num_days() as usize;
+            Ok(manifests[index..index + days].to_vec())
+        };
+        let (last_available, first_missing) =
+            bisect(manifests[0].clone(), target, "rustc", 30, fetch)
+                .unwrap()
+                .unwrap();
+        assert_eq!(manifests[10].date, last_available.date);
+        assert_eq!(manifests[9].date, first_missing.date);
+        assert_eq!(
+            vec![(manifests[1].date, 7), (manifests[8].date, 7)],
+            fetched
+        );
+    }
+
+    #[test]
+    fn gives_up_after_max_days() {
+        let end = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+        let mut manifests = fake_data::manifests(&FakeData::default(), "nightly", end, 10);
+        for manifest in &mut manifests {
+            manifest.packages.remove("rustc");
+        }
+        let mut looked_up = 0;
+        let found = bisect(
+            manifests[0].clone(),
+            "x86_64-unknown-linux-gnu",
+            "rustc",
+            10,
+            |end, days| {
+                looked_up += days;
+                let index = (manifests[0].date - end).num_days() as usize;
+                Ok(manifests[index..index + days].to_vec())
+            },
+        )
+        .unwrap();
+        assert!(found.is_none());
+        assert_eq!(9, looked_up);
+    }
+}
@@ -0,0 +1,196 @@
+use anyhow::Context;
+use chrono::Utc;
+use rustup_available_packages::cache::{EvictionPolicy, FsCache};
+use structopt::StructOpt;
+
+use rustup_status::{channel_cache, lookup_days, opts::Config, warm_cache, Caches};
+
+use crate::ConfigOpt;
+
+#[derive(StructOpt)]
+pub enum CacheCmd {
+    #[structopt(
+        name = "clean",
+        about = "Removes all the cached manifests of the configured channels"
+    )]
+    Clean(ConfigOpt),
+    #[structopt(
+        name = "list",
+        about = "Lists the cached manifests of the configured channels"
+    )]
+    List(ConfigOpt),
+    #[structopt(
+        name = "verify",
+        about = "Checks that the cached manifests can be read and haven't been altered"
+    )]
+    Verify(VerifyOpt),
+    #[structopt(
+        name = "prune",
+        about = "Removes the cached manifests that are too old"
+    )]
+    Prune(PruneOpt),
+    #[structopt(
+        name = "warm",
+        about = "Downloads the manifests into the cache without rendering anything"
+    )]
+    Warm(WarmOpt),
+}
+
+#[derive(StructOpt)]
+pub struct VerifyOpt {
+    #[structopt(flatten)]
+    config: ConfigOpt,
+    #[structopt(
+        long = "remove",
+        help = "Removes the manifests that fail the verification, so they are downloaded again"
+    )]
+    remove: bool,
+}
+
+#[derive(StructOpt)]
+pub struct PruneOpt {
+    #[structopt(flatten)]
+    config: ConfigOpt,
+    #[structopt(
+        long = "keep-days",
+        help = "Keeps the manifests of this many last days, defaults to the `cache_limits`"
+    )]
+    keep_days: Option<u32>,
+}
+
+#[derive(StructOpt)]
+pub struct WarmOpt {
+    #[structopt(flatten)]
+    config: ConfigOpt,
+    #[structopt(
+        long = "days",
+        help = "How many days back to download the manifests for, defaults to as many as \
+                rendering looks up"
+    )]
+    days: Option<usize>,
+}
+
+/// Loads the configuration, unless no cache is configured.
+fn load(opts: &ConfigOpt) -> anyhow::Result<Option<Config>> {
+    let config = opts.load()?;
+    if config.cache_path.is_none() {
+        println!("No cache is configured");
+        return Ok(None);
+    }
+    Ok(Some(config))
+}
+
+pub fn run(cmd: CacheCmd) -> anyhow::Result<()> {
+    match cmd {
+        CacheCmd::Clean(opts) => {
+            let config = match load(&opts)? {
+                Some(config) => config,
+                None => return Ok(()),
+            };
+            for channel in &config.channels {
+                let removed = channel_cache(&config, channel)?
+                    .clear()
+                    .with_context(|| format!("Can't clean the cache of {}", channel))?;
+                println!("{}: removed {} manifests", channel, removed.len());
+            }
+            Ok(())
+        }
+        CacheCmd::List(opts) => {
+            let config = match load(&opts)? {
+                Some(config) => config,
+                None => return Ok(()),
+            };
+            for channel in &config.channels {
+                let entries = channel_cache(&config, channel)?
+                    .entries()
+                    .with_context(|| format!("Can't list the cache of {}", channel))?;
+                for entry in &entries {
+                    println!(
+                        "{}\t{}\t{}\t{}",
+                        channel,
+                        entry.date,
+                        entry.size,
+                        entry.hash.as_deref().unwrap_or("-")
+                    );
+                }
+                let total: u64 = entries.iter().map(|entry| entry.size).sum();
+                println!("{}: {} manifests, {} bytes", channel, entries.len(), total);
+            }
+            Ok(())
+        }
+        CacheCmd::Verify(opts) => {
+            let config = match load(&opts.config)? {
+                Some(config) => config,
+                None => return Ok(()),
+            };
+            let mut failed = 0;
+            for channel in &config.channels {
+                let entries = channel_cache(&config, channel)?
+                    .entries()
+                    .with_context(|| format!("Can't list the cache of {}", channel))?;
+                for entry in &entries {
+                    if let Err(e) = FsCache::verify(entry) {
+                        failed += 1;
+                        println!("{} {}: {}", channel, entry.date, e);
+                        if opts.remove {
+                            FsCache::remove(entry)?;
+                        }
+                    }
+                }
+                println!("{}: verified {} manifests", channel, entries.len());
+            }
+            match failed {
+                0 => Ok(()),
+                _ if opts.remove => {
+                    println!("Removed {} manifests", failed);
+                    Ok(())
+                }
+                _ => anyhow::bail!("{} cached manifests have failed the verification", failed),
+            }
+        }
+        CacheCmd::Prune(opts) => {
+            let config = match load(&opts.config)? {
+                Some(config) => config,
+                None => return Ok(()),
+            };
+            let policy = match opts.keep_days {
+                Some(days) => EvictionPolicy {
+                    max_age: Some(chrono::Duration::days(days.into())),
+                    max_size: None,
+                },
+                None => (&config.cache_limits).into(),
+            };
+            if policy.max_age.is_none() && policy.max_size.is_none() {
+                println!("Nothing to prune, pass --keep-days or configure `cache_limits`");
+                return Ok(());
+            }
+            for channel in &config.channels {
+                let removed = channel_cache(&config, channel)?
+                    .evict(&policy, Utc::now().date_naive())
+                    .with_context(|| format!("Can't prune the cache of {}", channel))?;
+                println!("{}: removed {} manifests", channel, removed.len());
+            }
+            Ok(())
+        }
+        CacheCmd::Warm(opts) => {
+            let config = match load(&opts.config)? {
+                Some(config) => config,
+                None => return Ok(()),
+            };
+            let days = match opts.days {
+                Some(days) => days,
+                None => lookup_days(&config)?,
+            };
+            let caches = Caches::new(&config)?;
+            for channel in &config.channels {
+                let report = warm_cache(&config, &caches, channel, days)
+                    .with_context(|| format!("Can't warm the cache of {}", channel))?;
+                println!(
+                    "{}: downloaded {} manifests, {} were cached already, {} days skipped",
+                    channel, report.fetched, report.cached, report.skipped_days
+                );
+            }
+            Ok(())
+        }
+    }
+}
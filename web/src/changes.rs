@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::{
+    generators::GeneratorInput,
+    html::Pages,
+    opts,
+    output::{Output, Purpose},
+    target_pages,
+    tiers_table::TierChange,
+    AdditionalData,
+};
+
+pub const TEMPLATE_NAME: &str = "changes";
+
+/// A package on a target.
+#[derive(Serialize)]
+struct Entry<'a> {
+    target: &'a str,
+    package: &'a str,
+}
+
+/// Everything that has changed on a single day.
+#[derive(Serialize)]
+struct Day<'a> {
+    date: NaiveDate,
+    appeared: Vec<Entry<'a>>,
+    disappeared: Vec<Entry<'a>>,
+    /// Targets that have moved to another tier.
+    tier_changes: Vec<&'a TierChange>,
+}
+
+#[derive(Serialize)]
+struct ChangesData<'a> {
+    channel: &'a str,
+    /// All the days but the oldest one, the most recent first.
+    days: Vec<Day<'a>>,
+}
+
+#[derive(Serialize)]
+struct ChangesPageData<'a> {
+    #[serde(flatten)]
+    changes: &'a ChangesData<'a>,
+    /// Links to the pages of the targets relative to the page, keyed by target.
+    target_pages: BTreeMap<&'a str, String>,
+    additional: &'a AdditionalData<'a>,
+}
+
+/// Generates a page and a JSON file listing the packages that appeared on or
+/// disappeared from any target, as well as the tier changes, on every day but
+/// the oldest one.
+///
+/// The page template is expected to be registered under the [`TEMPLATE_NAME`].
+pub fn generate_changes(
+    pages: &Pages,
+    tier_changes: &[TierChange],
+    config: &opts::Changes,
+    out: &mut dyn Output,
+) -> anyhow::Result<()> {
+    let GeneratorInput {
+        data,
+        dates,
+        channel,
+        ..
+    } = *pages.input;
+    let mut days: Vec<_> = dates
+        .iter()
+        .take(dates.len().saturating_sub(1))
+        .map(|&date| Day {
+            date,
+            appeared: Vec::new(),
+            disappeared: Vec::new(),
+            tier_changes: tier_changes
+                .iter()
+                .filter(|change| change.date == date)
+                .collect(),
+        })
+        .collect();
+    for change in data.changes(dates) {
+        let day = days
+            .iter_mut()
+            .find(|day| day.date == change.date)
+            .expect("Changes are found on the given dates only");
+        let entry = Entry {
+            target: change.target,
+            package: change.package,
+        };
+        if change.available {
+            day.appeared.push(entry);
+        } else {
+            day.disappeared.push(entry);
+        }
+    }
+    let changes = ChangesData {
+        channel: channel.name,
+        days,
+    };
+    let json_path = channel.relocate(&config.json_path);
+    log::info!("Writing changes to {:?}", json_path);
+    out.set_purpose(Purpose::ChangesJson);
+    out.write(&json_path, &serde_json::to_vec(&changes)?)?;
+
+    let output_path = channel.relocate(&config.output_path);
+    let targets = data
+        .get_available_targets()
+        .into_iter()
+        .chain(tier_changes.iter().map(|change| change.target.as_str()));
+    let target_pages = target_pages(&pages.config.output_pattern, targets, channel, &output_path)?;
+    let rendered = pages
+        .templates
+        .render_page(
+            TEMPLATE_NAME,
+            &ChangesPageData {
+                changes: &changes,
+                target_pages,
+                additional: pages.additional,
+            },
+            &output_path,
+        )
+        .with_context(|| format!("Can't render [{:?}]", config.template_path))?;
+    log::info!("Writing changes page to {:?}", output_path);
+    out.set_purpose(Purpose::ChangesPage);
+    out.write(&output_path, rendered.as_bytes())
+}
@@ -0,0 +1,100 @@
+use std::io::{self, Write};
+
+use anyhow::Context;
+use rustup_available_packages::{AvailabilityData, Downloader};
+use serde::Serialize;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+pub struct CheckOpt {
+    #[structopt(
+        long = "channel",
+        help = "A release channel to check",
+        default_value = "nightly"
+    )]
+    channel: String,
+    #[structopt(
+        short = "t",
+        long = "target",
+        help = "A target to check, might be repeated",
+        required = true,
+        number_of_values = 1
+    )]
+    targets: Vec<String>,
+    #[structopt(
+        short = "p",
+        long = "package",
+        help = "A package to check, might be repeated",
+        required = true,
+        number_of_values = 1
+    )]
+    packages: Vec<String>,
+    #[structopt(long = "json", help = "Print the report in JSON")]
+    json: bool,
+}
+
+#[derive(Serialize)]
+struct Status<'a> {
+    target: &'a str,
+    package: &'a str,
+    available: bool,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    channel: &'a str,
+    date: String,
+    statuses: Vec<Status<'a>>,
+}
+
+/// Checks whether all the requested packages are available in the latest
+/// manifest. Returns `false` if any of them is missing.
+pub fn run(opts: &CheckOpt) -> anyhow::Result<bool> {
+    let manifest = Downloader::with_default_source(&opts.channel)
+        .get_latest_manifest()
+        .with_context(|| format!("Can't fetch the latest {} manifest", opts.channel))?;
+    let date = manifest.date;
+    let mut data = AvailabilityData::default();
+    data.add_manifest(manifest);
+
+    let statuses: Vec<_> = opts
+        .targets
+        .iter()
+        .flat_map(|target| {
+            let data = &data;
+            opts.packages.iter().map(move |package| Status {
+                target,
+                package,
+                available: data.last_available(target, package) == Some(date),
+            })
+        })
+        .collect();
+    let all_available = statuses.iter().all(|status| status.available);
+
+    let mut out = io::stdout().lock();
+    if opts.json {
+        let report = Report {
+            channel: &opts.channel,
+            date: date.format("%Y-%m-%d").to_string(),
+            statuses,
+        };
+        serde_json::to_writer_pretty(&mut out, &report)?;
+        writeln!(out)?;
+    } else {
+        writeln!(out, "Latest {} manifest: {}", opts.channel, date)?;
+        for status in &statuses {
+            writeln!(
+                out,
+                "{:>9}  {} on {}",
+                if status.available {
+                    "available"
+                } else {
+                    "MISSING"
+                },
+                status.package,
+                status.target
+            )?;
+        }
+    }
+    Ok(all_available)
+}
@@ -0,0 +1,372 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use chrono::NaiveDate;
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::deploy::list_files;
+
+#[derive(StructOpt)]
+pub struct CompareOutputOpt {
+    #[structopt(help = "A file tree rendered earlier", parse(from_os_str))]
+    old_dir: PathBuf,
+    #[structopt(help = "A file tree rendered later", parse(from_os_str))]
+    new_dir: PathBuf,
+    #[structopt(long = "json", help = "Print the report in JSON")]
+    json: bool,
+    #[structopt(
+        short = "o",
+        long = "output",
+        help = "Also write the report in JSON to a file",
+        parse(from_os_str)
+    )]
+    output: Option<PathBuf>,
+}
+
+/// The status of a package on a day, as the `$target/$package.json` files of
+/// a file tree tell it.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+struct DayStatus {
+    /// `None` if it's unknown, as the day has no manifest or the manifest
+    /// doesn't list the target.
+    available: Option<bool>,
+    version: Option<String>,
+}
+
+/// The days of every target and package of a file tree, keyed by the
+/// target's directory relative to the tree and the package.
+type Tree = BTreeMap<(String, String), BTreeMap<NaiveDate, DayStatus>>;
+
+/// Loads the package files of a file tree, skipping the other JSON files like
+/// the package summaries.
+fn load_tree(dir: &Path) -> anyhow::Result<Tree> {
+    let mut files = Vec::new();
+    list_files(dir, "", &mut files)?;
+    let mut tree = Tree::new();
+    for (name, path) in files {
+        let (target, package) = match name
+            .strip_suffix(".json")
+            .and_then(|name| name.rsplit_once('/'))
+        {
+            Some(split) => split,
+            None => continue,
+        };
+        let contents = fs::read(&path).with_context(|| format!("Can't read {}", path.display()))?;
+        let value: serde_json::Value = serde_json::from_slice(&contents)
+            .with_context(|| format!("Can't parse {}", path.display()))?;
+        let object = match value
+            .as_object()
+            .filter(|object| object.contains_key("stats"))
+        {
+            Some(object) => object,
+            None => continue,
+        };
+        let days = object
+            .iter()
+            .filter_map(|(key, day)| {
+                let date = key.parse().ok()?;
+                let flag = |name| day.get(name).and_then(|flag| flag.as_bool());
+                let unknown =
+                    flag("target_missing") == Some(true) || flag("manifest_missing") == Some(true);
+                let available = flag("available")?;
+                let status = DayStatus {
+                    available: if unknown { None } else { Some(available) },
+                    version: day
+                        .get("version")
+                        .and_then(|version| version.as_str())
+                        .map(String::from),
+                };
+                Some((date, status))
+            })
+            .collect();
+        tree.insert((target.to_string(), package.to_string()), days);
+    }
+    Ok(tree)
+}
+
+fn latest_date(tree: &Tree) -> Option<NaiveDate> {
+    tree.values()
+        .filter_map(|days| days.keys().last())
+        .max()
+        .copied()
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+struct Change<'a> {
+    target: &'a str,
+    package: &'a str,
+}
+
+/// A day both trees have, which they disagree on.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+struct Rewrite<'a> {
+    target: &'a str,
+    package: &'a str,
+    date: NaiveDate,
+    old: &'a DayStatus,
+    new: &'a DayStatus,
+}
+
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    old_date: Option<NaiveDate>,
+    new_date: Option<NaiveDate>,
+    /// Packages available on the latest day of the new tree but not on the
+    /// latest day of the old one. The days the package is unknown on are
+    /// never a change.
+    appeared: Vec<Change<'a>>,
+    disappeared: Vec<Change<'a>>,
+    /// Targets and packages that only one of the trees has a file of.
+    only_in_old: Vec<Change<'a>>,
+    only_in_new: Vec<Change<'a>>,
+    rewritten: Vec<Rewrite<'a>>,
+}
+
+fn describe(status: &DayStatus) -> String {
+    match (status.available, &status.version) {
+        (Some(true), Some(version)) => format!("present ({})", version),
+        (Some(true), None) => "present".into(),
+        (Some(false), _) => "missing".into(),
+        (None, _) => "unknown".into(),
+    }
+}
+
+/// Compares two file trees, the `old` one rendered earlier.
+fn compare<'a>(old: &'a Tree, new: &'a Tree) -> Report<'a> {
+    let old_date = latest_date(old);
+    let new_date = latest_date(new);
+    // A package without a file or a day isn't available, while an unknown
+    // day is `None`.
+    let available = |tree: &Tree, key: &(String, String), date: Option<NaiveDate>| match date
+        .and_then(|date| tree.get(key)?.get(&date))
+    {
+        Some(status) => status.available,
+        None => Some(false),
+    };
+
+    let mut report = Report {
+        old_date,
+        new_date,
+        appeared: Vec::new(),
+        disappeared: Vec::new(),
+        only_in_old: Vec::new(),
+        only_in_new: Vec::new(),
+        rewritten: Vec::new(),
+    };
+    let keys: BTreeSet<_> = old.keys().chain(new.keys()).collect();
+    for key in keys {
+        let change = || Change {
+            target: &key.0,
+            package: &key.1,
+        };
+        match (available(old, key, old_date), available(new, key, new_date)) {
+            (Some(false), Some(true)) => report.appeared.push(change()),
+            (Some(true), Some(false)) => report.disappeared.push(change()),
+            _ => {}
+        }
+        let (old_days, new_days) = match (old.get(key), new.get(key)) {
+            (Some(old_days), Some(new_days)) => (old_days, new_days),
+            (Some(_), None) => {
+                report.only_in_old.push(change());
+                continue;
+            }
+            (None, _) => {
+                report.only_in_new.push(change());
+                continue;
+            }
+        };
+        for (&date, old_status) in old_days {
+            match new_days.get(&date) {
+                Some(new_status) if new_status != old_status => report.rewritten.push(Rewrite {
+                    target: &key.0,
+                    package: &key.1,
+                    date,
+                    old: old_status,
+                    new: new_status,
+                }),
+                _ => {}
+            }
+        }
+    }
+    report
+}
+
+/// Compares two file trees, e.g. the ones of two versions of the pipeline or
+/// of two runs.
+pub fn run(opts: &CompareOutputOpt) -> anyhow::Result<()> {
+    let old = load_tree(&opts.old_dir)?;
+    let new = load_tree(&opts.new_dir)?;
+    let report = compare(&old, &new);
+
+    if let Some(output) = &opts.output {
+        fs::write(output, serde_json::to_vec_pretty(&report)?)
+            .with_context(|| format!("Can't write the report to {}", output.display()))?;
+    }
+    let mut out = io::stdout().lock();
+    if opts.json {
+        serde_json::to_writer_pretty(&mut out, &report)?;
+        writeln!(out)?;
+        return Ok(());
+    }
+    let date = |date: Option<NaiveDate>| date.map_or_else(|| "no days".into(), |d| d.to_string());
+    writeln!(
+        out,
+        "Changes between {} ({}) and {} ({}):",
+        opts.old_dir.display(),
+        date(report.old_date),
+        opts.new_dir.display(),
+        date(report.new_date)
+    )?;
+    if report.appeared.is_empty()
+        && report.disappeared.is_empty()
+        && report.only_in_old.is_empty()
+        && report.only_in_new.is_empty()
+        && report.rewritten.is_empty()
+    {
+        writeln!(out, "  none")?;
+    }
+    for (sign, changes) in [("+", &report.appeared), ("-", &report.disappeared)] {
+        for change in changes {
+            writeln!(out, "{} {} on {}", sign, change.package, change.target)?;
+        }
+    }
+    for (tree, changes) in [("old", &report.only_in_old), ("new", &report.only_in_new)] {
+        for change in changes {
+            writeln!(
+                out,
+                "only in the {} tree: {} on {}",
+                tree, change.package, change.target
+            )?;
+        }
+    }
+    for rewrite in &report.rewritten {
+        writeln!(
+            out,
+            "rewritten: {} on {} on {}: {} -> {}",
+            rewrite.package,
+            rewrite.target,
+            rewrite.date,
+            describe(rewrite.old),
+            describe(rewrite.new)
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_tree(dir: &Path, files: &[(&str, &str)]) {
+        for &(name, contents) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, day).unwrap()
+    }
+
+    #[test]
+    fn loads_the_package_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        write_tree(
+            dir,
+            &[
+                (
+                    "linux/rls.json",
+                    r#"{"2024-01-01": {"available": true, "version": "1.0"},
+                        "2024-01-02": {"available": false, "target_missing": true},
+                        "2024-01-03": {"available": false, "manifest_missing": true},
+                        "stats": {}}"#,
+                ),
+                ("linux/summary.json", r#"{"packages": []}"#),
+                ("index.json", r#"{"stats": {}}"#),
+            ],
+        );
+        let tree = load_tree(dir).unwrap();
+
+        assert_eq!(tree.len(), 1);
+        let days = &tree[&("linux".to_string(), "rls".to_string())];
+        assert_eq!(
+            days[&date(1)],
+            DayStatus {
+                available: Some(true),
+                version: Some("1.0".into())
+            }
+        );
+        assert_eq!(days[&date(2)].available, None);
+        assert_eq!(days[&date(3)].available, None);
+    }
+
+    #[test]
+    fn unknown_days_are_not_changes() {
+        let status = |available| DayStatus {
+            available,
+            version: None,
+        };
+        let key = |target: &str, package: &str| (target.to_string(), package.to_string());
+        let days = |statuses: Vec<(u32, Option<bool>)>| {
+            statuses
+                .into_iter()
+                .map(|(day, available)| (date(day), status(available)))
+                .collect::<BTreeMap<_, _>>()
+        };
+        let old: Tree = vec![
+            (key("linux", "rls"), days(vec![(1, Some(true))])),
+            (key("linux", "miri"), days(vec![(1, Some(false))])),
+            (key("linux", "clippy"), days(vec![(1, Some(true))])),
+            (key("mac", "rls"), days(vec![(1, Some(true))])),
+        ]
+        .into_iter()
+        .collect();
+        let new: Tree = vec![
+            (key("linux", "rls"), days(vec![(1, Some(false)), (2, None)])),
+            (
+                key("linux", "miri"),
+                days(vec![(1, Some(false)), (2, Some(true))]),
+            ),
+            (
+                key("linux", "clippy"),
+                days(vec![(1, Some(true)), (2, Some(false))]),
+            ),
+            (key("win", "rls"), days(vec![(2, Some(true))])),
+        ]
+        .into_iter()
+        .collect();
+
+        let report = compare(&old, &new);
+        let change = |target, package| Change { target, package };
+        assert_eq!(report.old_date, Some(date(1)));
+        assert_eq!(report.new_date, Some(date(2)));
+        assert_eq!(
+            report.appeared,
+            [change("linux", "miri"), change("win", "rls")]
+        );
+        assert_eq!(
+            report.disappeared,
+            [change("linux", "clippy"), change("mac", "rls")]
+        );
+        assert_eq!(report.only_in_old, [change("mac", "rls")]);
+        assert_eq!(report.only_in_new, [change("win", "rls")]);
+        assert_eq!(
+            report.rewritten,
+            [Rewrite {
+                target: "linux",
+                package: "rls",
+                date: date(1),
+                old: &status(Some(true)),
+                new: &status(Some(false)),
+            }]
+        );
+    }
+}
@@ -1,11 +1,15 @@
 use std::collections::HashMap;
 
-use anyhow::Context;
 use log::LevelFilter;
-use strum::IntoEnumIterator;
-use tl::{ParserOptions, VDom};
 
-use crate::opts::{Config, Html, Tier};
+use rustup_status::{
+    opts::{
+        Aliases, Badges, CacheLimits, Changes, Config, Csv, Engine, Feed, Globs, Html, JsonDump,
+        LastGood, LogFormat, Network, OnMissingDays, OutputSettings, PackagePages, Period, Retry,
+        Search, SearchPage, Sitemap, Source, Strictness, Tier, Trends,
+    },
+    platform_support,
+};
 
 pub fn gen_config() -> anyhow::Result<Config> {
     Ok(Config {
@@ -13,76 +17,144 @@ pub fn gen_config() -> anyhow::Result<Config> {
             template_path: "template.html".into(),
             output_pattern: "output/{{target}}.html".into(),
             tiers: gen_tiers()?,
+            platform_support: None,
+            package_pages: Some(PackagePages {
+                template_path: "package_template.html".into(),
+                output_pattern: "output/packages/{{package}}.html".into(),
+            }),
+            search: Some(Search {
+                index_path: "output/search-index.json".into(),
+                fallback_page: Some(SearchPage {
+                    template_path: "search_template.html".into(),
+                    output_path: "output/search.html".into(),
+                }),
+            }),
+            index_template: Some("index_template.html".into()),
+            watch_sets: Vec::new(),
+            changes: Some(Changes {
+                template_path: "changes_template.html".into(),
+                output_path: "output/changes.html".into(),
+                json_path: "output/changes.json".into(),
+            }),
+            engine: Engine::Handlebars,
+            template_dirs: Vec::new(),
+            script_helpers: Default::default(),
+            assets: None,
+            heatmaps: None,
+            toolstate: None,
+            social_card_template: None,
+            social_card_url: None,
+            plain_text: None,
+            i18n: None,
+            theme: Default::default(),
+            render_threads: None,
         },
         days_in_past: 7,
+        start_date: None,
+        end_date: None,
+        keep_going: false,
         additional_lookup_days: 22,
-        channel: "nightly".into(),
+        max_missing_days: 7,
+        max_lookup_days: None,
+        on_missing_days: OnMissingDays::Fail,
+        channels: vec!["nightly".into(), "beta".into(), "stable".into()],
+        source: Source::Http,
+        manifest_base_url: None,
+        manifest_headers: Default::default(),
+        download_concurrency: 8,
+        retry: Retry {
+            max_attempts: 5,
+            initial_backoff_ms: 1000,
+            max_backoff_ms: 30_000,
+            jitter: 0.2,
+        },
+        network: Network {
+            connect_timeout_secs: 10,
+            timeout_secs: 30,
+            proxy: None,
+            user_agent: None,
+            root_certificates: Vec::new(),
+            only_custom_roots: false,
+        },
+        verification: None,
+        tolerate_failed_days: 0,
         verbosity: LevelFilter::Info,
+        log_format: LogFormat::Text,
         cache_path: Some("/tmp/manifests/".into()),
+        compress_cache: true,
+        memory_cache_size: 0,
+        cache_limits: CacheLimits {
+            max_age_days: Some(90),
+            max_size_mb: None,
+            evict_every_hours: 24,
+        },
         file_tree_output: "output/".into(),
+        state_path: None,
+        site_root: None,
+        metrics_file: None,
+        report_file: None,
+        strictness: Strictness::default(),
+        lock: None,
+        storage: None,
+        json_dump: Some(JsonDump {
+            path: "output/availability.json".into(),
+            gzip: false,
+        }),
+        csv: Some(Csv {
+            output_dir: "output/csv/".into(),
+        }),
+        feed: Some(Feed {
+            output_pattern: "output/feeds/{{target}}.xml".into(),
+            history_path: None,
+            max_entries: 50,
+        }),
+        badges: Some(Badges {
+            output_pattern: "output/badges/{{target}}/{{package}}.svg".into(),
+        }),
+        trends: Some(Trends {
+            template_path: "trends_template.html".into(),
+            output_pattern: "output/trends/{{target}}.html".into(),
+            period: Period::Week,
+            // As many as the `days_in_past` and `additional_lookup_days` cover.
+            periods: 4,
+        }),
+        archive: None,
+        last_good: Some(LastGood {
+            output_pattern: "output/last-good/{{target}}.json".into(),
+            packages: vec![
+                "rustc".into(),
+                "cargo".into(),
+                "clippy".into(),
+                "rustfmt".into(),
+            ],
+        }),
+        notifications: None,
+        github_issues: None,
+        deploy: None,
+        sitemap: Some(Sitemap {
+            path: "output/sitemap.xml".into(),
+            base_url: "https://rust-lang.github.io/rustup-components-history/".into(),
+        }),
+        output: OutputSettings {
+            minify: false,
+            precompress: false,
+            tree_archive: None,
+            archive: None,
+            site_manifest: None,
+        },
+        aliases: Aliases {
+            packages: [("rust-analyzer-preview", "rust-analyzer")]
+                .iter()
+                .map(|&(name, alias)| (name.into(), alias.into()))
+                .collect(),
+            targets: Default::default(),
+        },
+        targets: Globs::default(),
+        package_filters: Vec::new(),
     })
 }
 
 fn gen_tiers() -> anyhow::Result<HashMap<Tier, Vec<String>>> {
-    let bytes =
-        reqwest::blocking::get("https://doc.rust-lang.org/nightly/rustc/platform-support.html")?
-            .text()?;
-    let html = tl::parse(&bytes, ParserOptions::default())?;
-
-    let mut tiers = HashMap::new();
-    for tier in Tier::iter().filter(|t| *t != Tier::UnknownTier) {
-        tiers.insert(tier, collect_targets_for_tier(&html, tier)?);
-    }
-    Ok(tiers)
-}
-
-fn collect_targets_for_tier(html: &VDom, tier: Tier) -> anyhow::Result<Vec<String>> {
-    let mut targets = Vec::new();
-
-    for table_row in html
-        .query_selector("tbody")
-        .unwrap()
-        .nth(match tier {
-            Tier::Tier1 => 0,
-            Tier::Tier2 => 1,
-            Tier::Tier25 => 2,
-            Tier::Tier3 => 3,
-            Tier::UnknownTier => unreachable!(),
-        })
-        .context("Unexpected tier table layout")?
-        .get(html.parser())
-        .unwrap()
-        .children()
-        .context("tbody is not a tag")?
-        .top()
-        .iter()
-    {
-        if let Some(table_row) = table_row.get(html.parser()).unwrap().as_tag() {
-            if table_row.name() != "tr" {
-                continue;
-            }
-
-            targets.push(
-                table_row
-                    .query_selector(html.parser(), "td")
-                    .unwrap()
-                    .next()
-                    .context("Table row does not have any columns.")?
-                    .get(html.parser())
-                    .unwrap()
-                    .as_tag()
-                    .context("td is not a tag")?
-                    .query_selector(html.parser(), "code")
-                    .unwrap()
-                    .next()
-                    .context("Table row does not have a code element in its first column")?
-                    .get(html.parser())
-                    .unwrap()
-                    .inner_text(html.parser())
-                    .into_owned(),
-            );
-        }
-    }
-
-    Ok(targets)
+    let page = reqwest::blocking::get(platform_support::PAGE_URL)?.text()?;
+    platform_support::parse_page(&page)
 }
@@ -0,0 +1,131 @@
+use std::iter;
+
+use crate::{
+    csv_field,
+    generators::GeneratorInput,
+    opts,
+    output::{Output, Purpose},
+    with_missing_manifests,
+};
+
+/// Writes an `availability-$target.csv` file per target of the input, with a
+/// row per package and a column per date.
+pub fn generate_csv(
+    input: &GeneratorInput,
+    config: &opts::Csv,
+    out: &mut dyn Output,
+) -> anyhow::Result<()> {
+    let GeneratorInput {
+        data,
+        dates,
+        targets,
+        channel,
+        ..
+    } = *input;
+    let output = channel.dir(&config.output_dir);
+    let mut pkgs: Vec<_> = data.get_available_packages().into_iter().collect();
+    pkgs.sort_unstable();
+    let dates = &with_missing_manifests(data, dates);
+
+    let header = iter::once("package".to_string())
+        .chain(dates.iter().map(|date| date.format("%Y-%m-%d").to_string()))
+        .chain(iter::once("last_available".to_string()))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    for target in targets {
+        let mut contents = header.clone();
+        contents.push_str("\r\n");
+        for pkg in &pkgs {
+            let row = match data.get_availability_row(target, pkg, dates) {
+                Some(row) => row,
+                None => continue,
+            };
+            contents.push_str(&csv_field(pkg));
+            for (date, available) in dates.iter().zip(&row.availability_list) {
+                contents.push(',');
+                // The days without a manifest or on which the manifest
+                // doesn't list the target are left empty.
+                if data.is_target_listed(target, *date) {
+                    contents.push_str(if *available { "true" } else { "false" });
+                }
+            }
+            contents.push(',');
+            if let Some(date) = row.last_available {
+                contents.push_str(&date.format("%Y-%m-%d").to_string());
+            }
+            contents.push_str("\r\n");
+        }
+        let path = output.join(format!("availability-{}.csv", target));
+        out.set_purpose(Purpose::Csv);
+        out.write(&path, contents.as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use chrono::NaiveDate;
+    use rustup_available_packages::AvailabilityData;
+
+    use super::*;
+    use crate::{output::MemoryOutput, test_utils::manifest, ChannelInfo, Shared};
+
+    /// Renders the CSV files of the `targets` into memory.
+    fn render(data: &AvailabilityData, dates: &[NaiveDate], targets: &[&str]) -> MemoryOutput {
+        let targets = targets.iter().copied().collect();
+        let channels = ["nightly".to_string()];
+        let shared = Shared::empty();
+        let input = GeneratorInput::test(
+            data,
+            dates,
+            &targets,
+            ChannelInfo::new("nightly", &channels),
+            &shared,
+        );
+        let config = opts::Csv {
+            output_dir: "csv".into(),
+        };
+        let mut out = MemoryOutput::default();
+        generate_csv(&input, &config, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn missing_manifests_are_empty() {
+        let date = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        let mut data = AvailabilityData::default();
+        let target = "x86_64-unknown-linux-gnu";
+        for day in [1, 3] {
+            data.add_manifest(manifest(date(day), &[("cargo", target, true)]));
+        }
+        data.add_missing_manifest(date(2));
+
+        let out = render(&data, &[date(3), date(1)], &[target]);
+        let csv = &out.into_tree(Path::new("csv"))["availability-x86_64-unknown-linux-gnu.csv"];
+        assert_eq!(
+            String::from_utf8_lossy(csv),
+            "package,2024-01-03,2024-01-02,2024-01-01,last_available\r\ncargo,true,,true,2024-01-03\r\n"
+        );
+    }
+
+    #[test]
+    fn unlisted_days_are_empty() {
+        let date = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        let mut data = AvailabilityData::default();
+        data.add_manifest(manifest(date(1), &[("cargo", "a", true)]));
+        data.add_manifest(manifest(
+            date(2),
+            &[("cargo", "a", true), ("cargo", "b", true)],
+        ));
+
+        let out = render(&data, &[date(2), date(1)], &["b"]);
+        let csv = &out.into_tree(Path::new("csv"))["availability-b.csv"];
+        assert_eq!(
+            String::from_utf8_lossy(csv),
+            "package,2024-01-02,2024-01-01,last_available\r\ncargo,true,,2024-01-02\r\n"
+        );
+    }
+}
@@ -0,0 +1,295 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use git2::{Cred, ErrorCode, FileMode, Oid, PushOptions, RemoteCallbacks, Repository, Signature};
+use rustup_status::{
+    http::Http,
+    opts::{Config, Deploy, DeployTarget, GitDeploy},
+};
+
+/// Writes the files of a directory to the repository as a tree. Symlinks are
+/// kept as links, just like `git add` does. Empty directories are skipped
+/// since git can't keep them.
+fn write_tree(repo: &Repository, dir: &Path) -> anyhow::Result<Option<Oid>> {
+    let mut builder = repo.treebuilder(None)?;
+    let entries = fs::read_dir(dir).with_context(|| format!("Can't read {}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .with_context(|| format!("{} isn't a valid UTF-8 path", path.display()))?;
+        if name == ".git" {
+            continue;
+        }
+        let metadata = fs::symlink_metadata(&path)
+            .with_context(|| format!("Can't read {}", path.display()))?;
+        if metadata.file_type().is_symlink() {
+            let target = fs::read_link(&path)
+                .with_context(|| format!("Can't read the link {}", path.display()))?;
+            let target = target
+                .to_str()
+                .with_context(|| format!("{} isn't a valid UTF-8 path", target.display()))?;
+            let blob = repo.blob(target.as_bytes())?;
+            builder.insert(name, blob, FileMode::Link.into())?;
+        } else if metadata.is_dir() {
+            if let Some(tree) = write_tree(repo, &path)? {
+                builder.insert(name, tree, FileMode::Tree.into())?;
+            }
+        } else {
+            let blob = repo
+                .blob_path(&path)
+                .with_context(|| format!("Can't add {} to the repository", path.display()))?;
+            builder.insert(name, blob, FileMode::Blob.into())?;
+        }
+    }
+    if builder.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(builder.write()?))
+}
+
+/// Lists the files under a directory, following the symlinks, with their
+/// `/`-separated paths relative to the directory.
+pub(crate) fn list_files(
+    dir: &Path,
+    relative: &str,
+    files: &mut Vec<(String, PathBuf)>,
+) -> anyhow::Result<()> {
+    let entries = fs::read_dir(dir).with_context(|| format!("Can't read {}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .with_context(|| format!("{} isn't a valid UTF-8 path", path.display()))?;
+        let name = format!("{}{}", relative, name);
+        let metadata =
+            fs::metadata(&path).with_context(|| format!("Can't read {}", path.display()))?;
+        if metadata.is_dir() {
+            list_files(&path, &format!("{}/", name), files)?;
+        } else {
+            files.push((name, path));
+        }
+    }
+    Ok(())
+}
+
+fn signature(repo: &Repository, deploy: &GitDeploy) -> anyhow::Result<Signature<'static>> {
+    let signature = match &deploy.author {
+        Some(author) => Signature::now(&author.name, &author.email),
+        None => repo.signature(),
+    };
+    signature.context("Can't make up the author of the commit, set `deploy.author`")
+}
+
+fn push(
+    repo: &Repository,
+    deploy: &GitDeploy,
+    remote: &str,
+    reference: &str,
+) -> anyhow::Result<()> {
+    let mut remote = match repo.find_remote(remote) {
+        Ok(remote) => remote,
+        Err(_) => repo
+            .remote_anonymous(remote)
+            .with_context(|| format!("{} is neither a remote nor a URL", remote))?,
+    };
+    let refspec = format!(
+        "{}{}:{}",
+        if deploy.force { "+" } else { "" },
+        reference,
+        reference
+    );
+    let token = deploy
+        .token
+        .clone()
+        .or_else(|| env::var("GITHUB_TOKEN").ok());
+    let mut rejected = None;
+    {
+        let mut callbacks = RemoteCallbacks::new();
+        // libgit2 asks again and again as long as the credentials are rejected.
+        let mut asked = false;
+        callbacks.credentials(|_url, username, allowed| {
+            if std::mem::replace(&mut asked, true) {
+                return Err(git2::Error::from_str("the credentials have been rejected"));
+            }
+            match &token {
+                Some(token) if allowed.is_user_pass_plaintext() => {
+                    Cred::userpass_plaintext("x-access-token", token)
+                }
+                _ if allowed.is_ssh_key() => Cred::ssh_key_from_agent(username.unwrap_or("git")),
+                _ => Cred::default(),
+            }
+        });
+        callbacks.push_update_reference(|_reference, status| {
+            rejected = status.map(str::to_string);
+            Ok(())
+        });
+        let mut options = PushOptions::new();
+        options.remote_callbacks(callbacks);
+        remote
+            .push(&[refspec.as_str()], Some(&mut options))
+            .with_context(|| format!("Can't push to {}", remote.url().unwrap_or_default()))?;
+    }
+    if let Some(status) = rejected {
+        anyhow::bail!(
+            "The push of {} has been rejected: {}",
+            deploy.branch,
+            status
+        );
+    }
+    log::info!(
+        "Pushed {} to {}",
+        deploy.branch,
+        remote.url().unwrap_or_default()
+    );
+    Ok(())
+}
+
+/// Deploys the rendered site to the configured target.
+pub fn run(config: &Config, deploy: &Deploy) -> anyhow::Result<()> {
+    let directory = deploy
+        .directory
+        .as_ref()
+        .or(config.site_root.as_ref())
+        .context("Nothing to deploy, set either `deploy.directory` or `site_root`")?;
+    match &deploy.target {
+        DeployTarget::Git(git) => commit(directory, git),
+        DeployTarget::S3(s3) => {
+            let http = Http::new(&config.network, &config.retry)?;
+            crate::s3::sync(directory, s3, &http)
+        }
+        DeployTarget::Sftp(sftp) => crate::sftp::sync(directory, sftp),
+    }
+}
+
+/// Commits a directory to the configured branch, unless it hasn't changed,
+/// and pushes the branch if a remote is configured.
+fn commit(directory: &Path, deploy: &GitDeploy) -> anyhow::Result<()> {
+    let repo = Repository::discover(&deploy.repository).with_context(|| {
+        format!(
+            "Can't open a git repository at {}",
+            deploy.repository.display()
+        )
+    })?;
+    let tree = write_tree(&repo, directory)?
+        .with_context(|| format!("{} is empty", directory.display()))?;
+    let tree = repo.find_tree(tree)?;
+    let reference = format!("refs/heads/{}", deploy.branch);
+    let parent = match repo.find_reference(&reference) {
+        Ok(reference) => Some(reference.peel_to_commit()?),
+        Err(e) if e.code() == ErrorCode::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    if parent
+        .as_ref()
+        .is_some_and(|parent| parent.tree_id() == tree.id())
+    {
+        log::info!("The site hasn't changed since the last deployment");
+        if let Some(remote) = &deploy.remote {
+            push(&repo, deploy, remote, &reference)?;
+        }
+        return Ok(());
+    }
+    let signature = signature(&repo, deploy)?;
+    let parents: Vec<_> = parent.iter().collect();
+    let commit = repo
+        .commit(
+            Some(&reference),
+            &signature,
+            &signature,
+            &deploy.message,
+            &tree,
+            &parents,
+        )
+        .with_context(|| format!("Can't commit to {}", deploy.branch))?;
+    log::info!("Committed {} to {}", commit, deploy.branch);
+    if let Some(remote) = &deploy.remote {
+        if let Err(e) = push(&repo, deploy, remote, &reference) {
+            // Otherwise the next run would build on top of a commit the remote
+            // has never accepted.
+            match &parent {
+                Some(parent) => {
+                    repo.reference(
+                        &reference,
+                        parent.id(),
+                        true,
+                        "Undo the commit that hasn't been pushed",
+                    )?;
+                }
+                None => repo.find_reference(&reference)?.delete()?,
+            }
+            log::info!("Reset {} to what it was before the commit", deploy.branch);
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rustup_status::opts::DeployAuthor;
+
+    fn scratch() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("site")).unwrap();
+        dir
+    }
+
+    fn deploy(repository: &Path, remote: Option<&Path>) -> GitDeploy {
+        GitDeploy {
+            repository: repository.into(),
+            branch: "gh-pages".into(),
+            message: "Update".into(),
+            author: Some(DeployAuthor {
+                name: "Status bot".into(),
+                email: "status@example.com".into(),
+            }),
+            remote: remote.map(|remote| remote.display().to_string()),
+            force: false,
+            token: None,
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_kept_as_links() {
+        let tmp = scratch();
+        let dir = tmp.path();
+        let repo = Repository::init(dir).unwrap();
+        fs::write(dir.join("site/index.html"), "index").unwrap();
+        std::os::unix::fs::symlink("index.html", dir.join("site/latest.html")).unwrap();
+
+        let tree = write_tree(&repo, &dir.join("site")).unwrap().unwrap();
+        let tree = repo.find_tree(tree).unwrap();
+        let link = tree.get_name("latest.html").unwrap();
+        assert_eq!(link.filemode(), i32::from(FileMode::Link));
+        let target = repo.find_blob(link.id()).unwrap();
+        assert_eq!(target.content(), b"index.html");
+        assert_eq!(
+            tree.get_name("index.html").unwrap().filemode(),
+            i32::from(FileMode::Blob)
+        );
+    }
+
+    #[test]
+    fn failed_push_resets_the_branch() {
+        let tmp = scratch();
+        let dir = tmp.path();
+        let repo = Repository::init(dir).unwrap();
+        fs::write(dir.join("site/index.html"), "first").unwrap();
+        commit(&dir.join("site"), &deploy(dir, None)).unwrap();
+        let first = repo.refname_to_id("refs/heads/gh-pages").unwrap();
+
+        fs::write(dir.join("site/index.html"), "second").unwrap();
+        let unreachable = dir.join("no-such-remote");
+        assert!(commit(&dir.join("site"), &deploy(dir, Some(&unreachable))).is_err());
+        assert_eq!(repo.refname_to_id("refs/heads/gh-pages").unwrap(), first);
+    }
+}
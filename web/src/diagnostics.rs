@@ -0,0 +1,324 @@
+//! Structured descriptions of failures, for the tools that run the binary.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+/// What kind of a problem made a run fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    /// The configuration can't be read or is invalid.
+    Config,
+    /// A manifest can't be downloaded.
+    Network,
+    /// A manifest can't be parsed.
+    Manifest,
+    /// A manifest doesn't match its checksum or signature.
+    Verification,
+    /// A template can't be parsed or rendered.
+    Template,
+    /// A file can't be parsed.
+    Parse,
+    /// A file can't be read or written.
+    Io,
+    /// Anything else.
+    Other,
+}
+
+/// Where something failed, attached to an error with [`Locate::locate`].
+/// Shown as its message, so it replaces a plain context message.
+#[derive(Debug, Default)]
+pub struct Location {
+    message: String,
+    category: Option<Category>,
+    channel: Option<String>,
+    target: Option<String>,
+    date: Option<NaiveDate>,
+    path: Option<PathBuf>,
+}
+
+impl Location {
+    pub fn new(message: impl Into<String>) -> Self {
+        Location {
+            message: message.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Overrides the category guessed from the underlying error.
+    pub fn category(mut self, category: Category) -> Self {
+        self.category = Some(category);
+        self
+    }
+
+    pub fn channel(mut self, channel: impl Into<String>) -> Self {
+        self.channel = Some(channel.into());
+        self
+    }
+
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    pub fn date(mut self, date: NaiveDate) -> Self {
+        self.date = Some(date);
+        self
+    }
+
+    pub fn path(mut self, path: impl AsRef<Path>) -> Self {
+        self.path = Some(path.as_ref().into());
+        self
+    }
+}
+
+/// A [`Location`] of an error, which is its source.
+#[derive(Debug)]
+struct Located {
+    location: Location,
+    source: anyhow::Error,
+}
+
+impl fmt::Display for Located {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.location.message)
+    }
+}
+
+impl std::error::Error for Located {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+/// Like [`anyhow::Context`], but the context is a [`Location`].
+pub trait Locate<T> {
+    fn locate(self, location: impl FnOnce() -> Location) -> anyhow::Result<T>;
+}
+
+impl<T, E: Into<anyhow::Error>> Locate<T> for Result<T, E> {
+    fn locate(self, location: impl FnOnce() -> Location) -> anyhow::Result<T> {
+        self.map_err(|source| {
+            anyhow::Error::new(Located {
+                location: location(),
+                source: source.into(),
+            })
+        })
+    }
+}
+
+/// How the binary reports a failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// The error chain on stderr.
+    Human,
+    /// The error chain on stderr and a [`Diagnostic`] on stdout.
+    Json,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            _ => anyhow::bail!("Unknown error format {}, expected human or json", s),
+        }
+    }
+}
+
+/// A failure of a run.
+#[derive(Debug, Serialize)]
+pub struct Diagnostic {
+    /// The outermost message.
+    pub error: String,
+    /// All the messages, the outermost first.
+    pub chain: Vec<String>,
+    pub category: Category,
+    pub channel: Option<String>,
+    pub target: Option<String>,
+    pub date: Option<NaiveDate>,
+    pub path: Option<PathBuf>,
+}
+
+/// Finds the first `YYYY-MM-DD` date in a message, e.g. in a manifest's URL.
+fn find_date(message: &str) -> Option<NaiveDate> {
+    message
+        .char_indices()
+        .filter_map(|(idx, _)| message.get(idx..idx + 10))
+        .find_map(|candidate| NaiveDate::parse_from_str(candidate, "%Y-%m-%d").ok())
+}
+
+/// Finds the date of the manifest a library error is about, e.g. the one in
+/// the manifest's URL.
+pub fn date_of(error: &rustup_available_packages::Error) -> Option<NaiveDate> {
+    use rustup_available_packages::Error;
+
+    match error {
+        Error::TomlDe(_, manifest)
+        | Error::TomlSer(_, manifest)
+        | Error::Reqwest(_, manifest)
+        | Error::BadResponse(_, manifest)
+        | Error::Io(_, manifest)
+        | Error::Verification(_, manifest) => find_date(manifest),
+    }
+}
+
+/// Guesses the category of an error by its type.
+fn categorize(error: &(dyn std::error::Error + 'static)) -> Option<Category> {
+    use rustup_available_packages::Error;
+
+    if let Some(error) = error.downcast_ref::<Error>() {
+        return Some(match error {
+            Error::TomlDe(..) | Error::TomlSer(..) => Category::Manifest,
+            Error::Reqwest(..) | Error::BadResponse(..) => Category::Network,
+            Error::Io(..) => Category::Io,
+            Error::Verification(..) => Category::Verification,
+        });
+    }
+    if error.is::<reqwest::Error>() {
+        Some(Category::Network)
+    } else if error.is::<handlebars::RenderError>() || error.is::<handlebars::TemplateError>() {
+        Some(Category::Template)
+    } else if error.is::<serde_json::Error>()
+        || error.is::<serde_yaml::Error>()
+        || error.is::<toml::de::Error>()
+    {
+        Some(Category::Parse)
+    } else if error.is::<std::io::Error>() {
+        Some(Category::Io)
+    } else {
+        None
+    }
+}
+
+/// Finds a field of the innermost location it's set in, as the innermost
+/// locations are the most specific ones.
+fn innermost<'a, T: ?Sized>(
+    locations: &[&'a Location],
+    field: impl Fn(&'a Location) -> Option<&'a T>,
+) -> Option<&'a T> {
+    locations.iter().rev().find_map(|location| field(location))
+}
+
+impl Diagnostic {
+    pub fn new(error: &anyhow::Error) -> Self {
+        let locations: Vec<_> = error
+            .chain()
+            .filter_map(|error| error.downcast_ref::<Located>())
+            .map(|located| &located.location)
+            .collect();
+        let chain: Vec<_> = error.chain().map(ToString::to_string).collect();
+        // The library's errors are more specific than their sources.
+        let category = locations
+            .iter()
+            .find_map(|location| location.category)
+            .or_else(|| {
+                error
+                    .chain()
+                    .find(|error| error.is::<rustup_available_packages::Error>())
+                    .and_then(categorize)
+            })
+            .or_else(|| error.chain().rev().find_map(categorize))
+            .unwrap_or(Category::Other);
+        Diagnostic {
+            error: error.to_string(),
+            category,
+            channel: innermost(&locations, |l| l.channel.as_ref()).cloned(),
+            target: innermost(&locations, |l| l.target.as_ref()).cloned(),
+            date: innermost(&locations, |l| l.date.as_ref())
+                .copied()
+                .or_else(|| chain.iter().rev().find_map(|message| find_date(message))),
+            path: innermost(&locations, |l| l.path.as_ref()).cloned(),
+            chain,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use rustup_available_packages::Error;
+
+    use super::*;
+
+    const URL: &str = "https://static.rust-lang.org/dist/2024-05-03/channel-rust-nightly.toml";
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, day).unwrap()
+    }
+
+    #[test]
+    fn finds_dates() {
+        assert_eq!(find_date(URL), Some(date(3)));
+        assert_eq!(find_date("2024-13-01 or 2024-05-02"), Some(date(2)));
+        assert_eq!(find_date("no date in here"), None);
+        assert_eq!(find_date("ü2024-05-01"), Some(date(1)));
+    }
+
+    #[test]
+    fn categorizes_errors() {
+        let bad_response = Error::BadResponse(reqwest::StatusCode::NOT_FOUND, URL.into());
+        assert_eq!(categorize(&bad_response), Some(Category::Network));
+        assert_eq!(date_of(&bad_response), Some(date(3)));
+        let verification = Error::Verification("Bad hash".into(), URL.into());
+        assert_eq!(categorize(&verification), Some(Category::Verification));
+        let io = io::Error::new(io::ErrorKind::NotFound, "gone");
+        assert_eq!(categorize(&io), Some(Category::Io));
+        let json = serde_json::from_str::<u32>("nope").unwrap_err();
+        assert_eq!(categorize(&json), Some(Category::Parse));
+        assert_eq!(categorize(&fmt::Error), None);
+    }
+
+    #[test]
+    fn diagnostics_of_located_errors() {
+        let error = Err::<(), _>(Error::BadResponse(
+            reqwest::StatusCode::NOT_FOUND,
+            URL.into(),
+        ))
+        .locate(|| Location::new("Can't download").channel("nightly"))
+        .locate(|| {
+            Location::new("Can't render")
+                .channel("beta")
+                .target("x86_64-unknown-linux-gnu")
+                .path("out/index.html")
+        })
+        .unwrap_err();
+        let diagnostic = Diagnostic::new(&error);
+        assert_eq!(diagnostic.error, "Can't render");
+        assert_eq!(diagnostic.chain.len(), 3);
+        assert_eq!(diagnostic.category, Category::Network);
+        // The innermost location wins.
+        assert_eq!(diagnostic.channel.as_deref(), Some("nightly"));
+        assert_eq!(
+            diagnostic.target.as_deref(),
+            Some("x86_64-unknown-linux-gnu")
+        );
+        assert_eq!(diagnostic.date, Some(date(3)));
+        assert_eq!(diagnostic.path, Some(PathBuf::from("out/index.html")));
+
+        let error = Err::<(), _>(io::Error::other("on 2024-05-01"))
+            .locate(|| {
+                Location::new("Can't archive")
+                    .category(Category::Config)
+                    .date(date(2))
+            })
+            .unwrap_err();
+        let diagnostic = Diagnostic::new(&error);
+        assert_eq!(diagnostic.category, Category::Config);
+        assert_eq!(diagnostic.date, Some(date(2)));
+        assert_eq!(diagnostic.channel, None);
+
+        let diagnostic = Diagnostic::new(&anyhow::anyhow!("Something else"));
+        assert_eq!(diagnostic.category, Category::Other);
+        assert_eq!(diagnostic.date, None);
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use anyhow::Context;
+use chrono::NaiveDate;
+use rustup_available_packages::{manifest::Manifest, Downloader};
+use rustup_status::diagnostics::{Locate, Location};
+use serde::Serialize;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+pub struct DiffOpt {
+    #[structopt(
+        long = "channel",
+        help = "A release channel to download manifests from",
+        default_value = "nightly"
+    )]
+    channel: String,
+    #[structopt(
+        long = "from",
+        help = "The older manifest: either a date (YYYY-MM-DD) or a path to a manifest file"
+    )]
+    from: ManifestSource,
+    #[structopt(
+        long = "to",
+        help = "The newer manifest: either a date (YYYY-MM-DD) or a path to a manifest file"
+    )]
+    to: ManifestSource,
+    #[structopt(long = "json", help = "Print the report in JSON")]
+    json: bool,
+}
+
+/// Where to get a manifest from.
+enum ManifestSource {
+    Date(NaiveDate),
+    File(PathBuf),
+}
+
+impl FromStr for ManifestSource {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.parse() {
+            Ok(date) => ManifestSource::Date(date),
+            Err(_) => ManifestSource::File(s.into()),
+        })
+    }
+}
+
+impl ManifestSource {
+    fn load(&self, channel: &str) -> anyhow::Result<Manifest> {
+        match self {
+            ManifestSource::Date(date) => Downloader::with_default_source(channel)
+                .get_manifest(*date)
+                .locate(|| {
+                    Location::new(format!("Can't fetch the {} manifest for {}", channel, date))
+                        .channel(channel)
+                        .date(*date)
+                }),
+            ManifestSource::File(path) => Manifest::load_from_fs(path)
+                .with_context(|| format!("Can't load a manifest from {:?}", path)),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Change<'a> {
+    target: &'a str,
+    package: &'a str,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    from: String,
+    to: String,
+    appeared: Vec<Change<'a>>,
+    disappeared: Vec<Change<'a>>,
+}
+
+/// Prints target/package combinations that appeared or disappeared between
+/// two manifests.
+pub fn run(opts: &DiffOpt) -> anyhow::Result<()> {
+    let from = opts.from.load(&opts.channel)?;
+    let to = opts.to.load(&opts.channel)?;
+    let from_packages = from.available_packages();
+    let to_packages = to.available_packages();
+    let report = Report {
+        from: from.date.format("%Y-%m-%d").to_string(),
+        to: to.date.format("%Y-%m-%d").to_string(),
+        appeared: to_packages
+            .difference(&from_packages)
+            .map(|&(target, package)| Change { target, package })
+            .collect(),
+        disappeared: from_packages
+            .difference(&to_packages)
+            .map(|&(target, package)| Change { target, package })
+            .collect(),
+    };
+
+    let mut out = io::stdout().lock();
+    if opts.json {
+        serde_json::to_writer_pretty(&mut out, &report)?;
+        writeln!(out)?;
+    } else {
+        writeln!(out, "Changes between {} and {}:", report.from, report.to)?;
+        if report.appeared.is_empty() && report.disappeared.is_empty() {
+            writeln!(out, "  none")?;
+        }
+        for (sign, changes) in [("+", &report.appeared), ("-", &report.disappeared)] {
+            for change in changes {
+                writeln!(out, "{} {} on {}", sign, change.package, change.target)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,293 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context;
+use chrono::{Duration, NaiveDate, Utc};
+use handlebars::Handlebars;
+use rustup_available_packages::{
+    manifest::{Manifest, PackageInfo, PackageTargets},
+    AvailabilityData,
+};
+
+use crate::{
+    assets::Assets,
+    filters::Filters,
+    generate_channel,
+    i18n::Catalog,
+    opts::{Config, Source},
+    output::MemoryOutput,
+    output_pattern, platform_support,
+    templates::Templates,
+    ChannelInfo, LoadedChannel, PathRenderData, Shared,
+};
+
+/// Results of a dry run.
+#[derive(Debug, Default)]
+pub struct DryRunReport {
+    /// Files that would have been written.
+    pub files: Vec<PathBuf>,
+    /// Everything that is wrong with the configuration.
+    pub errors: Vec<anyhow::Error>,
+}
+
+/// Makes up a manifest where some packages are missing on some days, so both
+/// branches of the templates are exercised.
+fn synthetic_manifest(date: NaiveDate, idx: usize) -> Manifest {
+    let targets = ["x86_64-unknown-linux-gnu", "aarch64-apple-darwin", "*"];
+    let packages = [("rustc", 0), ("clippy", 2), ("miri", 3), ("rust-src", 0)];
+    let packages = packages
+        .iter()
+        .map(|&(package, missing_every)| {
+            let targets = targets
+                .iter()
+                // Only `rust-src` is available on the wildcard target.
+                .filter(|&&target| (target == "*") == (package == "rust-src"))
+                .map(|&target| {
+                    let available = missing_every == 0 || !idx.is_multiple_of(missing_every);
+                    let info = PackageInfo {
+                        available,
+                        ..Default::default()
+                    };
+                    (target.to_string(), info)
+                })
+                .collect();
+            let info = PackageTargets {
+                version: Some(format!("1.0.0-nightly ({})", date)),
+                git_commit_hash: Some(format!("{:040x}", idx)),
+                targets,
+            };
+            (package.to_string(), info)
+        })
+        .collect();
+    Manifest {
+        date,
+        packages,
+        renames: HashMap::new(),
+        profiles: HashMap::new(),
+    }
+}
+
+fn check_templates(config: &Config, errors: &mut Vec<anyhow::Error>) {
+    let html = &config.html;
+    let templates = std::iter::once(&html.template_path)
+        .chain(html.package_pages.iter().map(|pages| &pages.template_path))
+        .chain(
+            html.search
+                .iter()
+                .flat_map(|search| &search.fallback_page)
+                .map(|page| &page.template_path),
+        )
+        .chain(&html.index_template)
+        .chain(&html.social_card_template)
+        .chain(html.changes.iter().map(|changes| &changes.template_path))
+        .chain(config.trends.iter().map(|trends| &trends.template_path))
+        .chain(
+            config
+                .notifications
+                .iter()
+                .flat_map(|notifications| &notifications.emails)
+                .flat_map(|email| [&email.text_template, &email.html_template]),
+        );
+    let mut engine = Templates::new(html.engine);
+    for dir in &html.template_dirs {
+        if let Err(e) = engine.register_dir(dir) {
+            errors.push(e.context("Invalid template directory"));
+        }
+    }
+    for (name, path) in &html.script_helpers {
+        if let Err(e) = engine.register_script_helper(name, path) {
+            errors.push(e);
+        }
+    }
+    if let Some(assets) = &html.assets {
+        match Assets::load(assets) {
+            Ok(assets) => engine.register_assets(&assets),
+            Err(e) => errors.push(e.context("Invalid assets")),
+        }
+    }
+    if let Some(i18n) = &html.i18n {
+        match Catalog::load(i18n) {
+            Ok(catalog) => engine.register_catalog(Arc::new(catalog)),
+            Err(e) => errors.push(e.context("Invalid localization")),
+        }
+    }
+    for path in templates {
+        let name = path.display().to_string();
+        if let Err(e) = engine.register_file(&name, path) {
+            errors.push(e.context("Invalid template"));
+        }
+    }
+}
+
+fn check_patterns(config: &Config, errors: &mut Vec<anyhow::Error>) {
+    let patterns = std::iter::once(&config.html.output_pattern)
+        .chain(
+            config
+                .html
+                .package_pages
+                .iter()
+                .map(|pages| &pages.output_pattern),
+        )
+        .chain(config.feed.iter().map(|feed| &feed.output_pattern))
+        .chain(config.badges.iter().map(|badges| &badges.output_pattern))
+        .chain(config.trends.iter().map(|trends| &trends.output_pattern))
+        .chain(
+            config
+                .html
+                .heatmaps
+                .iter()
+                .map(|heatmaps| &heatmaps.output_pattern),
+        )
+        .chain(
+            config
+                .last_good
+                .iter()
+                .map(|last_good| &last_good.output_pattern),
+        )
+        .chain(config.html.i18n.iter().map(|i18n| &i18n.output_pattern));
+    let data = PathRenderData {
+        package: Some("rustc"),
+        ..PathRenderData::localized("x86_64-unknown-linux-gnu", "en")
+    };
+    let handlebars = Handlebars::new();
+    for pattern in patterns {
+        if let Err(e) = handlebars.render_template(pattern, &data) {
+            errors
+                .push(anyhow::Error::new(e).context(format!("Invalid output pattern {}", pattern)));
+        }
+    }
+    // The pages of the targets must neither overwrite each other nor leave
+    // the output directory.
+    let page_patterns = std::iter::once(&config.html.output_pattern)
+        .chain(config.html.i18n.iter().map(|i18n| &i18n.output_pattern));
+    for pattern in page_patterns {
+        if !output_pattern::mentions_target(&handlebars, pattern) {
+            log::warn!(
+                "Output pattern {} doesn't contain {{{{target}}}}, all the targets would be written to the same file",
+                pattern
+            );
+        }
+        let path = match handlebars.render_template(pattern, &data) {
+            Ok(path) => path,
+            Err(_) => continue,
+        };
+        for name in &config.channels {
+            let channel = ChannelInfo::new(name, &config.channels);
+            let path = channel.relocate(Path::new(&path));
+            let root = output_pattern::root(pattern, channel);
+            if let Err(e) = output_pattern::check_inside(&root, &path)
+                .and_then(|_| output_pattern::check_site_root(config.site_root.as_deref(), &path))
+            {
+                errors.push(e.context(format!("Invalid output pattern {}", pattern)));
+            }
+        }
+    }
+}
+
+/// Validates the configuration and test-renders everything against synthetic
+/// data, without downloading manifests or writing anything.
+pub fn dry_run(mut config: Config) -> DryRunReport {
+    let mut report = DryRunReport::default();
+    check_templates(&config, &mut report.errors);
+    check_patterns(&config, &mut report.errors);
+    if let Err(e) = config.output.check() {
+        report.errors.push(e.context("Invalid output settings"));
+    }
+    if let Err(e) = Filters::new(&config) {
+        report.errors.push(e.context("Invalid filters"));
+    }
+    if let Some(notifications) = &config.notifications {
+        if notifications.state_path.is_none() && config.state_path.is_none() {
+            report.errors.push(anyhow::anyhow!(
+                "Notifications need either their own `state_path` or the global one"
+            ));
+        }
+    }
+    if let Source::Local { path } = &config.source {
+        if !path.is_dir() {
+            report.errors.push(anyhow::anyhow!(
+                "Manifests directory {} doesn't exist",
+                path.display()
+            ));
+        }
+    }
+    if let Some(keyring) = config
+        .verification
+        .as_ref()
+        .and_then(|verification| verification.keyring.as_ref())
+    {
+        if !keyring.is_file() {
+            report.errors.push(anyhow::anyhow!(
+                "Verification keyring {} doesn't exist",
+                keyring.display()
+            ));
+        }
+    }
+    // A vendored copy of the platform support data is read anyway, but the page
+    // isn't fetched.
+    if let Some(platform_support) = config
+        .html
+        .platform_support
+        .as_ref()
+        .filter(|platform_support| platform_support.path.is_some())
+    {
+        match platform_support::load(&config, platform_support) {
+            Ok(tiers) => config.html.tiers = tiers,
+            Err(e) => report
+                .errors
+                .push(e.context("Invalid platform support data")),
+        }
+    }
+    if !report.errors.is_empty() {
+        // Rendering would only fail with the same errors again.
+        return report;
+    }
+
+    // Anything that touches the world outside of the output is switched off.
+    config.state_path = None;
+    // Stop at the first failure of a channel to report it with all the details.
+    config.keep_going = false;
+    config.notifications = None;
+    config.github_issues = None;
+    if let Some(feed) = &mut config.feed {
+        feed.history_path = None;
+    }
+
+    let today = Utc::now().date_naive();
+    let days = config.days_in_past.max(1);
+    let shared = match Shared::load(&config) {
+        Ok(shared) => shared,
+        Err(e) => {
+            report.errors.push(e);
+            return report;
+        }
+    };
+    let mut out = MemoryOutput::default();
+    for channel in &config.channels {
+        let manifests: Vec<_> = (0..days)
+            .map(|idx| synthetic_manifest(today - Duration::days(idx as i64), idx))
+            .collect();
+        let all_dates = manifests.iter().map(|manifest| manifest.date).collect();
+        let mut data = AvailabilityData::default();
+        data.add_manifests(manifests);
+        let loaded = LoadedChannel {
+            name: channel.clone(),
+            data,
+            all_dates,
+            rendered: days,
+            manifests: Default::default(),
+            phases: Default::default(),
+        };
+        if let Err(e) = generate_channel(&config, &loaded, &shared, &mut out)
+            .with_context(|| format!("Can't render channel {}", channel))
+        {
+            report.errors.push(e);
+        }
+    }
+    report.files = out.paths();
+    report.files.sort_unstable();
+    report
+}
@@ -0,0 +1,100 @@
+use anyhow::Context;
+use chrono::NaiveDate;
+use rustup_available_packages::AvailabilityData;
+use rustup_status::{channel_cache, downloader, http::Http, toolstate::Explainer};
+use structopt::StructOpt;
+
+use crate::ConfigOpt;
+
+#[derive(StructOpt)]
+pub struct ExplainOpt {
+    #[structopt(flatten)]
+    config: ConfigOpt,
+    #[structopt(short = "t", long = "target", help = "A target to look into")]
+    target: String,
+    #[structopt(short = "p", long = "package", help = "A missing package")]
+    package: String,
+    #[structopt(
+        long = "days",
+        help = "How many days to look back",
+        default_value = "30"
+    )]
+    days: usize,
+    #[structopt(
+        long = "github-token",
+        help = "A GitHub token, which raises the API's rate limits, overrides the configuration"
+    )]
+    github_token: Option<String>,
+    #[structopt(
+        long = "history-url",
+        help = "Where to fetch the toolstate histories from, with {{os}} in place of the OS, \
+                overrides the configuration"
+    )]
+    history_url: Option<String>,
+}
+
+/// Finds the day a package went missing and the commit that broke it
+/// according to the toolstate history.
+pub fn run(opts: &ExplainOpt) -> anyhow::Result<()> {
+    let (mut config, channel) = opts.config.load_with_channel()?;
+    let manifests = downloader(&config, &channel, opts.days)?
+        .set_cache(channel_cache(&config, &channel)?)
+        .get_last_manifests(opts.days)
+        .with_context(|| format!("Can't fetch the {} manifests", channel))?;
+    let mut dates: Vec<_> = manifests.iter().map(|manifest| manifest.date).collect();
+    dates.sort_unstable_by(|a, b| b.cmp(a));
+    let mut data = AvailabilityData::default();
+    data.add_manifests(manifests);
+
+    let mut toolstate = config.html.toolstate.take().unwrap_or_default();
+    if let Some(token) = &opts.github_token {
+        toolstate.token = Some(token.clone());
+    }
+    if let Some(url) = &opts.history_url {
+        toolstate.history_url = url.clone();
+    }
+    let http = Http::new(&config.network, &config.retry)?;
+    let explanation = Explainer::new(&toolstate, &http)
+        .explain(&data, &dates, &opts.target, &opts.package)?
+        .with_context(|| {
+            format!(
+                "{} either is available on {} or hasn't been available in the last {} days",
+                opts.package, opts.target, opts.days
+            )
+        })?;
+    let describe = |date: NaiveDate, commit: &Option<String>| match commit {
+        Some(commit) => format!("{} (rustc {})", date, commit),
+        None => date.to_string(),
+    };
+    println!(
+        "Last available: {}",
+        describe(
+            explanation.last_available,
+            &explanation.last_available_commit
+        )
+    );
+    println!(
+        "First missing:  {}",
+        describe(explanation.first_missing, &explanation.first_missing_commit)
+    );
+    if let Some(url) = &explanation.compare_url {
+        println!("Changes: {}", url);
+    }
+    match &explanation.broken_by {
+        Some(culprit) => {
+            let by = match culprit.pull_request {
+                Some(number) => format!("rust-lang/rust#{}", number),
+                None => format!("rust-lang/rust@{}", culprit.commit),
+            };
+            println!(
+                "{} broken by {} ({}): {}",
+                opts.package, by, culprit.status, culprit.url
+            );
+        }
+        None => println!(
+            "The toolstate history doesn't tell what broke {}",
+            opts.package
+        ),
+    }
+    Ok(())
+}
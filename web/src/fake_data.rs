@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate};
+use rustup_available_packages::manifest::{Manifest, PackageInfo, PackageTargets};
+
+use crate::opts::{Config, FakeData};
+
+/// Names of the first made-up targets, so the configured tiers apply to them.
+const TARGETS: &[&str] = &[
+    "x86_64-unknown-linux-gnu",
+    "x86_64-pc-windows-msvc",
+    "aarch64-apple-darwin",
+    "aarch64-unknown-linux-gnu",
+    "x86_64-apple-darwin",
+    "i686-pc-windows-msvc",
+    "wasm32-unknown-unknown",
+    "armv7-unknown-linux-gnueabihf",
+    "riscv64gc-unknown-linux-gnu",
+    "x86_64-unknown-freebsd",
+];
+
+/// Names of the first made-up packages, the profiles are made of them.
+const PACKAGES: &[&str] = &[
+    "rustc",
+    "cargo",
+    "rust-std",
+    "rust-docs",
+    "rustfmt",
+    "clippy",
+    "miri",
+    "rust-src",
+    "rust-analyzer",
+    "llvm-tools",
+    "rustc-dev",
+    "rust-analysis",
+];
+
+/// [SplitMix64](https://prng.di.unimi.it/splitmix64.c), good enough to make
+/// up the same data for the same seed on every platform.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns `true` with the given probability.
+    fn chance(&mut self, probability: f64) -> bool {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64 <= probability
+    }
+
+    /// Returns a number in `1..=max`.
+    fn up_to(&mut self, max: u64) -> u64 {
+        self.next() % max.max(1) + 1
+    }
+}
+
+fn name(names: &[&str], prefix: &str, idx: usize) -> String {
+    match names.get(idx) {
+        Some(name) => name.to_string(),
+        None => format!("{}-{}", prefix, idx),
+    }
+}
+
+/// Makes up the manifests of `days` days ending at `end`, in descending order
+/// of dates like the downloaded ones. Packages are missing for a few days now
+/// and then, and some of them don't exist on some targets at all.
+pub fn manifests(config: &FakeData, channel: &str, end: NaiveDate, days: usize) -> Vec<Manifest> {
+    let mut rng = Rng(config.seed);
+    let targets: Vec<_> = (0..config.targets)
+        .map(|idx| name(TARGETS, "target", idx))
+        .collect();
+    let packages: Vec<_> = (0..config.packages)
+        .map(|idx| name(PACKAGES, "package", idx))
+        .collect();
+    // Whether a package exists on a target at all. The first package is
+    // everywhere, so no target is empty.
+    let exists: Vec<Vec<bool>> = packages
+        .iter()
+        .enumerate()
+        .map(|(idx, _)| {
+            targets
+                .iter()
+                .map(|_| idx == 0 || !rng.chance(0.2))
+                .collect()
+        })
+        .collect();
+    let mut profiles = HashMap::new();
+    for (profile, size) in [("minimal", 3), ("default", 6), ("complete", packages.len())] {
+        let size = size.min(packages.len());
+        profiles.insert(profile.to_string(), packages[..size].to_vec());
+    }
+
+    // Days left of the ongoing outage of every package on every target.
+    let mut outages = vec![vec![0u64; targets.len()]; packages.len()];
+    let mut manifests: Vec<_> = (0..days)
+        .rev()
+        .filter_map(|day| end.checked_sub_signed(Duration::days(day as i64)))
+        .map(|date| {
+            let commit = format!(
+                "{:016x}{:016x}{:08x}",
+                rng.next(),
+                rng.next(),
+                rng.next() >> 32
+            );
+            let packages = packages
+                .iter()
+                .enumerate()
+                .map(|(pkg_idx, package)| {
+                    let targets = targets
+                        .iter()
+                        .enumerate()
+                        .filter(|&(target_idx, _)| exists[pkg_idx][target_idx])
+                        .map(|(target_idx, target)| {
+                            let outage = &mut outages[pkg_idx][target_idx];
+                            if *outage == 0 && rng.chance(config.outage_probability) {
+                                *outage = rng.up_to(config.max_outage_days);
+                            }
+                            let available = *outage == 0;
+                            *outage = outage.saturating_sub(1);
+                            let info = PackageInfo {
+                                available,
+                                ..Default::default()
+                            };
+                            (target.clone(), info)
+                        })
+                        .collect();
+                    let info = PackageTargets {
+                        version: Some(format!("1.0.0-{} ({})", channel, date)),
+                        git_commit_hash: Some(commit.clone()),
+                        targets,
+                    };
+                    (package.clone(), info)
+                })
+                .collect();
+            Manifest {
+                date,
+                packages,
+                renames: HashMap::new(),
+                profiles: profiles.clone(),
+            }
+        })
+        .collect();
+    manifests.reverse();
+    manifests
+}
+
+/// Switches off everything that would tell the world about made-up data, or
+/// mix it up with the real one: the state, notifications, GitHub issues, the
+/// deployment, the snapshots, the archives, the metrics and the feeds'
+/// history.
+pub fn isolate(config: &mut Config) {
+    config.state_path = None;
+    config.notifications = None;
+    config.github_issues = None;
+    config.deploy = None;
+    config.site_root = None;
+    config.storage = None;
+    config.archive = None;
+    config.output.archive = None;
+    config.output.tree_archive = None;
+    config.metrics_file = None;
+    if let Some(feed) = &mut config.feed {
+        feed.history_path = None;
+    }
+}
+
+/// Made-up data of `days` days ending on 2023-03-01 for the tests, along with
+/// its dates in descending order.
+#[cfg(test)]
+pub(crate) fn test_data(
+    days: usize,
+) -> (rustup_available_packages::AvailabilityData, Vec<NaiveDate>) {
+    let end = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+    let manifests = manifests(&FakeData::default(), "nightly", end, days);
+    let dates = manifests.iter().map(|manifest| manifest.date).collect();
+    let mut data = rustup_available_packages::AvailabilityData::default();
+    data.add_manifests(manifests);
+    (data, dates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fake(seed: u64) -> FakeData {
+        FakeData {
+            seed,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn manifests_are_deterministic() {
+        let end = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+        let first = manifests(&fake(7), "nightly", end, 10);
+        assert_eq!(first, manifests(&fake(7), "nightly", end, 10));
+        assert_ne!(first, manifests(&fake(8), "nightly", end, 10));
+        assert_eq!(first.len(), 10);
+        assert_eq!(first[0].date, end);
+        assert_eq!(first[9].date, NaiveDate::from_ymd_opt(2023, 2, 20).unwrap());
+    }
+
+    #[test]
+    fn first_package_is_everywhere() {
+        let config = fake(1);
+        let end = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+        for manifest in manifests(&config, "beta", end, 3) {
+            assert_eq!(manifest.packages["rustc"].targets.len(), config.targets);
+        }
+    }
+
+    #[test]
+    fn isolated_config_writes_nothing_outside() {
+        let mut config: Config = serde_yaml::from_str(
+            r#"
+days_in_past: 7
+template_path: template.html
+output_pattern: "output/{{target}}.html"
+file_tree_output: output
+state_path: state.json
+site_root: output
+metrics_file: metrics.prom
+archive:
+  path: archive
+output:
+  archive: site.tar.gz
+  tree_archive: tree.tar.gz
+"#,
+        )
+        .unwrap();
+        isolate(&mut config);
+        assert!(config.state_path.is_none());
+        assert!(config.site_root.is_none());
+        assert!(config.metrics_file.is_none());
+        assert!(config.archive.is_none());
+        assert!(config.output.archive.is_none());
+        assert!(config.output.tree_archive.is_none());
+    }
+}
@@ -0,0 +1,329 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    fs::{self, File},
+    path::Path,
+};
+
+use anyhow::Context;
+use chrono::NaiveDate;
+use handlebars::Handlebars;
+use rustup_available_packages::AvailabilityData;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    escape_xml,
+    heatmap::relative_link,
+    opts,
+    output::{Output, Purpose},
+    target_output_path, ChannelInfo, PathRenderData,
+};
+
+/// What happened to a package on a specific day.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+enum Change {
+    Appeared,
+    Disappeared,
+}
+
+/// A single feed entry.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Event {
+    date: NaiveDate,
+    package: String,
+    change: Change,
+}
+
+/// Feed entries per target.
+type History = BTreeMap<String, BTreeSet<Event>>;
+
+/// Finds days when packages appeared or disappeared on a target.
+///
+/// `dates` must be sorted in descending order.
+fn find_events(data: &AvailabilityData, target: &str, dates: &[NaiveDate]) -> Vec<Event> {
+    data.target_changes(target, dates)
+        .into_iter()
+        .map(|change| Event {
+            date: change.date,
+            package: change.package.to_string(),
+            change: if change.available {
+                Change::Appeared
+            } else {
+                Change::Disappeared
+            },
+        })
+        .collect()
+}
+
+fn load_history(path: &Path) -> anyhow::Result<History> {
+    if !path.exists() {
+        return Ok(History::new());
+    }
+    let f = File::open(path)?;
+    Ok(serde_json::from_reader(f)?)
+}
+
+fn save_history(path: &Path, history: &History) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_vec(history)?)?;
+    Ok(())
+}
+
+fn atom_timestamp(date: NaiveDate) -> String {
+    date.format("%Y-%m-%dT00:00:00Z").to_string()
+}
+
+/// Renders the feed of a target, linking to the target's `page` and the feed
+/// itself by their paths relative to the feed. `latest` is the date of the
+/// latest manifest.
+fn render_feed<'a>(
+    channel: &str,
+    target: &str,
+    links: &Links,
+    latest: Option<NaiveDate>,
+    events: impl IntoIterator<Item = &'a Event>,
+) -> Result<String, std::fmt::Error> {
+    let events: Vec<_> = events.into_iter().collect();
+    // An empty feed is as new as the data it's made of.
+    let updated = events
+        .iter()
+        .map(|event| event.date)
+        .max()
+        .or(latest)
+        .map_or_else(|| "1970-01-01T00:00:00Z".into(), atom_timestamp);
+    let page = escape_xml(&links.page);
+    let mut feed = String::new();
+    writeln!(feed, r#"<?xml version="1.0" encoding="utf-8"?>"#)?;
+    writeln!(feed, r#"<feed xmlns="http://www.w3.org/2005/Atom">"#)?;
+    writeln!(
+        feed,
+        "  <id>tag:rustup-components-history,2019:{}/{}</id>",
+        escape_xml(channel),
+        escape_xml(target)
+    )?;
+    writeln!(
+        feed,
+        "  <title>Rustup packages availability changes on {} ({})</title>",
+        escape_xml(target),
+        escape_xml(channel)
+    )?;
+    writeln!(feed, "  <updated>{}</updated>", updated)?;
+    writeln!(
+        feed,
+        r#"  <link rel="self" type="application/atom+xml" href="{}"/>"#,
+        escape_xml(&links.feed)
+    )?;
+    writeln!(
+        feed,
+        r#"  <link rel="alternate" type="text/html" href="{}"/>"#,
+        page
+    )?;
+    writeln!(
+        feed,
+        "  <author><name>rustup-components-history</name></author>"
+    )?;
+    for event in events {
+        let (verb, id) = match event.change {
+            Change::Appeared => ("is available again", "appeared"),
+            Change::Disappeared => ("is missing", "disappeared"),
+        };
+        let title = format!(
+            "{} {} on {} since {}",
+            escape_xml(&event.package),
+            verb,
+            escape_xml(target),
+            event.date
+        );
+        writeln!(feed, "  <entry>")?;
+        writeln!(
+            feed,
+            "    <id>tag:rustup-components-history,{}:{}/{}/{}/{}</id>",
+            event.date,
+            escape_xml(channel),
+            escape_xml(target),
+            escape_xml(&event.package),
+            id
+        )?;
+        writeln!(feed, "    <title>{}</title>", title)?;
+        writeln!(
+            feed,
+            "    <updated>{}</updated>",
+            atom_timestamp(event.date)
+        )?;
+        writeln!(
+            feed,
+            r#"    <link rel="alternate" type="text/html" href="{}"/>"#,
+            page
+        )?;
+        writeln!(
+            feed,
+            "    <content type=\"text\">The {} manifest of {} {} {} for {}.</content>",
+            escape_xml(channel),
+            event.date,
+            match event.change {
+                Change::Appeared => "provides",
+                Change::Disappeared => "doesn't provide",
+            },
+            escape_xml(&event.package),
+            escape_xml(target)
+        )?;
+        writeln!(feed, "  </entry>")?;
+    }
+    writeln!(feed, "</feed>")?;
+    Ok(feed)
+}
+
+/// Where a feed links to, relative to the feed's directory.
+struct Links {
+    /// The feed itself.
+    feed: String,
+    /// The HTML page of the feed's target.
+    page: String,
+}
+
+/// Generates an Atom feed of availability changes for every target.
+///
+/// `dates` are all the known dates in descending order, not only the rendered
+/// ones.
+pub fn generate_feeds(
+    data: &AvailabilityData,
+    dates: &[NaiveDate],
+    config: &opts::Feed,
+    page_pattern: &str,
+    channel: ChannelInfo,
+    out: &mut dyn Output,
+) -> anyhow::Result<()> {
+    let history_path = config
+        .history_path
+        .as_ref()
+        .map(|path| channel.relocate(path));
+    let mut history = match &history_path {
+        Some(path) => load_history(path)
+            .with_context(|| format!("Can't load feed history {}", path.display()))?,
+        None => History::new(),
+    };
+
+    let handlebars = Handlebars::new();
+    for target in data.get_available_targets() {
+        let events = history.entry(target.to_string()).or_default();
+        events.extend(find_events(data, target, dates));
+        // Keep only the most recent entries.
+        while events.len() > config.max_entries {
+            events.pop_first();
+        }
+
+        let output_path = handlebars
+            .render_template(&config.output_pattern, &PathRenderData::target(target))
+            .with_context(|| format!("Invalid feed output pattern: {}", config.output_pattern))?;
+        let output_path = channel.relocate(Path::new(&output_path));
+        let page_path = target_output_path(&handlebars, page_pattern, target, channel)?;
+        let feed_dir = output_path.parent().unwrap_or_else(|| Path::new(""));
+        let links = Links {
+            feed: relative_link(feed_dir, &output_path),
+            page: relative_link(feed_dir, &page_path),
+        };
+        log::info!("Writing feed for {} to {:?}", target, output_path);
+        let feed = render_feed(
+            channel.name,
+            target,
+            &links,
+            dates.first().copied(),
+            events.iter().rev(),
+        )?;
+        out.set_purpose(Purpose::Feed);
+        out.write(&output_path, feed.as_bytes())?;
+    }
+
+    if let Some(path) = &history_path {
+        save_history(path, &history)
+            .with_context(|| format!("Can't save feed history {}", path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fake_data::test_data, output::MemoryOutput};
+
+    fn links() -> Links {
+        Links {
+            feed: "x86_64-unknown-linux-gnu.xml".into(),
+            page: "../x86_64-unknown-linux-gnu.html".into(),
+        }
+    }
+
+    #[test]
+    fn empty_feed_is_dated_by_the_data() {
+        let latest = NaiveDate::from_ymd_opt(2023, 3, 1);
+        let feed =
+            render_feed("nightly", "x86_64-unknown-linux-gnu", &links(), latest, []).unwrap();
+        assert!(feed.contains("<updated>2023-03-01T00:00:00Z</updated>"));
+        assert!(feed.contains(
+            r#"<link rel="self" type="application/atom+xml" href="x86_64-unknown-linux-gnu.xml"/>"#
+        ));
+        assert!(!feed.contains("<entry>"));
+    }
+
+    #[test]
+    fn entries_link_to_the_page() {
+        let event = Event {
+            date: NaiveDate::from_ymd_opt(2023, 2, 27).unwrap(),
+            package: "miri".into(),
+            change: Change::Disappeared,
+        };
+        let latest = NaiveDate::from_ymd_opt(2023, 3, 1);
+        let feed = render_feed(
+            "nightly",
+            "x86_64-unknown-linux-gnu",
+            &links(),
+            latest,
+            [&event],
+        )
+        .unwrap();
+        assert!(feed.contains("<updated>2023-02-27T00:00:00Z</updated>"));
+        assert!(feed.contains(
+            "<title>miri is missing on x86_64-unknown-linux-gnu since 2023-02-27</title>"
+        ));
+        assert_eq!(
+            feed.matches(
+                r#"<link rel="alternate" type="text/html" href="../x86_64-unknown-linux-gnu.html"/>"#
+            )
+            .count(),
+            2
+        );
+        assert!(feed.contains(
+            r#"<content type="text">The nightly manifest of 2023-02-27 doesn't provide miri for x86_64-unknown-linux-gnu.</content>"#
+        ));
+    }
+
+    #[test]
+    fn feeds_of_every_target() {
+        let (data, dates) = test_data(10);
+        let config = opts::Feed {
+            output_pattern: "output/feeds/{{target}}.xml".into(),
+            history_path: None,
+            max_entries: 5,
+        };
+        let channels = vec!["nightly".to_string(), "beta".to_string()];
+        let channel = ChannelInfo::new("beta", &channels);
+        let mut out = MemoryOutput::default();
+        generate_feeds(
+            &data,
+            &dates,
+            &config,
+            "output/{{target}}.html",
+            channel,
+            &mut out,
+        )
+        .unwrap();
+        let feeds = out.into_tree(Path::new("output/feeds/beta"));
+        assert_eq!(feeds.len(), data.get_available_targets().len());
+        let feed = String::from_utf8(feeds["aarch64-apple-darwin.xml"].clone()).unwrap();
+        assert!(feed.contains(r#"href="../../beta/aarch64-apple-darwin.html""#));
+        assert!(feed.matches("<entry>").count() <= 5);
+    }
+}
@@ -0,0 +1,66 @@
+use anyhow::Context;
+use glob::Pattern;
+
+use crate::opts::{self, Config};
+
+fn compile(patterns: &[String]) -> anyhow::Result<Vec<Pattern>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Pattern::new(pattern).with_context(|| format!("Invalid glob pattern {:?}", pattern))
+        })
+        .collect()
+}
+
+/// Compiled [`opts::Globs`].
+struct Globs {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl Globs {
+    fn new(globs: &opts::Globs) -> anyhow::Result<Self> {
+        Ok(Globs {
+            include: compile(&globs.include)?,
+            exclude: compile(&globs.exclude)?,
+        })
+    }
+
+    /// A name matches if any of the `include` patterns matches it, or there are
+    /// none, and none of the `exclude` ones does.
+    fn matches(&self, name: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| p.matches(name)))
+            && !self.exclude.iter().any(|p| p.matches(name))
+    }
+}
+
+/// Decides which packages are tracked on which targets.
+pub struct Filters {
+    targets: Globs,
+    packages: Vec<(Vec<Pattern>, Globs)>,
+}
+
+impl Filters {
+    pub fn new(config: &Config) -> anyhow::Result<Self> {
+        let packages = config
+            .package_filters
+            .iter()
+            .map(|filter| Ok((compile(&filter.targets)?, Globs::new(&filter.packages)?)))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Filters {
+            targets: Globs::new(&config.targets)?,
+            packages,
+        })
+    }
+
+    /// Whether a package is tracked on a target, i.e. the target is tracked
+    /// and every package filter that applies to it matches the package.
+    pub fn keeps(&self, target: &str, package: &str) -> bool {
+        // The wildcard target holds the packages that are available on every
+        // target, like `rust-src`.
+        (target == "*" || self.targets.matches(target))
+            && self.packages.iter().all(|(targets, packages)| {
+                !targets.iter().any(|p| p.matches(target)) || packages.matches(package)
+            })
+    }
+}
@@ -0,0 +1,370 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+    path::Path,
+};
+
+use anyhow::Context;
+use chrono::{NaiveDate, Utc};
+use rustup_available_packages::{
+    availability::{AvailabilityRow, AvailabilityStats, TargetLifecycle},
+    manifest::Artifact,
+    AvailabilityData,
+};
+
+use crate::{
+    generators::GeneratorInput,
+    output::{Output, Purpose, TarOutput},
+    with_missing_manifests,
+};
+
+/// Writes the machine-readable files of the input's targets under the
+/// channel's directory of the `output`, or into an `archive` of all the
+/// targets if there's one.
+pub fn generate_fs_tree(
+    input: &GeneratorInput,
+    output: &Path,
+    archive: Option<&Path>,
+    out: &mut dyn Output,
+) -> anyhow::Result<()> {
+    let GeneratorInput {
+        data,
+        dates,
+        channel,
+        ..
+    } = *input;
+    let tree_dir = channel.dir(output);
+    match archive {
+        Some(archive_path) => {
+            // The archive replaces the previous one, so it has to contain
+            // every target rather than the changed ones.
+            // The date of the latest manifest rather than the current time
+            // keeps the archive of the same data the same.
+            let mtime = dates
+                .first()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .map_or_else(Utc::now, |time| time.and_utc());
+            let mut archive = TarOutput::new(&tree_dir, mtime);
+            write_tree(
+                data,
+                dates,
+                &data.get_available_targets(),
+                &tree_dir,
+                &mut archive,
+            )?;
+            let archive_path = channel.relocate(archive_path);
+            log::info!("Writing the file tree to {:?}", archive_path);
+            out.set_purpose(Purpose::TreeArchive);
+            out.write(&archive_path, &archive.into_bytes()?)
+        }
+        None => write_tree(data, dates, input.targets, &tree_dir, out),
+    }
+}
+
+/// Serializes a list of packages to json.
+fn packages_json(pkgs: impl IntoIterator<Item = impl Display>) -> serde_json::Result<Vec<u8>> {
+    serde_json::to_vec(
+        &pkgs
+            .into_iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<String>>(),
+    )
+}
+
+/// Writes the machine-readable files of the given targets under the `output`
+/// directory: a file with the last available date and a JSON with the
+/// availability history per package. The summaries of the packages cover all
+/// the available targets.
+fn write_tree(
+    data: &AvailabilityData,
+    dates: &[NaiveDate],
+    targets: &HashSet<&str>,
+    output: &Path,
+    out: &mut dyn Output,
+) -> anyhow::Result<()> {
+    let pkgs = data.get_available_packages();
+
+    let contents = packages_json(&pkgs).with_context(|| "packages.json")?;
+    out.set_purpose(Purpose::PackageList);
+    out.write(&output.join("packages.json"), &contents)?;
+
+    let mut summaries: HashMap<&str, PackageSummary> = HashMap::new();
+    // The summaries are rewritten as a whole, so they cover the targets that
+    // haven't changed as well.
+    for target in data.get_available_targets() {
+        let target_path = output.join(target);
+        let changed = targets.contains(target);
+
+        for pkg in &pkgs {
+            let row = if let Some(row) = data.get_availability_row(target, pkg, dates) {
+                row
+            } else {
+                continue;
+            };
+            // This should always be true, but better to output nothing than to panic
+            // or output corrupt data.
+            let complete = dates.len() == row.availability_list.len();
+            if complete {
+                summaries
+                    .entry(pkg)
+                    .or_insert_with(|| PackageSummary::new(dates))
+                    .add(data, target, dates, &row.availability_list);
+            }
+            if !changed {
+                continue;
+            }
+
+            if let Some(date) = row.last_available {
+                let contents = format!("{}\n", date.format("%Y-%m-%d"));
+                out.set_purpose(Purpose::LastAvailable);
+                out.write(&target_path.join(pkg), contents.as_bytes())?;
+            } else {
+                // If a package is not available, don't create a file for it at
+                // all.
+            }
+
+            if complete {
+                let path = target_path.join(format!("{}.json", pkg));
+
+                let contents =
+                    serde_json::to_vec_pretty(&TargetPkg::new(data, target, dates, &row))?;
+                out.set_purpose(Purpose::PackageJson);
+                out.write(&path, &contents)?;
+            }
+        }
+    }
+
+    let packages_path = output.join("packages");
+    out.set_purpose(Purpose::PackageSummary);
+    for (pkg, mut summary) in summaries {
+        summary.missing_on.sort_unstable();
+        let contents = serde_json::to_vec_pretty(&summary)?;
+        out.write(&packages_path.join(format!("{}.json", pkg)), &contents)?;
+    }
+    Ok(())
+}
+
+/// A package's status on a single day.
+#[derive(serde::Serialize)]
+pub(crate) struct DayStatus {
+    pub available: bool,
+    pub version: Option<String>,
+    /// Where the package can be downloaded from, on the latest day only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artifact: Option<Artifact>,
+    /// The commit `rustc` was built from on the day.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rustc_commit: Option<String>,
+    /// Whether the manifest doesn't list the target, so the package isn't
+    /// missing but unknown.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub target_missing: bool,
+    /// Whether there's no manifest for the day, so the package isn't missing
+    /// but unknown.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub manifest_missing: bool,
+}
+
+#[derive(serde::Serialize)]
+pub(crate) struct TargetPkg {
+    #[serde(flatten)]
+    availability: BTreeMap<String, DayStatus>,
+    last_available: Option<String>,
+    stats: AvailabilityStats,
+    /// Whether the target has been added or removed within the dates.
+    target_lifecycle: TargetLifecycle,
+}
+
+impl TargetPkg {
+    pub(crate) fn new(
+        data: &AvailabilityData,
+        target: &str,
+        dates: &[NaiveDate],
+        row: &AvailabilityRow,
+    ) -> Self {
+        TargetPkg {
+            availability: dates
+                .iter()
+                .zip(row.availability_list.iter())
+                .zip(row.version_list.iter())
+                .zip(row.artifact_list.iter())
+                .enumerate()
+                .map(|(idx, (((date, &available), version), artifact))| {
+                    let status = DayStatus {
+                        available,
+                        version: version.clone(),
+                        // The older locations are rarely of use, while they
+                        // take most of the space.
+                        artifact: artifact.clone().filter(|_| idx == 0),
+                        rustc_commit: data.rustc_commit(*date).map(str::to_string),
+                        target_missing: !data.is_manifest_missing(*date)
+                            && !data.is_target_listed(target, *date),
+                        manifest_missing: false,
+                    };
+                    (date.format("%Y-%m-%d").to_string(), status)
+                })
+                .chain(
+                    with_missing_manifests(data, dates)
+                        .into_iter()
+                        .filter(|date| !dates.contains(date))
+                        .map(|date| {
+                            let status = DayStatus {
+                                available: false,
+                                version: None,
+                                artifact: None,
+                                rustc_commit: None,
+                                target_missing: false,
+                                manifest_missing: true,
+                            };
+                            (date.format("%Y-%m-%d").to_string(), status)
+                        }),
+                )
+                .collect(),
+            last_available: row.last_available.map(|d| d.format("%Y-%m-%d").to_string()),
+            stats: row.stats,
+            target_lifecycle: data.target_lifecycle(target, dates),
+        }
+    }
+}
+
+/// Availability of a package across all the targets.
+#[derive(serde::Serialize)]
+struct PackageSummary<'a> {
+    /// How many targets the package has been seen on.
+    targets: usize,
+    /// The number of targets that had the package on each date. The days the
+    /// target isn't listed on, or that have no manifest, don't count.
+    available_on: BTreeMap<String, usize>,
+    /// Targets missing the package on the latest date.
+    missing_on: Vec<&'a str>,
+}
+
+impl<'a> PackageSummary<'a> {
+    fn new(dates: &[NaiveDate]) -> Self {
+        PackageSummary {
+            targets: 0,
+            available_on: dates
+                .iter()
+                .map(|date| (date.format("%Y-%m-%d").to_string(), 0))
+                .collect(),
+            missing_on: Vec::new(),
+        }
+    }
+
+    /// Adds the availability of the package on a target, aligned with the dates.
+    fn add(
+        &mut self,
+        data: &AvailabilityData,
+        target: &'a str,
+        dates: &[NaiveDate],
+        availability_list: &[bool],
+    ) {
+        self.targets += 1;
+        for (&date, &available) in dates.iter().zip(availability_list) {
+            if data.is_target_listed(target, date) {
+                *self
+                    .available_on
+                    .entry(date.format("%Y-%m-%d").to_string())
+                    .or_default() += usize::from(available);
+            }
+        }
+        // A package isn't missing if the latest manifest is, or if it doesn't
+        // list the target.
+        let listed = dates
+            .first()
+            .is_some_and(|&date| data.is_target_listed(target, date));
+        if listed && availability_list.first() == Some(&false) {
+            self.missing_on.push(target);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::manifest;
+
+    #[test]
+    fn missing_manifests_are_unknown() {
+        let date = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        let mut data = AvailabilityData::default();
+        let target = "x86_64-unknown-linux-gnu";
+        for day in [1, 3] {
+            data.add_manifest(manifest(date(day), &[("cargo", target, true)]));
+        }
+        data.add_missing_manifest(date(2));
+        let dates = [date(3), date(1)];
+
+        let row = data.get_availability_row(target, "cargo", dates).unwrap();
+        let json = serde_json::to_value(TargetPkg::new(&data, target, &dates, &row)).unwrap();
+        assert_eq!(json["2024-01-02"]["manifest_missing"], true);
+        assert_eq!(json["2024-01-01"]["available"], true);
+        assert!(json["2024-01-01"].get("manifest_missing").is_none());
+    }
+
+    #[test]
+    fn unknown_latest_days_are_not_missing() {
+        let date = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        let mut data = AvailabilityData::default();
+        data.add_manifest(manifest(
+            date(1),
+            &[("cargo", "a", true), ("cargo", "b", true)],
+        ));
+        data.add_manifest(manifest(date(2), &[("cargo", "a", false)]));
+        data.add_missing_manifest(date(3));
+
+        // The latest manifest doesn't list `b`.
+        let dates = [date(2), date(1)];
+        let mut summary = PackageSummary::new(&dates);
+        summary.add(&data, "a", &dates, &[false, true]);
+        summary.add(&data, "b", &dates, &[false, true]);
+        assert_eq!(summary.missing_on, ["a"]);
+
+        // The latest manifest is missing.
+        let dates = [date(3), date(2), date(1)];
+        let mut summary = PackageSummary::new(&dates);
+        summary.add(&data, "a", &dates, &[false, false, true]);
+        assert!(summary.missing_on.is_empty());
+    }
+
+    #[test]
+    fn available_on_counts_the_known_days() {
+        let date = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        let mut data = AvailabilityData::default();
+        data.add_manifest(manifest(
+            date(1),
+            &[("cargo", "a", true), ("cargo", "b", true)],
+        ));
+        data.add_manifest(manifest(date(2), &[("cargo", "a", true)]));
+        data.add_missing_manifest(date(3));
+
+        let dates = [date(3), date(2), date(1)];
+        let mut summary = PackageSummary::new(&dates);
+        // The availability of the unknown days is whatever the table says.
+        summary.add(&data, "a", &dates, &[true, true, true]);
+        summary.add(&data, "b", &dates, &[true, true, true]);
+        let counts: Vec<_> = summary
+            .available_on
+            .iter()
+            .map(|(date, &count)| (date.as_str(), count))
+            .collect();
+        assert_eq!(
+            counts,
+            [("2024-01-01", 2), ("2024-01-02", 1), ("2024-01-03", 0)]
+        );
+    }
+
+    #[test]
+    fn days_have_rustc_commits() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let target = "x86_64-unknown-linux-gnu";
+        let mut manifest = manifest(date, &[("rustc", target, true)]);
+        manifest.packages.get_mut("rustc").unwrap().git_commit_hash = Some("abc".into());
+        let mut data = AvailabilityData::default();
+        data.add_manifest(manifest);
+
+        let row = data.get_availability_row(target, "rustc", [date]).unwrap();
+        let json = serde_json::to_value(TargetPkg::new(&data, target, &[date], &row)).unwrap();
+        assert_eq!(json["2024-01-01"]["rustc_commit"], "abc");
+    }
+}
@@ -0,0 +1,388 @@
+//! The outputs rendered for every channel, each enabled by its own section of
+//! the configuration.
+//!
+//! A new kind of output is a module with a type implementing
+//! [`OutputGenerator`], listed in [`configured`].
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    path::Path,
+};
+
+use chrono::NaiveDate;
+use rustup_available_packages::AvailabilityData;
+
+use crate::{
+    archive, badges, csv,
+    diagnostics::{Locate, Location},
+    explain_missing, feed, fs_tree, heatmap, html, json_dump, last_good,
+    opts::{self, Config},
+    output::Output,
+    tiers_table::TierChange,
+    trends, ChannelInfo, Shared,
+};
+
+/// Everything known about a channel that is being rendered.
+pub struct GeneratorInput<'a> {
+    pub data: &'a AvailabilityData,
+    /// The rendered dates, the latest first.
+    pub dates: &'a [NaiveDate],
+    /// The rendered dates followed by the ones looked up further back.
+    pub all_dates: &'a [NaiveDate],
+    /// The dates whose manifests have been re-published since the previous
+    /// run.
+    pub republished: &'a [NaiveDate],
+    /// The targets that have changed since the previous run.
+    pub targets: &'a HashSet<&'a str>,
+    /// The tier changes noticed by this and the previous runs.
+    pub tier_changes: &'a [TierChange],
+    pub channel: ChannelInfo<'a>,
+    /// What's loaded once for all the channels.
+    pub shared: &'a Shared<'a>,
+}
+
+#[cfg(test)]
+impl<'a> GeneratorInput<'a> {
+    /// The input of a test rendering the `targets` on every one of the `dates`.
+    pub(crate) fn test(
+        data: &'a AvailabilityData,
+        dates: &'a [NaiveDate],
+        targets: &'a HashSet<&'a str>,
+        channel: ChannelInfo<'a>,
+        shared: &'a Shared<'a>,
+    ) -> Self {
+        GeneratorInput {
+            data,
+            dates,
+            all_dates: dates,
+            republished: &[],
+            targets,
+            tier_changes: &[],
+            channel,
+            shared,
+        }
+    }
+}
+
+/// Renders a kind of output of a channel.
+pub trait OutputGenerator<'c> {
+    /// Makes a generator out of its section of the configuration, if the
+    /// section is there.
+    fn configure(config: &'c Config) -> Option<Self>
+    where
+        Self: Sized;
+
+    /// A short name of the output for the logs.
+    fn name(&self) -> &'static str;
+
+    /// Renders the output. Returns the targets that have been skipped because
+    /// they have failed to render.
+    fn generate<'a>(
+        &self,
+        input: &GeneratorInput<'a>,
+        out: &mut dyn Output,
+    ) -> anyhow::Result<Vec<&'a str>>;
+}
+
+/// Returns the generators enabled by a configuration, in the order they run.
+pub fn configured(config: &Config) -> Vec<Box<dyn OutputGenerator<'_> + '_>> {
+    fn add<'c, G: OutputGenerator<'c> + 'c>(
+        generators: &mut Vec<Box<dyn OutputGenerator<'c> + 'c>>,
+        config: &'c Config,
+    ) {
+        if let Some(generator) = G::configure(config) {
+            generators.push(Box::new(generator));
+        }
+    }
+
+    let mut generators = Vec::new();
+    add::<Html>(&mut generators, config);
+    add::<FsTree>(&mut generators, config);
+    add::<JsonDump>(&mut generators, config);
+    add::<Csv>(&mut generators, config);
+    add::<Feeds>(&mut generators, config);
+    add::<Badges>(&mut generators, config);
+    add::<Heatmaps>(&mut generators, config);
+    add::<Trends>(&mut generators, config);
+    add::<LastGood>(&mut generators, config);
+    add::<Archive>(&mut generators, config);
+    generators
+}
+
+/// The pages of the targets and the pages listing them.
+pub struct Html<'c> {
+    config: &'c Config,
+}
+
+impl<'c> OutputGenerator<'c> for Html<'c> {
+    fn configure(config: &'c Config) -> Option<Self> {
+        Some(Html { config })
+    }
+
+    fn name(&self) -> &'static str {
+        "html"
+    }
+
+    fn generate<'a>(
+        &self,
+        input: &GeneratorInput<'a>,
+        out: &mut dyn Output,
+    ) -> anyhow::Result<Vec<&'a str>> {
+        let explanations = explain_missing(self.config, input.data, input.dates, input.targets)?;
+        let options = html::Options {
+            keep_going: self.config.keep_going,
+            site_root: self.config.site_root.as_deref(),
+            explanations: &explanations,
+        };
+        html::generate_html(input, &self.config.html, &options, out)
+    }
+}
+
+/// The machine-readable files of the targets, either as a tree of files or
+/// as a single archive.
+pub struct FsTree<'c> {
+    output: &'c Path,
+    archive: Option<&'c Path>,
+}
+
+impl<'c> OutputGenerator<'c> for FsTree<'c> {
+    fn configure(config: &'c Config) -> Option<Self> {
+        Some(FsTree {
+            output: &config.file_tree_output,
+            archive: config.output.tree_archive.as_deref(),
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        "file tree"
+    }
+
+    fn generate<'a>(
+        &self,
+        input: &GeneratorInput<'a>,
+        out: &mut dyn Output,
+    ) -> anyhow::Result<Vec<&'a str>> {
+        fs_tree::generate_fs_tree(input, self.output, self.archive, out)?;
+        Ok(Vec::new())
+    }
+}
+
+/// A single JSON with the availability of everything.
+pub struct JsonDump<'c>(&'c opts::JsonDump);
+
+impl<'c> OutputGenerator<'c> for JsonDump<'c> {
+    fn configure(config: &'c Config) -> Option<Self> {
+        config.json_dump.as_ref().map(JsonDump)
+    }
+
+    fn name(&self) -> &'static str {
+        "json dump"
+    }
+
+    fn generate<'a>(
+        &self,
+        input: &GeneratorInput<'a>,
+        out: &mut dyn Output,
+    ) -> anyhow::Result<Vec<&'a str>> {
+        json_dump::generate_json_dump(input, self.0, out)?;
+        Ok(Vec::new())
+    }
+}
+
+/// A CSV table per target.
+pub struct Csv<'c>(&'c opts::Csv);
+
+impl<'c> OutputGenerator<'c> for Csv<'c> {
+    fn configure(config: &'c Config) -> Option<Self> {
+        config.csv.as_ref().map(Csv)
+    }
+
+    fn name(&self) -> &'static str {
+        "csv"
+    }
+
+    fn generate<'a>(
+        &self,
+        input: &GeneratorInput<'a>,
+        out: &mut dyn Output,
+    ) -> anyhow::Result<Vec<&'a str>> {
+        csv::generate_csv(input, self.0, out)?;
+        Ok(Vec::new())
+    }
+}
+
+/// An Atom feed per target.
+pub struct Feeds<'c> {
+    feed: &'c opts::Feed,
+    /// The pattern of the pages the feeds link to.
+    page_pattern: &'c str,
+}
+
+impl<'c> OutputGenerator<'c> for Feeds<'c> {
+    fn configure(config: &'c Config) -> Option<Self> {
+        config.feed.as_ref().map(|feed| Feeds {
+            feed,
+            page_pattern: &config.html.output_pattern,
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        "feeds"
+    }
+
+    fn generate<'a>(
+        &self,
+        input: &GeneratorInput<'a>,
+        out: &mut dyn Output,
+    ) -> anyhow::Result<Vec<&'a str>> {
+        feed::generate_feeds(
+            input.data,
+            input.all_dates,
+            self.feed,
+            self.page_pattern,
+            input.channel,
+            out,
+        )?;
+        Ok(Vec::new())
+    }
+}
+
+/// A badge per target and package.
+pub struct Badges<'c>(&'c opts::Badges);
+
+impl<'c> OutputGenerator<'c> for Badges<'c> {
+    fn configure(config: &'c Config) -> Option<Self> {
+        config.badges.as_ref().map(Badges)
+    }
+
+    fn name(&self) -> &'static str {
+        "badges"
+    }
+
+    fn generate<'a>(
+        &self,
+        input: &GeneratorInput<'a>,
+        out: &mut dyn Output,
+    ) -> anyhow::Result<Vec<&'a str>> {
+        badges::generate_badges(input.data, input.all_dates, self.0, input.channel, out)?;
+        Ok(Vec::new())
+    }
+}
+
+/// A calendar heatmap per target and package.
+pub struct Heatmaps<'c>(&'c opts::Heatmaps);
+
+impl<'c> OutputGenerator<'c> for Heatmaps<'c> {
+    fn configure(config: &'c Config) -> Option<Self> {
+        config.html.heatmaps.as_ref().map(Heatmaps)
+    }
+
+    fn name(&self) -> &'static str {
+        "heatmaps"
+    }
+
+    fn generate<'a>(
+        &self,
+        input: &GeneratorInput<'a>,
+        out: &mut dyn Output,
+    ) -> anyhow::Result<Vec<&'a str>> {
+        heatmap::generate_heatmaps(input.data, input.all_dates, self.0, input.channel, out)?;
+        Ok(Vec::new())
+    }
+}
+
+/// A page of the long-term trends per target.
+pub struct Trends<'c> {
+    trends: &'c opts::Trends,
+    html: &'c opts::Html,
+}
+
+impl<'c> OutputGenerator<'c> for Trends<'c> {
+    fn configure(config: &'c Config) -> Option<Self> {
+        config.trends.as_ref().map(|trends| Trends {
+            trends,
+            html: &config.html,
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        "trends"
+    }
+
+    fn generate<'a>(
+        &self,
+        input: &GeneratorInput<'a>,
+        out: &mut dyn Output,
+    ) -> anyhow::Result<Vec<&'a str>> {
+        trends::generate_trends(
+            input.data,
+            input.all_dates,
+            self.trends,
+            self.html,
+            input.channel,
+            input.shared,
+            out,
+        )?;
+        Ok(Vec::new())
+    }
+}
+
+/// The latest date every target had the required packages on.
+pub struct LastGood<'c>(&'c opts::LastGood);
+
+impl<'c> OutputGenerator<'c> for LastGood<'c> {
+    fn configure(config: &'c Config) -> Option<Self> {
+        config.last_good.as_ref().map(LastGood)
+    }
+
+    fn name(&self) -> &'static str {
+        "last good"
+    }
+
+    fn generate<'a>(
+        &self,
+        input: &GeneratorInput<'a>,
+        out: &mut dyn Output,
+    ) -> anyhow::Result<Vec<&'a str>> {
+        last_good::generate_last_good(input.data, self.0, input.channel, out)?;
+        Ok(Vec::new())
+    }
+}
+
+/// Permanent snapshots of every day.
+pub struct Archive<'c>(&'c opts::Archive);
+
+impl<'c> OutputGenerator<'c> for Archive<'c> {
+    fn configure(config: &'c Config) -> Option<Self> {
+        config.archive.as_ref().map(Archive)
+    }
+
+    fn name(&self) -> &'static str {
+        "archive"
+    }
+
+    fn generate<'a>(
+        &self,
+        input: &GeneratorInput<'a>,
+        out: &mut dyn Output,
+    ) -> anyhow::Result<Vec<&'a str>> {
+        // The archived days whose manifests have been re-published are out
+        // of date.
+        let days: BTreeSet<_> = input
+            .all_dates
+            .first()
+            .into_iter()
+            .chain(input.republished)
+            .collect();
+        for &date in days {
+            archive::generate_archive(input.data, date, self.0, input.channel, out).locate(
+                || {
+                    Location::new(format!("Can't archive {}", date))
+                        .channel(input.channel.name)
+                        .date(date)
+                },
+            )?;
+        }
+        Ok(Vec::new())
+    }
+}
@@ -0,0 +1,173 @@
+use std::{collections::HashMap, env};
+
+use chrono::NaiveDate;
+use reqwest::blocking::{RequestBuilder, Response};
+use rustup_available_packages::AvailabilityData;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{http::Http, opts, ChannelInfo};
+
+/// An issue as returned by the GitHub API.
+#[derive(Deserialize)]
+struct Issue {
+    number: u64,
+    title: String,
+    /// Set if the "issue" is actually a pull request.
+    #[serde(default)]
+    pull_request: Option<serde_json::Value>,
+}
+
+/// A thin client of the GitHub issues API of a single repository.
+struct Repo<'a> {
+    http: &'a Http,
+    config: &'a opts::GithubIssues,
+    token: String,
+}
+
+impl<'a> Repo<'a> {
+    fn request(&self, method: reqwest::Method, path: &str) -> RequestBuilder {
+        let url = format!(
+            "{}/repos/{}/{}",
+            self.config.api_url.trim_end_matches('/'),
+            self.config.repo,
+            path
+        );
+        self.http
+            .client()
+            .request(method, url)
+            .bearer_auth(&self.token)
+            .header("Accept", "application/vnd.github+json")
+    }
+
+    fn send(&self, request: RequestBuilder) -> anyhow::Result<Response> {
+        Ok(self.http.send(request)?.error_for_status()?)
+    }
+
+    /// Returns the open issues marked with the configured label.
+    fn open_issues(&self) -> anyhow::Result<Vec<Issue>> {
+        let mut issues = Vec::new();
+        for page in 1.. {
+            let batch: Vec<Issue> = self
+                .send(self.request(reqwest::Method::GET, "issues").query(&[
+                    ("state", "open"),
+                    ("labels", &self.config.label),
+                    ("per_page", "100"),
+                    ("page", &page.to_string()),
+                ]))?
+                .json()?;
+            let last = batch.len() < 100;
+            issues.extend(batch.into_iter().filter(|i| i.pull_request.is_none()));
+            if last {
+                break;
+            }
+        }
+        Ok(issues)
+    }
+
+    fn open(&self, title: &str, body: &str) -> anyhow::Result<()> {
+        self.send(
+            self.request(reqwest::Method::POST, "issues")
+                .json(&json!({ "title": title, "body": body, "labels": [self.config.label] })),
+        )?;
+        Ok(())
+    }
+
+    fn close(&self, number: u64, comment: &str) -> anyhow::Result<()> {
+        self.send(
+            self.request(
+                reqwest::Method::POST,
+                &format!("issues/{}/comments", number),
+            )
+            .json(&json!({ "body": comment })),
+        )?;
+        self.send(
+            self.request(reqwest::Method::PATCH, &format!("issues/{}", number))
+                .json(&json!({ "state": "closed", "state_reason": "completed" })),
+        )?;
+        Ok(())
+    }
+}
+
+fn title(channel: &str, target: &str, package: &str) -> String {
+    format!("`{}` is missing on `{}` ({})", package, target, channel)
+}
+
+/// Opens an issue for every watched package that has been missing on a
+/// watched target for more than the configured number of days, and closes the
+/// issues of the packages that are available again.
+///
+/// `dates` must be sorted in descending order.
+pub fn sync_issues(
+    data: &AvailabilityData,
+    dates: &[NaiveDate],
+    config: &opts::GithubIssues,
+    channel: ChannelInfo,
+    http: &Http,
+) -> anyhow::Result<()> {
+    let latest = match dates.first() {
+        Some(&date) => date,
+        None => return Ok(()),
+    };
+    let token = match config
+        .token
+        .clone()
+        .or_else(|| env::var("GITHUB_TOKEN").ok())
+    {
+        Some(token) => token,
+        None => anyhow::bail!("No token to manage the issues of {} with", config.repo),
+    };
+    let repo = Repo {
+        http,
+        config,
+        token,
+    };
+    let open: HashMap<String, u64> = match repo.open_issues() {
+        Ok(issues) => issues
+            .into_iter()
+            .map(|issue| (issue.title, issue.number))
+            .collect(),
+        // The GitHub API being down shouldn't prevent the pages from being
+        // updated.
+        Err(e) => {
+            log::error!("Can't list the issues of {}: {:?}", config.repo, e);
+            return Ok(());
+        }
+    };
+
+    for target in &config.targets {
+        for package in &config.packages {
+            let title = title(channel.name, target, package);
+            let last_available = data.last_available(target, package);
+            // Packages that haven't been available within the lookup window
+            // have been missing for longer than it.
+            let missing_days = last_available.map(|date| (latest - date).num_days() as usize);
+            let result = match open.get(&title) {
+                Some(&number) if missing_days == Some(0) => {
+                    log::info!("Closing issue #{} of {}", number, config.repo);
+                    let comment = format!("`{}` is available again as of {}.", package, latest);
+                    repo.close(number, &comment)
+                }
+                None if missing_days.is_none_or(|days| days > config.after_days) => {
+                    let since = match last_available {
+                        Some(date) => format!("was last available on {}", date),
+                        None => {
+                            format!("has been missing since at least {}", dates[dates.len() - 1])
+                        }
+                    };
+                    let body = format!(
+                        "`{}` {} on `{}` in the {} channel, as of {}.",
+                        package, since, target, channel.name, latest
+                    );
+                    log::info!("Opening an issue about {} on {}", package, target);
+                    repo.open(&title, &body)
+                }
+                _ => Ok(()),
+            };
+            if let Err(e) = result {
+                log::error!("Can't update the issues of {}: {:?}", config.repo, e);
+            }
+        }
+    }
+    Ok(())
+}
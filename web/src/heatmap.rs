@@ -0,0 +1,151 @@
+use std::{
+    fmt::Write,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::Context;
+use chrono::{Datelike, Duration, NaiveDate};
+use handlebars::Handlebars;
+use rustup_available_packages::AvailabilityData;
+
+use crate::{
+    opts,
+    output::{Output, Purpose},
+    ChannelInfo, PathRenderData,
+};
+
+const AVAILABLE_COLOR: &str = "#40c463";
+const MISSING_COLOR: &str = "#e05d44";
+const UNKNOWN_COLOR: &str = "#ebedf0";
+/// Size of a day's cell in pixels.
+const CELL: i64 = 10;
+/// Gap between the cells in pixels.
+const GAP: i64 = 2;
+
+/// Returns the path of a package's heatmap on a target.
+pub fn output_path(
+    config: &opts::Heatmaps,
+    target: &str,
+    package: &str,
+    channel: ChannelInfo,
+) -> anyhow::Result<PathBuf> {
+    let path = Handlebars::new()
+        .render_template(
+            &config.output_pattern,
+            &PathRenderData {
+                package: Some(package),
+                ..PathRenderData::target(target)
+            },
+        )
+        .with_context(|| format!("Invalid heatmap output pattern: {}", config.output_pattern))?;
+    Ok(channel.relocate(Path::new(&path)))
+}
+
+/// Makes a relative link from a page in the `from` directory to the `to` file.
+pub fn relative_link(from: &Path, to: &Path) -> String {
+    let from: Vec<_> = from
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .collect();
+    let to: Vec<_> = to
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    std::iter::repeat_n("..".into(), from.len() - common)
+        .chain(to[common..].iter().map(|c| c.as_os_str().to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Renders a calendar of the days from `start` to `end` (inclusive), a column
+/// per week starting on Monday.
+fn render_heatmap(
+    title: &str,
+    start: NaiveDate,
+    end: NaiveDate,
+    status: impl Fn(NaiveDate) -> Option<bool>,
+) -> String {
+    let first_monday = start - Duration::days(start.weekday().num_days_from_monday().into());
+    let weeks = (end - first_monday).num_days() / 7 + 1;
+    let title = crate::escape_xml(title);
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" role="img" aria-label="{title}">
+  <title>{title}</title>
+"#,
+        width = weeks * (CELL + GAP) - GAP,
+        height = 7 * (CELL + GAP) - GAP,
+    );
+    let mut day = start;
+    while day <= end {
+        let (color, description) = match status(day) {
+            Some(true) => (AVAILABLE_COLOR, "available"),
+            Some(false) => (MISSING_COLOR, "missing"),
+            None => (UNKNOWN_COLOR, "no manifest"),
+        };
+        let _ = writeln!(
+            svg,
+            r#"  <rect x="{}" y="{}" width="{CELL}" height="{CELL}" rx="2" fill="{}"><title>{}: {}</title></rect>"#,
+            (day - first_monday).num_days() / 7 * (CELL + GAP),
+            i64::from(day.weekday().num_days_from_monday()) * (CELL + GAP),
+            color,
+            day.format("%Y-%m-%d"),
+            description,
+        );
+        day += Duration::days(1);
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Generates a heatmap of the last `days` of every package on every target.
+///
+/// `dates` are all the known dates in descending order, not only the rendered
+/// ones. Days without a manifest are shown as unknown, while the days before
+/// the oldest known one aren't shown at all.
+pub fn generate_heatmaps(
+    data: &AvailabilityData,
+    dates: &[NaiveDate],
+    config: &opts::Heatmaps,
+    channel: ChannelInfo,
+    out: &mut dyn Output,
+) -> anyhow::Result<()> {
+    let end = match dates.first() {
+        Some(&end) => end,
+        None => return Ok(()),
+    };
+    let start = end - Duration::days(config.days.saturating_sub(1) as i64);
+    // Only the looked up days are known.
+    let oldest = *dates.last().unwrap_or(&end);
+    let start = if start < oldest {
+        log::warn!(
+            "Heatmaps show {} days, but only the {} days since {} are known; raise `additional_lookup_days` to show them all",
+            config.days,
+            (end - oldest).num_days() + 1,
+            oldest
+        );
+        oldest
+    } else {
+        start
+    };
+    let pkgs = data.get_available_packages();
+    for target in data.get_available_targets() {
+        log::info!("Generating heatmaps for {}", target);
+        for pkg in &pkgs {
+            let row = match data.get_availability_row(target, pkg, dates) {
+                Some(row) => row,
+                None => continue,
+            };
+            let status = |day| {
+                let idx = dates.iter().position(|&date| date == day)?;
+                Some(row.availability_list[idx])
+            };
+            let title = format!("{} on {}", pkg, target);
+            let heatmap = render_heatmap(&title, start, end, status);
+            let path = output_path(config, target, pkg, channel)?;
+            out.set_purpose(Purpose::Heatmap);
+            out.write(&path, heatmap.as_bytes())?;
+        }
+    }
+    Ok(())
+}
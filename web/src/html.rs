@@ -0,0 +1,480 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    iter,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use chrono::NaiveDate;
+use handlebars::Handlebars;
+use rayon::prelude::*;
+use rustup_available_packages::{table::Table, AvailabilityData};
+use serde::Serialize;
+
+use crate::{
+    changes,
+    diagnostics::{Locate, Location},
+    generators::GeneratorInput,
+    heatmap, index, opts,
+    output::{Output, Purpose},
+    output_pattern, package_pages, plain_text, progress, recent_tier_changes, search, social_cards,
+    target_output_path, target_pages,
+    templates::Templates,
+    toolstate::Explanation,
+    with_missing_manifests, AdditionalData, ChannelInfo, PathRenderData,
+};
+
+/// Data of a target's page on top of the [`AdditionalData`].
+#[derive(Serialize)]
+pub(crate) struct TargetPageData<'a> {
+    #[serde(flatten)]
+    common: &'a AdditionalData<'a>,
+    /// Links to the heatmaps of the target's packages, if they are generated.
+    heatmaps: BTreeMap<&'a str, String>,
+    /// The absolute URL of the target's social preview card, if it is
+    /// rendered.
+    social_card: Option<String>,
+    /// Links to the pages of the targets of the tiers table, keyed by target.
+    target_pages: BTreeMap<&'a str, String>,
+    /// Links to the target's page on every channel, keyed by channel.
+    channel_pages: BTreeMap<&'a str, String>,
+    /// The language of the page, if the pages are localized.
+    lang: Option<&'a str>,
+    /// Explanations of the packages missing on the latest day, if the
+    /// toolstate history is configured.
+    explanations: BTreeMap<&'a str, Explanation>,
+}
+
+/// How the pages are rendered, besides their configuration.
+pub struct Options<'a> {
+    /// Whether a target that fails to render is skipped rather than aborting
+    /// the whole rendering.
+    pub keep_going: bool,
+    /// The directory no page may be written outside of, if there's one.
+    pub site_root: Option<&'a Path>,
+    /// Explanations of the packages missing on the latest date, keyed by
+    /// target.
+    pub explanations: &'a HashMap<&'a str, BTreeMap<&'a str, Explanation>>,
+}
+
+/// What every page of a channel is rendered with.
+pub struct Pages<'a> {
+    pub input: &'a GeneratorInput<'a>,
+    pub config: &'a opts::Html,
+    pub templates: &'a Templates,
+    pub additional: &'a AdditionalData<'a>,
+}
+
+/// A page of a target in one of the languages.
+pub(crate) struct TargetPage<'a> {
+    pub path: &'a Path,
+    /// The language of the page, if the pages are localized.
+    pub lang: Option<&'a str>,
+    /// The absolute URL of the target's social preview card, if it is
+    /// rendered.
+    pub social_card: Option<&'a str>,
+    pub explanations: BTreeMap<&'a str, Explanation>,
+}
+
+/// Renders the HTML pages of the input's targets, as well as the pages that
+/// list all the targets or packages, if configured. Returns the targets that
+/// have been skipped with `keep_going`.
+///
+/// The input's tier changes that happened on the rendered dates are marked in
+/// the tiers table and listed on the changes page.
+pub fn generate_html<'a>(
+    input: &GeneratorInput<'a>,
+    config: &opts::Html,
+    options: &Options,
+    out: &mut dyn Output,
+) -> anyhow::Result<Vec<&'a str>> {
+    const TEMPLATE_NAME: &str = "target_info";
+    let GeneratorInput {
+        data,
+        dates,
+        targets,
+        channel,
+        shared,
+        ..
+    } = *input;
+    let opts::Html {
+        template_path,
+        output_pattern,
+        tiers,
+        platform_support,
+        package_pages,
+        search,
+        index_template,
+        watch_sets: _,
+        changes,
+        engine,
+        template_dirs,
+        script_helpers,
+        assets: _,
+        heatmaps: _,
+        toolstate: _,
+        social_card_template,
+        social_card_url,
+        plain_text,
+        i18n,
+        theme,
+        render_threads: _,
+    } = config;
+    let mut templates = Templates::new(*engine);
+    for dir in template_dirs {
+        templates.register_dir(dir)?;
+    }
+    for (name, path) in script_helpers {
+        templates.register_script_helper(name, path)?;
+    }
+    shared.register(&mut templates);
+    templates.register_file(TEMPLATE_NAME, template_path)?;
+    if let Some(package_pages) = package_pages {
+        templates.register_file(package_pages::TEMPLATE_NAME, &package_pages.template_path)?;
+    }
+    if let Some(page) = search
+        .as_ref()
+        .and_then(|search| search.fallback_page.as_ref())
+    {
+        templates.register_file(search::TEMPLATE_NAME, &page.template_path)?;
+    }
+    if let Some(changes) = changes {
+        templates.register_file(changes::TEMPLATE_NAME, &changes.template_path)?;
+    }
+    if let Some(index_template) = index_template {
+        templates.register_file(index::TEMPLATE_NAME, index_template)?;
+    }
+    let card_url = match social_card_template {
+        Some(card_template) => {
+            templates.register_file(social_cards::TEMPLATE_NAME, card_template)?;
+            Some(
+                social_card_url
+                    .as_deref()
+                    .context("social_card_url is required to link the social cards")?,
+            )
+        }
+        None => None,
+    };
+    let handlebars = Handlebars::new();
+
+    let all_targets = data.get_available_targets();
+    // Incremental runs render only some targets, but their pages must not
+    // collide with the others either.
+    check_output_paths(
+        &handlebars,
+        output_pattern,
+        i18n.as_ref(),
+        &all_targets,
+        channel,
+        options.site_root,
+    )?;
+
+    let tier_changes = recent_tier_changes(input.tier_changes, dates);
+    let additional = AdditionalData::new(tiers, &all_targets, &tier_changes, theme, channel);
+    if platform_support.is_some() {
+        for target in additional.tiers.unknown_targets() {
+            log::warn!(
+                channel = channel.name;
+                "{} is in the {} manifests but not in the platform support data",
+                target,
+                channel.name
+            );
+        }
+    }
+
+    let rendering = progress::bar(targets.len(), format!("Rendering {} targets", channel.name));
+    // Rendering is CPU-bound, while the output isn't thread-safe: the pages
+    // are rendered in parallel and written one by one afterwards.
+    let render_target = |target: &str| -> anyhow::Result<Vec<(PathBuf, Vec<u8>, Purpose)>> {
+        log::info!(target_triple = target; "Processing target {}", target);
+        let mut files = Vec::new();
+        let output_path = target_output_path(&handlebars, output_pattern, target, channel)?;
+        let card_path = card_url.map(|_| social_cards::output_path(&output_path));
+        let card_url = card_url
+            .zip(card_path.as_deref())
+            .map(|(base_url, card_path)| social_cards::url(base_url, options.site_root, card_path))
+            .transpose()?;
+        if let Some(card_path) = &card_path {
+            let card = social_cards::render_card(
+                &templates,
+                &shared.card_fonts,
+                data,
+                dates,
+                tiers,
+                target,
+                &additional,
+            )
+            .locate(|| {
+                Location::new(format!("Can't render a social card for [{}]", target))
+                    .channel(channel.name)
+                    .target(target)
+                    .path(card_path)
+            })?;
+            files.push((card_path.clone(), card, Purpose::SocialCard));
+        }
+        // The default language's pages are the unlocalized ones.
+        let default_lang = i18n.as_ref().and_then(|i18n| i18n.languages.first());
+        let localized = i18n.iter().flat_map(|i18n| {
+            i18n.languages
+                .iter()
+                .map(move |lang| (&i18n.output_pattern, Some(lang)))
+        });
+        let pages = iter::once((output_pattern, default_lang)).chain(localized);
+        for (idx, (pattern, lang)) in pages.enumerate() {
+            let output_path = match lang {
+                Some(lang) if pattern != output_pattern => {
+                    localized_output_path(&handlebars, pattern, target, lang, channel)?
+                }
+                _ => output_path.clone(),
+            };
+            let page = TargetPage {
+                path: &output_path,
+                lang: lang.map(String::as_str),
+                social_card: card_url.as_deref(),
+                explanations: options
+                    .explanations
+                    .get(target)
+                    .cloned()
+                    .unwrap_or_default(),
+            };
+            let table = target_table(data, dates, target, config, &additional, channel, page)?;
+            let rendered = templates
+                .render_page(TEMPLATE_NAME, &table, &output_path)
+                .locate(|| {
+                    Location::new(format!(
+                        "Can't render [{:?}] for [{}]",
+                        template_path, target
+                    ))
+                    .channel(channel.name)
+                    .target(target)
+                    .path(&output_path)
+                })?;
+            // Only the unlocalized page gets a plain-text version.
+            if let Some(plain_text) = plain_text.as_ref().filter(|_| idx == 0) {
+                let text = plain_text::render(&table, channel.name, plain_text.days)?;
+                let text_path = plain_text::output_path(&output_path);
+                files.push((text_path, text.into_bytes(), Purpose::PlainText));
+            }
+            files.push((output_path, rendered.into_bytes(), Purpose::TargetPage));
+        }
+        Ok(files)
+    };
+    let targets: Vec<_> = targets.iter().copied().collect();
+    let results: Vec<_> = shared.render_pool.install(|| {
+        targets
+            .par_iter()
+            .map(|&target| {
+                let result = render_target(target);
+                rendering.inc(1);
+                result
+            })
+            .collect()
+    });
+    rendering.finish_and_clear();
+
+    let mut failed = Vec::new();
+    for (target, result) in targets.into_iter().zip(results) {
+        let result = result.and_then(|files| {
+            files.into_iter().try_for_each(|(path, contents, purpose)| {
+                out.set_purpose(purpose);
+                log::info!(target_triple = target; "Writing target {} to {:?}", target, path);
+                out.write(&path, &contents)
+            })
+        });
+        match result {
+            Err(e) if options.keep_going => {
+                log::error!(target_triple = target; "Skipping target {}: {:#}", target, e);
+                failed.push(target);
+            }
+            result => result?,
+        }
+    }
+
+    let pages = Pages {
+        input,
+        config,
+        templates: &templates,
+        additional: &additional,
+    };
+    if let Some(package_pages) = package_pages {
+        package_pages::generate_package_html(&pages, package_pages, out)?;
+    }
+    if let Some(search) = search {
+        search::generate_search_index(&pages, search, out)?;
+    }
+    if let Some(changes) = changes {
+        changes::generate_changes(&pages, &tier_changes, changes, out)?;
+    }
+    if let Some(index_template) = index_template {
+        index::generate_index(&pages, out)
+            .with_context(|| format!("Can't render [{:?}]", index_template))?;
+    }
+    failed.sort_unstable();
+    Ok(failed)
+}
+
+/// Makes sure that no two pages of the targets have the same path and that
+/// none of them leaves the directory of its pattern, nor the `site_root` (the
+/// working directory if there's none).
+fn check_output_paths(
+    handlebars: &Handlebars,
+    output_pattern: &str,
+    i18n: Option<&opts::I18n>,
+    targets: &HashSet<&str>,
+    channel: ChannelInfo,
+    site_root: Option<&Path>,
+) -> anyhow::Result<()> {
+    if !output_pattern::mentions_target(handlebars, output_pattern) {
+        log::warn!(
+            "Output pattern {} doesn't contain {{{{target}}}}, all the targets would be written to the same file",
+            output_pattern
+        );
+    }
+    let mut targets: Vec<_> = targets.iter().copied().collect();
+    targets.sort_unstable();
+    let mut collisions = output_pattern::Collisions::default();
+    let root = output_pattern::root(output_pattern, channel);
+    for &target in &targets {
+        let path = target_output_path(handlebars, output_pattern, target, channel)?;
+        output_pattern::check_inside(&root, &path)
+            .and_then(|_| output_pattern::check_site_root(site_root, &path))
+            .and_then(|_| collisions.add(target.to_string(), &path))
+            .with_context(|| format!("Invalid output pattern {}", output_pattern))?;
+    }
+    // The pages of the default language might be the unlocalized ones.
+    if let Some(i18n) = i18n.filter(|i18n| i18n.output_pattern != output_pattern) {
+        let root = output_pattern::root(&i18n.output_pattern, channel);
+        for lang in &i18n.languages {
+            for &target in &targets {
+                let path =
+                    localized_output_path(handlebars, &i18n.output_pattern, target, lang, channel)?;
+                output_pattern::check_inside(&root, &path)
+                    .and_then(|_| output_pattern::check_site_root(site_root, &path))
+                    .and_then(|_| collisions.add(format!("{} ({})", target, lang), &path))
+                    .with_context(|| {
+                        format!("Invalid localized output pattern {}", i18n.output_pattern)
+                    })?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn localized_output_path(
+    handlebars: &Handlebars,
+    output_pattern: &str,
+    target: &str,
+    lang: &str,
+    channel: ChannelInfo,
+) -> anyhow::Result<PathBuf> {
+    let output_path = handlebars
+        .render_template(output_pattern, &PathRenderData::localized(target, lang))
+        .with_context(|| format!("Invalid localized output pattern: {}", output_pattern))?;
+    Ok(channel.relocate(Path::new(&output_path)))
+}
+
+/// Makes the data a target's page is rendered from.
+pub(crate) fn target_table<'a>(
+    data: &'a AvailabilityData,
+    dates: &[NaiveDate],
+    target: &'a str,
+    config: &opts::Html,
+    additional: &'a AdditionalData<'a>,
+    channel: ChannelInfo,
+    page: TargetPage<'a>,
+) -> anyhow::Result<Table<'a, TargetPageData<'a>>> {
+    let page_dir = page.path.parent().unwrap_or_else(|| Path::new(""));
+    let heatmaps = match &config.heatmaps {
+        Some(config) => data
+            .get_available_packages()
+            .into_iter()
+            .filter(|package| data.get_availability_row(target, package, dates).is_some())
+            .map(|package| {
+                let path = heatmap::output_path(config, target, package, channel)?;
+                Ok((package, heatmap::relative_link(page_dir, &path)))
+            })
+            .collect::<anyhow::Result<_>>()?,
+        None => BTreeMap::new(),
+    };
+    let target_pages = target_pages(
+        &config.output_pattern,
+        additional.tiers.targets(),
+        channel,
+        page.path,
+    )?;
+    // Every channel's page has the same name in the channel's directory.
+    let file_name = page
+        .path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let channel_pages = additional
+        .channels
+        .iter()
+        .map(|link| (link.name, format!("{}{}", link.prefix, file_name)))
+        .collect();
+    Ok(Table::builder(data, target)
+        .dates(with_missing_manifests(data, dates))
+        .additional(TargetPageData {
+            common: additional,
+            heatmaps,
+            social_card: page.social_card.map(String::from),
+            target_pages,
+            channel_pages,
+            lang: page.lang,
+            explanations: page.explanations,
+        })
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::manifest;
+
+    #[test]
+    fn channel_links_follow_the_output_pattern() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let target = "x86_64-unknown-linux-gnu";
+        let mut data = AvailabilityData::default();
+        data.add_manifest(manifest(date, &[("cargo", target, true)]));
+        let targets = iter::once(target).collect();
+        let channels = vec!["nightly".to_string(), "beta".to_string()];
+        let config: opts::Html = serde_yaml::from_str(
+            "template_path: t.html\noutput_pattern: out/{{target}}/index.html",
+        )
+        .unwrap();
+        for (name, expected) in [
+            (
+                "nightly",
+                [("beta", "beta/index.html"), ("nightly", "index.html")],
+            ),
+            (
+                "beta",
+                [("beta", "index.html"), ("nightly", "../index.html")],
+            ),
+        ] {
+            let channel = ChannelInfo::new(name, &channels);
+            let additional =
+                AdditionalData::new(&HashMap::new(), &targets, &[], &config.theme, channel);
+            let output_path =
+                target_output_path(&Handlebars::new(), &config.output_pattern, target, channel)
+                    .unwrap();
+            let page = TargetPage {
+                path: &output_path,
+                lang: None,
+                social_card: None,
+                explanations: BTreeMap::new(),
+            };
+            let table =
+                target_table(&data, &[date], target, &config, &additional, channel, page).unwrap();
+            let links: Vec<_> = table
+                .additional
+                .channel_pages
+                .iter()
+                .map(|(&channel, link)| (channel, link.as_str()))
+                .collect();
+            assert_eq!(links, expected);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! The HTTP client of the requests other than the manifest downloads, e.g. the
+//! webhooks, the GitHub and the S3 APIs.
+
+use std::{fs, time::Duration};
+
+use anyhow::Context;
+use reqwest::blocking::{Client, ClientBuilder, RequestBuilder, Response};
+use rustup_available_packages::{retry::RetryPolicy, Error};
+
+use crate::opts::{Network, Retry};
+
+/// Starts building a client that honors the network settings.
+pub(crate) fn client_builder(network: &Network) -> anyhow::Result<ClientBuilder> {
+    let user_agent = match &network.user_agent {
+        Some(user_agent) => user_agent.clone(),
+        None => format!("rustup-available-packages/{}", env!("CARGO_PKG_VERSION")),
+    };
+    let mut builder = Client::builder()
+        .user_agent(user_agent)
+        .connect_timeout(Duration::from_secs(network.connect_timeout_secs))
+        .timeout(Duration::from_secs(network.timeout_secs))
+        .tls_built_in_root_certs(!network.only_custom_roots);
+    if let Some(proxy) = &network.proxy {
+        let proxy =
+            reqwest::Proxy::all(proxy).with_context(|| format!("Invalid proxy {}", proxy))?;
+        builder = builder.proxy(proxy);
+    }
+    for path in &network.root_certificates {
+        let pem = fs::read(path)
+            .with_context(|| format!("Can't read root certificate {}", path.display()))?;
+        let certificate = reqwest::Certificate::from_pem(&pem)
+            .with_context(|| format!("Invalid root certificate {}", path.display()))?;
+        builder = builder.add_root_certificate(certificate);
+    }
+    Ok(builder)
+}
+
+/// A client that honors the network settings and retries the failed requests
+/// the way the manifest downloads are retried.
+pub struct Http {
+    client: Client,
+    retry: RetryPolicy,
+}
+
+impl Http {
+    pub fn new(network: &Network, retry: &Retry) -> anyhow::Result<Self> {
+        Ok(Http {
+            client: client_builder(network)?
+                .build()
+                .context("Can't build an HTTP client")?,
+            retry: retry.into(),
+        })
+    }
+
+    /// Gives access to the client to build requests with.
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Sends a request, retrying network errors and `5xx` / `429` responses.
+    /// The other responses are returned as they are.
+    pub fn send(&self, request: RequestBuilder) -> anyhow::Result<Response> {
+        let request = request.build()?;
+        let url = request.url().to_string();
+        if request.try_clone().is_none() {
+            // Streamed bodies can only be sent once.
+            return Ok(self.client.execute(request)?);
+        }
+        let response = self.retry.run(|| {
+            let response = self
+                .client
+                .execute(request.try_clone().unwrap())
+                .map_err(|e| Error::Reqwest(e, url.clone()))?;
+            let status = response.status();
+            if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                Err(Error::BadResponse(status, url.clone()))
+            } else {
+                Ok(response)
+            }
+        })?;
+        Ok(response)
+    }
+}
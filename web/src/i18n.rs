@@ -0,0 +1,195 @@
+//! Localized labels of the pages, looked up in [Fluent](https://projectfluent.org) catalogs.
+
+use std::{collections::HashMap, fs};
+
+use anyhow::Context;
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource, FluentValue};
+use serde_json::Value;
+use unic_langid::LanguageIdentifier;
+
+use crate::opts;
+
+/// Messages of all the configured languages.
+pub struct Catalog {
+    /// The first configured language, which is used if a message isn't
+    /// translated into the requested one.
+    default: String,
+    bundles: HashMap<String, FluentBundle<FluentResource>>,
+}
+
+impl Catalog {
+    /// Reads a `{language}.ftl` file of every language from the catalog
+    /// directory.
+    pub fn load(config: &opts::I18n) -> anyhow::Result<Self> {
+        let default = config
+            .languages
+            .first()
+            .context("At least one language has to be configured")?
+            .clone();
+        let mut bundles = HashMap::new();
+        for language in &config.languages {
+            let id: LanguageIdentifier = language
+                .parse()
+                .with_context(|| format!("Invalid language {:?}", language))?;
+            let path = config.catalog_dir.join(format!("{}.ftl", language));
+            let source = fs::read_to_string(&path)
+                .with_context(|| format!("Can't read catalog {:?}", path))?;
+            let resource = FluentResource::try_new(source).map_err(|(_, errors)| {
+                anyhow::anyhow!("Invalid catalog {:?}: {:?}", path, errors)
+            })?;
+            let mut bundle = FluentBundle::new_concurrent(vec![id]);
+            // Unicode isolation marks only get in the way in HTML.
+            bundle.set_use_isolating(false);
+            bundle
+                .add_resource(resource)
+                .map_err(|errors| anyhow::anyhow!("Invalid catalog {:?}: {:?}", path, errors))?;
+            bundles.insert(language.clone(), bundle);
+        }
+        Ok(Catalog { default, bundles })
+    }
+
+    /// Formats a message in a `language`, or in the default one if the
+    /// message isn't translated. The `args` are passed to it as variables.
+    pub fn translate(
+        &self,
+        language: Option<&str>,
+        key: &str,
+        args: &HashMap<&str, &Value>,
+    ) -> Result<String, String> {
+        let language = language.unwrap_or(&self.default);
+        let bundle = self
+            .bundles
+            .get(language)
+            .ok_or_else(|| format!("Unknown language {:?}", language))?;
+        let bundles = [bundle, &self.bundles[&self.default]];
+        let (bundle, pattern) = bundles
+            .iter()
+            .copied()
+            .find_map(|bundle| {
+                let pattern = bundle.get_message(key)?.value()?;
+                Some((bundle, pattern))
+            })
+            .ok_or_else(|| format!("Unknown message {:?}", key))?;
+        let mut fluent_args = FluentArgs::new();
+        for (&name, &value) in args {
+            let value = match value {
+                Value::Number(number) => number.as_f64().map(FluentValue::from),
+                Value::String(s) => Some(FluentValue::from(s.as_str())),
+                Value::Null => None,
+                other => Some(FluentValue::from(other.to_string())),
+            };
+            fluent_args.set(name, value.unwrap_or(FluentValue::None));
+        }
+        let mut errors = Vec::new();
+        let formatted = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+        match errors.first() {
+            Some(e) => Err(format!("Can't format message {:?}: {}", key, e)),
+            None => Ok(formatted.into_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{opts::Engine, templates::Templates};
+
+    /// Writes an English and a German catalog into a new directory.
+    fn catalog() -> (Catalog, tempfile::TempDir) {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::write(
+            dir.join("en.ftl"),
+            "greeting = Hello, { $name }!\nmissing = { $count } missing\nonly-en = Only in English\n",
+        )
+        .unwrap();
+        fs::write(dir.join("de.ftl"), "greeting = Hallo, { $name }!\n").unwrap();
+        let catalog = Catalog::load(&opts::I18n {
+            catalog_dir: dir.into(),
+            languages: vec!["en".into(), "de".into()],
+            output_pattern: "{{target}}/{{lang}}.html".into(),
+        })
+        .unwrap();
+        (catalog, tmp)
+    }
+
+    #[test]
+    fn translate() {
+        let (catalog, _dir) = catalog();
+        let name = json!("Ferris");
+        let count = json!(3);
+        let args = [("name", &name), ("count", &count)]
+            .iter()
+            .copied()
+            .collect();
+
+        assert_eq!(
+            catalog.translate(Some("de"), "greeting", &args).unwrap(),
+            "Hallo, Ferris!"
+        );
+        assert_eq!(
+            catalog.translate(None, "greeting", &args).unwrap(),
+            "Hello, Ferris!"
+        );
+        assert_eq!(
+            catalog.translate(Some("de"), "missing", &args).unwrap(),
+            "3 missing"
+        );
+        assert_eq!(
+            catalog
+                .translate(Some("de"), "only-en", &HashMap::new())
+                .unwrap(),
+            "Only in English"
+        );
+        assert!(catalog
+            .translate(Some("de"), "unknown", &args)
+            .unwrap_err()
+            .contains("Unknown message"));
+        assert!(catalog
+            .translate(Some("fr"), "greeting", &args)
+            .unwrap_err()
+            .contains("Unknown language"));
+    }
+
+    #[test]
+    fn t_helper() {
+        let (catalog, dir) = catalog();
+        let dir = dir.path();
+        let catalog = Arc::new(catalog);
+        let data = json!({ "additional": { "lang": "de" } });
+        let cases = [
+            (
+                Engine::Handlebars,
+                "page.hbs",
+                r#"{{t "greeting" name="Ferris"}} {{t "greeting" name="Ferris" lang="en"}}"#,
+            ),
+            (
+                Engine::Tera,
+                "page.tera",
+                r#"{{ t(key="greeting", lang=additional.lang, name="Ferris") }} {{ t(key="greeting", lang="en", name="Ferris") }}"#,
+            ),
+        ];
+        for &(engine, file, template) in &cases {
+            let path = dir.join(file);
+            fs::write(&path, template).unwrap();
+            let mut templates = Templates::new(engine);
+            templates.register_catalog(Arc::clone(&catalog));
+            templates.register_file("page", &path).unwrap();
+            assert_eq!(
+                templates.render("page", &data).unwrap(),
+                "Hallo, Ferris! Hello, Ferris!"
+            );
+        }
+
+        let path = dir.join("unknown.hbs");
+        fs::write(&path, r#"{{t "unknown"}}"#).unwrap();
+        let mut templates = Templates::new(Engine::Handlebars);
+        templates.register_catalog(catalog);
+        templates.register_file("unknown", &path).unwrap();
+        assert!(templates.render("unknown", &data).is_err());
+    }
+}
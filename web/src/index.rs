@@ -0,0 +1,144 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use chrono::NaiveDate;
+use handlebars::Handlebars;
+use rustup_available_packages::{availability::TargetLifecycle, AvailabilityData};
+use serde::Serialize;
+
+use crate::{
+    generators::GeneratorInput,
+    html::Pages,
+    opts::Tier,
+    output::{Output, Purpose},
+    target_pages,
+    watch_sets::WatchSetSummary,
+    AdditionalData, ChannelInfo, PathRenderData,
+};
+
+pub const TEMPLATE_NAME: &str = "index";
+
+/// A target as shown on the landing page.
+#[derive(Serialize)]
+struct TargetSummary<'a> {
+    name: &'a str,
+    /// How many packages that have been available within the rendered dates
+    /// are missing in the latest manifest.
+    missing: usize,
+    /// Whether the target has been added or removed within the rendered dates.
+    lifecycle: TargetLifecycle,
+}
+
+#[derive(Serialize)]
+struct TierSummary<'a> {
+    tier: Tier,
+    targets: Vec<TargetSummary<'a>>,
+    /// The total number of missing packages on the tier's targets.
+    missing: usize,
+}
+
+#[derive(Serialize)]
+struct IndexData<'a> {
+    /// The date of the latest manifest.
+    date: Option<NaiveDate>,
+    tiers: Vec<TierSummary<'a>>,
+    watch_sets: Vec<WatchSetSummary<'a>>,
+    /// Links to the pages of the targets relative to the page, keyed by target.
+    target_pages: BTreeMap<&'a str, String>,
+    additional: &'a AdditionalData<'a>,
+}
+
+pub fn count_missing(data: &AvailabilityData, target: &str, dates: &[NaiveDate]) -> usize {
+    // Nothing is missing on a target that isn't listed anymore.
+    if !dates
+        .first()
+        .is_some_and(|&date| data.is_target_listed(target, date))
+    {
+        return 0;
+    }
+    data.get_available_packages()
+        .into_iter()
+        .filter_map(|package| data.get_availability_row(target, package, dates))
+        .filter(|row| row.availability_list.first() == Some(&false))
+        .count()
+}
+
+/// Returns the path of the landing page, which is put next to the targets'
+/// pages.
+pub fn output_path(output_pattern: &str, channel: ChannelInfo) -> anyhow::Result<PathBuf> {
+    let target_page = Handlebars::new()
+        .render_template(output_pattern, &PathRenderData::target("index"))
+        .with_context(|| format!("Invalid output pattern: {}", output_pattern))?;
+    Ok(channel.relocate(&Path::new(&target_page).with_file_name("index.html")))
+}
+
+/// Renders a landing page listing all the available targets grouped by tier
+/// and summarizing the watch sets.
+///
+/// The page template is expected to be registered under the [`TEMPLATE_NAME`].
+pub fn generate_index(pages: &Pages, out: &mut dyn Output) -> anyhow::Result<()> {
+    let GeneratorInput {
+        data,
+        dates,
+        channel,
+        ..
+    } = *pages.input;
+    let config = pages.config;
+    let output_path = &output_path(&config.output_pattern, channel)?;
+    let tier_of: HashMap<&str, Tier> = config
+        .tiers
+        .iter()
+        .flat_map(|(&tier, targets)| targets.iter().map(move |target| (target.as_str(), tier)))
+        .collect();
+    let mut grouped: BTreeMap<Tier, Vec<TargetSummary>> = BTreeMap::new();
+    for target in data.get_available_targets() {
+        grouped
+            .entry(tier_of.get(target).copied().unwrap_or(Tier::UnknownTier))
+            .or_default()
+            .push(TargetSummary {
+                name: target,
+                missing: count_missing(data, target, dates),
+                lifecycle: data.target_lifecycle(target, dates),
+            });
+    }
+    let tiers = grouped
+        .into_iter()
+        .map(|(tier, mut targets)| {
+            targets.sort_unstable_by_key(|target| target.name);
+            TierSummary {
+                tier,
+                missing: targets.iter().map(|target| target.missing).sum(),
+                targets,
+            }
+        })
+        .collect();
+    let targets = data.get_available_targets().into_iter().chain(
+        config
+            .watch_sets
+            .iter()
+            .flat_map(|set| set.targets.iter().map(String::as_str)),
+    );
+    let target_pages = target_pages(&config.output_pattern, targets, channel, output_path)?;
+
+    let rendered = pages.templates.render_page(
+        TEMPLATE_NAME,
+        &IndexData {
+            date: dates.first().copied(),
+            tiers,
+            watch_sets: config
+                .watch_sets
+                .iter()
+                .map(|set| WatchSetSummary::new(data, dates, set))
+                .collect(),
+            target_pages,
+            additional: pages.additional,
+        },
+        output_path,
+    )?;
+    log::info!("Writing index page to {:?}", output_path);
+    out.set_purpose(Purpose::IndexPage);
+    out.write(output_path, rendered.as_bytes())
+}
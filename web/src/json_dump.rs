@@ -0,0 +1,51 @@
+use std::{collections::BTreeMap, io::Write, path::Path};
+
+use flate2::{write::GzEncoder, Compression};
+
+use crate::{
+    fs_tree::TargetPkg,
+    generators::GeneratorInput,
+    opts,
+    output::{Output, Purpose},
+};
+
+/// Writes all the availability data into a single JSON file, optionally
+/// gzip-compressed.
+pub fn generate_json_dump(
+    input: &GeneratorInput,
+    config: &opts::JsonDump,
+    out: &mut dyn Output,
+) -> anyhow::Result<()> {
+    let GeneratorInput {
+        data,
+        dates,
+        channel,
+        ..
+    } = *input;
+    let pkgs = data.get_available_packages();
+    let dump: BTreeMap<_, BTreeMap<_, _>> = data
+        .get_available_targets()
+        .into_iter()
+        .map(|target| {
+            let packages = pkgs
+                .iter()
+                .filter_map(|pkg| data.get_availability_row(target, pkg, dates))
+                .map(|row| (row.package_name, TargetPkg::new(data, target, dates, &row)))
+                .collect();
+            (target, packages)
+        })
+        .collect();
+    let contents = serde_json::to_vec(&dump)?;
+
+    let path = channel.relocate(&config.path);
+    out.set_purpose(Purpose::JsonDump);
+    if config.gzip {
+        let mut path = path.into_os_string();
+        path.push(".gz");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&contents)?;
+        out.write(Path::new(&path), &encoder.finish()?)
+    } else {
+        out.write(&path, &contents)
+    }
+}
@@ -0,0 +1,58 @@
+use std::path::Path;
+
+use anyhow::Context;
+use chrono::NaiveDate;
+use handlebars::Handlebars;
+use rustup_available_packages::AvailabilityData;
+use serde::Serialize;
+
+use crate::{
+    opts,
+    output::{Output, Purpose},
+    ChannelInfo, PathRenderData,
+};
+
+/// The most recent toolchain that has all the required packages on a target.
+#[derive(Serialize)]
+struct LastGood<'a> {
+    channel: &'a str,
+    target: &'a str,
+    packages: &'a [String],
+    date: Option<NaiveDate>,
+    /// A toolchain name that rustup understands, e.g. `nightly-2024-06-01`.
+    toolchain: Option<String>,
+    /// The commit `rustc` of the toolchain was built from.
+    rustc_commit: Option<&'a str>,
+}
+
+/// Generates a JSON file per target with the latest date on which all the
+/// configured packages were available at the same time.
+pub fn generate_last_good(
+    data: &AvailabilityData,
+    config: &opts::LastGood,
+    channel: ChannelInfo,
+    out: &mut dyn Output,
+) -> anyhow::Result<()> {
+    let handlebars = Handlebars::new();
+    let packages: Vec<_> = config.packages.iter().map(String::as_str).collect();
+    for target in data.get_available_targets() {
+        let date = data.last_available_together(target, &packages);
+        let last_good = LastGood {
+            channel: channel.name,
+            target,
+            packages: &config.packages,
+            date,
+            toolchain: date.map(|date| format!("{}-{}", channel.name, date.format("%Y-%m-%d"))),
+            rustc_commit: date.and_then(|date| data.rustc_commit(date)),
+        };
+        let output_path = handlebars
+            .render_template(&config.output_pattern, &PathRenderData::target(target))
+            .with_context(|| format!("Invalid output pattern: {}", config.output_pattern))?;
+        out.set_purpose(Purpose::LastGood);
+        out.write(
+            &channel.relocate(Path::new(&output_path)),
+            &serde_json::to_vec(&last_good)?,
+        )?;
+    }
+    Ok(())
+}
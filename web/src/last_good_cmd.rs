@@ -0,0 +1,63 @@
+use anyhow::Context;
+use rustup_available_packages::AvailabilityData;
+use rustup_status::{channel_cache, downloader};
+use structopt::StructOpt;
+
+use crate::ConfigOpt;
+
+#[derive(StructOpt)]
+pub struct LastGoodOpt {
+    #[structopt(flatten)]
+    config: ConfigOpt,
+    #[structopt(short = "t", long = "target", help = "A target to look into")]
+    target: String,
+    #[structopt(
+        short = "p",
+        long = "packages",
+        help = "Comma-separated packages that have to be available",
+        required = true,
+        use_delimiter = true
+    )]
+    packages: Vec<String>,
+    #[structopt(
+        long = "days",
+        help = "How many days to look back",
+        default_value = "30"
+    )]
+    days: usize,
+    #[structopt(
+        long = "toolchain",
+        help = "Print a toolchain name, like nightly-2024-06-01, instead of a bare date"
+    )]
+    toolchain: bool,
+}
+
+/// Prints the latest date on which all the requested packages were available
+/// on a target.
+pub fn run(opts: &LastGoodOpt) -> anyhow::Result<()> {
+    let (config, channel) = opts.config.load_with_channel()?;
+    let manifests = downloader(&config, &channel, opts.days)?
+        .set_cache(channel_cache(&config, &channel)?)
+        .get_last_manifests(opts.days)
+        .with_context(|| format!("Can't fetch the {} manifests", channel))?;
+    let mut data = AvailabilityData::default();
+    data.add_manifests(manifests);
+
+    let packages: Vec<_> = opts.packages.iter().map(String::as_str).collect();
+    let date = data
+        .last_available_together(&opts.target, &packages)
+        .with_context(|| {
+            format!(
+                "{} haven't been available on {} at the same time in the last {} days",
+                opts.packages.join(", "),
+                opts.target,
+                opts.days
+            )
+        })?;
+    if opts.toolchain {
+        println!("{}-{}", channel, date.format("%Y-%m-%d"));
+    } else {
+        println!("{}", date.format("%Y-%m-%d"));
+    }
+    Ok(())
+}
@@ -0,0 +1,1283 @@
+//! The rendering pipeline of the rustup components availability pages.
+//!
+//! [`render_site`] does everything the `render` command of the binary does, while the generators
+//! can be used separately to embed only some parts of the pipeline.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    io::{self, Write},
+    iter, mem,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use assets::Assets;
+use chrono::{DateTime, NaiveDate, Utc};
+use diagnostics::{Locate, Location};
+use filters::Filters;
+use generators::GeneratorInput;
+use handlebars::Handlebars;
+use http::Http;
+use i18n::Catalog;
+use lock::RunLock;
+use metrics::{CountingCache, Download, Metrics};
+use opts::{Config, OnMissingDays, Source, Theme, Tier};
+use output::{ArchivingOutput, CompressingOutput, FsOutput, MinifyingOutput, Output, StagedOutput};
+use progress::ProgressCache;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use rustup_available_packages::{
+    cache::{FsCache, MemoryCache},
+    manifest::Manifest,
+    AvailabilityData, DefaultSource, Downloader, LocalSource, SourceInfo,
+};
+use serde::Serialize;
+use site_manifest::SiteManifestOutput;
+use state::RenderState;
+use storage::Storage;
+use templates::Templates;
+use theme::ThemeData;
+use tiers_table::{TierChange, TiersTable};
+use toolstate::Explanation;
+
+mod aliases;
+mod archive;
+mod assets;
+mod badges;
+mod changes;
+mod csv;
+pub mod diagnostics;
+pub mod dry_run;
+pub mod fake_data;
+mod feed;
+mod filters;
+mod fs_tree;
+pub mod generators;
+mod github_issues;
+mod heatmap;
+mod html;
+pub mod http;
+mod i18n;
+mod index;
+mod json_dump;
+mod last_good;
+pub mod lock;
+mod metrics;
+mod notify;
+pub mod opts;
+pub mod output;
+mod output_pattern;
+mod package_pages;
+mod plain_text;
+pub mod platform_support;
+pub mod progress;
+mod search;
+mod site_manifest;
+mod sitemap;
+mod social_cards;
+mod state;
+pub mod storage;
+mod strictness;
+mod templates;
+#[cfg(test)]
+mod test_utils;
+mod theme;
+pub mod tiers_table;
+pub mod toolstate;
+mod trends;
+pub mod warnings;
+mod watch_sets;
+
+#[derive(Serialize)]
+pub struct PathRenderData<'a> {
+    target: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    package: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lang: Option<&'a str>,
+}
+
+impl<'a> PathRenderData<'a> {
+    pub fn target(target: &'a str) -> Self {
+        PathRenderData {
+            target,
+            package: None,
+            lang: None,
+        }
+    }
+
+    /// A target's page in a given language.
+    pub fn localized(target: &'a str, lang: &'a str) -> Self {
+        PathRenderData {
+            lang: Some(lang),
+            ..PathRenderData::target(target)
+        }
+    }
+}
+
+/// Escapes a string so it can be put into an XML text node or an attribute.
+pub fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Version of the data the templates get, see `TEMPLATES.md`. It is bumped
+/// whenever a field is removed or changes its meaning.
+pub const CONTEXT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+pub struct AdditionalData<'a> {
+    /// The [`CONTEXT_VERSION`].
+    context_version: u32,
+    tiers: TiersTable<'a>,
+    datetime: String,
+    /// When the page is rendered, e.g. for `{{iso8601 additional.generated_at}}`.
+    generated_at: DateTime<Utc>,
+    channel: &'a str,
+    channels: Vec<ChannelLink<'a>>,
+    theme: ThemeData<'a>,
+}
+
+impl<'a> AdditionalData<'a> {
+    fn new(
+        tiers: &HashMap<Tier, Vec<String>>,
+        targets: &HashSet<&'a str>,
+        tier_changes: &[TierChange],
+        theme: &'a Theme,
+        channel: ChannelInfo<'a>,
+    ) -> Self {
+        let generated_at = Utc::now();
+        AdditionalData {
+            context_version: CONTEXT_VERSION,
+            tiers: TiersTable::new(tiers.clone(), targets, tier_changes),
+            datetime: generated_at.format("%d %b %Y, %H:%M:%S UTC").to_string(),
+            generated_at,
+            channel: channel.name,
+            channels: channel.links(),
+            theme: ThemeData::new(theme),
+        }
+    }
+}
+
+/// What's loaded once per run and shared by all the channels.
+pub struct Shared<'c> {
+    assets: Option<Assets<'c>>,
+    catalog: Option<Arc<Catalog>>,
+    /// The fonts of the social cards, if they are rendered.
+    card_fonts: Arc<resvg::usvg::fontdb::Database>,
+    /// Threads the pages of the targets are rendered by.
+    render_pool: rayon::ThreadPool,
+}
+
+impl<'c> Shared<'c> {
+    pub fn load(config: &'c Config) -> anyhow::Result<Self> {
+        Ok(Shared {
+            assets: config
+                .html
+                .assets
+                .as_ref()
+                .map(Assets::load)
+                .transpose()
+                .context("Invalid assets")?,
+            catalog: config
+                .html
+                .i18n
+                .as_ref()
+                .map(|i18n| Catalog::load(i18n).map(Arc::new))
+                .transpose()
+                .context("Invalid localization")?,
+            card_fonts: match config.html.social_card_template {
+                Some(_) => social_cards::load_fonts(),
+                None => Default::default(),
+            },
+            render_pool: rayon::ThreadPoolBuilder::new()
+                .num_threads(config.html.render_threads.unwrap_or(0))
+                .build()
+                .context("Can't start the rendering threads")?,
+        })
+    }
+
+    /// Nothing loaded, for the tests.
+    #[cfg(test)]
+    pub(crate) fn empty() -> Self {
+        Shared {
+            assets: None,
+            catalog: None,
+            card_fonts: Default::default(),
+            render_pool: rayon::ThreadPoolBuilder::new()
+                .num_threads(1)
+                .build()
+                .unwrap(),
+        }
+    }
+
+    /// Registers the helpers that depend on the loaded data.
+    fn register(&self, templates: &mut Templates) {
+        if let Some(assets) = &self.assets {
+            templates.register_assets(assets);
+        }
+        if let Some(catalog) = &self.catalog {
+            templates.register_catalog(Arc::clone(catalog));
+        }
+    }
+
+    /// Copies the assets into the output, if there are any.
+    fn write_assets(&self, out: &mut dyn Output) -> anyhow::Result<()> {
+        match &self.assets {
+            Some(assets) => assets.write(out),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A link to the same page on another channel.
+#[derive(Serialize)]
+struct ChannelLink<'a> {
+    name: &'a str,
+    /// A relative path from the current page's directory to the channel's one.
+    prefix: String,
+    current: bool,
+}
+
+/// Describes which channel is being rendered among all the configured ones.
+#[derive(Clone, Copy)]
+pub struct ChannelInfo<'a> {
+    pub name: &'a str,
+    all: &'a [String],
+}
+
+impl<'a> ChannelInfo<'a> {
+    /// Describes a channel `name` among `all` the configured channels.
+    pub fn new(name: &'a str, all: &'a [String]) -> Self {
+        ChannelInfo { name, all }
+    }
+
+    /// The first configured channel is rendered straight into the configured
+    /// locations, every other one gets its own subdirectory.
+    fn is_primary(&self) -> bool {
+        self.all.first().map(String::as_str) == Some(self.name)
+    }
+
+    /// Returns the channel's output directory under a given base directory.
+    fn dir(&self, base: &Path) -> PathBuf {
+        if self.is_primary() {
+            base.into()
+        } else {
+            base.join(self.name)
+        }
+    }
+
+    /// Moves a file path into the channel's output directory.
+    pub fn relocate(&self, file: &Path) -> PathBuf {
+        match (file.parent(), file.file_name()) {
+            (Some(parent), Some(file_name)) => self.dir(parent).join(file_name),
+            _ => self.dir(Path::new("")).join(file),
+        }
+    }
+
+    fn links(&self) -> Vec<ChannelLink<'a>> {
+        let up = if self.is_primary() { "" } else { "../" };
+        self.all
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| ChannelLink {
+                name,
+                prefix: if name == self.name {
+                    String::new()
+                } else if idx == 0 {
+                    up.to_string()
+                } else {
+                    format!("{}{}/", up, name)
+                },
+                current: name == self.name,
+            })
+            .collect()
+    }
+}
+
+/// Keeps the tier changes that happened on the given dates.
+fn recent_tier_changes(tier_changes: &[TierChange], dates: &[NaiveDate]) -> Vec<TierChange> {
+    tier_changes
+        .iter()
+        .filter(|change| dates.last().is_some_and(|&oldest| change.date >= oldest))
+        .cloned()
+        .collect()
+}
+
+/// Makes the path of a target's page.
+fn target_output_path(
+    handlebars: &Handlebars,
+    output_pattern: &str,
+    target: &str,
+    channel: ChannelInfo,
+) -> anyhow::Result<PathBuf> {
+    let output_path = handlebars
+        .render_template(output_pattern, &PathRenderData::target(target))
+        .with_context(|| format!("Invalid output pattern: {}", output_pattern))?;
+    Ok(channel.relocate(Path::new(&output_path)))
+}
+
+/// Links to the pages of the `targets` relative to another page of the channel
+/// written to `page`, keyed by target.
+fn target_pages<'t>(
+    output_pattern: &str,
+    targets: impl IntoIterator<Item = &'t str>,
+    channel: ChannelInfo,
+    page: &Path,
+) -> anyhow::Result<BTreeMap<&'t str, String>> {
+    let handlebars = Handlebars::new();
+    let dir = page.parent().unwrap_or_else(|| Path::new(""));
+    targets
+        .into_iter()
+        .map(|target| {
+            let path = target_output_path(&handlebars, output_pattern, target, channel)?;
+            Ok((target, heatmap::relative_link(dir, &path)))
+        })
+        .collect()
+}
+
+/// Puts the days without a manifest back between the `dates`, which are
+/// sorted in descending order, so a table shows them instead of skipping them.
+fn with_missing_manifests(data: &AvailabilityData, dates: &[NaiveDate]) -> Vec<NaiveDate> {
+    let (latest, oldest) = match (dates.first(), dates.last()) {
+        (Some(&latest), Some(&oldest)) => (latest, oldest),
+        _ => return Vec::new(),
+    };
+    (0..=(latest - oldest).num_days())
+        .map(|days| latest - chrono::Duration::days(days))
+        .filter(|date| dates.contains(date) || data.is_manifest_missing(*date))
+        .collect()
+}
+
+/// Makes the exact data the template of a target's page gets in a loaded
+/// channel, as pretty-printed JSON.
+pub fn dump_context(
+    config: &Config,
+    loaded: &LoadedChannel,
+    target: &str,
+) -> anyhow::Result<String> {
+    let channel = ChannelInfo::new(&loaded.name, &config.channels);
+    let all_targets = loaded.data.get_available_targets();
+    let target = all_targets
+        .get(target)
+        .copied()
+        .with_context(|| format!("{} isn't in the {} manifests", target, channel.name))?;
+    let dates = &loaded.all_dates[..loaded.rendered];
+    // Only the changes noticed by the previous runs are known.
+    let state = RenderState::load(config.state_path.as_ref().map(|p| channel.relocate(p)))?;
+    let tier_changes = recent_tier_changes(state.tier_changes(), dates);
+    let additional = AdditionalData::new(
+        &config.html.tiers,
+        &all_targets,
+        &tier_changes,
+        &config.html.theme,
+        channel,
+    );
+    let output_path = target_output_path(
+        &Handlebars::new(),
+        &config.html.output_pattern,
+        target,
+        channel,
+    )?;
+    let card_url = match (
+        &config.html.social_card_template,
+        &config.html.social_card_url,
+    ) {
+        (Some(_), Some(base_url)) => Some(social_cards::url(
+            base_url,
+            config.site_root.as_deref(),
+            &social_cards::output_path(&output_path),
+        )?),
+        _ => None,
+    };
+    let lang = config
+        .html
+        .i18n
+        .as_ref()
+        .and_then(|i18n| i18n.languages.first());
+    let explanations = explain_missing(config, &loaded.data, dates, &iter::once(target).collect())?
+        .remove(target)
+        .unwrap_or_default();
+    let page = html::TargetPage {
+        path: &output_path,
+        lang: lang.map(String::as_str),
+        social_card: card_url.as_deref(),
+        explanations,
+    };
+    let table = html::target_table(
+        &loaded.data,
+        dates,
+        target,
+        &config.html,
+        &additional,
+        channel,
+        page,
+    )?;
+    Ok(serde_json::to_string_pretty(&table)?)
+}
+
+/// Quotes a CSV field if needed.
+pub fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// What has been rendered.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub started_at: DateTime<Utc>,
+    /// Wall-clock time of the whole run.
+    pub duration_secs: f64,
+    pub channels: Vec<ChannelReport>,
+    /// Channels that have failed to render with `keep_going`.
+    pub failed_channels: Vec<String>,
+    /// Warnings and errors logged during the run.
+    pub warnings: Vec<String>,
+    /// Metrics of the run in the Prometheus text format.
+    #[serde(skip)]
+    pub metrics: String,
+}
+
+/// How a channel's manifests have been obtained.
+#[derive(Debug, Default, Serialize)]
+pub struct ManifestsReport {
+    /// Manifests that have been downloaded, including the latest one.
+    pub fetched: usize,
+    /// Manifests that have been found in the cache.
+    pub cached: usize,
+    /// Days whose manifests are missing or have failed to download. Only the
+    /// failed ones count for the channels that aren't published every day.
+    pub skipped_days: usize,
+    /// Days whose manifests have been re-published since they were cached.
+    pub republished: Vec<NaiveDate>,
+}
+
+impl ManifestsReport {
+    fn new(channel: &str, days: usize, downloaded: usize, download: &Download) -> Self {
+        ManifestsReport {
+            fetched: downloaded.saturating_sub(download.cache_hits),
+            cached: download.cache_hits,
+            skipped_days: if publishes_daily(channel) {
+                days.saturating_sub(downloaded)
+            } else {
+                download.failed.len()
+            },
+            republished: download.republished.clone(),
+        }
+    }
+}
+
+/// Wall-clock time of every phase of a channel's rendering.
+#[derive(Debug, Default, Serialize)]
+pub struct PhasesReport {
+    pub download_secs: f64,
+    pub parse_secs: f64,
+    pub render_secs: f64,
+}
+
+/// A package that has gone missing on a target in the latest manifest.
+#[derive(Debug, Serialize)]
+pub struct Regression {
+    pub target: String,
+    pub package: String,
+}
+
+/// What has been rendered for a single channel.
+#[derive(Debug, Serialize)]
+pub struct ChannelReport {
+    pub channel: String,
+    /// The rendered dates, in descending order.
+    pub dates: Vec<NaiveDate>,
+    /// All the available targets, sorted by name.
+    pub targets: Vec<String>,
+    /// Targets whose pages have been re-rendered, sorted by name.
+    pub rendered_targets: Vec<String>,
+    /// Targets whose pages have failed to render, sorted by name.
+    pub failed_targets: Vec<String>,
+    /// Outputs that have failed to render with `keep_going`, e.g. the
+    /// `file tree`.
+    pub failed_outputs: Vec<String>,
+    /// All the HTML pages of the channel, including the ones that haven't
+    /// changed.
+    pub pages: Vec<PathBuf>,
+    /// All the discovered packages, sorted by name.
+    pub packages: Vec<String>,
+    /// Packages that have gone missing in the latest manifest.
+    pub regressions: Vec<Regression>,
+    pub manifests: ManifestsReport,
+    pub phases: PhasesReport,
+}
+
+/// Takes the configured run lock, if any, which is held until the returned
+/// value is dropped.
+pub fn acquire_lock(config: &Config) -> anyhow::Result<Option<RunLock>> {
+    config.lock.as_ref().map(RunLock::acquire).transpose()
+}
+
+/// Renders the site to the file system according to a given configuration.
+///
+/// If a lock is configured, it is held for the whole run. If the tiers are
+/// taken from the platform support page, they are loaded into the `config`.
+pub fn render_site(config: &mut Config) -> anyhow::Result<Report> {
+    let _lock = acquire_lock(config)?;
+    render_site_locked(config)
+}
+
+/// Same as [`render_site`], but leaves the locking to the caller, e.g. to
+/// keep the lock until the site is deployed.
+pub fn render_site_locked(config: &mut Config) -> anyhow::Result<Report> {
+    platform_support::apply(config)?;
+    let caches = Caches::new(config)?;
+    let report = match &config.site_root {
+        Some(root) => render(config, &caches, &mut StagedOutput::new(root)?)?,
+        None => render(config, &caches, &mut FsOutput::default())?,
+    };
+    strictness::check(&config.strictness, &report)?;
+    Ok(report)
+}
+
+/// Renders all the configured channels into a given output.
+///
+/// The run lock isn't taken, see [`acquire_lock`].
+pub fn render(config: &Config, caches: &Caches, out: &mut dyn Output) -> anyhow::Result<Report> {
+    render_keeping_data(config, caches, out).map(|(report, _)| report)
+}
+
+/// Same as [`render`], but also returns the loaded channels, e.g. to answer
+/// queries about them.
+pub fn render_keeping_data(
+    config: &Config,
+    caches: &Caches,
+    out: &mut dyn Output,
+) -> anyhow::Result<(Report, Vec<LoadedChannel>)> {
+    config.output.check()?;
+    let started_at = Utc::now();
+    let started = Instant::now();
+    // Left over from a previous rendering in the serve mode.
+    warnings::take();
+    // Archives the files the way they end up on the disk.
+    let mut archiving;
+    let out: &mut dyn Output = match &config.output.archive {
+        Some(path) => {
+            archiving = ArchivingOutput::new(out, path);
+            &mut archiving
+        }
+        None => out,
+    };
+    // Lists the compressed copies as well, but not the archive itself.
+    let mut site_manifest;
+    let out: &mut dyn Output = match &config.output.site_manifest {
+        Some(path) => {
+            let incremental = config.state_path.is_some();
+            site_manifest = SiteManifestOutput::new(out, path, incremental, started_at);
+            &mut site_manifest
+        }
+        None => out,
+    };
+    let mut compressing;
+    let out: &mut dyn Output = if config.output.precompress {
+        compressing = CompressingOutput::new(out);
+        &mut compressing
+    } else {
+        out
+    };
+    // Minified before being compressed.
+    let mut minifying;
+    let out: &mut dyn Output = if config.output.minify {
+        minifying = MinifyingOutput::new(out);
+        &mut minifying
+    } else {
+        out
+    };
+    let shared = Shared::load(config)?;
+    shared.write_assets(out)?;
+    let mut channels = Vec::new();
+    let mut failed_channels = Vec::new();
+    let mut states = Vec::new();
+    let mut loaded_channels = Vec::new();
+    let mut metrics = Metrics::default();
+    for channel in &config.channels {
+        let channel = ChannelInfo::new(channel, &config.channels);
+        let cache = caches.get(channel.name);
+        let rendered =
+            render_channel(config, cache, channel, &shared, &mut metrics, out).locate(|| {
+                Location::new(format!("Can't render channel {}", channel.name))
+                    .channel(channel.name)
+            });
+        let (report, state, loaded) = match rendered {
+            Err(e) if config.keep_going => {
+                log::error!(channel = channel.name; "Skipping channel {}: {:#}", channel.name, e);
+                failed_channels.push(channel.name.to_string());
+                continue;
+            }
+            rendered => rendered?,
+        };
+        channels.push(report);
+        states.push(state);
+        loaded_channels.push(loaded);
+    }
+    if let Some(sitemap) = &config.sitemap {
+        let pages: Vec<_> = channels
+            .iter()
+            .flat_map(|channel| channel.pages.iter().cloned())
+            .collect();
+        sitemap::generate_sitemap(&pages, sitemap, out)?;
+    }
+    out.finish()?;
+    // Only now the rendered targets are guaranteed to be in place.
+    for state in states {
+        state.save()?;
+    }
+    let failed: Vec<_> = channels
+        .iter()
+        .flat_map(|report| {
+            let channel = &report.channel;
+            let targets = report.failed_targets.iter();
+            let outputs = report.failed_outputs.iter();
+            targets
+                .chain(outputs)
+                .map(move |failed| format!("{}/{}", channel, failed))
+        })
+        .chain(failed_channels.iter().cloned())
+        .collect();
+    metrics.add_failures(failed.len());
+    let report = Report {
+        started_at,
+        duration_secs: started.elapsed().as_secs_f64(),
+        channels,
+        failed_channels,
+        warnings: warnings::take(),
+        metrics: metrics.render(),
+    };
+    // Both are written even if some targets have failed to render.
+    if let Some(path) = &config.report_file {
+        write_atomically(path, &serde_json::to_vec_pretty(&report)?)
+            .with_context(|| format!("Can't write the report to {}", path.display()))?;
+    }
+    if let Some(path) = &config.metrics_file {
+        // Scrapers must never see a half-written file.
+        write_atomically(path, report.metrics.as_bytes())
+            .with_context(|| format!("Can't write metrics to {}", path.display()))?;
+    }
+    if !failed.is_empty() {
+        anyhow::bail!(
+            "Failed to render {} targets, outputs or channels: {}",
+            failed.len(),
+            failed.join(", ")
+        );
+    }
+    Ok((report, loaded_channels))
+}
+
+/// Writes a file so its readers never see it half-written.
+fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = output::temp_file_for(path)?;
+    file.write_all(contents)?;
+    file.persist(path).map(drop).map_err(|e| e.error)
+}
+
+/// Explains the packages missing on the latest date on the given targets, if
+/// the toolstate history is configured. The history is fetched once rather
+/// than by every rendering thread.
+pub fn explain_missing<'a>(
+    config: &Config,
+    data: &'a AvailabilityData,
+    dates: &[NaiveDate],
+    targets: &HashSet<&'a str>,
+) -> anyhow::Result<HashMap<&'a str, BTreeMap<&'a str, Explanation>>> {
+    Ok(match &config.html.toolstate {
+        Some(toolstate) => {
+            let http = Http::new(&config.network, &config.retry)?;
+            toolstate::explain_missing(data, dates, targets, toolstate, &http)
+        }
+        None => HashMap::new(),
+    })
+}
+
+/// Builds an HTTP client to download manifests with.
+fn http_client(config: &Config) -> anyhow::Result<reqwest::blocking::Client> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &config.manifest_headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .with_context(|| format!("Invalid header name {}", name))?;
+        let mut value = HeaderValue::from_str(value)
+            .with_context(|| format!("Invalid value of the {} header", name))?;
+        value.set_sensitive(true);
+        headers.insert(name, value);
+    }
+    http::client_builder(&config.network)?
+        .default_headers(headers)
+        .build()
+        .context("Can't build an HTTP client")
+}
+
+/// Initializes an on-disk cache of a channel's manifests.
+pub fn channel_cache(config: &Config, channel: &str) -> anyhow::Result<FsCache> {
+    if let Some(cache_path) = config.cache_path.as_ref() {
+        // Manifests are cached by date, so different channels must not share a directory.
+        let path = cache_path.join(channel);
+        if config.channels.first().map(String::as_str) == Some(channel) && !path.exists() {
+            migrate_cache(cache_path, &path)
+                .with_context(|| format!("Can't move the cached manifests to {:?}", path))?;
+        }
+        let cache = FsCache::new(path).with_context(|| "Can't initialize cache")?;
+        Ok(if config.compress_cache {
+            cache.compressed()
+        } else {
+            cache
+        })
+    } else {
+        Ok(FsCache::noop())
+    }
+}
+
+/// Moves the manifests cached by the versions of the tool that supported a single channel, right
+/// in the `cache_path`, to the directory of the channel.
+fn migrate_cache(cache_path: &Path, channel_path: &Path) -> std::io::Result<()> {
+    if !cache_path.is_dir() {
+        return Ok(());
+    }
+    let mut created = false;
+    for entry in fs::read_dir(cache_path)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let is_cached = name
+            .to_str()
+            .is_some_and(|name| name.ends_with(".toml") || name.ends_with(".toml.zst"));
+        if !is_cached || !entry.file_type()?.is_file() {
+            continue;
+        }
+        if !created {
+            log::info!("Moving the cached manifests to {:?}", channel_path);
+            fs::create_dir_all(channel_path)?;
+            created = true;
+        }
+        fs::rename(entry.path(), channel_path.join(name))?;
+    }
+    Ok(())
+}
+
+type ChannelCache = Arc<MemoryCache<FsCache>>;
+
+/// Manifests caches of all the channels, which might outlive a single rendering.
+pub struct Caches(HashMap<String, ChannelCache>);
+
+impl Caches {
+    pub fn new(config: &Config) -> anyhow::Result<Self> {
+        config
+            .channels
+            .iter()
+            .map(|channel| {
+                let cache = channel_cache(config, channel)?;
+                let cache = MemoryCache::new(cache, config.memory_cache_size);
+                Ok((channel.clone(), Arc::new(cache)))
+            })
+            .collect::<anyhow::Result<_>>()
+            .map(Caches)
+    }
+
+    fn get(&self, channel: &str) -> ChannelCache {
+        Arc::clone(&self.0[channel])
+    }
+}
+
+fn render_channel(
+    config: &Config,
+    cache: ChannelCache,
+    channel: ChannelInfo,
+    shared: &Shared,
+    metrics: &mut Metrics,
+    out: &mut dyn Output,
+) -> anyhow::Result<(ChannelReport, RenderState, LoadedChannel)> {
+    let mut loaded = load_channel(config, cache, channel, metrics)?;
+    let started = Instant::now();
+    let (mut report, state) = generate_channel(config, &loaded, shared, out)?;
+    report.manifests = mem::take(&mut loaded.manifests);
+    report.phases = PhasesReport {
+        render_secs: started.elapsed().as_secs_f64(),
+        ..mem::take(&mut loaded.phases)
+    };
+    Ok((report, state, loaded))
+}
+
+/// Calculates how many days back the manifests are looked up for.
+pub fn lookup_days(config: &Config) -> anyhow::Result<usize> {
+    let window = match config.start_date {
+        Some(start) => {
+            let end = config.end_date.unwrap_or_else(|| Utc::now().date_naive());
+            anyhow::ensure!(
+                start <= end,
+                "The start date {} is after the end date {}",
+                start,
+                end
+            );
+            (end - start).num_days() as usize + 1
+        }
+        None => config.days_in_past,
+    };
+    let days = window + config.additional_lookup_days;
+    match config.max_lookup_days {
+        Some(max) if days > max => {
+            log::warn!(
+                "Looking {} days back rather than {} because of `max_lookup_days`",
+                max,
+                days
+            );
+            Ok(max)
+        }
+        _ => Ok(days),
+    }
+}
+
+/// Downloads the manifests of a channel's last `days` days into its cache
+/// the way a rendering does, without rendering anything.
+pub fn warm_cache(
+    config: &Config,
+    caches: &Caches,
+    channel: &str,
+    days: usize,
+) -> anyhow::Result<ManifestsReport> {
+    if let Source::Fake(_) = config.source {
+        anyhow::bail!("Made-up manifests aren't cached");
+    }
+    let channel = ChannelInfo::new(channel, &config.channels);
+    let (manifests, download) =
+        download_manifests(config, caches.get(channel.name), channel, days)?;
+    Ok(ManifestsReport::new(
+        channel.name,
+        days,
+        manifests.len(),
+        &download,
+    ))
+}
+
+/// A channel's manifests, loaded and filtered.
+pub struct LoadedChannel {
+    pub name: String,
+    pub data: AvailabilityData,
+    /// All the known dates, in descending order.
+    pub all_dates: Vec<NaiveDate>,
+    /// How many of the `all_dates` are rendered.
+    pub rendered: usize,
+    pub manifests: ManifestsReport,
+    /// Time spent downloading and parsing the manifests.
+    pub phases: PhasesReport,
+}
+
+/// Loads the manifests of all the channels, so they can be rendered again
+/// and again without downloading anything.
+pub fn load_channels(config: &Config, caches: &Caches) -> anyhow::Result<Vec<LoadedChannel>> {
+    let mut metrics = Metrics::default();
+    config
+        .channels
+        .iter()
+        .map(|channel| load_channel_metered(config, caches, channel, &mut metrics))
+        .collect()
+}
+
+/// Loads the manifests of a single configured channel.
+pub fn load_single_channel(
+    config: &Config,
+    caches: &Caches,
+    channel: &str,
+) -> anyhow::Result<LoadedChannel> {
+    load_channel_metered(config, caches, channel, &mut Metrics::default())
+}
+
+fn load_channel_metered(
+    config: &Config,
+    caches: &Caches,
+    channel: &str,
+    metrics: &mut Metrics,
+) -> anyhow::Result<LoadedChannel> {
+    let channel = ChannelInfo::new(channel, &config.channels);
+    load_channel(config, caches.get(channel.name), channel, metrics).locate(|| {
+        Location::new(format!("Can't load channel {}", channel.name)).channel(channel.name)
+    })
+}
+
+/// Renders the HTML pages of all the targets of the loaded channels.
+pub fn render_html(
+    config: &Config,
+    channels: &[LoadedChannel],
+    out: &mut dyn Output,
+) -> anyhow::Result<()> {
+    // Left over from the previous rendering in the watch mode.
+    warnings::take();
+    let shared = Shared::load(config)?;
+    shared.write_assets(out)?;
+    for loaded in channels {
+        let channel = ChannelInfo::new(&loaded.name, &config.channels);
+        let dates = &loaded.all_dates[..loaded.rendered];
+        let targets = loaded.data.get_available_targets();
+        // Only the changes noticed by the previous runs are known.
+        let state = RenderState::load(config.state_path.as_ref().map(|p| channel.relocate(p)))?;
+        let explanations = explain_missing(config, &loaded.data, dates, &targets)?;
+        let input = GeneratorInput {
+            data: &loaded.data,
+            dates,
+            all_dates: &loaded.all_dates,
+            republished: &loaded.manifests.republished,
+            targets: &targets,
+            tier_changes: state.tier_changes(),
+            channel,
+            shared: &shared,
+        };
+        let options = html::Options {
+            keep_going: config.keep_going,
+            site_root: config.site_root.as_deref(),
+            explanations: &explanations,
+        };
+        html::generate_html(&input, &config.html, &options, out).locate(|| {
+            Location::new(format!("Can't render channel {}", channel.name)).channel(channel.name)
+        })?;
+    }
+    out.finish()
+}
+
+fn load_channel(
+    config: &Config,
+    cache: ChannelCache,
+    channel: ChannelInfo,
+    metrics: &mut Metrics,
+) -> anyhow::Result<LoadedChannel> {
+    log::info!(channel = channel.name; "Processing channel {}", channel.name);
+    let mut data: AvailabilityData = Default::default();
+    let filters = Filters::new(config)?;
+    let days = lookup_days(config)?;
+    let (mut manifests, download) = match &config.source {
+        Source::Fake(fake) => {
+            let end = config.end_date.unwrap_or_else(|| Utc::now().date_naive());
+            let manifests = fake_data::manifests(fake, channel.name, end, days);
+            (manifests, Download::default())
+        }
+        _ => download_manifests(config, cache, channel, days)?,
+    };
+    let downloaded = manifests.len();
+    if let (Some(latest), Some(oldest)) = (manifests.first(), manifests.last()) {
+        log::info!(
+            channel = channel.name;
+            "Found {} manifests of {} from {} to {} ({} from the cache) within {} days",
+            downloaded,
+            channel.name,
+            oldest.date,
+            latest.date,
+            download.cache_hits,
+            days
+        );
+    }
+    for manifest in &mut manifests {
+        aliases::apply(manifest, &config.aliases);
+    }
+    if let Some(storage) = &config.storage {
+        let mut db = Storage::open(&storage.sqlite)?;
+        db.store(channel.name, &manifests)?;
+        if let Some(oldest) = manifests.last().map(|manifest| manifest.date) {
+            let history = db.history(channel.name, oldest, storage.history_days)?;
+            log::info!(
+                channel = channel.name;
+                "Read {} older manifests of {} from {}",
+                history.len(),
+                channel.name,
+                storage.sqlite.display()
+            );
+            manifests.extend(history);
+        }
+    }
+    let all_dates: Vec<_> = manifests.iter().map(|manifest| manifest.date).collect();
+    if publishes_daily(channel.name) {
+        for pair in all_dates.windows(2) {
+            for days in 1..(pair[0] - pair[1]).num_days() {
+                data.add_missing_manifest(pair[0] - chrono::Duration::days(days));
+            }
+        }
+    }
+    // Whatever the channel, a manifest that failed to download is unknown.
+    for &date in &download.failed {
+        data.add_missing_manifest(date);
+    }
+    let rendered = match config.start_date {
+        Some(start) => all_dates.iter().take_while(|&&date| date >= start).count(),
+        None => config.days_in_past.min(all_dates.len()),
+    };
+    let manifests_report = ManifestsReport::new(channel.name, days, downloaded, &download);
+    let started = Instant::now();
+    let parsing = progress::bar(manifests.len(), format!("Parsing {}", channel.name));
+    for manifest in manifests {
+        data.add_manifest_filtered(manifest, |target, package| filters.keeps(target, package));
+        parsing.inc(1);
+    }
+    parsing.finish_and_clear();
+    let phases = PhasesReport {
+        download_secs: download.duration.as_secs_f64(),
+        parse_secs: started.elapsed().as_secs_f64(),
+        render_secs: 0.,
+    };
+    metrics.add_channel(channel.name, &data, all_dates.first().copied(), &download);
+    Ok(LoadedChannel {
+        name: channel.name.to_string(),
+        data,
+        all_dates,
+        rendered,
+        manifests: manifests_report,
+        phases,
+    })
+}
+
+/// Whether a channel has a manifest every day. Only nightly does, beta and
+/// stable manifests show up on release days only, so a day without them isn't
+/// worth showing.
+pub(crate) fn publishes_daily(channel: &str) -> bool {
+    channel == "nightly"
+}
+
+/// Builds a downloader of a channel's manifests the way the configuration
+/// says: from its source, through its network settings, with its retries,
+/// verification and limits of the missing days. `days` is how many days are
+/// going to be looked up.
+pub fn downloader<'a>(
+    config: &'a Config,
+    channel: &'a str,
+    days: usize,
+) -> anyhow::Result<Downloader<Box<dyn SourceInfo<Url = String> + Sync + 'a>>> {
+    let skip_missing_days = if publishes_daily(channel) {
+        config.max_missing_days
+    } else {
+        days
+    };
+    let source: Box<dyn SourceInfo<Url = String> + Sync> = match &config.source {
+        Source::Http => {
+            let mut source = DefaultSource::new(channel);
+            if let Some(base_url) = &config.manifest_base_url {
+                source.override_base(base_url.trim_end_matches('/').into());
+            }
+            Box::new(source)
+        }
+        Source::Local { path } => Box::new(LocalSource::new(channel, path)),
+        Source::Fake(_) => anyhow::bail!("Fake manifests aren't downloaded"),
+    };
+    let mut downloader = Downloader::new(source)
+        .set_client(http_client(config)?)
+        .skip_missing_days(skip_missing_days)
+        .count_missing_days_in_a_row(true)
+        .stop_at_missing_days(config.on_missing_days == OnMissingDays::Truncate)
+        .concurrency(config.download_concurrency)
+        .retry_policy((&config.retry).into())
+        .tolerate_failed_days(config.tolerate_failed_days);
+    if let Some(verification) = &config.verification {
+        downloader = downloader.verification(verification.into());
+    }
+    Ok(downloader)
+}
+
+/// Downloads `days` manifests of a channel, or takes them from the cache.
+fn download_manifests(
+    config: &Config,
+    cache: ChannelCache,
+    channel: ChannelInfo,
+    days: usize,
+) -> anyhow::Result<(Vec<Manifest>, Download)> {
+    let downloading = progress::bar(days, format!("Downloading {}", channel.name));
+    let counting = Arc::new(CountingCache::new(Arc::clone(&cache)));
+    let downloader = downloader(config, channel.name, days)?.set_cache(ProgressCache::new(
+        Arc::clone(&counting),
+        downloading.clone(),
+    ));
+    let started = Instant::now();
+    let manifests = match config.end_date {
+        Some(end) => downloader.get_manifests_until(end, days),
+        None => downloader.get_last_manifests(days),
+    };
+    let date = manifests.as_ref().err().and_then(diagnostics::date_of);
+    let manifests = manifests.locate(|| {
+        let location = Location::new(format!("Can't download the {} manifests", channel.name))
+            .channel(channel.name);
+        match date {
+            Some(date) => location.date(date),
+            None => location,
+        }
+    })?;
+    downloading.finish_and_clear();
+    let (cache_hits, cache_misses) = counting.counts();
+    let download = Download {
+        duration: started.elapsed(),
+        cache_hits,
+        cache_misses,
+        republished: cache.inner().take_republished(),
+        failed: downloader.failed_days(),
+    };
+    let limits = &config.cache_limits;
+    let removed = cache
+        .inner()
+        .evict_every(
+            Duration::from_secs(limits.evict_every_hours * 3600),
+            &limits.into(),
+            Utc::now().date_naive(),
+        )
+        .with_context(|| "Can't evict cached manifests")?;
+    if !removed.is_empty() {
+        log::info!("Removed {} manifests from the cache", removed.len());
+    }
+    Ok((manifests, download))
+}
+
+/// Runs all the configured generators over a loaded channel. Its republished
+/// days are archived again.
+///
+/// The returned state has to be saved once the output is in place.
+fn generate_channel(
+    config: &Config,
+    loaded: &LoadedChannel,
+    shared: &Shared,
+    out: &mut dyn Output,
+) -> anyhow::Result<(ChannelReport, RenderState)> {
+    let channel = ChannelInfo::new(&loaded.name, &config.channels);
+    let data = &loaded.data;
+    let all_dates = &loaded.all_dates[..];
+    let dates = &all_dates[..loaded.rendered];
+    log::info!("Available targets: {:?}", data.get_available_targets());
+    log::info!("Available packages: {:?}", data.get_available_packages());
+
+    let mut state = RenderState::load(config.state_path.as_ref().map(|p| channel.relocate(p)))?;
+    if let Some(&latest) = all_dates.first() {
+        state.update_tiers(&config.html.tiers, latest);
+    }
+    if let Some(&oldest) = all_dates.last() {
+        state.prune_tier_changes(oldest);
+    }
+    if let Some(assets) = &shared.assets {
+        state.update_assets(assets.fingerprint());
+    }
+    let mut targets = state.changed_targets(data, dates)?;
+    // The site archive replaces the previous one, so every target goes in.
+    if config.output.archive.is_some() {
+        targets = data.get_available_targets();
+    }
+    // The state is updated while the outputs borrow the changes.
+    let tier_changes = state.tier_changes().to_vec();
+    let input = GeneratorInput {
+        data,
+        dates,
+        all_dates,
+        republished: &loaded.manifests.republished,
+        targets: &targets,
+        tier_changes: &tier_changes,
+        channel,
+        shared,
+    };
+    let mut failed = Vec::new();
+    let mut failed_outputs = Vec::new();
+    for generator in generators::configured(config) {
+        log::debug!("Generating {} of {}", generator.name(), channel.name);
+        match generator.generate(&input, out) {
+            Ok(targets) => failed.extend(targets),
+            Err(e) if config.keep_going => {
+                log::error!(
+                    channel = channel.name;
+                    "Skipping the {} of {}: {:#}",
+                    generator.name(),
+                    channel.name,
+                    e
+                );
+                failed_outputs.push(generator.name().to_string());
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    failed.sort_unstable();
+    failed.dedup();
+    // Failed targets have to be rendered again next time, and so do all of
+    // them if it isn't known which ones a failed output has got to.
+    if failed_outputs.is_empty() {
+        failed.iter().for_each(|target| state.forget(target));
+    } else {
+        targets.iter().for_each(|target| state.forget(target));
+    }
+    if let Some(notifications) = &config.notifications {
+        notify::notify(
+            data,
+            all_dates,
+            notifications,
+            config.html.engine,
+            channel,
+            &state,
+            &Http::new(&config.network, &config.retry)?,
+        )?;
+    }
+    if let Some(&latest) = all_dates.first() {
+        state.set_available(state::snapshot(data, latest));
+    }
+    if let Some(github_issues) = &config.github_issues {
+        let http = Http::new(&config.network, &config.retry)?;
+        github_issues::sync_issues(data, all_dates, github_issues, channel, &http)?;
+    }
+    let sorted = |targets: &HashSet<&str>| {
+        let mut targets: Vec<_> = targets.iter().map(|target| target.to_string()).collect();
+        targets.sort_unstable();
+        targets
+    };
+    let report = ChannelReport {
+        channel: channel.name.to_string(),
+        dates: dates.to_vec(),
+        targets: sorted(&data.get_available_targets()),
+        rendered_targets: sorted(&(&targets - &failed.iter().copied().collect())),
+        failed_targets: failed.iter().map(|target| target.to_string()).collect(),
+        failed_outputs,
+        pages: sitemap::channel_pages(data, &config.html, channel)?,
+        packages: sorted(&data.get_available_packages()),
+        regressions: data
+            .changes(&all_dates[..all_dates.len().min(2)])
+            .into_iter()
+            .filter(|change| !change.available)
+            .map(|change| Regression {
+                target: change.target.to_string(),
+                package: change.package.to_string(),
+            })
+            .collect(),
+        manifests: ManifestsReport::default(),
+        phases: PhasesReport::default(),
+    };
+    Ok((report, state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_files_of_the_same_stem_atomically() {
+        let dir = tempfile::tempdir().unwrap();
+        write_atomically(&dir.path().join("run.json"), b"{}").unwrap();
+        write_atomically(&dir.path().join("run.prom"), b"# metrics").unwrap();
+        let mut names: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["run.json", "run.prom"]);
+        assert_eq!(fs::read(dir.path().join("run.json")).unwrap(), b"{}");
+    }
+
+    #[test]
+    fn migrates_single_channel_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("2019-01-01.toml"), "").unwrap();
+        fs::write(root.join("validators.toml"), "").unwrap();
+        fs::write(root.join("notes.txt"), "").unwrap();
+
+        migrate_cache(root, &root.join("nightly")).unwrap();
+        assert!(root.join("nightly/2019-01-01.toml").exists());
+        assert!(root.join("nightly/validators.toml").exists());
+        assert!(!root.join("2019-01-01.toml").exists());
+        assert!(root.join("notes.txt").exists());
+    }
+}
@@ -0,0 +1,125 @@
+use std::{
+    fs::{self, File, OpenOptions, TryLockError},
+    io::Write,
+    path::{Path, PathBuf},
+    process,
+};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::opts;
+
+/// Whoever holds a lock, as written into the lock file.
+#[derive(Serialize, Deserialize)]
+struct Holder {
+    pid: u32,
+    host: Option<String>,
+    since: DateTime<Utc>,
+}
+
+/// Returns the name of the host, if it is known.
+fn hostname() -> Option<String> {
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .map(|name| name.trim().to_string())
+}
+
+impl Holder {
+    fn current() -> Self {
+        Holder {
+            pid: process::id(),
+            host: hostname(),
+            since: Utc::now(),
+        }
+    }
+}
+
+/// A lock held for the duration of a run, released on drop.
+///
+/// The lock is an advisory lock of the operating system on the kept open
+/// file, so it's released even if the process dies. The file only tells who
+/// holds it.
+pub struct RunLock {
+    path: PathBuf,
+    file: File,
+}
+
+impl RunLock {
+    /// Takes the lock, or fails if another run holds it.
+    pub fn acquire(config: &opts::Lock) -> anyhow::Result<Self> {
+        let path = &config.path;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("Can't open lock file {}", path.display()))?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => anyhow::bail!("{}", held_by(path)),
+            Err(TryLockError::Error(e)) => {
+                return Err(e).with_context(|| format!("Can't lock {}", path.display()))
+            }
+        }
+        file.set_len(0)
+            .and_then(|()| file.write_all(&serde_json::to_vec(&Holder::current())?))
+            .and_then(|()| file.flush())
+            .with_context(|| format!("Can't write lock file {}", path.display()))?;
+        log::debug!("Locked {}", path.display());
+        Ok(RunLock {
+            path: path.clone(),
+            file,
+        })
+    }
+}
+
+/// Describes who holds a lock, as far as its file tells.
+fn held_by(path: &Path) -> String {
+    let holder = fs::read(path)
+        .ok()
+        .and_then(|contents| serde_json::from_slice::<Holder>(&contents).ok());
+    match holder {
+        Some(holder) => format!(
+            "Another run (pid {} on {}) has been holding {} since {}",
+            holder.pid,
+            holder.host.as_deref().unwrap_or("an unknown host"),
+            path.display(),
+            holder.since
+        ),
+        None => format!("Another run is holding {}", path.display()),
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        // The file itself is kept: removing it would let a run lock a new file
+        // while another one still waits on the removed one.
+        if let Err(e) = self.file.set_len(0) {
+            log::error!("Can't clear lock file {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn excludes_other_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = opts::Lock {
+            path: dir.path().join("run.lock"),
+        };
+        let lock = RunLock::acquire(&config).unwrap();
+        let err = RunLock::acquire(&config).err().unwrap().to_string();
+        assert!(err.contains(&format!("pid {}", process::id())), "{}", err);
+        drop(lock);
+        let _lock = RunLock::acquire(&config).unwrap();
+    }
+}
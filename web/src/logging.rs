@@ -0,0 +1,90 @@
+use std::io::{self, Write};
+
+use chrono::{SecondsFormat, Utc};
+use indicatif::MultiProgress;
+use log::{
+    kv::{Key, Value, VisitSource},
+    Level, LevelFilter, Log, Metadata, Record,
+};
+use rustup_status::{
+    opts::{Config, LogFormat},
+    warnings,
+};
+use serde_json::Map;
+
+/// Collects the structured fields of a record.
+struct Fields(Map<String, serde_json::Value>);
+
+impl<'kvs> VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), log::kv::Error> {
+        let value = serde_json::to_value(&value)
+            .unwrap_or_else(|_| serde_json::Value::String(value.to_string()));
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+/// Prints the log messages above the progress bars.
+struct AboveBars(&'static MultiProgress);
+
+impl Write for AboveBars {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.suspend(|| io::stderr().write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+/// Records the warnings and errors for the run report, whatever the
+/// verbosity, before passing them on.
+struct Recording(env_logger::Logger);
+
+impl Log for Recording {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Warn || self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() <= Level::Warn {
+            warnings::record(record.args().to_string());
+        }
+        self.0.log(record)
+    }
+
+    fn flush(&self) {
+        self.0.flush()
+    }
+}
+
+/// Initializes the logger according to the configuration, keeping the
+/// progress `bars`, if any, below the messages.
+pub fn init(config: &Config, bars: Option<&'static MultiProgress>) {
+    let mut builder = env_logger::Builder::from_default_env();
+    builder.filter_level(config.verbosity);
+    if let Some(bars) = bars {
+        builder.target(env_logger::Target::Pipe(Box::new(AboveBars(bars))));
+    }
+    if config.log_format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let mut fields = Fields(Map::new());
+            let _ = record.key_values().visit(&mut fields);
+            let mut object = fields.0;
+            object.insert(
+                "timestamp".into(),
+                Utc::now()
+                    .to_rfc3339_opts(SecondsFormat::Millis, true)
+                    .into(),
+            );
+            object.insert("level".into(), record.level().as_str().into());
+            object.insert("target".into(), record.target().into());
+            object.insert("message".into(), record.args().to_string().into());
+            writeln!(buf, "{}", serde_json::Value::Object(object))
+        });
+    }
+    let logger = builder.build();
+    let max_level = logger.filter().max(LevelFilter::Warn);
+    log::set_boxed_logger(Box::new(Recording(logger))).expect("The logger is initialized once");
+    log::set_max_level(max_level);
+}
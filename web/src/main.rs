@@ -1,45 +1,279 @@
 use std::{
-    collections::HashMap,
-    fmt::Display,
     fs::{create_dir_all, File},
-    io::{self, Write},
-    path::{Path, PathBuf},
+    io::{self, IsTerminal, Write},
+    path::PathBuf,
 };
 
 use anyhow::Context;
-use chrono::{NaiveDate, Utc};
-use handlebars::{handlebars_helper, Handlebars};
-use opts::Config;
-use rustup_available_packages::{cache::FsCache, table::Table, AvailabilityData, Downloader};
-use serde::Serialize;
-use structopt::StructOpt;
-use tiers_table::TiersTable;
+use chrono::NaiveDate;
+use log::LevelFilter;
+use rustup_status::{
+    acquire_lock,
+    diagnostics::{Category, Diagnostic, ErrorFormat, Locate, Location},
+    dump_context, load_single_channel,
+    opts::{Config, ConfigFormat, Source},
+    render_site_locked, Caches,
+};
+use structopt::{clap::Shell, StructOpt};
 
+mod api;
+mod bisect;
+mod cache_cmd;
+mod check;
+mod compare_output;
 mod config_gen;
-mod opts;
-mod tiers_table;
+mod deploy;
+mod diff;
+mod explain_cmd;
+mod last_good_cmd;
+mod logging;
+mod query;
+mod s3;
+mod serve;
+mod sftp;
+mod status_cmd;
+#[cfg(test)]
+mod test_utils;
+mod tui;
+mod watch;
 
 #[derive(StructOpt)]
 #[structopt(about = "Rust tools per-release availability monitor")]
+struct Opts {
+    #[structopt(
+        long = "error-format",
+        help = "How to report a failure: human, or json to also print a JSON object describing it to stdout",
+        default_value = "human",
+        possible_values = &["human", "json"],
+        global = true
+    )]
+    error_format: ErrorFormat,
+    #[structopt(subcommand)]
+    cmd: CmdOpts,
+}
+
+#[derive(StructOpt)]
 enum CmdOpts {
     #[structopt(name = "render", about = "Renders pages using provided configuration")]
-    Render(ConfigOpt),
+    Render(RenderOpt),
     #[structopt(
         name = "print_config",
         about = "Prints the default configuration to stdout"
     )]
     PrintConfig(PrintConfig),
+    #[structopt(
+        name = "serve",
+        about = "Serves rendered pages over HTTP, re-rendering them periodically"
+    )]
+    Serve(serve::ServeOpt),
+    #[structopt(
+        name = "check",
+        about = "Checks that packages are available in the latest manifest, exits with a non-zero code otherwise"
+    )]
+    Check(check::CheckOpt),
+    #[structopt(
+        name = "diff",
+        about = "Shows which packages appeared or disappeared between two manifests"
+    )]
+    Diff(diff::DiffOpt),
+    #[structopt(
+        name = "compare-output",
+        about = "Shows how two rendered file trees differ, e.g. the ones of two versions of the pipeline"
+    )]
+    CompareOutput(compare_output::CompareOutputOpt),
+    #[structopt(
+        name = "last-good",
+        about = "Prints the latest date on which all the given packages were available on a target"
+    )]
+    LastGood(last_good_cmd::LastGoodOpt),
+    #[structopt(
+        name = "bisect",
+        about = "Finds the day a package disappeared from a target, looking further back than the rendered days"
+    )]
+    Bisect(bisect::BisectOpt),
+    #[structopt(
+        name = "explain",
+        about = "Tells which change of rust-lang/rust broke a missing package, according to the toolstate history"
+    )]
+    Explain(explain_cmd::ExplainOpt),
+    #[structopt(name = "cache", about = "Manages the manifests cache")]
+    Cache(cache_cmd::CacheCmd),
+    #[structopt(
+        name = "query",
+        about = "Prints the stored or cached statuses of the packages that match an expression"
+    )]
+    Query(query::QueryOpt),
+    #[structopt(
+        name = "tui",
+        about = "Browses the stored or cached statuses of the packages in the terminal"
+    )]
+    Tui(tui::TuiOpt),
+    #[structopt(
+        name = "status",
+        about = "Prints a table of the recent availability of a target's packages"
+    )]
+    Status(status_cmd::StatusOpt),
+    #[structopt(
+        name = "completions",
+        about = "Prints a completion script of the command line for a shell"
+    )]
+    Completions(Completions),
 }
 
+/// A configuration file and overrides of its fields. The overrides take
+/// precedence over the file, and the command line flags take precedence over
+/// the environment variables.
 #[derive(StructOpt)]
-struct ConfigOpt {
+pub struct ConfigOpt {
     #[structopt(
         short = "c",
         long = "config",
         help = "Path to a configuration file",
+        env = "RUSTUP_STATUS_CONFIG",
+        parse(from_os_str)
+    )]
+    pub config_path: PathBuf,
+    #[structopt(
+        long = "days-in-past",
+        help = "How many days to render, overrides the configuration",
+        env = "RUSTUP_STATUS_DAYS_IN_PAST"
+    )]
+    days_in_past: Option<usize>,
+    #[structopt(
+        long = "channel",
+        help = "A release channel to process, might be repeated. Overrides the configuration",
+        env = "RUSTUP_STATUS_CHANNEL",
+        number_of_values = 1,
+        use_delimiter = true
+    )]
+    channels: Vec<String>,
+    #[structopt(
+        long = "cache-path",
+        help = "Path to the manifests cache, overrides the configuration",
+        env = "RUSTUP_STATUS_CACHE_PATH",
+        parse(from_os_str)
+    )]
+    cache_path: Option<PathBuf>,
+    #[structopt(
+        long = "verbosity",
+        help = "Log level (OFF, ERROR, WARN, INFO, DEBUG or TRACE), overrides the configuration",
+        env = "RUSTUP_STATUS_VERBOSITY"
+    )]
+    verbosity: Option<LevelFilter>,
+}
+
+impl ConfigOpt {
+    /// Loads the configuration file and applies the overrides.
+    pub fn load(&self) -> anyhow::Result<Config> {
+        let mut config = Config::load(&self.config_path).locate(|| {
+            Location::new(format!("Can't load config {:?}", self.config_path))
+                .category(Category::Config)
+                .path(&self.config_path)
+        })?;
+        if let Some(days_in_past) = self.days_in_past {
+            config.days_in_past = days_in_past;
+        }
+        if !self.channels.is_empty() {
+            config.channels = self.channels.clone();
+            config.check_channels()?;
+        }
+        if let Some(cache_path) = &self.cache_path {
+            config.cache_path = Some(cache_path.clone());
+        }
+        if let Some(verbosity) = self.verbosity {
+            config.verbosity = verbosity;
+        }
+        Ok(config)
+    }
+
+    /// Loads the configuration for a command that looks into a single
+    /// channel, the first one.
+    pub fn load_with_channel(&self) -> anyhow::Result<(Config, String)> {
+        let config = self.load()?;
+        let channel = config
+            .channels
+            .first()
+            .cloned()
+            .context("No channel is configured")?;
+        Ok((config, channel))
+    }
+}
+
+#[derive(StructOpt)]
+struct RenderOpt {
+    #[structopt(flatten)]
+    config: ConfigOpt,
+    #[structopt(
+        long = "start-date",
+        help = "The first date to render (YYYY-MM-DD), overrides the configuration"
+    )]
+    start_date: Option<NaiveDate>,
+    #[structopt(
+        long = "end-date",
+        help = "The last date to render (YYYY-MM-DD), overrides the configuration"
+    )]
+    end_date: Option<NaiveDate>,
+    #[structopt(
+        long = "dry-run",
+        help = "Validates the configuration and templates against made-up data without downloading or writing anything"
+    )]
+    dry_run: bool,
+    #[structopt(
+        long = "keep-going",
+        help = "Renders the rest of the targets, outputs and channels if one of them fails, overrides the configuration"
+    )]
+    keep_going: bool,
+    #[structopt(
+        long = "metrics-file",
+        help = "A file to write Prometheus metrics to, overrides the configuration",
         parse(from_os_str)
     )]
-    config_path: PathBuf,
+    metrics_file: Option<PathBuf>,
+    #[structopt(
+        long = "report-file",
+        help = "A file to write a JSON report of the run to, overrides the configuration",
+        parse(from_os_str)
+    )]
+    report_file: Option<PathBuf>,
+    #[structopt(
+        long = "progress",
+        help = "Shows the progress of every phase, unless stderr isn't a terminal"
+    )]
+    progress: bool,
+    #[structopt(
+        long = "fake-data",
+        help = "Renders made-up manifests instead of the real ones, without touching the network, the state or the cache"
+    )]
+    fake_data: bool,
+    #[structopt(
+        long = "watch",
+        help = "Renders the HTML pages again whenever a template changes, reusing the loaded manifests",
+        conflicts_with = "dry-run"
+    )]
+    watch: bool,
+    #[structopt(
+        long = "dump-context",
+        value_name = "target",
+        help = "Prints the data the template of a target's page gets in JSON instead of rendering anything. The first channel is used, pick another one with --channel",
+        conflicts_with_all = &["dry-run", "watch"]
+    )]
+    dump_context: Option<String>,
+    #[structopt(
+        long = "deploy",
+        help = "Deploys the rendered site as configured in the deploy section",
+        conflicts_with_all = &["dry-run", "watch", "dump-context", "fake-data"]
+    )]
+    deploy: bool,
+}
+
+#[derive(StructOpt)]
+struct Completions {
+    #[structopt(
+        help = "A shell to print the completions for",
+        possible_values = &Shell::variants(),
+        case_insensitive = true
+    )]
+    shell: Shell,
 }
 
 #[derive(StructOpt)]
@@ -51,149 +285,145 @@ struct PrintConfig {
         parse(from_os_str)
     )]
     config_path: Option<PathBuf>,
+    #[structopt(
+        short = "f",
+        long = "format",
+        help = "Format of the configuration: yaml, json or toml. Guessed by the file extension by default"
+    )]
+    format: Option<ConfigFormat>,
+    #[structopt(
+        long = "schema",
+        help = "Prints a JSON Schema of the configuration instead",
+        conflicts_with = "format"
+    )]
+    schema: bool,
 }
 
-#[derive(Serialize)]
-struct PathRenderData<'a> {
-    target: &'a str,
-}
-
-#[derive(Serialize)]
-struct TiersData<'a> {
-    tiers: TiersTable<'a>,
-    datetime: String,
-}
-
-fn generate_html(
-    data: &AvailabilityData,
-    dates: &[NaiveDate],
-    opts::Html {
-        template_path,
-        output_pattern,
-        tiers,
-    }: opts::Html,
-) -> anyhow::Result<()> {
-    const TEMPLATE_NAME: &str = "target_info";
-    let mut handlebars = Handlebars::new();
-    handlebars_helper!(streq: |x: str, y: str| x  == y);
-    handlebars.register_helper("streq", Box::new(streq));
-    handlebars.set_strict_mode(true);
-    handlebars
-        .register_template_file(TEMPLATE_NAME, &template_path)
-        .with_context(|| format!("File path: {:?}", &template_path))?;
-
-    let all_targets = data.get_available_targets();
-
-    let additional = TiersData {
-        tiers: TiersTable::new(tiers, &all_targets),
-        datetime: Utc::now().format("%d %b %Y, %H:%M:%S UTC").to_string(),
-    };
-
-    for target in &all_targets {
-        log::info!("Processing target {}", target);
-        let output_path = handlebars
-            .render_template(&output_pattern, &PathRenderData { target })
-            .with_context(|| format!("Invalid output pattern: {}", &output_pattern))?;
-        if let Some(parent) = Path::new(&output_path).parent() {
-            create_dir_all(parent)
-                .with_context(|| format!("Can't create path {}", parent.display()))?;
-        }
-        log::info!("Preparing file {}", output_path);
-        let out = File::create(&output_path)
-            .with_context(|| format!("Can't create file [{}]", output_path))?;
-
-        let table = Table::builder(&data, target)
-            .dates(dates)
-            .additional(&additional)
-            .build();
-
-        log::info!("Writing target {} to {:?}", target, output_path);
-        handlebars
-            .render_to_write(TEMPLATE_NAME, &table, out)
-            .with_context(|| format!("Can't render [{:?}] for [{}]", template_path, target))?;
+fn main() {
+    let opts = Opts::from_args();
+    if let Err(e) = run(opts.cmd) {
+        eprintln!("Error: {:?}", e);
+        if opts.error_format == ErrorFormat::Json {
+            match serde_json::to_string(&Diagnostic::new(&e)) {
+                Ok(diagnostic) => println!("{}", diagnostic),
+                Err(e) => eprintln!("Can't serialize the error: {}", e),
+            }
+        }
+        std::process::exit(1);
     }
-    Ok(())
-}
-
-/// Saves a list of packages to a json file.
-fn packages_json(
-    pkgs: impl IntoIterator<Item = impl Display>,
-    path: impl AsRef<Path>,
-) -> io::Result<()> {
-    let contents = serde_json::to_vec(
-        &pkgs
-            .into_iter()
-            .map(|p| p.to_string())
-            .collect::<Vec<String>>(),
-    )?;
-    std::fs::write(path, contents)
 }
 
-fn generate_fs_tree(
-    data: &AvailabilityData,
-    dates: &[NaiveDate],
-    output: &Path,
-) -> anyhow::Result<()> {
-    let targets = data.get_available_targets();
-    let pkgs = data.get_available_packages();
-
-    packages_json(&pkgs, output.join("packages.json")).with_context(|| "packages.json")?;
-
-    for target in targets {
-        let target_path = output.join(target);
-        create_dir_all(&target_path)
-            .with_context(|| format!("Can't create path {}", target_path.display()))?;
-
-        for pkg in &pkgs {
-            let row = if let Some(row) = data.get_availability_row(target, pkg, dates) {
-                row
+fn run(cmd_opts: CmdOpts) -> anyhow::Result<()> {
+    let (mut config, deploy) = match cmd_opts {
+        CmdOpts::Render(RenderOpt {
+            config,
+            start_date,
+            end_date,
+            dry_run,
+            keep_going,
+            metrics_file,
+            report_file,
+            progress,
+            fake_data,
+            watch,
+            dump_context,
+            deploy,
+        }) => {
+            let mut loaded = config.load()?;
+            loaded.start_date = start_date.or(loaded.start_date);
+            loaded.end_date = end_date.or(loaded.end_date);
+            loaded.keep_going |= keep_going;
+            loaded.metrics_file = metrics_file.or(loaded.metrics_file);
+            loaded.report_file = report_file.or(loaded.report_file);
+            if fake_data && !matches!(loaded.source, Source::Fake(_)) {
+                loaded.source = Source::Fake(Default::default());
+            }
+            if let Source::Fake(_) = loaded.source {
+                rustup_status::fake_data::isolate(&mut loaded);
+            }
+            let bars = if progress && io::stderr().is_terminal() {
+                Some(rustup_status::progress::enable())
             } else {
-                continue;
+                None
             };
-            if let Some(date) = row.last_available {
-                let path = target_path.join(pkg);
-                let mut f = File::create(&path)
-                    .with_context(|| format!("Can't create file {}", path.display()))?;
-                writeln!(f, "{}", date.format("%Y-%m-%d"))?;
-            } else {
-                // If a package is not available, don't create a file for it at
-                // all.
+            logging::init(&loaded, bars);
+            if dry_run {
+                return run_dry(loaded);
             }
-
-            // This should always be true, but better to output nothing than to panic
-            // or output corrupt data.
-            if dates.len() == row.availability_list.len() {
-                let path = target_path.join(&format!("{}.json", pkg));
-
-                let contents = serde_json::to_vec_pretty(&TargetPkg {
-                    availability: dates
-                        .iter()
-                        .zip(row.availability_list.iter())
-                        .map(|(date, avail)| (date.format("%Y-%m-%d").to_string(), *avail))
-                        .collect(),
-                    last_available: row.last_available.map(|d| d.format("%Y-%m-%d").to_string()),
-                })?;
-                std::fs::write(&path, contents)
-                    .with_context(|| format!("Can't write file {}", path.display()))?;
+            if watch {
+                rustup_status::platform_support::apply(&mut loaded)?;
+                return watch::run(loaded);
+            }
+            if let Some(target) = dump_context {
+                rustup_status::platform_support::apply(&mut loaded)?;
+                return run_dump_context(&loaded, &target);
+            }
+            if deploy && loaded.deploy.is_none() {
+                anyhow::bail!("--deploy needs the deploy section of the configuration");
             }
+            (loaded, deploy)
         }
-    }
-    Ok(())
-}
-
-#[derive(serde::Serialize)]
-struct TargetPkg {
-    #[serde(flatten)]
-    availability: HashMap<String, bool>,
-    last_available: Option<String>,
-}
-
-fn main() -> anyhow::Result<()> {
-    let cmd_opts = CmdOpts::from_args();
-    let config = match cmd_opts {
-        CmdOpts::Render(cmd_opts) => Config::load(&cmd_opts.config_path)
-            .with_context(|| format!("Can't load config {:?}", cmd_opts.config_path))?,
-        CmdOpts::PrintConfig(PrintConfig { config_path }) => {
+        CmdOpts::Serve(serve_opts) => {
+            let config = serve_opts.config.load()?;
+            logging::init(&config, None);
+            return serve::run(config, serve_opts);
+        }
+        CmdOpts::Check(check_opts) => {
+            env_logger::init();
+            if !check::run(&check_opts)? {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        CmdOpts::Diff(diff_opts) => {
+            env_logger::init();
+            return diff::run(&diff_opts);
+        }
+        CmdOpts::CompareOutput(compare_opts) => {
+            env_logger::init();
+            return compare_output::run(&compare_opts);
+        }
+        CmdOpts::LastGood(last_good_opts) => {
+            env_logger::init();
+            return last_good_cmd::run(&last_good_opts);
+        }
+        CmdOpts::Bisect(bisect_opts) => {
+            env_logger::init();
+            return bisect::run(&bisect_opts);
+        }
+        CmdOpts::Explain(explain_opts) => {
+            env_logger::init();
+            return explain_cmd::run(&explain_opts);
+        }
+        CmdOpts::Cache(cache_cmd) => {
+            env_logger::init();
+            return cache_cmd::run(cache_cmd);
+        }
+        CmdOpts::Query(query_opts) => {
+            env_logger::init();
+            return query::run(&query_opts);
+        }
+        CmdOpts::Tui(tui_opts) => {
+            env_logger::init();
+            return tui::run(&tui_opts);
+        }
+        CmdOpts::Status(status_opts) => {
+            env_logger::init();
+            return status_cmd::run(&status_opts);
+        }
+        CmdOpts::Completions(Completions { shell }) => {
+            Opts::clap().gen_completions_to(
+                env!("CARGO_BIN_NAME"),
+                shell,
+                &mut io::stdout().lock(),
+            );
+            return Ok(());
+        }
+        CmdOpts::PrintConfig(PrintConfig {
+            config_path,
+            format,
+            schema,
+        }) => {
             const HEADER: &str = "\
 # DO NOT EDIT MANUALLY
 #
@@ -201,50 +431,67 @@ fn main() -> anyhow::Result<()> {
 # $ cargo r -p rustup-available-packages-web -- print_config -c config.yaml
 ";
 
-            let config = config_gen::gen_config()?;
+            let format = format
+                .or_else(|| config_path.as_deref().and_then(ConfigFormat::from_path))
+                .unwrap_or(ConfigFormat::Yaml);
+            let config = if schema {
+                Config::schema()?
+            } else {
+                config_gen::gen_config()?.dump(format)?
+            };
+            // JSON doesn't support comments.
+            let header = if schema || format == ConfigFormat::Json {
+                ""
+            } else {
+                HEADER
+            };
             if let Some(path) = config_path {
                 if let Some(parent) = path.parent() {
                     create_dir_all(parent)?;
                 }
 
-                let f = File::create(&path)?;
-                writeln!(&f, "{HEADER}")?;
-                serde_yaml::to_writer(&f, &config)
+                let mut f = File::create(&path)?;
+                writeln!(f, "{header}")
+                    .and_then(|_| f.write_all(config.as_bytes()))
                     .with_context(|| format!("Failed to write config {path:?}"))?;
             } else {
                 let mut out = io::stdout().lock();
-                writeln!(out, "{HEADER}")?;
-                serde_yaml::to_writer(out, &config)?;
+                writeln!(out, "{header}")?;
+                out.write_all(config.as_bytes())?;
             }
             return Ok(());
         }
     };
-    env_logger::Builder::from_default_env()
-        .filter_level(config.verbosity)
-        .init();
-
-    let mut data: AvailabilityData = Default::default();
-    let cache = if let Some(cache_path) = config.cache_path.as_ref() {
-        FsCache::new(cache_path).with_context(|| "Can't initialize cache")?
-    } else {
-        FsCache::noop()
-    };
-    let downloader = Downloader::with_default_source(&config.channel)
-        .set_cache(cache)
-        .skip_missing_days(7);
-    let manifests =
-        downloader.get_last_manifests(config.days_in_past + config.additional_lookup_days)?;
-    let dates: Vec<_> = manifests
-        .iter()
-        .map(|manifest| manifest.date)
-        .take(config.days_in_past)
-        .collect();
-    data.add_manifests(manifests);
-    log::info!("Available targets: {:?}", data.get_available_targets());
-    log::info!("Available packages: {:?}", data.get_available_packages());
-
-    generate_html(&data, &dates, config.html)?;
-    generate_fs_tree(&data, &dates, &config.file_tree_output)?;
+    // Held until the site is deployed, so another run can't replace it halfway.
+    let _lock = acquire_lock(&config)?;
+    render_site_locked(&mut config)?;
+    if let Some(settings) = config.deploy.as_ref().filter(|_| deploy) {
+        deploy::run(&config, settings)?;
+    }
+    Ok(())
+}
 
+fn run_dump_context(config: &Config, target: &str) -> anyhow::Result<()> {
+    let channel = config
+        .channels
+        .first()
+        .context("No channels are configured")?;
+    let caches = Caches::new(config)?;
+    let channel = load_single_channel(config, &caches, channel)?;
+    println!("{}", dump_context(config, &channel, target)?);
     Ok(())
 }
+
+fn run_dry(config: Config) -> anyhow::Result<()> {
+    let report = rustup_status::dry_run::dry_run(config);
+    for path in &report.files {
+        println!("Would write {}", path.display());
+    }
+    if report.errors.is_empty() {
+        return Ok(());
+    }
+    for error in &report.errors {
+        eprintln!("Error: {:#}", error);
+    }
+    anyhow::bail!("Found {} errors", report.errors.len())
+}
@@ -0,0 +1,288 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use rustup_available_packages::{
+    cache::{Cache, Validators},
+    manifest::Manifest,
+    AvailabilityData,
+};
+
+/// Counts hits and misses of a wrapped cache.
+pub struct CountingCache<C> {
+    inner: C,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl<C> CountingCache<C> {
+    pub fn new(inner: C) -> Self {
+        CountingCache {
+            inner,
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the numbers of hits and misses so far.
+    pub fn counts(&self) -> (usize, usize) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+}
+
+impl<C: Cache> Cache for CountingCache<C> {
+    fn get(&self, day: NaiveDate) -> Option<Manifest> {
+        let manifest = self.inner.get(day);
+        let counter = if manifest.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        manifest
+    }
+
+    fn store(&self, manifest: &Manifest) {
+        self.inner.store(manifest)
+    }
+
+    fn get_validators(&self, url: &str) -> Option<Validators> {
+        self.inner.get_validators(url)
+    }
+
+    fn store_validators(&self, url: &str, validators: &Validators) {
+        self.inner.store_validators(url, validators)
+    }
+}
+
+/// Cache hits and misses of every channel since the start of the process, so
+/// the counters keep growing across the renderings of the serve mode.
+static CACHE_LOOKUPS: Mutex<BTreeMap<String, (usize, usize)>> = Mutex::new(BTreeMap::new());
+
+/// A metric with all of its samples.
+struct Family {
+    help: &'static str,
+    /// Either a `gauge` or a `counter`.
+    kind: &'static str,
+    samples: Vec<String>,
+}
+
+/// Metrics of a run, rendered in the Prometheus text format.
+#[derive(Default)]
+pub struct Metrics {
+    families: BTreeMap<&'static str, Family>,
+}
+
+/// What has been measured while downloading a channel's manifests.
+#[derive(Default)]
+pub struct Download {
+    pub duration: Duration,
+    pub cache_hits: usize,
+    pub cache_misses: usize,
+    /// Days whose cached manifests have turned out to be re-published.
+    pub republished: Vec<NaiveDate>,
+    /// Days whose manifests have failed to download and have been skipped.
+    pub failed: Vec<NaiveDate>,
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn timestamp(date: NaiveDate) -> f64 {
+    let midnight = date.and_hms_opt(0, 0, 0).expect("Valid time");
+    Utc.from_utc_datetime(&midnight).timestamp() as f64
+}
+
+impl Metrics {
+    fn gauge(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        labels: &[(&str, &str)],
+        value: f64,
+    ) {
+        self.sample("gauge", name, help, labels, value)
+    }
+
+    fn counter(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        labels: &[(&str, &str)],
+        value: f64,
+    ) {
+        self.sample("counter", name, help, labels, value)
+    }
+
+    fn sample(
+        &mut self,
+        kind: &'static str,
+        name: &'static str,
+        help: &'static str,
+        labels: &[(&str, &str)],
+        value: f64,
+    ) {
+        let family = self.families.entry(name).or_insert_with(|| Family {
+            help,
+            kind,
+            samples: Vec::new(),
+        });
+        let labels: Vec<_> = labels
+            .iter()
+            .map(|(label, value)| format!("{}=\"{}\"", label, escape(value)))
+            .collect();
+        let sample = if labels.is_empty() {
+            format!("{} {}", name, value)
+        } else {
+            format!("{}{{{}}} {}", name, labels.join(","), value)
+        };
+        family.samples.push(sample);
+    }
+
+    /// Records the metrics of a rendered channel.
+    pub fn add_channel(
+        &mut self,
+        channel: &str,
+        data: &AvailabilityData,
+        latest: Option<NaiveDate>,
+        download: &Download,
+    ) {
+        let labels = [("channel", channel)];
+        self.gauge(
+            "rustup_download_duration_seconds",
+            "Time spent getting the manifests of a channel.",
+            &labels,
+            download.duration.as_secs_f64(),
+        );
+        let (hits, misses) = CACHE_LOOKUPS
+            .lock()
+            .map(|mut lookups| {
+                let total = lookups.entry(channel.to_string()).or_default();
+                total.0 += download.cache_hits;
+                total.1 += download.cache_misses;
+                *total
+            })
+            .unwrap_or((download.cache_hits, download.cache_misses));
+        self.counter(
+            "rustup_cache_hits_total",
+            "Manifests that have been found in the cache.",
+            &labels,
+            hits as f64,
+        );
+        self.counter(
+            "rustup_cache_misses_total",
+            "Manifests that haven't been found in the cache.",
+            &labels,
+            misses as f64,
+        );
+        let lookups = download.cache_hits + download.cache_misses;
+        if lookups != 0 {
+            self.gauge(
+                "rustup_cache_hit_ratio",
+                "Share of the manifests that have been found in the cache.",
+                &labels,
+                download.cache_hits as f64 / lookups as f64,
+            );
+        }
+        let latest = match latest {
+            Some(latest) => latest,
+            None => return,
+        };
+        self.gauge(
+            "rustup_last_manifest_timestamp_seconds",
+            "Date of the latest manifest of a channel.",
+            &labels,
+            timestamp(latest),
+        );
+
+        let mut targets: Vec<_> = data.get_available_targets().into_iter().collect();
+        targets.sort_unstable();
+        let mut packages: Vec<_> = data.get_available_packages().into_iter().collect();
+        packages.sort_unstable();
+        for target in targets {
+            for &package in &packages {
+                if let Some(row) = data.get_availability_row(target, package, [latest]) {
+                    self.gauge(
+                        "rustup_component_available",
+                        "Whether a package is available in the latest manifest.",
+                        &[
+                            ("channel", channel),
+                            ("target", target),
+                            ("package", package),
+                        ],
+                        if row.availability_list[0] { 1. } else { 0. },
+                    );
+                }
+            }
+        }
+    }
+
+    /// Records how many targets, outputs and channels have failed to render.
+    pub fn add_failures(&mut self, failures: usize) {
+        self.gauge(
+            "rustup_render_failures",
+            "Targets, outputs and channels that have failed to render.",
+            &[],
+            failures as f64,
+        );
+    }
+
+    /// Renders the metrics in the text exposition format.
+    pub fn render(mut self) -> String {
+        self.gauge(
+            "rustup_render_timestamp_seconds",
+            "When the site has been rendered.",
+            &[],
+            Utc::now().timestamp() as f64,
+        );
+        let mut text = String::new();
+        for (name, family) in &self.families {
+            let _ = writeln!(text, "# HELP {} {}", name, family.help);
+            let _ = writeln!(text, "# TYPE {} {}", name, family.kind);
+            for sample in &family.samples {
+                let _ = writeln!(text, "{}", sample);
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_lookups_are_counters() {
+        let download = Download {
+            cache_hits: 3,
+            cache_misses: 1,
+            ..Default::default()
+        };
+        let data = AvailabilityData::default();
+        let mut metrics = Metrics::default();
+        metrics.add_channel("metrics-test", &data, None, &download);
+        metrics.add_channel("metrics-test", &data, None, &download);
+        metrics.add_failures(2);
+        let text = metrics.render();
+
+        assert!(text.contains("# TYPE rustup_cache_hits_total counter"));
+        assert!(text.contains("rustup_cache_hits_total{channel=\"metrics-test\"} 6"));
+        assert!(text.contains("rustup_cache_misses_total{channel=\"metrics-test\"} 2"));
+        assert!(text.contains("# TYPE rustup_cache_hit_ratio gauge"));
+        assert!(text.contains("rustup_render_failures 2"));
+    }
+}
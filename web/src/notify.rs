@@ -0,0 +1,306 @@
+use std::{
+    env,
+    fmt::Write,
+    fs::{self, File},
+    path::Path,
+};
+
+use anyhow::Context;
+use chrono::{NaiveDate, Utc};
+use lettre::{
+    message::{Mailbox, MultiPart},
+    transport::smtp::authentication::Credentials,
+    Message, SmtpTransport, Transport,
+};
+use rustup_available_packages::AvailabilityData;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{
+    http::Http,
+    opts::{self, Engine, SmtpSecurity, WebhookKind},
+    state::{self, Available, RenderState},
+    templates::Templates,
+    ChannelInfo,
+};
+
+/// A package whose availability has changed since the previous run.
+#[derive(Serialize)]
+struct Change<'a> {
+    target: &'a str,
+    package: &'a str,
+    last_available: Option<NaiveDate>,
+}
+
+/// A payload that is POSTed to generic webhooks.
+#[derive(Serialize)]
+struct Payload<'a> {
+    channel: &'a str,
+    date: NaiveDate,
+    /// Packages that were available during the previous run but are missing now.
+    regressions: Vec<&'a Change<'a>>,
+    /// Packages that were missing during the previous run but are available now.
+    recoveries: Vec<&'a Change<'a>>,
+}
+
+fn load_available(path: &Path) -> anyhow::Result<Option<Available>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let f = File::open(path)?;
+    Ok(Some(serde_json::from_reader(f)?))
+}
+
+fn save_available(path: &Path, available: &Available) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_vec(available)?)?;
+    Ok(())
+}
+
+fn pairs(available: &Available) -> impl Iterator<Item = (&str, &str)> {
+    available.iter().flat_map(|(target, packages)| {
+        packages
+            .iter()
+            .map(move |package| (target.as_str(), package.as_str()))
+    })
+}
+
+fn missing_in(available: &Available, target: &str, package: &str) -> bool {
+    !available
+        .get(target)
+        .is_some_and(|packages| packages.contains(package))
+}
+
+/// Selects the changes of the given `targets` and `packages`, all of them if
+/// either list is empty.
+fn tracked<'a>(
+    targets: &[String],
+    packages: &[String],
+    changes: &'a [Change<'a>],
+) -> Vec<&'a Change<'a>> {
+    let is_tracked = |change: &&Change| {
+        (targets.is_empty() || targets.iter().any(|t| t == change.target))
+            && (packages.is_empty() || packages.iter().any(|p| p == change.package))
+    };
+    changes.iter().filter(is_tracked).collect()
+}
+
+/// Renders a human-readable message. `bold` wraps the header into the
+/// markup-specific emphasis.
+fn message(payload: &Payload, bold: impl Fn(&str) -> String) -> Result<String, std::fmt::Error> {
+    let mut text = bold(&subject(payload));
+    for change in &payload.regressions {
+        write!(
+            text,
+            "\n❌ {} is missing on {}",
+            change.package, change.target
+        )?;
+        if let Some(date) = change.last_available {
+            write!(text, " (last available on {})", date)?;
+        }
+    }
+    for change in &payload.recoveries {
+        write!(
+            text,
+            "\n✅ {} is available on {}",
+            change.package, change.target
+        )?;
+    }
+    Ok(text)
+}
+
+fn send(http: &Http, webhook: &opts::Webhook, payload: &Payload) -> anyhow::Result<()> {
+    let client = http.client();
+    let request = match webhook.kind {
+        WebhookKind::Generic => client.post(&webhook.url).json(payload),
+        WebhookKind::Slack => client
+            .post(&webhook.url)
+            .json(&json!({ "text": message(payload, |s| format!("*{}*", s))? })),
+        WebhookKind::Discord => client
+            .post(&webhook.url)
+            .json(&json!({ "content": message(payload, |s| format!("**{}**", s))? })),
+        WebhookKind::Matrix => {
+            // Matrix requires a unique transaction ID per message.
+            let url = format!(
+                "{}/rustup-{}-{}",
+                webhook.url.trim_end_matches('/'),
+                payload.channel,
+                Utc::now().timestamp_millis()
+            );
+            let html = message(payload, |s| format!("<b>{}</b>", crate::escape_xml(s)))?;
+            client.put(url).json(&json!({
+                "msgtype": "m.text",
+                "body": message(payload, str::to_string)?,
+                "format": "org.matrix.custom.html",
+                "formatted_body": html.replace('\n', "<br>"),
+            }))
+        }
+    };
+    let request = match &webhook.access_token {
+        Some(token) => request.bearer_auth(token),
+        None => request,
+    };
+    http.send(request)?.error_for_status()?;
+    Ok(())
+}
+
+fn subject(payload: &Payload) -> String {
+    format!(
+        "Rustup packages availability changes on {} ({})",
+        payload.channel, payload.date
+    )
+}
+
+fn send_email(email: &opts::Email, engine: Engine, payload: &Payload) -> anyhow::Result<()> {
+    let mut templates = Templates::new(engine);
+    templates.register_file("text", &email.text_template)?;
+    templates.register_file("html", &email.html_template)?;
+    let body = MultiPart::alternative_plain_html(
+        templates.render("text", payload)?,
+        templates.render("html", payload)?,
+    );
+    let mut message = Message::builder()
+        .from(email.from.parse::<Mailbox>()?)
+        .subject(subject(payload));
+    for recipient in &email.to {
+        message = message.to(recipient.parse::<Mailbox>()?);
+    }
+    let message = message.multipart(body)?;
+
+    let smtp = &email.smtp;
+    let mut transport = match smtp.security {
+        SmtpSecurity::Tls => SmtpTransport::relay(&smtp.host)?,
+        SmtpSecurity::StartTls => SmtpTransport::starttls_relay(&smtp.host)?,
+        SmtpSecurity::None => SmtpTransport::builder_dangerous(&smtp.host),
+    };
+    if let Some(port) = smtp.port {
+        transport = transport.port(port);
+    }
+    if let Some(username) = &smtp.username {
+        let password = smtp
+            .password
+            .clone()
+            .or_else(|| env::var("SMTP_PASSWORD").ok())
+            .unwrap_or_default();
+        transport = transport.credentials(Credentials::new(username.clone(), password));
+    }
+    transport.build().send(&message)?;
+    Ok(())
+}
+
+/// Notifies the configured webhooks about packages that were available during
+/// the previous run but are missing in the latest manifest, and vice versa.
+///
+/// The digest emails are rendered with the templates of the `engine`. The
+/// available packages are remembered in the notifications' own state file, or
+/// taken from the run's `state` if there's none.
+///
+/// `dates` must be sorted in descending order. Nothing is sent on the very
+/// first run, since there's nothing to compare with.
+pub fn notify(
+    data: &AvailabilityData,
+    dates: &[NaiveDate],
+    config: &opts::Notifications,
+    engine: Engine,
+    channel: ChannelInfo,
+    state: &RenderState,
+    http: &Http,
+) -> anyhow::Result<()> {
+    let latest = match dates.first() {
+        Some(&date) => date,
+        None => return Ok(()),
+    };
+    let state_path = config
+        .state_path
+        .as_ref()
+        .map(|path| channel.relocate(path));
+    let previous = match &state_path {
+        Some(path) => load_available(path)
+            .with_context(|| format!("Can't load notifications state {}", path.display()))?,
+        None => {
+            anyhow::ensure!(
+                state.is_persistent(),
+                "Notifications need either their own `state_path` or the global one"
+            );
+            state.available().cloned()
+        }
+    };
+
+    let available = state::snapshot(data, latest);
+
+    let (regressions, recoveries): (Vec<_>, Vec<_>) = match &previous {
+        Some(previous) => (
+            pairs(previous)
+                .filter(|(target, package)| missing_in(&available, target, package))
+                .map(|(target, package)| Change {
+                    target,
+                    package,
+                    last_available: data.last_available(target, package),
+                })
+                .collect(),
+            pairs(&available)
+                // Don't report every package of a brand new target.
+                .filter(|(target, package)| {
+                    previous.contains_key(*target) && missing_in(previous, target, package)
+                })
+                .map(|(target, package)| Change {
+                    target,
+                    package,
+                    last_available: data.last_available(target, package),
+                })
+                .collect(),
+        ),
+        None => Default::default(),
+    };
+
+    for webhook in &config.webhooks {
+        let payload = Payload {
+            channel: channel.name,
+            date: latest,
+            regressions: tracked(&webhook.targets, &webhook.packages, &regressions),
+            recoveries: tracked(&webhook.targets, &webhook.packages, &recoveries),
+        };
+        if payload.regressions.is_empty() && payload.recoveries.is_empty() {
+            continue;
+        }
+        log::info!(
+            "Notifying {} about {} regressions and {} recoveries",
+            webhook.url,
+            payload.regressions.len(),
+            payload.recoveries.len()
+        );
+        // A broken webhook shouldn't prevent the pages from being updated.
+        if let Err(e) = send(http, webhook, &payload) {
+            log::error!("Can't notify {}: {:?}", webhook.url, e);
+        }
+    }
+    for email in &config.emails {
+        let payload = Payload {
+            channel: channel.name,
+            date: latest,
+            regressions: tracked(&email.targets, &email.packages, &regressions),
+            recoveries: tracked(&email.targets, &email.packages, &recoveries),
+        };
+        if payload.regressions.is_empty() && payload.recoveries.is_empty() {
+            continue;
+        }
+        let recipients = email.to.join(", ");
+        log::info!(
+            "Emailing {} about {} regressions and {} recoveries",
+            recipients,
+            payload.regressions.len(),
+            payload.recoveries.len()
+        );
+        if let Err(e) = send_email(email, engine, &payload) {
+            log::error!("Can't email {}: {:?}", recipients, e);
+        }
+    }
+
+    match &state_path {
+        Some(path) => save_available(path, &available)
+            .with_context(|| format!("Can't save notifications state {}", path.display())),
+        None => Ok(()),
+    }
+}
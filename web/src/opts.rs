@@ -1,16 +1,33 @@
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
     fs::File,
+    io::Read,
     path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
 };
 
+use chrono::NaiveDate;
 use log::LevelFilter;
-use serde::{Deserialize, Serialize, Serializer};
+use rustup_available_packages::{aggregate, cache::EvictionPolicy, retry::RetryPolicy, verify};
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use strum::EnumIter;
 
 /// Support tiers: https://doc.rust-lang.org/nightly/rustc/platform-support.html.
 #[derive(
-    Debug, Serialize, Deserialize, Hash, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, EnumIter,
+    Debug,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    Hash,
+    PartialEq,
+    Eq,
+    Clone,
+    Copy,
+    PartialOrd,
+    Ord,
+    EnumIter,
 )]
 pub enum Tier {
     /// Tier 1 platforms.
@@ -26,6 +43,8 @@ pub enum Tier {
     #[serde(rename = "Tier 3")]
     Tier3,
     #[doc(hidden)]
+    #[allow(clippy::enum_variant_names)]
+    #[serde(alias = "Unknown tier")]
     UnknownTier,
 }
 
@@ -33,41 +52,171 @@ fn default_verbosity() -> LevelFilter {
     LevelFilter::Warn
 }
 
-fn default_channel() -> String {
-    String::from("nightly")
+fn default_download_concurrency() -> usize {
+    4
+}
+
+fn default_channels() -> Vec<String> {
+    vec![String::from("nightly")]
+}
+
+/// Either a single value or a list of values.
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+/// Accepts either a single value or a list of values.
+fn one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
 }
 
 fn default_additional_days() -> usize {
     0
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+fn default_max_missing_days() -> usize {
+    7
+}
+
+/// Configuration of the tool.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Config {
     #[serde(flatten)]
     pub html: Html,
     /// For how many days in the past would you like to peek.
     pub days_in_past: usize,
+    /// The first date to render. If set, `days_in_past` is ignored and all the
+    /// days from `start_date` to `end_date` are rendered.
+    #[serde(default)]
+    pub start_date: Option<NaiveDate>,
+    /// The last date to render.
+    ///
+    /// If omitted, the latest available manifest is the last one.
+    #[serde(default)]
+    pub end_date: Option<NaiveDate>,
+    /// Keep rendering the other targets, outputs and channels if one of them
+    /// fails, and report all the failures at the end of the run.
+    #[serde(default)]
+    pub keep_going: bool,
     /// For how many additional days should we look into to calculate "the last
     /// available" date.
     #[serde(default = "default_additional_days")]
     pub additional_lookup_days: usize,
-    /// A release channel to check.
+    /// How many nightly manifests in a row might be missing before the lookup
+    /// gives up. Beta and stable manifests only show up on release days, so
+    /// any number of them might be missing.
+    #[serde(default = "default_max_missing_days")]
+    pub max_missing_days: usize,
+    /// The most days to look back for, `additional_lookup_days` included, e.g.
+    /// to keep a distant `start_date` from downloading years of manifests.
+    ///
+    /// If omitted, there's no limit.
+    #[serde(default)]
+    pub max_lookup_days: Option<usize>,
+    /// What happens once more than `max_missing_days` nightly manifests in a
+    /// row are missing: the run either fails (`fail`, the default) or renders
+    /// the days found by then (`truncate`).
+    #[serde(default)]
+    pub on_missing_days: OnMissingDays,
+    /// Release channels to check, e.g. nightly, beta and stable. A single
+    /// channel name is accepted as well (also under the old `channel` key).
+    ///
+    /// The first channel is rendered right under the configured output
+    /// locations, while every other channel gets its own `$channel`
+    /// subdirectory next to it.
     ///
     /// If omitted, the default channel is nightly.
-    #[serde(default = "default_channel")]
-    pub channel: String,
+    #[serde(
+        default = "default_channels",
+        alias = "channel",
+        deserialize_with = "one_or_many"
+    )]
+    #[schemars(with = "OneOrMany<String>")]
+    pub channels: Vec<String>,
+    /// Where the manifests are taken from.
+    ///
+    /// If omitted, they are downloaded over HTTP.
+    #[serde(default)]
+    pub source: Source,
+    /// A base URL of the manifests, e.g. a local mirror of
+    /// `https://static.rust-lang.org/dist`. Only used by the `http` source.
+    ///
+    /// If omitted, the official source is used.
+    #[serde(default)]
+    pub manifest_base_url: Option<String>,
+    /// Additional HTTP headers to send when downloading manifests, e.g. for
+    /// authentication on a mirror.
+    #[serde(default)]
+    pub manifest_headers: BTreeMap<String, String>,
+    /// How many manifests might be downloaded and parsed simultaneously.
+    ///
+    /// If omitted, up to 4 manifests are downloaded at once.
+    #[serde(default = "default_download_concurrency")]
+    pub download_concurrency: usize,
+    /// How failed downloads are retried.
+    ///
+    /// If omitted, nothing is retried.
+    #[serde(default)]
+    pub retry: Retry,
+    /// Network settings of the manifests' downloads.
+    #[serde(default)]
+    pub network: Network,
+    /// Verification of the downloaded manifests against the checksums and
+    /// signatures published next to them.
+    ///
+    /// If omitted, nothing is verified.
+    #[serde(default)]
+    pub verification: Option<Verification>,
+    /// For how many days the manifests might fail to download (after all the
+    /// retries) without failing the whole run. Such days are skipped with a
+    /// warning.
+    ///
+    /// If omitted, any failure is fatal.
+    #[serde(default)]
+    pub tolerate_failed_days: usize,
     /// Verbosity level, might be one of the following: OFF, ERROR, WARN, INFO,
     /// DEBUG or TRACE.
     ///
     /// If omitted, the default level is WARN.
     #[serde(default = "default_verbosity")]
+    #[schemars(with = "String")]
     pub verbosity: LevelFilter,
+    /// Format of the log messages: `text` (the default) or `json`, which
+    /// prints an object per line with the `timestamp`, `level`, `target`,
+    /// `message` and the structured fields of a message, like the
+    /// `target_triple` or the `manifest_date`.
+    #[serde(default)]
+    pub log_format: LogFormat,
     /// A path where to store the downloaded manifests.
     ///
     /// If omitted, no cache will be used, i.e. all the manifests will be
     /// re-downloaded every time you run the tool.
     #[serde(default)]
     pub cache_path: Option<PathBuf>,
+    /// Whether the cached manifests should be stored zstd-compressed.
+    #[serde(default)]
+    pub compress_cache: bool,
+    /// How many parsed manifests per channel might be kept in memory, which
+    /// speeds up repeated renderings in the serve mode.
+    ///
+    /// If omitted, nothing is kept in memory.
+    #[serde(default)]
+    pub memory_cache_size: usize,
+    /// Limits of the manifests cache, applied after a run once in a while.
+    ///
+    /// If omitted, the cache grows without bounds.
+    #[serde(default)]
+    pub cache_limits: CacheLimits,
     ///A path where a file tree of available packages will be created. The tool
     /// will generate a set of files under a given *output* directory with the
     /// following pattern: file_tree_output/$target/$package, where $target
@@ -77,12 +226,920 @@ pub struct Config {
     /// which represents the latest date when the package was (is) available for
     /// that specific target.
     pub file_tree_output: PathBuf,
+    /// A path to a JSON file the state of the previous run is kept in: hashes
+    /// of the rendered data and a snapshot of the available packages. Targets
+    /// whose data hasn't changed since the previous run are not re-rendered,
+    /// neither as HTML pages nor in the file tree, so the previous output must
+    /// be kept in place.
+    ///
+    /// If omitted, all the targets are rendered on every run.
+    #[serde(default)]
+    pub state_path: Option<PathBuf>,
+    /// The directory the whole site is published in. If set, all the files are
+    /// rendered into a staging copy of it, which replaces the directory only
+    /// if the whole run succeeds, so a failed run never leaves a mix of old and
+    /// new files behind.
+    ///
+    /// Every output has to be located under this directory, while the files
+    /// that are kept between runs (like `state_path`) have to be outside of it.
+    #[serde(default)]
+    pub site_root: Option<PathBuf>,
+    /// A file to write Prometheus metrics of every run to, e.g. for the
+    /// textfile collector of the node exporter.
+    #[serde(default)]
+    pub metrics_file: Option<PathBuf>,
+    /// A JSON file to write a report of every run to: the downloaded and
+    /// cached manifests, the rendered targets, the discovered packages, the
+    /// regressions, the time spent in every phase and the warnings.
+    #[serde(default)]
+    pub report_file: Option<PathBuf>,
+    /// Problems with the data that fail a run, besides the hard errors.
+    ///
+    /// If omitted, only the hard errors fail.
+    #[serde(default)]
+    pub strictness: Strictness,
+    /// A lock file that prevents overlapping runs from rendering at the same
+    /// time.
+    ///
+    /// If omitted, runs aren't locked.
+    #[serde(default)]
+    pub lock: Option<Lock>,
+    /// A database every run stores the availability of the downloaded days
+    /// in, so the history isn't limited by the lookup window.
+    ///
+    /// If omitted, nothing is stored.
+    #[serde(default)]
+    pub storage: Option<Storage>,
+    /// A single JSON file with all the availability data.
+    ///
+    /// If omitted, no such file is generated.
+    #[serde(default)]
+    pub json_dump: Option<JsonDump>,
+    /// CSV export of the availability tables.
+    ///
+    /// If omitted, no CSV files are generated.
+    #[serde(default)]
+    pub csv: Option<Csv>,
+    /// Atom feeds of availability changes.
+    ///
+    /// If omitted, no feeds are generated.
+    #[serde(default)]
+    pub feed: Option<Feed>,
+    /// SVG badges with the current status of every package.
+    ///
+    /// If omitted, no badges are generated.
+    #[serde(default)]
+    pub badges: Option<Badges>,
+    /// Pages with the availability rolled up into weeks or months, to show
+    /// long-term trends without rendering a column per day.
+    ///
+    /// If omitted, no such pages are generated.
+    #[serde(default)]
+    pub trends: Option<Trends>,
+    /// Daily snapshots of the availability data, which are kept beyond the
+    /// rendered range of dates.
+    ///
+    /// If omitted, no snapshots are stored.
+    #[serde(default)]
+    pub archive: Option<Archive>,
+    /// A JSON file per target with the latest date on which a set of packages
+    /// were all available, e.g. to keep a `rust-toolchain` file up to date.
+    ///
+    /// If omitted, no such files are generated.
+    #[serde(default)]
+    pub last_good: Option<LastGood>,
+    /// Notifications about packages that have gone missing since the previous
+    /// run.
+    ///
+    /// If omitted, no notifications are sent.
+    #[serde(default)]
+    pub notifications: Option<Notifications>,
+    /// GitHub issues about watched packages that have been missing for a
+    /// while.
+    ///
+    /// If omitted, no issues are filed.
+    #[serde(default)]
+    pub github_issues: Option<GithubIssues>,
+    /// A git branch the rendered site is committed to by `render --deploy`,
+    /// e.g. the one GitHub Pages are served from.
+    ///
+    /// If omitted, the site can't be deployed.
+    #[serde(default)]
+    pub deploy: Option<Deploy>,
+    /// A sitemap of all the rendered pages.
+    ///
+    /// If omitted, no sitemap is generated.
+    #[serde(default)]
+    pub sitemap: Option<Sitemap>,
+    /// How the generated files are written.
+    #[serde(default)]
+    pub output: OutputSettings,
+    /// Other names to show the packages and targets under, so the history of
+    /// a renamed package continues in a single row.
+    #[serde(default)]
+    pub aliases: Aliases,
+    /// Restricts the targets that are tracked, e.g. to `*-linux-*` and
+    /// `wasm32-*`.
+    ///
+    /// If omitted, every target found in the manifests is tracked.
+    #[serde(default)]
+    pub targets: Globs,
+    /// Restricts the packages that are tracked on some targets, so e.g. the
+    /// pages of niche targets only list the packages that matter there.
+    ///
+    /// If omitted, every package is tracked on every target.
+    #[serde(default)]
+    pub package_filters: Vec<PackageFilter>,
+}
+
+/// Configuration of the consolidated JSON file.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct JsonDump {
+    /// A path of the file. It will contain a map of targets to maps of packages
+    /// to their availability, in the same format as the `$package.json` files of
+    /// the file tree.
+    pub path: PathBuf,
+    /// Whether the file should be gzip-compressed, in which case a `.gz` suffix
+    /// is appended to the path.
+    #[serde(default)]
+    pub gzip: bool,
+}
+
+/// CSV-related configuration.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Csv {
+    /// A directory where an `availability-$target.csv` file will be created for
+    /// every target. Each file contains a row per package and a column per
+    /// date.
+    pub output_dir: PathBuf,
+}
+
+/// Run locking configuration.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Lock {
+    /// A path to the lock file, which has to be shared by the overlapping
+    /// runs.
+    pub path: PathBuf,
+}
+
+fn default_feed_entries() -> usize {
+    50
+}
+
+/// Feeds-related configuration.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Feed {
+    /// A pattern that will be used to render feed files. Any instance of a
+    /// `{{target}}` will be replaced with a target name.
+    pub output_pattern: String,
+    /// A path to a JSON file where the feed entries are kept between runs, so
+    /// changes that have left the lookup window are not forgotten.
+    ///
+    /// If omitted, only the changes within the lookup window are reported.
+    #[serde(default)]
+    pub history_path: Option<PathBuf>,
+    /// Maximum number of entries per feed.
+    #[serde(default = "default_feed_entries")]
+    pub max_entries: usize,
+}
+
+/// Badges-related configuration.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Badges {
+    /// A pattern that will be used to render badge files. Any instance of a
+    /// `{{target}}` will be replaced with a target name, and any instance of a
+    /// `{{package}}` with a package name.
+    pub output_pattern: String,
+}
+
+/// Storage of the availability history.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Storage {
+    /// A SQLite database, e.g. `history.db`, which is created if it doesn't
+    /// exist. The stored days that are older than the downloaded ones are
+    /// rendered along with them.
+    pub sqlite: PathBuf,
+    /// How many days older than the downloaded ones are read from the
+    /// database.
+    #[serde(default = "default_history_days")]
+    pub history_days: usize,
+}
+
+fn default_history_days() -> usize {
+    365
+}
+
+/// Trend pages configuration.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Trends {
+    /// Path to a template file.
+    pub template_path: PathBuf,
+    /// A pattern that will be used to render output files. Any instance of a
+    /// `{{target}}` will be replaced with a target name.
+    pub output_pattern: String,
+    /// What the days are rolled up into: `week` (the default) or `month`.
+    #[serde(default)]
+    pub period: Period,
+    /// How many of the most recent periods are shown. The days are only known
+    /// within `days_in_past` and `additional_lookup_days`.
+    #[serde(default = "default_trend_periods")]
+    pub periods: usize,
+}
+
+fn default_trend_periods() -> usize {
+    104
+}
+
+/// A period the days are rolled up into.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Period {
+    /// A week starting on Monday.
+    #[default]
+    Week,
+    /// A calendar month.
+    Month,
+}
+
+impl From<Period> for aggregate::Period {
+    fn from(period: Period) -> Self {
+        match period {
+            Period::Week => aggregate::Period::Week,
+            Period::Month => aggregate::Period::Month,
+        }
+    }
+}
+
+/// "Last good" toolchains configuration.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct LastGood {
+    /// A pattern that will be used to render output files. Any instance of a
+    /// `{{target}}` will be replaced with a target name.
+    pub output_pattern: String,
+    /// Packages that have to be available.
+    pub packages: Vec<String>,
+}
+
+/// Archive-related configuration.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Archive {
+    /// A directory to put the `$date/availability.json` snapshots and the
+    /// `latest` link to the most recent one into.
+    pub path: PathBuf,
+    /// For how many days the snapshots are kept. The latest snapshot is kept
+    /// anyway, so `0` keeps only that one.
+    ///
+    /// If omitted, the snapshots are kept forever.
+    #[serde(default)]
+    pub keep_days: Option<usize>,
+}
+
+/// Sitemap-related configuration.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Sitemap {
+    /// Where to put the sitemap. Only the pages under its directory are listed.
+    pub path: PathBuf,
+    /// The URL the sitemap's directory is published at, e.g.
+    /// `https://rust-lang.github.io/rustup-components-history/`.
+    pub base_url: String,
+}
+
+/// Notifications-related configuration.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Notifications {
+    /// A path to a JSON file where the packages available during the previous
+    /// run are kept.
+    ///
+    /// If omitted, they are kept in the global `state_path`.
+    #[serde(default)]
+    pub state_path: Option<PathBuf>,
+    /// Webhooks to notify.
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
+    /// Digest emails to send.
+    #[serde(default)]
+    pub emails: Vec<Email>,
+}
+
+/// A URL that availability changes are sent to.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Webhook {
+    /// How the message should be formatted.
+    #[serde(default)]
+    pub kind: WebhookKind,
+    /// For the Matrix webhooks it's the URL of the room's `send/m.room.message`
+    /// endpoint, e.g. `https://matrix.org/_matrix/client/v3/rooms/!id:matrix.org/send/m.room.message`.
+    pub url: String,
+    /// A token that is sent in the `Authorization: Bearer` header, which is
+    /// required by Matrix.
+    #[serde(default)]
+    pub access_token: Option<String>,
+    /// Targets the webhook is interested in.
+    ///
+    /// If empty, all the targets are tracked.
+    #[serde(default)]
+    pub targets: Vec<String>,
+    /// Packages the webhook is interested in.
+    ///
+    /// If empty, all the packages are tracked.
+    #[serde(default)]
+    pub packages: Vec<String>,
+}
+
+/// Formats of notification messages.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookKind {
+    /// A JSON listing the changes is POSTed.
+    #[default]
+    Generic,
+    /// A message is posted to a Slack incoming webhook.
+    Slack,
+    /// A message is posted to a Discord webhook.
+    Discord,
+    /// A message is sent to a Matrix room.
+    Matrix,
+}
+
+fn default_github_api_url() -> String {
+    "https://api.github.com".into()
+}
+
+fn default_github_label() -> String {
+    "rustup-status".into()
+}
+
+/// Configuration of the GitHub issues integration.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct GithubIssues {
+    /// The repository to file the issues in, e.g. `owner/repo`.
+    pub repo: String,
+    /// A token that is allowed to manage the repository's issues.
+    ///
+    /// If omitted, the `GITHUB_TOKEN` environment variable is used.
+    #[serde(default)]
+    pub token: Option<String>,
+    /// Base URL of the API, which differs for GitHub Enterprise.
+    #[serde(default = "default_github_api_url")]
+    pub api_url: String,
+    /// A label the filed issues are marked with, which is how they are found
+    /// again to be closed.
+    #[serde(default = "default_github_label")]
+    pub label: String,
+    /// For how many days a package has to be missing for an issue to be
+    /// opened. A package that hasn't been available at all within the lookup
+    /// window is considered missing for longer than that.
+    #[serde(default)]
+    pub after_days: usize,
+    /// Targets to watch.
+    pub targets: Vec<String>,
+    /// Packages to watch.
+    pub packages: Vec<String>,
+}
+
+fn default_deploy_repository() -> PathBuf {
+    ".".into()
+}
+
+fn default_deploy_branch() -> String {
+    "gh-pages".into()
+}
+
+fn default_deploy_message() -> String {
+    "Update the availability pages".into()
+}
+
+/// Configuration of the deployment of the rendered site.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Deploy {
+    /// The directory to deploy.
+    ///
+    /// If omitted, `site_root` is deployed.
+    #[serde(default)]
+    pub directory: Option<PathBuf>,
+    /// Where the site is deployed to.
+    #[serde(flatten)]
+    pub target: DeployTarget,
+}
+
+/// A place the rendered site is deployed to.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum DeployTarget {
+    /// The site is committed to a git branch, e.g. the one GitHub Pages are
+    /// served from.
+    Git(GitDeploy),
+    /// The site is synced to an S3-compatible bucket.
+    S3(S3Deploy),
+    /// The site is uploaded to a directory of an SSH server over SFTP.
+    Sftp(SftpDeploy),
+}
+
+/// Configuration of the deployment to a git branch.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct GitDeploy {
+    /// A path to the git repository (or anywhere inside of it) to commit to.
+    #[serde(default = "default_deploy_repository")]
+    pub repository: PathBuf,
+    /// The branch to commit to, the deployed directory becomes its root. It is
+    /// created if it doesn't exist yet.
+    #[serde(default = "default_deploy_branch")]
+    pub branch: String,
+    /// The commit message.
+    #[serde(default = "default_deploy_message")]
+    pub message: String,
+    /// The author of the commits.
+    ///
+    /// If omitted, the `user.name` and `user.email` of the git configuration
+    /// are used.
+    #[serde(default)]
+    pub author: Option<DeployAuthor>,
+    /// A remote (a name or a URL) to push the branch to.
+    ///
+    /// If omitted, the branch is only committed to locally.
+    #[serde(default)]
+    pub remote: Option<String>,
+    /// Whether the remote branch is overwritten even if it has diverged.
+    #[serde(default)]
+    pub force: bool,
+    /// A token to push over HTTPS with. Over SSH the keys of the SSH agent are
+    /// used.
+    ///
+    /// If omitted, the `GITHUB_TOKEN` environment variable is used.
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+/// The author of the deployment commits.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct DeployAuthor {
+    /// The name of the author.
+    pub name: String,
+    /// The email address of the author.
+    pub email: String,
+}
+
+fn default_s3_region() -> String {
+    "us-east-1".into()
+}
+
+fn default_s3_concurrency() -> usize {
+    8
+}
+
+/// Configuration of the deployment to an S3-compatible bucket.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct S3Deploy {
+    /// The bucket to sync the site to.
+    pub bucket: String,
+    /// The region of the bucket.
+    #[serde(default = "default_s3_region")]
+    pub region: String,
+    /// The URL of the storage, e.g. of a MinIO server. The bucket is always
+    /// addressed in the path.
+    ///
+    /// If omitted, the AWS endpoint of the `region` is used.
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    /// A prefix of the keys the site is stored under, e.g. `status/`.
+    #[serde(default)]
+    pub prefix: String,
+    /// The access key.
+    ///
+    /// If omitted, the `AWS_ACCESS_KEY_ID` environment variable is used.
+    #[serde(default)]
+    pub access_key_id: Option<String>,
+    /// The secret key.
+    ///
+    /// If omitted, the `AWS_SECRET_ACCESS_KEY` environment variable is used.
+    #[serde(default)]
+    pub secret_access_key: Option<String>,
+    /// The `Cache-Control` headers of the uploaded files. The first rule
+    /// whose glob pattern matches the path of a file (relative to the deployed
+    /// directory) applies.
+    ///
+    /// If no rule matches, the header isn't set.
+    #[serde(default)]
+    pub cache_control: Vec<CacheControl>,
+    /// Whether the objects under the `prefix` that aren't among the deployed
+    /// files are deleted.
+    #[serde(default)]
+    pub delete: bool,
+    /// How many files are uploaded at once.
+    #[serde(default = "default_s3_concurrency")]
+    pub concurrency: usize,
+}
+
+/// A `Cache-Control` header of the files matching a pattern.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CacheControl {
+    /// A glob pattern, e.g. `*.html`.
+    pub pattern: String,
+    /// The header, e.g. `max-age=300`.
+    pub value: String,
+}
+
+fn default_ssh_port() -> u16 {
+    22
+}
+
+/// Configuration of the deployment over SFTP.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SftpDeploy {
+    /// The SSH server, e.g. `example.com`.
+    pub host: String,
+    /// The port of the SSH server.
+    #[serde(default = "default_ssh_port")]
+    pub port: u16,
+    /// The user to log in as.
+    pub user: String,
+    /// The directory on the server to upload the site to. It has to exist.
+    pub path: PathBuf,
+    /// A private key to log in with.
+    ///
+    /// If omitted, the keys of the SSH agent are used.
+    #[serde(default)]
+    pub private_key: Option<PathBuf>,
+    /// The passphrase of the private key.
+    ///
+    /// If omitted, the `SFTP_PASSPHRASE` environment variable is used, if set.
+    #[serde(default)]
+    pub passphrase: Option<String>,
+    /// A `known_hosts` file the key of the server has to be listed in.
+    ///
+    /// If omitted, `~/.ssh/known_hosts` is used.
+    #[serde(default)]
+    pub known_hosts: Option<PathBuf>,
+    /// Whether the files on the server that aren't among the deployed files
+    /// are deleted once everything has been uploaded.
+    #[serde(default)]
+    pub delete: bool,
+}
+
+/// A digest email about the availability changes.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Email {
+    /// The server to send the email through.
+    pub smtp: Smtp,
+    /// The sender, e.g. `Rustup status <status@example.com>`.
+    pub from: String,
+    /// The recipients.
+    pub to: Vec<String>,
+    /// Path to a template of the plain text body. It gets the `channel`, the
+    /// `date` of the latest manifest and the lists of `regressions` and
+    /// `recoveries`, rendered by the engine of the pages.
+    pub text_template: PathBuf,
+    /// Path to a template of the HTML body, which gets the same data.
+    pub html_template: PathBuf,
+    /// Targets the recipients are interested in.
+    ///
+    /// If empty, all the targets are tracked.
+    #[serde(default)]
+    pub targets: Vec<String>,
+    /// Packages the recipients are interested in.
+    ///
+    /// If empty, all the packages are tracked.
+    #[serde(default)]
+    pub packages: Vec<String>,
+}
+
+/// An SMTP server.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Smtp {
+    /// Host name of the server.
+    pub host: String,
+    /// Port of the server.
+    ///
+    /// If omitted, the default port of the `security` is used.
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// How the connection is secured.
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    /// User name to authenticate with.
+    ///
+    /// If omitted, no authentication is performed.
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Password to authenticate with.
+    ///
+    /// If omitted, the `SMTP_PASSWORD` environment variable is used.
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+/// Ways to secure an SMTP connection.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// The connection is encrypted from the start (port 465 by default).
+    #[default]
+    Tls,
+    /// The connection is upgraded with `STARTTLS` (port 587 by default).
+    StartTls,
+    /// The connection isn't encrypted at all (port 25 by default).
+    None,
+}
+
+/// Aliases of the packages and targets.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Aliases {
+    /// Maps package names onto the names to show them under, e.g.
+    /// `rust-analyzer-preview` onto `rust-analyzer`. The packages that end up
+    /// with the same name are merged.
+    #[serde(default)]
+    pub packages: BTreeMap<String, String>,
+    /// Maps target triples onto the names to show them under.
+    #[serde(default)]
+    pub targets: BTreeMap<String, String>,
+}
+
+/// Include and exclude lists of glob patterns, like `*-linux-*`.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Globs {
+    /// Only the names that match any of these patterns are included. If empty,
+    /// every name is.
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// The names that match any of these patterns are excluded, even if they
+    /// are included.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+/// Packages that are tracked on some targets.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PackageFilter {
+    /// Glob patterns of the targets the filter applies to.
+    pub targets: Vec<String>,
+    /// Glob patterns of the packages that are tracked on those targets.
+    #[serde(flatten)]
+    pub packages: Globs,
+}
+
+/// Post-processing of the generated files.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct OutputSettings {
+    /// Minify the HTML pages and strip the whitespace from the JSON files.
+    #[serde(default)]
+    pub minify: bool,
+    /// Write gzip- and brotli-compressed copies of the HTML, JSON, XML, SVG
+    /// and CSV files next to them, e.g. `index.html.gz` and `index.html.br`.
+    #[serde(default)]
+    pub precompress: bool,
+    /// Pack the file tree of every channel (everything `file_tree_output`
+    /// would get) into a gzip-compressed tarball at this path instead of
+    /// writing thousands of small files, e.g. `output/tree.tar.gz`.
+    #[serde(default)]
+    pub tree_archive: Option<PathBuf>,
+    /// Pack everything the run has written into a single archive at this
+    /// path, e.g. `site.tar.gz` (a gzip-compressed tarball) or `site.zip`.
+    /// Every target is rendered then, even if it hasn't changed.
+    #[serde(default)]
+    pub archive: Option<PathBuf>,
+    /// Write a JSON list of every generated file with its purpose, content
+    /// hash and generation time to this path, e.g. `output/site-manifest.json`.
+    #[serde(default)]
+    pub site_manifest: Option<PathBuf>,
+}
+
+impl OutputSettings {
+    /// Checks that the settings don't contradict each other.
+    pub fn check(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.tree_archive.is_none() || self.archive.is_none(),
+            "`tree_archive` can't be combined with `archive`, which has the file tree already"
+        );
+        Ok(())
+    }
+}
+
+/// Manifests cache limits.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CacheLimits {
+    /// Manifests older than this number of days are removed from the cache.
+    #[serde(default)]
+    pub max_age_days: Option<u32>,
+    /// The oldest manifests are removed from the cache until its size (in
+    /// megabytes) fits into this limit.
+    #[serde(default)]
+    pub max_size_mb: Option<u64>,
+    /// How often the limits are applied, in hours. The runs in between leave
+    /// the cache as it is.
+    #[serde(default = "default_evict_every_hours")]
+    pub evict_every_hours: u64,
+}
+
+impl Default for CacheLimits {
+    fn default() -> Self {
+        CacheLimits {
+            max_age_days: None,
+            max_size_mb: None,
+            evict_every_hours: default_evict_every_hours(),
+        }
+    }
+}
+
+fn default_evict_every_hours() -> u64 {
+    24
+}
+
+impl From<&CacheLimits> for EvictionPolicy {
+    fn from(limits: &CacheLimits) -> Self {
+        EvictionPolicy {
+            max_age: limits
+                .max_age_days
+                .map(|days| chrono::Duration::days(days.into())),
+            max_size: limits.max_size_mb.map(|mb| mb * 1024 * 1024),
+        }
+    }
+}
+
+fn default_max_attempts() -> u32 {
+    1
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    1000
+}
+
+fn default_max_backoff_ms() -> u64 {
+    30_000
+}
+
+fn default_connect_timeout_secs() -> u64 {
+    10
+}
+
+fn default_timeout_secs() -> u64 {
+    30
+}
+
+/// Network settings.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Network {
+    /// For how many seconds to wait for a connection to be established.
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+    /// For how many seconds to wait for a whole response, including the
+    /// connection.
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// A proxy to send all the requests through, e.g.
+    /// `http://proxy.example.com:3128`.
+    ///
+    /// If omitted, the `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY` environment
+    /// variables are honored.
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// The `User-Agent` header of the requests.
+    ///
+    /// If omitted, it's `rustup-available-packages/$version`.
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// Paths to additional root certificates (PEM) to trust, e.g. the ones of
+    /// a TLS-intercepting proxy.
+    #[serde(default)]
+    pub root_certificates: Vec<PathBuf>,
+    /// Trust only the `root_certificates`, not the system ones.
+    #[serde(default)]
+    pub only_custom_roots: bool,
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Network {
+            connect_timeout_secs: default_connect_timeout_secs(),
+            timeout_secs: default_timeout_secs(),
+            proxy: None,
+            user_agent: None,
+            root_certificates: Vec::new(),
+            only_custom_roots: false,
+        }
+    }
+}
+
+/// Manifest verification configuration.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Verification {
+    /// Fail on manifests that can't be verified, e.g. because there's no
+    /// `.sha256` file next to them. Otherwise they are only logged.
+    #[serde(default)]
+    pub required: bool,
+    /// A GPG keyring with the Rust release key to check the `.asc` signatures
+    /// with. Requires `gpgv`.
+    ///
+    /// If omitted, only the checksums are verified.
+    #[serde(default)]
+    pub keyring: Option<PathBuf>,
+}
+
+impl From<&Verification> for verify::Verification {
+    fn from(verification: &Verification) -> Self {
+        verify::Verification {
+            required: verification.required,
+            keyring: verification.keyring.clone(),
+        }
+    }
+}
+
+/// Problems with the data that fail a run.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Strictness {
+    /// Fail if more days than this have been skipped in the nightly channel,
+    /// because their manifests are missing or have failed to download. Other
+    /// channels aren't published every day.
+    ///
+    /// If omitted, any number of days might be skipped.
+    #[serde(default)]
+    pub max_skipped_days: Option<usize>,
+    /// Fail if any of the watched packages goes missing on any of the watched
+    /// targets in the latest manifest.
+    #[serde(default)]
+    pub regressions: Option<Regressions>,
+    /// Fail if any warning has been logged, e.g. about a missing manifest or
+    /// a manifest that couldn't be verified.
+    #[serde(default)]
+    pub fail_on_warnings: bool,
+}
+
+/// Regressions that fail a run.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Regressions {
+    /// Targets to watch.
+    ///
+    /// If empty, all the targets are watched.
+    #[serde(default)]
+    pub targets: Vec<String>,
+    /// Packages to watch.
+    ///
+    /// If empty, all the packages are watched.
+    #[serde(default)]
+    pub packages: Vec<String>,
+}
+
+/// Retry policy configuration.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Retry {
+    /// Maximum number of attempts to download a manifest, including the first
+    /// one. Only network errors, 5xx and 429 responses are retried.
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// A delay before the first retry, in milliseconds. Every next delay is
+    /// twice as long.
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    /// Maximum delay between attempts, in milliseconds.
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    /// A fraction (from 0 to 1) of a delay that is randomly added or
+    /// subtracted to it.
+    #[serde(default)]
+    pub jitter: f64,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Retry {
+            max_attempts: default_max_attempts(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+            jitter: 0.,
+        }
+    }
+}
+
+impl From<&Retry> for RetryPolicy {
+    fn from(retry: &Retry) -> Self {
+        RetryPolicy {
+            max_attempts: retry.max_attempts,
+            initial_backoff: Duration::from_millis(retry.initial_backoff_ms),
+            max_backoff: Duration::from_millis(retry.max_backoff_ms),
+            jitter: retry.jitter,
+        }
+    }
+}
+
+/// Where to take the platform tiers from.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PlatformSupport {
+    /// The platform support page to fetch.
+    #[serde(default = "default_platform_support_url")]
+    pub url: String,
+    /// A vendored copy to read instead of fetching the page: either a saved
+    /// page or a `.json` file mapping tiers to targets.
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+}
+
+fn default_platform_support_url() -> String {
+    crate::platform_support::PAGE_URL.into()
 }
 
 /// Html-related configuration
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Html {
-    /// Path to a handlebars template file.
+    /// Path to a template file.
     pub template_path: PathBuf,
     /// A pattern that will be used to render output files. Any instance of a
     /// `{{target}}` will be replaced with a target name.
@@ -91,6 +1148,422 @@ pub struct Html {
     #[serde(default)]
     #[serde(serialize_with = "ordered_map")]
     pub tiers: HashMap<Tier, Vec<String>>,
+    /// Takes the tiers from the official platform support data instead of
+    /// the `tiers` lists.
+    #[serde(default)]
+    pub platform_support: Option<PlatformSupport>,
+    /// Per-package pages, listing availability of a package on all the targets.
+    ///
+    /// If omitted, only per-target pages are rendered.
+    #[serde(default)]
+    pub package_pages: Option<PackagePages>,
+    /// A search index over all the targets and packages.
+    ///
+    /// If omitted, no index is generated.
+    #[serde(default)]
+    pub search: Option<Search>,
+    /// Path to a template of a landing page, which is rendered as
+    /// `index.html` next to the targets' pages.
+    ///
+    /// If omitted, no landing page is rendered.
+    #[serde(default)]
+    pub index_template: Option<PathBuf>,
+    /// Named sets of targets and packages, e.g. the ones a CI depends on,
+    /// summarized on the landing page as `watch_sets`.
+    #[serde(default)]
+    pub watch_sets: Vec<WatchSet>,
+    /// A changelog of the packages that appeared or disappeared on every
+    /// rendered day.
+    ///
+    /// If omitted, no changelog is generated.
+    #[serde(default)]
+    pub changes: Option<Changes>,
+    /// Engine of all the page templates.
+    #[serde(default)]
+    pub engine: Engine,
+    /// Directories of templates shared by the pages, like headers and
+    /// footers. Every `*.hbs` file (`*.tera` for Tera) is registered under its
+    /// path relative to the directory without the extension, so e.g.
+    /// `partials/header.hbs` is included with `{{> partials/header}}`. A
+    /// single directory is accepted as well (also under the `template_dir`
+    /// key).
+    ///
+    /// Templates of a later directory take precedence over the ones with the
+    /// same name in the earlier ones.
+    #[serde(default, alias = "template_dir", deserialize_with = "one_or_many")]
+    #[schemars(with = "OneOrMany<PathBuf>")]
+    pub template_dirs: Vec<PathBuf>,
+    /// Handlebars helpers written in [Rhai](https://rhai.rs), keyed by their
+    /// names. A script gets the helper's arguments as `params` and its named
+    /// arguments as `hash`, and its result is what the helper returns, e.g.
+    /// `{{shout row.package_name}}` with `params[0].to_upper()`.
+    #[serde(default)]
+    pub script_helpers: BTreeMap<String, PathBuf>,
+    /// Static files of the pages, like stylesheets, scripts and images,
+    /// copied into the output under names with a hash of their contents.
+    ///
+    /// If omitted, the `asset` helper isn't available.
+    #[serde(default)]
+    pub assets: Option<Assets>,
+    /// Calendar heatmaps of every package on every target. Their paths
+    /// relative to a target's page are available to its template as
+    /// `additional.heatmaps`, keyed by package.
+    ///
+    /// If omitted, no heatmaps are generated.
+    #[serde(default)]
+    pub heatmaps: Option<Heatmaps>,
+    /// Explanations of the packages missing on the latest day by the
+    /// toolstate history, available to a target's template as
+    /// `additional.explanations`, keyed by package.
+    ///
+    /// If omitted, nothing is explained.
+    #[serde(default)]
+    pub toolstate: Option<Toolstate>,
+    /// Path to a template of an SVG card per target for social link
+    /// previews, which is rendered as a PNG image next to the target's page
+    /// with a `.png` extension. Its URL is available to the page's template
+    /// as `additional.social_card`.
+    ///
+    /// If omitted, no cards are rendered.
+    #[serde(default)]
+    pub social_card_template: Option<PathBuf>,
+    /// The URL the `site_root` (the working directory if there's none) is
+    /// published at, e.g. `https://rust-lang.github.io/rustup-components-history/`.
+    /// Link previews only load absolute URLs, so the cards are linked by it.
+    ///
+    /// Required with a `social_card_template`.
+    #[serde(default)]
+    pub social_card_url: Option<String>,
+    /// Plain-text tables of the targets, written next to their pages with a
+    /// `.txt` extension, e.g. for `curl` in a terminal or screen readers.
+    ///
+    /// If omitted, no plain-text tables are generated.
+    #[serde(default)]
+    pub plain_text: Option<PlainText>,
+    /// Localized target pages.
+    ///
+    /// If omitted, the pages are only rendered in the language of their
+    /// templates and the `t` helper isn't available.
+    #[serde(default)]
+    pub i18n: Option<I18n>,
+    /// Colors and branding of the pages, available to the templates as
+    /// `additional.theme`.
+    #[serde(default)]
+    pub theme: Theme,
+    /// How many target pages are rendered simultaneously.
+    ///
+    /// If omitted, as many as there are CPUs.
+    #[serde(default)]
+    pub render_threads: Option<usize>,
+}
+
+fn default_plain_text_days() -> usize {
+    7
+}
+
+/// Plain-text tables configuration.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PlainText {
+    /// How many of the most recent days are shown, which keeps the lines
+    /// short enough for a terminal.
+    #[serde(default = "default_plain_text_days")]
+    pub days: usize,
+}
+
+/// Localization configuration.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct I18n {
+    /// A directory with a [Fluent](https://projectfluent.org) catalog per
+    /// language, named after it, e.g. `locales/de.ftl`.
+    pub catalog_dir: PathBuf,
+    /// Languages the target pages are rendered in, e.g. `[en, de, ja]`. The
+    /// first one is the default: the pages at the `output_pattern` are
+    /// rendered in it, and the messages that aren't translated into another
+    /// language are taken from it.
+    pub languages: Vec<String>,
+    /// A pattern that will be used to render the localized target pages. Any
+    /// instance of a `{{target}}` will be replaced with a target name, and any
+    /// instance of a `{{lang}}` with a language, e.g.
+    /// `output/{{target}}/{{lang}}.html`.
+    pub output_pattern: String,
+}
+
+/// Colors and branding of the pages.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Theme {
+    /// The name of the site, shown in the pages' headers.
+    #[serde(default)]
+    pub brand_name: Option<String>,
+    /// A URL of the site's logo, e.g. `https://example.com/logo.svg`. It's
+    /// used on every page as it is, so a relative one only works for the pages
+    /// in the site's root.
+    #[serde(default)]
+    pub brand_logo: Option<String>,
+    /// Colors of the light theme. The ones that aren't set default to the
+    /// Bootstrap ones.
+    #[serde(default)]
+    pub light: Palette,
+    /// Colors of the dark theme, if the pages have one. The ones that aren't
+    /// set fall back to the light theme's colors.
+    #[serde(default)]
+    pub dark: Option<Palette>,
+}
+
+/// Colors of a theme, e.g. `#b8daff`, or anything else CSS accepts.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+pub struct Palette {
+    /// The background of the pages.
+    #[serde(default)]
+    pub background: Option<String>,
+    /// The color of the text.
+    #[serde(default)]
+    pub text: Option<String>,
+    /// Marks the days a package was available on.
+    #[serde(default)]
+    pub available: Option<String>,
+    /// Marks the days a package was missing on.
+    #[serde(default)]
+    pub missing: Option<String>,
+    /// Marks the days without a manifest.
+    #[serde(default)]
+    pub no_manifest: Option<String>,
+    /// Colors of the tiers, keyed by the tiers' names.
+    #[serde(default)]
+    pub tiers: BTreeMap<Tier, String>,
+}
+
+/// Static assets configuration.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Assets {
+    /// Directories of the assets. An asset is known by its path relative to
+    /// its directory, e.g. `{{asset "css/style.css"}}` resolves to something
+    /// like `assets/css/style.0123456789.css`. A single directory is accepted
+    /// as well.
+    ///
+    /// Assets of a later directory take precedence over the ones with the
+    /// same path in the earlier ones.
+    #[serde(deserialize_with = "one_or_many")]
+    #[schemars(with = "OneOrMany<PathBuf>")]
+    pub dirs: Vec<PathBuf>,
+    /// The directory the hashed assets are written to.
+    pub output_dir: PathBuf,
+    /// The URL the `output_dir` is served at, e.g. `/assets` or a CDN's one,
+    /// which the hashed paths are appended to.
+    ///
+    /// If omitted, every page links the assets relative to itself. Outside of
+    /// the pages, e.g. in the emails, the paths in the output are used then.
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+fn default_heatmap_days() -> usize {
+    91
+}
+
+/// Heatmaps-related configuration.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Heatmaps {
+    /// A pattern that will be used to render heatmap files. Any instance of a
+    /// `{{target}}` will be replaced with a target name, and any instance of a
+    /// `{{package}}` with a package name.
+    pub output_pattern: String,
+    /// How many of the most recent days are shown. The days are only known
+    /// within `days_in_past` and `additional_lookup_days`.
+    #[serde(default = "default_heatmap_days")]
+    pub days: usize,
+}
+
+fn default_toolstate_history_url() -> String {
+    "https://raw.githubusercontent.com/rust-lang-nursery/rust-toolstate/master/history/{{os}}.tsv"
+        .into()
+}
+
+/// A named set of targets which all need the same packages.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WatchSet {
+    /// A name to show, e.g. `CI toolchains`.
+    pub name: String,
+    pub targets: Vec<String>,
+    /// Packages that have to be available on every target.
+    pub packages: Vec<String>,
+}
+
+/// Toolstate-related configuration.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Toolstate {
+    /// URL of the toolstate history of an OS. Any instance of an `{{os}}`
+    /// will be replaced with either `linux` or `windows`.
+    #[serde(default = "default_toolstate_history_url")]
+    pub history_url: String,
+    /// Base URL of the GitHub API, which is asked for the pull requests
+    /// that broke the tools.
+    #[serde(default = "default_github_api_url")]
+    pub api_url: String,
+    /// A GitHub token, which raises the API's rate limits.
+    ///
+    /// If omitted, the `GITHUB_TOKEN` environment variable is used.
+    #[serde(default)]
+    pub token: Option<String>,
+    /// Links the commits that broke the tools rather than their pull requests,
+    /// without asking the GitHub API. Only the tools' failures on the very
+    /// commits the nightlies are built from are found then.
+    #[serde(default)]
+    pub skip_pull_requests: bool,
+}
+
+impl Default for Toolstate {
+    fn default() -> Self {
+        Toolstate {
+            history_url: default_toolstate_history_url(),
+            api_url: default_github_api_url(),
+            token: None,
+            skip_pull_requests: false,
+        }
+    }
+}
+
+/// A source of the manifests.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Source {
+    /// The manifests are downloaded from `manifest_base_url`.
+    #[default]
+    Http,
+    /// The manifests are read from a directory laid out like
+    /// `https://static.rust-lang.org/dist`, i.e. with a `YYYY-MM-DD`
+    /// subdirectory per day.
+    Local {
+        /// Path to the directory.
+        path: PathBuf,
+    },
+    /// The manifests are made up, for developing templates without any
+    /// network access. The `render` command doesn't keep any state or notify
+    /// anyone with them.
+    Fake(FakeData),
+}
+
+/// Settings of the made-up manifests.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct FakeData {
+    /// How many targets to make up.
+    #[serde(default = "default_fake_targets")]
+    pub targets: usize,
+    /// How many packages to make up.
+    #[serde(default = "default_fake_packages")]
+    pub packages: usize,
+    /// The same seed always makes up the same data.
+    #[serde(default)]
+    pub seed: u64,
+    /// A probability of a package to go missing on a target on any day.
+    #[serde(default = "default_outage_probability")]
+    pub outage_probability: f64,
+    /// The longest outage in days.
+    #[serde(default = "default_max_outage_days")]
+    pub max_outage_days: u64,
+}
+
+impl Default for FakeData {
+    fn default() -> Self {
+        FakeData {
+            targets: default_fake_targets(),
+            packages: default_fake_packages(),
+            seed: 0,
+            outage_probability: default_outage_probability(),
+            max_outage_days: default_max_outage_days(),
+        }
+    }
+}
+
+fn default_fake_targets() -> usize {
+    6
+}
+
+fn default_fake_packages() -> usize {
+    10
+}
+
+fn default_outage_probability() -> f64 {
+    0.05
+}
+
+fn default_max_outage_days() -> u64 {
+    5
+}
+
+/// What happens when too many manifests in a row are missing.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OnMissingDays {
+    /// The run fails.
+    #[default]
+    Fail,
+    /// The days found by then are rendered.
+    Truncate,
+}
+
+/// A format of the log messages.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines.
+    #[default]
+    Text,
+    /// A JSON object per line.
+    Json,
+}
+
+/// A template engine.
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Engine {
+    /// [Handlebars](https://handlebarsjs.com/) templates.
+    #[default]
+    Handlebars,
+    /// [Tera](https://keats.github.io/tera/) templates.
+    Tera,
+}
+
+/// Changelog configuration.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Changes {
+    /// Path to a template file.
+    pub template_path: PathBuf,
+    /// Where to put the rendered page.
+    pub output_path: PathBuf,
+    /// Where to put the same changes in JSON.
+    pub json_path: PathBuf,
+}
+
+/// Search index configuration.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Search {
+    /// Where to put the JSON search index.
+    pub index_path: PathBuf,
+    /// A page listing the latest availability of every package on every
+    /// target, which doesn't need JavaScript to be searched through.
+    ///
+    /// If omitted, only the index is generated.
+    #[serde(default)]
+    pub fallback_page: Option<SearchPage>,
+}
+
+/// A search fallback page configuration.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SearchPage {
+    /// Path to a template file.
+    pub template_path: PathBuf,
+    /// Where to put the rendered page.
+    pub output_path: PathBuf,
+}
+
+/// Per-package pages configuration.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PackagePages {
+    /// Path to a template file.
+    pub template_path: PathBuf,
+    /// A pattern that will be used to render output files. Any instance of a
+    /// `{{package}}` will be replaced with a package name.
+    pub output_pattern: String,
 }
 
 fn ordered_map<S: Serializer, K: Ord + Serialize, V: Serialize>(
@@ -103,10 +1576,173 @@ fn ordered_map<S: Serializer, K: Ord + Serialize, V: Serialize>(
         .serialize(serializer)
 }
 
+/// Formats of configuration files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl ConfigFormat {
+    /// Guesses a format by a file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "yaml" | "yml" => Some(ConfigFormat::Yaml),
+            "json" => Some(ConfigFormat::Json),
+            "toml" => Some(ConfigFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for ConfigFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "yaml" | "yml" => Ok(ConfigFormat::Yaml),
+            "json" => Ok(ConfigFormat::Json),
+            "toml" => Ok(ConfigFormat::Toml),
+            _ => anyhow::bail!("Unknown config format {}, expected yaml, json or toml", s),
+        }
+    }
+}
+
 impl Config {
+    /// Loads a config, the format of which is guessed by the file extension.
+    /// Files with unknown extensions are treated as YAML.
     pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
         let path = path.as_ref();
-        let f = File::open(path)?;
-        Ok(serde_yaml::from_reader(f)?)
+        let mut f = File::open(path)?;
+        let config: Config = match ConfigFormat::from_path(path).unwrap_or(ConfigFormat::Yaml) {
+            // Unlike the errors of the formats themselves, these ones say
+            // which field is wrong, e.g. `retry.max_attempts`.
+            ConfigFormat::Yaml => {
+                serde_path_to_error::deserialize(serde_yaml::Deserializer::from_reader(f))?
+            }
+            ConfigFormat::Json => {
+                serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_reader(f))?
+            }
+            ConfigFormat::Toml => {
+                let mut data = String::new();
+                f.read_to_string(&mut data)?;
+                serde_path_to_error::deserialize(&mut toml::Deserializer::new(&data))?
+            }
+        };
+        config.check_channels()?;
+        Ok(config)
+    }
+
+    /// Checks that the channels are listed once each and that their names are
+    /// safe to use as directory names, as every channel gets its own cache and
+    /// output directories.
+    pub fn check_channels(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(!self.channels.is_empty(), "No channel is configured");
+        let mut seen = HashSet::new();
+        for channel in &self.channels {
+            let safe = channel
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'));
+            anyhow::ensure!(
+                safe && !channel.is_empty() && channel != "." && channel != "..",
+                "Invalid channel name {:?}, expected letters, digits, dots, dashes or underscores",
+                channel
+            );
+            anyhow::ensure!(seen.insert(channel), "Channel {} is listed twice", channel);
+        }
+        Ok(())
+    }
+
+    /// A JSON Schema of the configuration file.
+    pub fn schema() -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(&schemars::schema_for!(
+            Config
+        ))?)
+    }
+
+    /// Serializes the config in a given format.
+    pub fn dump(&self, format: ConfigFormat) -> anyhow::Result<String> {
+        Ok(match format {
+            ConfigFormat::Yaml => serde_yaml::to_string(self)?,
+            ConfigFormat::Json => serde_json::to_string_pretty(self)?,
+            // Going through a `Value` puts plain values before tables, which
+            // TOML requires.
+            ConfigFormat::Toml => toml::to_string(&toml::Value::try_from(self)?)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn channels_are_checked() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        let minimal =
+            "template_path: t.html\noutput_pattern: o.html\nfile_tree_output: out\ntiers: {}\ndays_in_past: 7\n";
+        fs::write(
+            &path,
+            format!("{}channels: [nightly, beta, nightly]\n", minimal),
+        )
+        .unwrap();
+        let err = Config::load(&path).unwrap_err().to_string();
+        assert!(err.contains("nightly is listed twice"), "{}", err);
+
+        fs::write(&path, minimal).unwrap();
+        let mut config = Config::load(&path).unwrap();
+        for channels in [
+            vec![],
+            vec![""],
+            vec![".."],
+            vec!["../nightly"],
+            vec!["nightly/beta"],
+            vec!["nightly", "beta", "nightly"],
+        ] {
+            config.channels = channels.into_iter().map(String::from).collect();
+            assert!(config.check_channels().is_err(), "{:?}", config.channels);
+        }
+        config.channels = vec!["1.75.0".into(), "nightly_2024-01-01".into()];
+        assert!(config.check_channels().is_ok());
+    }
+
+    #[test]
+    fn config_formats_ignore_case() {
+        for &(name, format) in &[
+            ("yaml", ConfigFormat::Yaml),
+            ("YML", ConfigFormat::Yaml),
+            ("Json", ConfigFormat::Json),
+            ("TOML", ConfigFormat::Toml),
+        ] {
+            assert_eq!(format, name.parse::<ConfigFormat>().unwrap());
+            let path = format!("config.{}", name);
+            assert_eq!(Some(format), ConfigFormat::from_path(Path::new(&path)));
+        }
+        assert!("xml".parse::<ConfigFormat>().is_err());
+    }
+
+    #[test]
+    fn load_errors_name_the_field() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let cases = [
+            ("config.yaml", "retry:\n  max_attempts: many\n"),
+            ("config.json", "{\"retry\": {\"max_attempts\": \"many\"}}"),
+            ("config.toml", "[retry]\nmax_attempts = \"many\"\n"),
+        ];
+        for &(name, contents) in &cases {
+            let path = dir.join(name);
+            fs::write(&path, contents).unwrap();
+            let error = Config::load(&path).unwrap_err().to_string();
+            assert!(
+                error.starts_with("retry.max_attempts"),
+                "{}: {}",
+                name,
+                error
+            );
+        }
     }
 }
@@ -0,0 +1,964 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fs::{
+        copy, create_dir_all, read_dir, read_link, remove_dir_all, remove_file, rename, write, File,
+    },
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, Utc};
+use flate2::{write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use tempfile::NamedTempFile;
+
+use crate::diagnostics::{Locate, Location};
+
+/// A destination for the generated files.
+pub trait Output {
+    /// Stores a file at a given path.
+    fn write(&mut self, path: &Path, contents: &[u8]) -> anyhow::Result<()>;
+
+    /// Points a symbolic link at a `target`, which is relative to the link's
+    /// directory, replacing the link if it exists.
+    fn symlink(&mut self, target: &Path, link: &Path) -> anyhow::Result<()>;
+
+    /// Lists the names of the entries of a directory. A missing directory is
+    /// considered empty.
+    fn list_dir(&self, path: &Path) -> anyhow::Result<Vec<String>>;
+
+    /// Removes a directory with all of its contents.
+    fn remove_dir(&mut self, path: &Path) -> anyhow::Result<()>;
+
+    /// Removes a file, if it's there.
+    fn remove_file(&mut self, path: &Path) -> anyhow::Result<()>;
+
+    /// Called once everything has been written successfully.
+    fn finish(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Tells what the files written from now on are, for the outputs that
+    /// keep track of it. Outputs that wrap others pass it on.
+    fn set_purpose(&mut self, _purpose: Purpose) {}
+}
+
+/// What a generated file is, as listed in the site manifest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Purpose {
+    #[default]
+    Other,
+    TargetPage,
+    SocialCard,
+    /// A target's table as plain text.
+    PlainText,
+    PackagePage,
+    IndexPage,
+    ChangesPage,
+    ChangesJson,
+    SearchPage,
+    SearchIndex,
+    /// The list of the packages of the file tree.
+    PackageList,
+    /// A file of the file tree with the last date a package was available.
+    LastAvailable,
+    /// The availability history of a package on a target.
+    PackageJson,
+    /// The availability of a package summed up across the targets.
+    PackageSummary,
+    TreeArchive,
+    JsonDump,
+    Csv,
+    Feed,
+    Badge,
+    Heatmap,
+    Trends,
+    LastGood,
+    Archive,
+    Sitemap,
+    /// A static asset with a hash in its name.
+    Asset,
+}
+
+/// Writes files straight to the file system, creating parent directories as needed.
+#[derive(Default)]
+pub struct FsOutput {
+    /// Directories that are known to exist, so thousands of files in the same
+    /// directory don't check it thousands of times.
+    created: HashSet<PathBuf>,
+}
+
+impl FsOutput {
+    fn create_parent(&mut self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            if !self.created.contains(parent) {
+                create_dir_all(parent).locate(|| {
+                    Location::new(format!("Can't create path {}", parent.display())).path(parent)
+                })?;
+                self.created.insert(parent.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Output for FsOutput {
+    fn write(&mut self, path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+        self.create_parent(path)?;
+        write(path, contents)
+            .locate(|| Location::new(format!("Can't write file {}", path.display())).path(path))
+    }
+
+    fn symlink(&mut self, target: &Path, link: &Path) -> anyhow::Result<()> {
+        self.create_parent(link)?;
+        let tmp = link.with_extension("tmp");
+        if tmp.symlink_metadata().is_ok() {
+            remove_file(&tmp)?;
+        }
+        // Renaming a new link over the old one swaps them atomically.
+        make_symlink(target, &tmp)
+            .and_then(|_| rename(&tmp, link))
+            .locate(|| {
+                Location::new(format!(
+                    "Can't link {} to {}",
+                    link.display(),
+                    target.display()
+                ))
+                .path(link)
+            })
+    }
+
+    fn list_dir(&self, path: &Path) -> anyhow::Result<Vec<String>> {
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        read_dir(path)
+            .with_context(|| format!("Can't read {}", path.display()))?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+            .collect()
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> anyhow::Result<()> {
+        self.created.retain(|dir| !dir.starts_with(path));
+        remove_dir_all(path).with_context(|| format!("Can't remove {}", path.display()))
+    }
+
+    fn remove_file(&mut self, path: &Path) -> anyhow::Result<()> {
+        if path.symlink_metadata().is_err() {
+            return Ok(());
+        }
+        remove_file(path).with_context(|| format!("Can't remove {}", path.display()))
+    }
+}
+
+/// Creates a temporary file next to `path`, to be persisted over it once it's
+/// written, so nobody sees a half-written file.
+pub(crate) fn temp_file_for(path: &Path) -> io::Result<NamedTempFile> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let file = NamedTempFile::new_in(dir)?;
+    // Unlike the temporary files, the outputs are readable by everyone.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.as_file()
+            .set_permissions(std::fs::Permissions::from_mode(0o644))?;
+    }
+    Ok(file)
+}
+
+#[cfg(unix)]
+fn make_symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn make_symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_dir(target, link)
+}
+
+/// Keeps all the files in memory.
+#[derive(Default)]
+pub struct MemoryOutput {
+    files: HashMap<PathBuf, Vec<u8>>,
+}
+
+impl MemoryOutput {
+    /// Paths of all the stored files, in no particular order.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.files.keys().cloned().collect()
+    }
+
+    /// Consumes the output and returns files that are located under the `root`
+    /// directory, keyed by their `/`-separated paths relative to the `root`.
+    pub fn into_tree(self, root: &Path) -> HashMap<String, Vec<u8>> {
+        self.files
+            .into_iter()
+            .filter_map(|(path, contents)| match path.strip_prefix(root) {
+                Ok(relative) => {
+                    let key = relative
+                        .components()
+                        .map(|c| c.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/");
+                    Some((key, contents))
+                }
+                Err(_) => {
+                    log::warn!("{} is outside of {}", path.display(), root.display());
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+impl Output for MemoryOutput {
+    fn write(&mut self, path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+        self.files.insert(path.into(), contents.to_vec());
+        Ok(())
+    }
+
+    /// Links are emulated by copying the files.
+    fn symlink(&mut self, target: &Path, link: &Path) -> anyhow::Result<()> {
+        let target = link.parent().unwrap_or(Path::new("")).join(target);
+        self.remove_dir(link)?;
+        let copies: Vec<_> = self
+            .files
+            .iter()
+            .filter_map(|(path, contents)| {
+                let relative = path.strip_prefix(&target).ok()?;
+                Some((link.join(relative), contents.clone()))
+            })
+            .collect();
+        self.files.extend(copies);
+        Ok(())
+    }
+
+    fn list_dir(&self, path: &Path) -> anyhow::Result<Vec<String>> {
+        let mut names: Vec<_> = self
+            .files
+            .keys()
+            .filter_map(|file| file.strip_prefix(path).ok()?.components().next())
+            .map(|name| name.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        Ok(names)
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> anyhow::Result<()> {
+        self.files.retain(|file, _| !file.starts_with(path));
+        Ok(())
+    }
+
+    fn remove_file(&mut self, path: &Path) -> anyhow::Result<()> {
+        self.files.remove(path);
+        Ok(())
+    }
+}
+
+/// Writes files into a staging copy of a site directory, which replaces the
+/// directory only once everything has been written successfully. If the
+/// rendering fails, the staging copy is thrown away.
+///
+/// The site directory is a symbolic link to the latest copy, e.g.
+/// `.site.1700000000123`, so it is swapped atomically. Only when a plain
+/// directory is replaced for the first time, there's a moment without a site.
+pub struct StagedOutput {
+    root: PathBuf,
+    staging: PathBuf,
+    fs: FsOutput,
+    finished: bool,
+}
+
+/// A hidden directory next to the `root`, e.g. `.site.staging` for `site`.
+fn sibling(root: &Path, suffix: &str) -> anyhow::Result<PathBuf> {
+    let name = root
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid site root {}", root.display()))?;
+    Ok(root.with_file_name(format!(".{}.{}", name.to_string_lossy(), suffix)))
+}
+
+fn copy_dir(from: &Path, to: &Path) -> anyhow::Result<()> {
+    create_dir_all(to).with_context(|| format!("Can't create path {}", to.display()))?;
+    for entry in read_dir(from).with_context(|| format!("Can't read {}", from.display()))? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            make_symlink(&read_link(entry.path())?, &target)
+                .with_context(|| format!("Can't copy {}", entry.path().display()))?;
+        } else if file_type.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            copy(entry.path(), &target)
+                .with_context(|| format!("Can't copy {}", entry.path().display()))?;
+        }
+    }
+    Ok(())
+}
+
+impl StagedOutput {
+    /// Starts staging the `root` directory. Its current contents are copied,
+    /// so the files that aren't written again are kept.
+    pub fn new(root: &Path) -> anyhow::Result<Self> {
+        let staging = sibling(root, "staging")?;
+        if staging.exists() {
+            log::warn!("Removing a leftover {}", staging.display());
+            remove_dir_all(&staging)
+                .with_context(|| format!("Can't remove {}", staging.display()))?;
+        }
+        if root.exists() {
+            copy_dir(root, &staging)?;
+        } else {
+            create_dir_all(&staging)
+                .with_context(|| format!("Can't create path {}", staging.display()))?;
+        }
+        Ok(StagedOutput {
+            root: root.into(),
+            staging,
+            fs: FsOutput::default(),
+            finished: false,
+        })
+    }
+}
+
+impl StagedOutput {
+    /// Where a file of the site is staged.
+    fn staged(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        let relative = path.strip_prefix(&self.root).map_err(|_| {
+            anyhow!(
+                "{} is outside of the site root {}",
+                path.display(),
+                self.root.display()
+            )
+        })?;
+        Ok(self.staging.join(relative))
+    }
+}
+
+impl Output for StagedOutput {
+    fn write(&mut self, path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+        let staged = self.staged(path)?;
+        self.fs.write(&staged, contents)
+    }
+
+    fn symlink(&mut self, target: &Path, link: &Path) -> anyhow::Result<()> {
+        let staged = self.staged(link)?;
+        self.fs.symlink(target, &staged)
+    }
+
+    fn list_dir(&self, path: &Path) -> anyhow::Result<Vec<String>> {
+        self.fs.list_dir(&self.staged(path)?)
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> anyhow::Result<()> {
+        let staged = self.staged(path)?;
+        self.fs.remove_dir(&staged)
+    }
+
+    fn remove_file(&mut self, path: &Path) -> anyhow::Result<()> {
+        let staged = self.staged(path)?;
+        self.fs.remove_file(&staged)
+    }
+
+    /// Swaps the staging copy with the site directory.
+    fn finish(&mut self) -> anyhow::Result<()> {
+        let stamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis());
+        let copy = sibling(&self.root, &stamp.to_string())?;
+        rename(&self.staging, &copy)
+            .with_context(|| format!("Can't move {} in place", self.staging.display()))?;
+        self.finished = true;
+        let previous = match self.root.symlink_metadata() {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                let target = read_link(&self.root)?;
+                Some(self.root.with_file_name(target))
+            }
+            Ok(_) => {
+                // A link can't replace a directory, so it's moved out of the way.
+                let old = sibling(&self.root, "old")?;
+                if old.exists() {
+                    remove_dir_all(&old)
+                        .with_context(|| format!("Can't remove {}", old.display()))?;
+                }
+                rename(&self.root, &old)
+                    .with_context(|| format!("Can't move {} away", self.root.display()))?;
+                Some(old)
+            }
+            Err(_) => None,
+        };
+        let link_target = copy.file_name().expect("The copy has a name");
+        if let Err(e) = self.fs.symlink(Path::new(link_target), &self.root) {
+            if let Some(previous) = previous.as_ref().filter(|_| !self.root.exists()) {
+                // Put the old site back in place.
+                let _ = rename(previous, &self.root);
+            }
+            return Err(e);
+        }
+        if let Some(previous) = previous.filter(|previous| previous.exists()) {
+            remove_dir_all(&previous)
+                .with_context(|| format!("Can't remove {}", previous.display()))?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for StagedOutput {
+    fn drop(&mut self) {
+        if !self.finished {
+            if let Err(e) = remove_dir_all(&self.staging) {
+                log::warn!("Can't remove {}: {}", self.staging.display(), e);
+            }
+        }
+    }
+}
+
+/// Minifies the HTML and JSON files before passing them to another output.
+pub struct MinifyingOutput<'a> {
+    inner: &'a mut dyn Output,
+    html: minify_html::Cfg,
+}
+
+impl<'a> MinifyingOutput<'a> {
+    pub fn new(inner: &'a mut dyn Output) -> Self {
+        MinifyingOutput {
+            inner,
+            html: minify_html::Cfg {
+                minify_css: true,
+                minify_js: true,
+                ..minify_html::Cfg::new()
+            },
+        }
+    }
+}
+
+/// Re-serializes a JSON without any whitespace, keeping the order of the keys.
+fn compact_json(contents: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut compact = Vec::with_capacity(contents.len());
+    serde_transcode::transcode(
+        &mut serde_json::Deserializer::from_slice(contents),
+        &mut serde_json::Serializer::new(&mut compact),
+    )?;
+    Ok(compact)
+}
+
+impl Output for MinifyingOutput<'_> {
+    fn write(&mut self, path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+        let extension = path.extension().and_then(|ext| ext.to_str());
+        let minified = match extension {
+            Some("html") => minify_html::minify(contents, &self.html),
+            Some("json") => compact_json(contents)
+                .with_context(|| format!("Invalid JSON in {}", path.display()))?,
+            _ => return self.inner.write(path, contents),
+        };
+        self.inner.write(path, &minified)
+    }
+
+    fn symlink(&mut self, target: &Path, link: &Path) -> anyhow::Result<()> {
+        self.inner.symlink(target, link)
+    }
+
+    fn list_dir(&self, path: &Path) -> anyhow::Result<Vec<String>> {
+        self.inner.list_dir(path)
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> anyhow::Result<()> {
+        self.inner.remove_dir(path)
+    }
+
+    fn remove_file(&mut self, path: &Path) -> anyhow::Result<()> {
+        self.inner.remove_file(path)
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.inner.finish()
+    }
+
+    fn set_purpose(&mut self, purpose: Purpose) {
+        self.inner.set_purpose(purpose)
+    }
+}
+
+/// Extensions of the files [`CompressingOutput`] compresses.
+const COMPRESSIBLE: &[&str] = &["html", "json", "xml", "svg", "csv"];
+
+/// Writes gzip- and brotli-compressed copies of the text files next to them,
+/// e.g. `index.html.gz` and `index.html.br`, so static hosts can serve them
+/// as they are.
+///
+/// Once everything is written, the compressed copies of the files that are
+/// gone, e.g. the pages of an older output pattern that have been deleted,
+/// are removed from the directories written to.
+pub struct CompressingOutput<'a> {
+    inner: &'a mut dyn Output,
+    dirs: BTreeSet<PathBuf>,
+}
+
+impl<'a> CompressingOutput<'a> {
+    pub fn new(inner: &'a mut dyn Output) -> Self {
+        CompressingOutput {
+            inner,
+            dirs: BTreeSet::new(),
+        }
+    }
+
+    /// Removes the compressed copies whose files aren't there anymore.
+    fn remove_orphans(&mut self) -> anyhow::Result<()> {
+        for dir in &self.dirs {
+            let names: BTreeSet<_> = self.inner.list_dir(dir)?.into_iter().collect();
+            let orphans = names.iter().filter(|name| {
+                [".gz", ".br"].iter().any(|suffix| {
+                    name.strip_suffix(suffix)
+                        .is_some_and(|original| !names.contains(original))
+                })
+            });
+            for name in orphans {
+                log::info!("Removing {}, the file it is a copy of is gone", name);
+                self.inner.remove_file(&dir.join(name))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Appends an extension to a path, e.g. `index.html` becomes `index.html.gz`.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
+
+impl Output for CompressingOutput<'_> {
+    fn write(&mut self, path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+        self.inner.write(path, contents)?;
+        let extension = path.extension().and_then(|ext| ext.to_str());
+        if !extension.is_some_and(|ext| COMPRESSIBLE.contains(&ext)) {
+            return Ok(());
+        }
+        if let Some(dir) = path.parent() {
+            self.dirs.insert(dir.to_path_buf());
+        }
+
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::best());
+        gzip.write_all(contents)?;
+        self.inner
+            .write(&with_suffix(path, ".gz"), &gzip.finish()?)?;
+
+        let mut brotli = Vec::new();
+        {
+            let mut encoder = brotli::CompressorWriter::new(&mut brotli, 4096, 9, 22);
+            encoder.write_all(contents)?;
+        }
+        self.inner.write(&with_suffix(path, ".br"), &brotli)
+    }
+
+    fn symlink(&mut self, target: &Path, link: &Path) -> anyhow::Result<()> {
+        self.inner.symlink(target, link)
+    }
+
+    fn list_dir(&self, path: &Path) -> anyhow::Result<Vec<String>> {
+        self.inner.list_dir(path)
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> anyhow::Result<()> {
+        self.inner.remove_dir(path)
+    }
+
+    /// Removes the compressed copies as well.
+    fn remove_file(&mut self, path: &Path) -> anyhow::Result<()> {
+        self.inner.remove_file(path)?;
+        self.inner.remove_file(&with_suffix(path, ".gz"))?;
+        self.inner.remove_file(&with_suffix(path, ".br"))
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.remove_orphans()?;
+        self.inner.finish()
+    }
+
+    fn set_purpose(&mut self, purpose: Purpose) {
+        self.inner.set_purpose(purpose)
+    }
+}
+
+/// Packs the files written under a root directory into a gzip-compressed
+/// tarball, which is kept in memory. It is compressed while being built, so
+/// only the compressed bytes are kept, and no buffering is needed.
+pub struct TarOutput {
+    root: PathBuf,
+    builder: tar::Builder<GzEncoder<Vec<u8>>>,
+    mtime: u64,
+}
+
+impl TarOutput {
+    /// Starts a tarball whose entries are all modified at `mtime`, so the same
+    /// files always make the same archive.
+    pub fn new(root: &Path, mtime: DateTime<Utc>) -> Self {
+        TarOutput {
+            root: root.into(),
+            builder: tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default())),
+            mtime: mtime.timestamp().max(0) as u64,
+        }
+    }
+
+    /// Finishes the archive.
+    pub fn into_bytes(self) -> anyhow::Result<Vec<u8>> {
+        Ok(self.builder.into_inner()?.finish()?)
+    }
+
+    /// The path of a file in the archive.
+    fn entry_path<'p>(&self, path: &'p Path) -> anyhow::Result<&'p Path> {
+        path.strip_prefix(&self.root).map_err(|_| {
+            anyhow!(
+                "{} is outside of the archived {}",
+                path.display(),
+                self.root.display()
+            )
+        })
+    }
+
+    fn header(&self, entry_type: tar::EntryType, size: u64) -> tar::Header {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_size(size);
+        header.set_mode(0o644);
+        header.set_mtime(self.mtime);
+        header
+    }
+}
+
+impl Output for TarOutput {
+    fn write(&mut self, path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+        let entry_path = self.entry_path(path)?;
+        let mut header = self.header(tar::EntryType::Regular, contents.len() as u64);
+        self.builder
+            .append_data(&mut header, entry_path, contents)
+            .with_context(|| format!("Can't archive {}", path.display()))
+    }
+
+    fn symlink(&mut self, target: &Path, link: &Path) -> anyhow::Result<()> {
+        let entry_path = self.entry_path(link)?;
+        let mut header = self.header(tar::EntryType::Symlink, 0);
+        self.builder
+            .append_link(&mut header, entry_path, target)
+            .with_context(|| format!("Can't archive {}", link.display()))
+    }
+
+    /// An archive starts empty.
+    fn list_dir(&self, _path: &Path) -> anyhow::Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> anyhow::Result<()> {
+        anyhow::bail!("Can't remove {} from an archive", path.display())
+    }
+
+    fn remove_file(&mut self, path: &Path) -> anyhow::Result<()> {
+        anyhow::bail!("Can't remove {} from an archive", path.display())
+    }
+}
+
+/// Passes the files on to another output, and packs all of them into an
+/// archive once everything has been written: a zip file if the archive's path
+/// ends with `.zip`, a gzip-compressed tarball otherwise. The files are
+/// archived relative to their closest common directory.
+///
+/// Only the paths are kept in memory: the files are read back from the disk
+/// once they are in place, one at a time.
+pub struct ArchivingOutput<'a> {
+    inner: &'a mut dyn Output,
+    path: PathBuf,
+    files: BTreeSet<PathBuf>,
+}
+
+impl<'a> ArchivingOutput<'a> {
+    pub fn new(inner: &'a mut dyn Output, path: &Path) -> Self {
+        ArchivingOutput {
+            inner,
+            path: path.into(),
+            files: BTreeSet::new(),
+        }
+    }
+
+    /// The closest directory all the files are in.
+    fn common_dir(&self) -> PathBuf {
+        let mut paths = self.files.iter();
+        let mut dir = paths
+            .next()
+            .and_then(|path| path.parent())
+            .map(PathBuf::from)
+            .unwrap_or_default();
+        for path in paths {
+            while !path.starts_with(&dir) {
+                dir.pop();
+            }
+        }
+        dir
+    }
+
+    /// The `/`-separated name of a file in the archive.
+    fn entry_name(root: &Path, path: &Path) -> String {
+        path.strip_prefix(root)
+            .expect("Files are in their common directory")
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    fn tarball(&self, root: &Path, file: File) -> anyhow::Result<()> {
+        let encoder = GzEncoder::new(BufWriter::new(file), Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        // Links are archived as links.
+        builder.follow_symlinks(false);
+        for path in &self.files {
+            builder
+                .append_path_with_name(path, Self::entry_name(root, path))
+                .with_context(|| format!("Can't archive {}", path.display()))?;
+        }
+        builder.into_inner()?.finish()?.flush()?;
+        Ok(())
+    }
+
+    fn zip(&self, root: &Path, file: File) -> anyhow::Result<()> {
+        let mut zip = zip::ZipWriter::new(BufWriter::new(file));
+        let options = zip::write::FileOptions::default();
+        for path in &self.files {
+            let name = Self::entry_name(root, path);
+            let metadata = path
+                .symlink_metadata()
+                .with_context(|| format!("Can't archive {}", path.display()))?;
+            if metadata.file_type().is_symlink() {
+                zip.add_symlink(name, read_link(path)?.to_string_lossy(), options)?;
+            } else {
+                zip.start_file(name, options)?;
+                io::copy(&mut File::open(path)?, &mut zip)
+                    .with_context(|| format!("Can't archive {}", path.display()))?;
+            }
+        }
+        zip.finish()?.flush()?;
+        Ok(())
+    }
+}
+
+impl Output for ArchivingOutput<'_> {
+    fn write(&mut self, path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+        self.inner.write(path, contents)?;
+        self.files.insert(path.into());
+        Ok(())
+    }
+
+    fn symlink(&mut self, target: &Path, link: &Path) -> anyhow::Result<()> {
+        self.inner.symlink(target, link)?;
+        self.files.insert(link.into());
+        Ok(())
+    }
+
+    fn list_dir(&self, path: &Path) -> anyhow::Result<Vec<String>> {
+        self.inner.list_dir(path)
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> anyhow::Result<()> {
+        self.inner.remove_dir(path)?;
+        self.files.retain(|file| !file.starts_with(path));
+        Ok(())
+    }
+
+    fn remove_file(&mut self, path: &Path) -> anyhow::Result<()> {
+        self.inner.remove_file(path)?;
+        self.files.remove(path);
+        Ok(())
+    }
+
+    fn set_purpose(&mut self, purpose: Purpose) {
+        self.inner.set_purpose(purpose)
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.inner.finish()?;
+        let root = self.common_dir();
+        log::info!("Writing the site archive to {:?}", self.path);
+        // Nobody must see a half-written archive.
+        let tmp = temp_file_for(&self.path).with_context(|| {
+            format!("Can't create a temporary file for {}", self.path.display())
+        })?;
+        let file = tmp.reopen()?;
+        if self.path.extension().is_some_and(|ext| ext == "zip") {
+            self.zip(&root, file)?
+        } else {
+            self.tarball(&root, file)?
+        };
+        tmp.persist(&self.path)
+            .map_err(|e| e.error)
+            .with_context(|| format!("Can't write the site archive {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+/// Guesses the `Content-Type` of a generated file by its extension.
+pub fn content_type(path: &str) -> &'static str {
+    match path.rsplit_once('.').map(|(_, extension)| extension) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("json") => "application/json",
+        Some("xml") => "application/xml",
+        Some("csv") => "text/csv; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("gz") => "application/gzip",
+        Some("br" | "zip") => "application/octet-stream",
+        // Files of the tree without an extension contain plain dates.
+        _ => "text/plain; charset=utf-8",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compressed_copies_of_removed_files_are_removed() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        write(dir.join("old.html.gz"), "stale").unwrap();
+        write(dir.join("old.html.br"), "stale").unwrap();
+        write(dir.join("kept.html"), "kept").unwrap();
+        write(dir.join("kept.html.gz"), "kept").unwrap();
+
+        let mut fs = FsOutput::default();
+        let mut out = CompressingOutput::new(&mut fs);
+        out.write(&dir.join("new.html"), b"new").unwrap();
+        out.finish().unwrap();
+
+        let mut names: Vec<_> = read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort_unstable();
+        assert_eq!(
+            names,
+            [
+                "kept.html",
+                "kept.html.gz",
+                "new.html",
+                "new.html.br",
+                "new.html.gz"
+            ]
+        );
+    }
+
+    #[test]
+    fn staged_output_swaps_a_link() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let root = dir.join("site");
+        // A plain directory of an earlier version is replaced.
+        create_dir_all(&root).unwrap();
+        write(root.join("kept.html"), "kept").unwrap();
+
+        let mut out = StagedOutput::new(&root).unwrap();
+        out.write(&root.join("a/page.html"), b"first").unwrap();
+        out.finish().unwrap();
+        assert!(root.symlink_metadata().unwrap().file_type().is_symlink());
+        assert_eq!(std::fs::read(root.join("a/page.html")).unwrap(), b"first");
+        assert_eq!(std::fs::read(root.join("kept.html")).unwrap(), b"kept");
+        let first = read_link(&root).unwrap();
+
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let mut out = StagedOutput::new(&root).unwrap();
+        out.write(&root.join("a/page.html"), b"second").unwrap();
+        // The site stays in place until the staging copy is finished.
+        assert_eq!(std::fs::read(root.join("a/page.html")).unwrap(), b"first");
+        out.finish().unwrap();
+        assert_eq!(std::fs::read(root.join("a/page.html")).unwrap(), b"second");
+        assert_ne!(read_link(&root).unwrap(), first);
+
+        // Only the current copy is left next to the link.
+        let mut names: Vec<_> = read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        let current = read_link(&root).unwrap().to_string_lossy().into_owned();
+        assert_eq!(names, [current, "site".to_string()]);
+
+        // A failed rendering leaves the site as it was.
+        let mut out = StagedOutput::new(&root).unwrap();
+        out.write(&root.join("a/page.html"), b"third").unwrap();
+        drop(out);
+        assert_eq!(std::fs::read(root.join("a/page.html")).unwrap(), b"second");
+        assert!(!dir.join(".site.staging").exists());
+    }
+
+    #[test]
+    fn tarballs_are_reproducible() {
+        let mtime = "2023-03-01T00:00:00Z".parse().unwrap();
+        let tarball = || {
+            let mut tar = TarOutput::new(Path::new("tree"), mtime);
+            tar.write(Path::new("tree/x/rustc"), b"2023-03-01\n")
+                .unwrap();
+            tar.into_bytes().unwrap()
+        };
+        let first = tarball();
+        assert_eq!(first, tarball());
+        let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(&first[..]));
+        let entry = tar.entries().unwrap().next().unwrap().unwrap();
+        assert_eq!(entry.header().mtime().unwrap(), 1_677_628_800);
+        assert_eq!(entry.path().unwrap(), Path::new("x/rustc"));
+    }
+
+    #[test]
+    fn archives_the_written_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let site = dir.join("site");
+        for name in ["site.tar.gz", "site.zip"] {
+            let path = dir.join(name);
+            let mut fs = FsOutput::default();
+            let mut out = ArchivingOutput::new(&mut fs, &path);
+            out.write(&site.join("index.html"), b"index").unwrap();
+            out.write(&site.join("gone/a.json"), b"{}").unwrap();
+            out.remove_dir(&site.join("gone")).unwrap();
+            out.write(&site.join("archive/2023-03-01/a.json"), b"[]")
+                .unwrap();
+            out.symlink(Path::new("2023-03-01"), &site.join("archive/latest"))
+                .unwrap();
+            out.finish().unwrap();
+
+            let mut entries = Vec::new();
+            if name.ends_with(".zip") {
+                let mut zip = zip::ZipArchive::new(File::open(&path).unwrap()).unwrap();
+                for idx in 0..zip.len() {
+                    entries.push(zip.by_index(idx).unwrap().name().to_string());
+                }
+            } else {
+                let gz = flate2::read::GzDecoder::new(File::open(&path).unwrap());
+                let mut tar = tar::Archive::new(gz);
+                for entry in tar.entries().unwrap() {
+                    let entry = entry.unwrap();
+                    let path = entry.path().unwrap().to_string_lossy().into_owned();
+                    if entry.header().entry_type().is_symlink() {
+                        let target = entry.link_name().unwrap().unwrap();
+                        assert_eq!(target, Path::new("2023-03-01"));
+                    }
+                    entries.push(path);
+                }
+            }
+            entries.sort();
+            assert_eq!(
+                entries,
+                ["archive/2023-03-01/a.json", "archive/latest", "index.html"],
+                "{}",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,221 @@
+//! Checks of the paths made by the output patterns, so a mistake in a pattern
+//! doesn't silently make all the targets overwrite one file or write files
+//! outside of the site.
+
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+};
+
+use handlebars::Handlebars;
+
+use crate::{ChannelInfo, PathRenderData};
+
+/// Returns the directory a pattern's paths of a channel are supposed to be in,
+/// which is the one of the pattern's part before the first expression.
+pub fn root(pattern: &str, channel: ChannelInfo) -> PathBuf {
+    let prefix = pattern.split("{{").next().unwrap_or_default();
+    let root = if prefix.ends_with('/') {
+        prefix.into()
+    } else {
+        Path::new(prefix)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default()
+    };
+    // The channel's directory goes right before the file name, so it is a part
+    // of the root only if no expression comes before the file name.
+    if Path::new(pattern).parent() == Some(root.as_path()) {
+        channel.dir(&root)
+    } else {
+        root
+    }
+}
+
+/// Resolves the `.` and `..` components of a path without looking at the
+/// filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                // `/..` is `/`.
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                _ => normalized.push(component),
+            },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Checks whether a pattern makes a different path for every target.
+pub fn mentions_target(handlebars: &Handlebars, pattern: &str) -> bool {
+    let render = |target| handlebars.render_template(pattern, &PathRenderData::target(target));
+    match (render("first-target"), render("second-target")) {
+        (Ok(first), Ok(second)) => first != second,
+        // Invalid patterns are reported elsewhere.
+        _ => true,
+    }
+}
+
+/// Checks that a path stays inside a `root` directory.
+pub fn check_inside(root: &Path, path: &Path) -> anyhow::Result<()> {
+    let root = normalize(root);
+    let inside = normalize(path).strip_prefix(&root).is_ok_and(|rest| {
+        rest.components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    });
+    anyhow::ensure!(
+        inside,
+        "{} is outside of {}",
+        path.display(),
+        root.display()
+    );
+    Ok(())
+}
+
+/// Checks that a path stays inside the `site_root`, or the working directory
+/// if there's none. Absolute paths are fine without a site root.
+pub fn check_site_root(site_root: Option<&Path>, path: &Path) -> anyhow::Result<()> {
+    match site_root {
+        Some(site_root) => check_inside(site_root, path),
+        None if path.is_absolute() => Ok(()),
+        None => check_inside(Path::new(""), path)
+            .map_err(|_| anyhow::anyhow!("{} is outside of the working directory", path.display())),
+    }
+}
+
+/// Remembers the paths of the pages to find the ones written more than once.
+#[derive(Default)]
+pub struct Collisions {
+    pages: HashMap<PathBuf, String>,
+}
+
+impl Collisions {
+    /// Records a page, failing if another page has the same path.
+    pub fn add(&mut self, page: String, path: &Path) -> anyhow::Result<()> {
+        match self.pages.get(&normalize(path)) {
+            Some(other) => anyhow::bail!(
+                "{} and {} would both be written to {}",
+                other,
+                page,
+                path.display()
+            ),
+            None => {
+                self.pages.insert(normalize(path), page);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channels() -> Vec<String> {
+        vec!["nightly".into(), "beta".into()]
+    }
+
+    #[test]
+    fn normalize_resolves_dots() {
+        assert_eq!(normalize(Path::new("a/./b/../c")), Path::new("a/c"));
+        assert_eq!(normalize(Path::new("a/../../b")), Path::new("../b"));
+        assert_eq!(normalize(Path::new("/../a")), Path::new("/a"));
+        assert_eq!(normalize(Path::new("./")), Path::new(""));
+    }
+
+    #[test]
+    fn root_of_a_pattern() {
+        let all = channels();
+        let nightly = ChannelInfo::new("nightly", &all);
+        let beta = ChannelInfo::new("beta", &all);
+        assert_eq!(root("output/{{target}}.html", nightly), Path::new("output"));
+        assert_eq!(
+            root("output/{{target}}.html", beta),
+            Path::new("output/beta")
+        );
+        assert_eq!(root("out/t-{{target}}.html", beta), Path::new("out/beta"));
+        assert_eq!(root("{{target}}.html", beta), Path::new("beta"));
+        // The channel's directory is next to the file name, below the target.
+        assert_eq!(root("output/{{target}}/en.html", beta), Path::new("output"));
+        assert_eq!(
+            beta.relocate(Path::new("output/x86_64-unknown-linux-gnu/en.html")),
+            Path::new("output/x86_64-unknown-linux-gnu/beta/en.html")
+        );
+    }
+
+    #[test]
+    fn pages_of_every_channel_are_inside_the_root() {
+        let all = channels();
+        let handlebars = Handlebars::new();
+        for pattern in &[
+            "output/{{target}}.html",
+            "output/{{target}}/en.html",
+            "site/{{target}}/index.html",
+        ] {
+            for name in &all {
+                let channel = ChannelInfo::new(name, &all);
+                let path = handlebars
+                    .render_template(pattern, &PathRenderData::target("wasm32-wasi"))
+                    .unwrap();
+                let path = channel.relocate(Path::new(&path));
+                check_inside(&root(pattern, channel), &path).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn check_inside_rejects_escapes() {
+        check_inside(Path::new("output/"), Path::new("output/a/b.html")).unwrap();
+        check_inside(Path::new("output"), Path::new("output/./b.html")).unwrap();
+        assert!(check_inside(Path::new("output"), Path::new("output/../b.html")).is_err());
+        assert!(check_inside(Path::new("output"), Path::new("outputs/b.html")).is_err());
+        assert!(check_inside(Path::new("output"), Path::new("output")).is_ok());
+    }
+
+    #[test]
+    fn check_site_root_rejects_escapes() {
+        // The pattern's own root doesn't catch this one.
+        let all = channels();
+        let pattern = "output/../../etc/{{target}}.html";
+        let path = Path::new("output/../../etc/x.html");
+        let nightly = ChannelInfo::new("nightly", &all);
+        check_inside(&root(pattern, nightly), path).unwrap();
+        assert!(check_site_root(None, path).is_err());
+        assert!(check_site_root(Some(Path::new("output")), path).is_err());
+        check_site_root(None, Path::new("output/x.html")).unwrap();
+        check_site_root(None, Path::new("/var/www/x.html")).unwrap();
+        assert!(check_site_root(Some(Path::new("site")), Path::new("/var/www/x.html")).is_err());
+    }
+
+    #[test]
+    fn mentions_target_of_a_pattern() {
+        let handlebars = Handlebars::new();
+        assert!(mentions_target(&handlebars, "output/{{target}}.html"));
+        assert!(!mentions_target(&handlebars, "output/index.html"));
+    }
+
+    #[test]
+    fn collisions_are_found() {
+        let mut collisions = Collisions::default();
+        collisions
+            .add("a".into(), Path::new("output/a.html"))
+            .unwrap();
+        collisions
+            .add("b".into(), Path::new("output/b.html"))
+            .unwrap();
+        let error = collisions
+            .add("c".into(), Path::new("output/./x/../a.html"))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "a and c would both be written to output/./x/../a.html"
+        );
+    }
+}
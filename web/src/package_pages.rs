@@ -0,0 +1,133 @@
+use std::{collections::BTreeMap, iter, path::Path};
+
+use anyhow::Context;
+use chrono::NaiveDate;
+use handlebars::Handlebars;
+use rustup_available_packages::{availability::AvailabilityStats, manifest::Artifact};
+use serde::Serialize;
+
+use crate::{
+    generators::GeneratorInput,
+    html::Pages,
+    opts,
+    output::{Output, Purpose},
+    progress, target_pages, AdditionalData, PathRenderData,
+};
+
+pub const TEMPLATE_NAME: &str = "package_info";
+
+/// Availability of a package on a single target.
+#[derive(Serialize)]
+struct TargetRow<'a> {
+    target: &'a str,
+    availability_list: Vec<bool>,
+    version_list: Vec<Option<String>>,
+    artifact_list: Vec<Option<Artifact>>,
+    last_available: Option<NaiveDate>,
+    stats: AvailabilityStats,
+}
+
+/// A ready-to-render table of a package's statuses per target.
+#[derive(Serialize)]
+struct PackageTable<'a> {
+    current_package: &'a str,
+    /// All the packages, sorted by name.
+    packages: &'a [&'a str],
+    title: Vec<String>,
+    dates: &'a [NaiveDate],
+    /// Names the package has been renamed to by the manifests.
+    renamed_to: Vec<&'a str>,
+    /// Targets the package has ever been available on, sorted by name.
+    targets_availability: Vec<TargetRow<'a>>,
+    /// Links to the pages of the targets relative to the page, keyed by target.
+    target_pages: BTreeMap<&'a str, String>,
+    additional: &'a AdditionalData<'a>,
+}
+
+/// Renders a page per package listing its availability on all the targets.
+///
+/// The page template is expected to be registered under the [`TEMPLATE_NAME`].
+pub fn generate_package_html(
+    pages: &Pages,
+    config: &opts::PackagePages,
+    out: &mut dyn Output,
+) -> anyhow::Result<()> {
+    let GeneratorInput {
+        data,
+        dates,
+        channel,
+        ..
+    } = *pages.input;
+    let mut packages: Vec<_> = data.get_available_packages().into_iter().collect();
+    packages.sort_unstable();
+    let mut targets: Vec<_> = data.get_available_targets().into_iter().collect();
+    targets.sort_unstable();
+    let title: Vec<_> = iter::once(String::new())
+        .chain(dates.iter().map(|date| date.format("%Y-%m-%d").to_string()))
+        .collect();
+
+    let rendering = progress::bar(
+        packages.len(),
+        format!("Rendering {} packages", channel.name),
+    );
+    for package in &packages {
+        rendering.inc(1);
+        log::info!(package = *package; "Processing package {}", package);
+        let output_path = Handlebars::new()
+            .render_template(
+                &config.output_pattern,
+                &PathRenderData {
+                    package: Some(package),
+                    ..PathRenderData::target("")
+                },
+            )
+            .with_context(|| {
+                format!("Invalid package output pattern: {}", config.output_pattern)
+            })?;
+        let output_path = channel.relocate(Path::new(&output_path));
+
+        let table = PackageTable {
+            current_package: package,
+            packages: &packages,
+            title: title.clone(),
+            dates,
+            renamed_to: data.rename_chain(package),
+            targets_availability: targets
+                .iter()
+                .filter_map(|target| {
+                    let row = data.get_availability_row(target, package, dates)?;
+                    Some(TargetRow {
+                        target,
+                        availability_list: row.availability_list,
+                        version_list: row.version_list,
+                        artifact_list: row.artifact_list,
+                        last_available: row.last_available,
+                        stats: row.stats,
+                    })
+                })
+                .collect(),
+            target_pages: target_pages(
+                &pages.config.output_pattern,
+                targets.iter().copied(),
+                channel,
+                &output_path,
+            )?,
+            additional: pages.additional,
+        };
+
+        let rendered = pages
+            .templates
+            .render_page(TEMPLATE_NAME, &table, &output_path)
+            .with_context(|| {
+                format!(
+                    "Can't render [{:?}] for [{}]",
+                    config.template_path, package
+                )
+            })?;
+        log::info!("Writing package {} to {:?}", package, output_path);
+        out.set_purpose(Purpose::PackagePage);
+        out.write(&output_path, rendered.as_bytes())?;
+    }
+    rendering.finish_and_clear();
+    Ok(())
+}
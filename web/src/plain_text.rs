@@ -0,0 +1,165 @@
+//! Fixed-width plain-text tables of the targets, for terminals, screen readers
+//! and e-mails.
+
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use rustup_available_packages::table::Table;
+
+/// Returns the path of a target's table, which is put next to its page.
+pub fn output_path(page: &Path) -> PathBuf {
+    page.with_extension("txt")
+}
+
+/// Writes a row of left-aligned cells, each padded to its column's width.
+fn write_row<'a>(
+    text: &mut String,
+    widths: &[usize],
+    cells: impl IntoIterator<Item = &'a str>,
+) -> std::fmt::Result {
+    let line = cells
+        .into_iter()
+        .zip(widths)
+        .map(|(cell, &width)| format!("{:width$}", cell, width = width))
+        .collect::<Vec<_>>()
+        .join("  ");
+    writeln!(text, "{}", line.trim_end())
+}
+
+/// Renders the `days` latest columns of a target's table.
+pub fn render<A>(table: &Table<'_, A>, channel: &str, days: usize) -> anyhow::Result<String> {
+    let days = days.min(table.dates.len());
+    let missing = |idx: usize| table.manifest_missing.get(idx).copied().unwrap_or(false);
+    let status = |idx: usize, available: Option<bool>, yes: &'static str, no: &'static str| {
+        match available {
+            _ if missing(idx) => "no manifest",
+            Some(true) => yes,
+            Some(false) => no,
+            None => "N/A",
+        }
+    };
+    // Unlike the target-independent packages, the target's own ones are
+    // unknown on the days the target isn't listed.
+    let unlisted = |idx: usize| table.target_missing.get(idx).copied().unwrap_or(false);
+    let own_status = |idx: usize, available: Option<bool>, yes: &'static str, no: &'static str| {
+        if unlisted(idx) {
+            "not listed"
+        } else {
+            status(idx, available, yes, no)
+        }
+    };
+
+    let dates: Vec<_> = table.dates[..days]
+        .iter()
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .collect();
+    let header = std::iter::once("Package".to_string())
+        .chain(dates)
+        .chain(std::iter::once("Last available".to_string()));
+    let mut rows: Vec<Vec<String>> = vec![header.collect()];
+    for profile in &table.profiles {
+        let statuses = profile
+            .availability_list
+            .iter()
+            .take(days)
+            .enumerate()
+            .map(|(idx, &available)| own_status(idx, available, "complete", "incomplete").into());
+        let row = std::iter::once(format!("{} profile", profile.name))
+            .chain(statuses)
+            .chain(std::iter::once(String::new()));
+        rows.push(row.collect());
+    }
+    // The target-independent packages follow the target's own ones.
+    let packages = table
+        .packages_availability
+        .iter()
+        .map(|package| (package, package.package_name.to_string(), true))
+        .chain(table.target_independent.iter().map(|package| {
+            let name = format!("{} (any target)", package.package_name);
+            (package, name, false)
+        }));
+    for (package, name, own) in packages {
+        let statuses =
+            package
+                .availability_list
+                .iter()
+                .take(days)
+                .enumerate()
+                .map(|(idx, &available)| {
+                    if own {
+                        own_status(idx, Some(available), "present", "missing").into()
+                    } else {
+                        status(idx, Some(available), "present", "missing").into()
+                    }
+                });
+        let last_available = package
+            .last_available
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|| "N/A".into());
+        let row = std::iter::once(name)
+            .chain(statuses)
+            .chain(std::iter::once(last_available));
+        rows.push(row.collect());
+    }
+
+    let columns = days + 2;
+    let widths: Vec<_> = (0..columns)
+        .map(|column| {
+            rows.iter()
+                .filter_map(|row| row.get(column))
+                .map(String::len)
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mut text = String::new();
+    writeln!(
+        text,
+        "Rustup packages availability on {} ({})",
+        table.current_target, channel
+    )?;
+    writeln!(text)?;
+    for row in &rows {
+        write_row(&mut text, &widths, row.iter().map(String::as_str))?;
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::manifest;
+    use chrono::NaiveDate;
+    use rustup_available_packages::AvailabilityData;
+
+    #[test]
+    fn renders_the_latest_days() {
+        let date = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        let target = "x86_64-unknown-linux-gnu";
+        let mut data = AvailabilityData::default();
+        for &(day, rls) in &[(1, true), (2, false), (4, true)] {
+            data.add_manifest(manifest(
+                date(day),
+                &[("cargo", target, true), ("rls", target, rls)],
+            ));
+        }
+        data.add_missing_manifest(date(3));
+        let table = Table::builder(&data, target)
+            .dates(vec![date(4), date(3), date(2), date(1)])
+            .build();
+
+        let text = render(&table, "nightly", 3).unwrap();
+        assert_eq!(
+            text,
+            concat!(
+                "Rustup packages availability on x86_64-unknown-linux-gnu (nightly)\n",
+                "\n",
+                "Package  2024-01-04  2024-01-03   2024-01-02  Last available\n",
+                "cargo    present     no manifest  present     2024-01-04\n",
+                "rls      present     no manifest  missing     2024-01-04\n",
+            )
+        );
+    }
+}
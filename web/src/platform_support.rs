@@ -0,0 +1,117 @@
+//! Platform tiers taken from the official platform support page.
+
+use std::{collections::HashMap, fs};
+
+use anyhow::Context;
+use strum::IntoEnumIterator;
+use tl::{ParserOptions, VDom};
+
+use crate::{
+    http::Http,
+    opts::{Config, PlatformSupport, Tier},
+};
+
+/// The official platform support page.
+pub const PAGE_URL: &str = "https://doc.rust-lang.org/nightly/rustc/platform-support.html";
+
+/// Extracts the targets of every tier from the tables of a platform support
+/// page.
+pub fn parse_page(page: &str) -> anyhow::Result<HashMap<Tier, Vec<String>>> {
+    let html = tl::parse(page, ParserOptions::default())?;
+
+    let mut tiers = HashMap::new();
+    for tier in Tier::iter().filter(|t| *t != Tier::UnknownTier) {
+        tiers.insert(tier, collect_targets_for_tier(&html, tier)?);
+    }
+    Ok(tiers)
+}
+
+fn collect_targets_for_tier(html: &VDom, tier: Tier) -> anyhow::Result<Vec<String>> {
+    let mut targets = Vec::new();
+
+    for table_row in html
+        .query_selector("tbody")
+        .unwrap()
+        .nth(match tier {
+            Tier::Tier1 => 0,
+            Tier::Tier2 => 1,
+            Tier::Tier25 => 2,
+            Tier::Tier3 => 3,
+            Tier::UnknownTier => unreachable!(),
+        })
+        .context("Unexpected tier table layout")?
+        .get(html.parser())
+        .unwrap()
+        .children()
+        .context("tbody is not a tag")?
+        .top()
+        .iter()
+    {
+        if let Some(table_row) = table_row.get(html.parser()).unwrap().as_tag() {
+            if table_row.name() != "tr" {
+                continue;
+            }
+
+            targets.push(
+                table_row
+                    .query_selector(html.parser(), "td")
+                    .unwrap()
+                    .next()
+                    .context("Table row does not have any columns.")?
+                    .get(html.parser())
+                    .unwrap()
+                    .as_tag()
+                    .context("td is not a tag")?
+                    .query_selector(html.parser(), "code")
+                    .unwrap()
+                    .next()
+                    .context("Table row does not have a code element in its first column")?
+                    .get(html.parser())
+                    .unwrap()
+                    .inner_text(html.parser())
+                    .into_owned(),
+            );
+        }
+    }
+
+    Ok(targets)
+}
+
+/// Loads the tiers from the page, or from its vendored copy: either the page
+/// itself or a JSON mapping tiers to targets, told apart by the extension.
+pub fn load(
+    config: &Config,
+    platform_support: &PlatformSupport,
+) -> anyhow::Result<HashMap<Tier, Vec<String>>> {
+    match &platform_support.path {
+        Some(path) => {
+            let contents = fs::read_to_string(path)
+                .with_context(|| format!("Can't read {}", path.display()))?;
+            if path.extension().is_some_and(|ext| ext == "json") {
+                serde_json::from_str(&contents)
+                    .with_context(|| format!("Can't parse {}", path.display()))
+            } else {
+                parse_page(&contents).with_context(|| format!("Can't parse {}", path.display()))
+            }
+        }
+        None => {
+            let url = &platform_support.url;
+            log::info!("Fetching the platform tiers from {}", url);
+            let http = Http::new(&config.network, &config.retry)?;
+            let page = http
+                .send(http.client().get(url))
+                .and_then(|response| Ok(response.error_for_status()?.text()?))
+                .with_context(|| format!("Can't fetch {}", url))?;
+            parse_page(&page).with_context(|| format!("Can't parse {}", url))
+        }
+    }
+}
+
+/// Replaces the configured tiers lists with the platform support data, if
+/// the configuration asks for it.
+pub fn apply(config: &mut Config) -> anyhow::Result<()> {
+    if let Some(platform_support) = &config.html.platform_support {
+        config.html.tiers = load(config, platform_support)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use std::{borrow::Cow, sync::OnceLock};
+
+use chrono::NaiveDate;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use rustup_available_packages::{
+    cache::{Cache, Validators},
+    manifest::Manifest,
+};
+
+static BARS: OnceLock<MultiProgress> = OnceLock::new();
+
+/// Enables the progress bars of the rendering phases, which are hidden
+/// otherwise. Whatever is printed to stderr has to go through
+/// [`MultiProgress::suspend`] of the returned bars.
+pub fn enable() -> &'static MultiProgress {
+    BARS.get_or_init(MultiProgress::new)
+}
+
+/// Starts a bar of a phase that takes `len` steps.
+pub(crate) fn bar(len: usize, message: impl Into<Cow<'static, str>>) -> ProgressBar {
+    let bars = match BARS.get() {
+        Some(bars) => bars,
+        None => return ProgressBar::hidden(),
+    };
+    let bar = bars.add(ProgressBar::new(len as u64));
+    bar.set_style(
+        ProgressStyle::with_template("{msg:32} [{bar:40}] {pos}/{len} ({eta})")
+            .expect("Valid template")
+            .progress_chars("=> "),
+    );
+    bar.set_message(message);
+    bar
+}
+
+/// Advances a progress bar with every manifest that a wrapped cache finds or
+/// stores, i.e. with every manifest that has been got one way or another.
+pub(crate) struct ProgressCache<C> {
+    inner: C,
+    bar: ProgressBar,
+}
+
+impl<C> ProgressCache<C> {
+    pub(crate) fn new(inner: C, bar: ProgressBar) -> Self {
+        ProgressCache { inner, bar }
+    }
+}
+
+impl<C: Cache> Cache for ProgressCache<C> {
+    fn get(&self, day: NaiveDate) -> Option<Manifest> {
+        let manifest = self.inner.get(day);
+        if manifest.is_some() {
+            self.bar.inc(1);
+        }
+        manifest
+    }
+
+    fn store(&self, manifest: &Manifest) {
+        self.bar.inc(1);
+        self.inner.store(manifest)
+    }
+
+    fn get_validators(&self, url: &str) -> Option<Validators> {
+        self.inner.get_validators(url)
+    }
+
+    fn store_validators(&self, url: &str, validators: &Validators) {
+        self.inner.store_validators(url, validators)
+    }
+}
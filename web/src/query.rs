@@ -0,0 +1,642 @@
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    iter::Peekable,
+    str::{CharIndices, FromStr},
+};
+
+use anyhow::Context;
+use chrono::NaiveDate;
+use rustup_available_packages::cache::Cache;
+use rustup_status::{channel_cache, csv_field, opts::Config, storage::Storage};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::ConfigOpt;
+
+#[derive(StructOpt)]
+pub struct QueryOpt {
+    #[structopt(flatten)]
+    config: ConfigOpt,
+    #[structopt(
+        help = "A filter of the rows, e.g. 'target == \"aarch64-apple-darwin\" && package == \"miri\" && !available'. Every row is printed if omitted"
+    )]
+    expression: Option<String>,
+    #[structopt(
+        short = "f",
+        long = "format",
+        help = "Format of the output: table, json or csv",
+        default_value = "table"
+    )]
+    format: Format,
+}
+
+enum Format {
+    Table,
+    Json,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Format::Table),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            _ => anyhow::bail!("Unknown format {}, expected table, json or csv", s),
+        }
+    }
+}
+
+/// The status of a package on a target on a day.
+#[derive(Serialize)]
+pub struct Row {
+    pub channel: String,
+    pub date: NaiveDate,
+    pub target: String,
+    pub package: String,
+    pub available: bool,
+    pub version: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    Channel,
+    Date,
+    Target,
+    Package,
+    Available,
+    Version,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "channel" => Field::Channel,
+            "date" => Field::Date,
+            "target" => Field::Target,
+            "package" => Field::Package,
+            "available" => Field::Available,
+            "version" => Field::Version,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Op(Op),
+    /// `~`, a glob match.
+    Matches,
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+/// Consumes the next character if it is the expected one.
+fn next_is(chars: &mut Peekable<CharIndices>, expected: char) -> bool {
+    chars.next_if(|&(_, c)| c == expected).is_some()
+}
+
+fn tokenize(s: &str) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((idx, c)) = chars.next() {
+        let token = match c {
+            _ if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '~' => Token::Matches,
+            '&' if next_is(&mut chars, '&') => Token::And,
+            '|' if next_is(&mut chars, '|') => Token::Or,
+            '=' if next_is(&mut chars, '=') => Token::Op(Op::Eq),
+            '!' if next_is(&mut chars, '=') => Token::Op(Op::Ne),
+            '!' => Token::Not,
+            '<' if next_is(&mut chars, '=') => Token::Op(Op::Le),
+            '<' => Token::Op(Op::Lt),
+            '>' if next_is(&mut chars, '=') => Token::Op(Op::Ge),
+            '>' => Token::Op(Op::Gt),
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, c)) => value.push(c),
+                            None => anyhow::bail!("Unterminated string at {}", idx),
+                        },
+                        Some((_, c)) => value.push(c),
+                        None => anyhow::bail!("Unterminated string at {}", idx),
+                    }
+                }
+                Token::Str(value)
+            }
+            _ if c.is_alphanumeric() || c == '_' => {
+                let mut end = idx + c.len_utf8();
+                while let Some((idx, c)) = chars.next_if(|&(_, c)| c.is_alphanumeric() || c == '_')
+                {
+                    end = idx + c.len_utf8();
+                }
+                Token::Ident(s[idx..end].to_string())
+            }
+            _ => anyhow::bail!("Unexpected {:?} at {}", c, idx),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// An operand of a comparison.
+enum Operand {
+    Field(Field),
+    Str(String),
+    Null,
+}
+
+/// A filter of the rows.
+enum Expr {
+    Bool(bool),
+    Available,
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Op, Operand, Operand),
+    Matches(Operand, glob::Pattern),
+}
+
+/// A recursive descent parser of:
+///
+/// ```text
+/// or      = and ("||" and)*
+/// and     = unary ("&&" unary)*
+/// unary   = "!" unary | "(" or ")" | "true" | "false" | "available"
+///         | operand ("==" | "!=" | "<" | "<=" | ">" | ">=") operand
+///         | operand "~" string
+/// operand = field | string | "null"
+/// ```
+struct Parser {
+    tokens: std::vec::IntoIter<Token>,
+    peeked: Option<Token>,
+}
+
+impl Parser {
+    fn peek(&mut self) -> Option<&Token> {
+        if self.peeked.is_none() {
+            self.peeked = self.tokens.next();
+        }
+        self.peeked.as_ref()
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        self.peeked.take().or_else(|| self.tokens.next())
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn or(&mut self) -> anyhow::Result<Expr> {
+        let mut expr = self.and()?;
+        while self.eat(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> anyhow::Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.eat(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> anyhow::Result<Expr> {
+        if self.eat(&Token::Not) {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat(&Token::Open) {
+            let expr = self.or()?;
+            anyhow::ensure!(self.eat(&Token::Close), "Expected a closing parenthesis");
+            return Ok(expr);
+        }
+        let left = match self.next() {
+            Some(Token::Ident(name)) => match name.as_str() {
+                "true" => return Ok(Expr::Bool(true)),
+                "false" => return Ok(Expr::Bool(false)),
+                "null" => Operand::Null,
+                _ => match Field::parse(&name) {
+                    Some(Field::Available) => return Ok(Expr::Available),
+                    Some(field) => Operand::Field(field),
+                    None => anyhow::bail!(
+                        "Unknown field {}, expected channel, date, target, package, available or version",
+                        name
+                    ),
+                },
+            },
+            Some(Token::Str(value)) => Operand::Str(value),
+            _ => anyhow::bail!("Expected a field, a string or a condition"),
+        };
+        match self.next() {
+            Some(Token::Op(op)) => Ok(Expr::Compare(op, left, self.operand()?)),
+            Some(Token::Matches) => match self.next() {
+                Some(Token::Str(pattern)) => {
+                    let pattern = glob::Pattern::new(&pattern)
+                        .with_context(|| format!("Invalid pattern {}", pattern))?;
+                    Ok(Expr::Matches(left, pattern))
+                }
+                _ => anyhow::bail!("Expected a pattern after ~"),
+            },
+            _ => anyhow::bail!("Expected a comparison"),
+        }
+    }
+
+    fn operand(&mut self) -> anyhow::Result<Operand> {
+        match self.next() {
+            Some(Token::Str(value)) => Ok(Operand::Str(value)),
+            Some(Token::Ident(name)) if name == "null" => Ok(Operand::Null),
+            Some(Token::Ident(name)) => match Field::parse(&name) {
+                Some(Field::Available) | None => {
+                    anyhow::bail!("{} can't be compared", name)
+                }
+                Some(field) => Ok(Operand::Field(field)),
+            },
+            _ => anyhow::bail!("Expected a field, a string or null"),
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?.into_iter(),
+            peeked: None,
+        };
+        let expr = parser.or()?;
+        anyhow::ensure!(parser.next().is_none(), "Unexpected trailing input");
+        Ok(expr)
+    }
+}
+
+impl Operand {
+    fn value<'a>(&'a self, row: &'a Row, date: &'a str) -> Option<&'a str> {
+        match self {
+            Operand::Field(Field::Channel) => Some(&row.channel),
+            Operand::Field(Field::Date) => Some(date),
+            Operand::Field(Field::Target) => Some(&row.target),
+            Operand::Field(Field::Package) => Some(&row.package),
+            Operand::Field(Field::Version) => row.version.as_deref(),
+            Operand::Field(Field::Available) => unreachable!("Not an operand"),
+            Operand::Str(value) => Some(value),
+            Operand::Null => None,
+        }
+    }
+}
+
+impl Expr {
+    /// Dates are compared as `YYYY-MM-DD` strings, i.e. chronologically.
+    fn matches(&self, row: &Row, date: &str) -> bool {
+        match self {
+            Expr::Bool(value) => *value,
+            Expr::Available => row.available,
+            Expr::Not(expr) => !expr.matches(row, date),
+            Expr::And(left, right) => left.matches(row, date) && right.matches(row, date),
+            Expr::Or(left, right) => left.matches(row, date) || right.matches(row, date),
+            Expr::Compare(op, left, right) => {
+                let (left, right) = (left.value(row, date), right.value(row, date));
+                match op {
+                    Op::Eq => left == right,
+                    Op::Ne => left != right,
+                    // Nothing is less or greater than null.
+                    _ if left.is_none() || right.is_none() => false,
+                    Op::Lt => left < right,
+                    Op::Le => left <= right,
+                    Op::Gt => left > right,
+                    Op::Ge => left >= right,
+                }
+            }
+            Expr::Matches(operand, pattern) => operand
+                .value(row, date)
+                .is_some_and(|value| pattern.matches(value)),
+        }
+    }
+}
+
+/// Reads the rows of a channel from the database, packages going by their
+/// original names. A package several names have been renamed to goes by the
+/// first of them.
+fn stored_rows(storage: &Storage, channel: &str) -> anyhow::Result<Vec<Row>> {
+    let mut stmt = storage.connection().prepare(
+        "SELECT a.date, a.target,
+                COALESCE(
+                    (SELECT MIN(r.old) FROM renames r
+                     WHERE r.channel = a.channel AND r.date = a.date AND r.new = a.package),
+                    a.package
+                ),
+                a.available, a.version
+         FROM availability a
+         WHERE a.channel = ?",
+    )?;
+    let rows = stmt
+        .query_map([channel], |row| {
+            Ok(Row {
+                channel: channel.to_string(),
+                date: row.get(0)?,
+                target: row.get(1)?,
+                package: row.get(2)?,
+                available: row.get(3)?,
+                version: row.get(4)?,
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+    Ok(rows)
+}
+
+/// Reads the rows of a channel from the cached manifests, packages going by
+/// their original names, just like [`stored_rows`] does.
+fn cached_rows(config: &Config, channel: &str) -> anyhow::Result<Vec<Row>> {
+    let cache = channel_cache(config, channel)?;
+    let mut rows = Vec::new();
+    for entry in cache
+        .entries()
+        .with_context(|| format!("Can't list the cached manifests of {}", channel))?
+    {
+        let manifest = match cache.get(entry.date) {
+            Some(manifest) => manifest,
+            None => continue,
+        };
+        let mut old_names: HashMap<&str, &str> = HashMap::new();
+        for (old, rename) in &manifest.renames {
+            let name = old_names.entry(&rename.to).or_insert(old);
+            *name = (*name).min(old.as_str());
+        }
+        for (package, info) in &manifest.packages {
+            let package = old_names.get(package.as_str()).copied().unwrap_or(package);
+            for (target, target_info) in &info.targets {
+                rows.push(Row {
+                    channel: channel.to_string(),
+                    date: manifest.date,
+                    target: target.clone(),
+                    package: package.to_string(),
+                    available: target_info.available,
+                    version: info.version.clone(),
+                });
+            }
+        }
+    }
+    Ok(rows)
+}
+
+fn print_table(out: &mut impl Write, rows: &[Row]) -> io::Result<()> {
+    let header = [
+        "channel",
+        "date",
+        "target",
+        "package",
+        "available",
+        "version",
+    ];
+    let cells: Vec<[String; 6]> = rows
+        .iter()
+        .map(|row| {
+            [
+                row.channel.clone(),
+                row.date.to_string(),
+                row.target.clone(),
+                row.package.clone(),
+                (if row.available { "yes" } else { "no" }).to_string(),
+                row.version.clone().unwrap_or_default(),
+            ]
+        })
+        .collect();
+    let mut widths = header.map(str::len);
+    for row in &cells {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let header = header.map(String::from);
+    for row in std::iter::once(&header).chain(&cells) {
+        let line: Vec<_> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        writeln!(out, "{}", line.join("  ").trim_end())?;
+    }
+    Ok(())
+}
+
+/// Reads the rows of all the channels from the configured storage or, if
+/// there's none, from the cached manifests.
+pub fn load_rows(config: &Config) -> anyhow::Result<Vec<Row>> {
+    let storage = match &config.storage {
+        Some(storage) => Some(Storage::open(&storage.sqlite)?),
+        None => None,
+    };
+    anyhow::ensure!(
+        storage.is_some() || config.cache_path.is_some(),
+        "Nothing to query, configure either `storage` or `cache_path`"
+    );
+    let mut rows = Vec::new();
+    for channel in &config.channels {
+        rows.extend(match &storage {
+            Some(storage) => stored_rows(storage, channel)?,
+            None => cached_rows(config, channel)?,
+        });
+    }
+    Ok(rows)
+}
+
+/// Prints the statuses of the packages that match an expression, taking them
+/// from the configured storage or, if there's none, from the cached
+/// manifests.
+pub fn run(opts: &QueryOpt) -> anyhow::Result<()> {
+    let expr: Expr = match &opts.expression {
+        Some(expression) => expression
+            .parse()
+            .with_context(|| format!("Invalid expression {}", expression))?,
+        None => Expr::Bool(true),
+    };
+    let config = opts.config.load()?;
+    let mut rows: Vec<_> = load_rows(&config)?
+        .into_iter()
+        .filter(|row| {
+            let date = row.date.format("%Y-%m-%d").to_string();
+            expr.matches(row, &date)
+        })
+        .collect();
+    rows.sort_unstable_by(|a, b| {
+        (&a.channel, b.date, &a.target, &a.package)
+            .cmp(&(&b.channel, a.date, &b.target, &b.package))
+    });
+
+    let mut out = io::stdout().lock();
+    match opts.format {
+        Format::Table => print_table(&mut out, &rows)?,
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut out, &rows)?;
+            writeln!(out)?;
+        }
+        Format::Csv => {
+            writeln!(out, "channel,date,target,package,available,version")?;
+            for row in &rows {
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{}",
+                    csv_field(&row.channel),
+                    row.date,
+                    csv_field(&row.target),
+                    csv_field(&row.package),
+                    row.available,
+                    csv_field(row.version.as_deref().unwrap_or_default())
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustup_available_packages::manifest::{Manifest, PackageInfo, PackageTargets, Rename};
+
+    fn row(date: &str, target: &str, package: &str, available: bool) -> Row {
+        Row {
+            channel: "nightly".into(),
+            date: date.parse().unwrap(),
+            target: target.into(),
+            package: package.into(),
+            available,
+            version: None,
+        }
+    }
+
+    fn matches(expression: &str, row: &Row) -> bool {
+        let expr: Expr = expression.parse().unwrap();
+        expr.matches(row, &row.date.format("%Y-%m-%d").to_string())
+    }
+
+    #[test]
+    fn precedence() {
+        let row = row("2023-03-01", "x86_64-unknown-linux-gnu", "miri", false);
+        // `&&` binds tighter than `||`.
+        assert!(matches("true || false && false", &row));
+        assert!(!matches("(true || false) && false", &row));
+        assert!(matches("!available && package == \"miri\"", &row));
+        assert!(!matches("!(available || package == \"miri\")", &row));
+        assert!(matches("version == null && version != \"1.0\"", &row));
+        // Nothing is less or greater than null.
+        assert!(!matches("version < \"1.0\" || version >= \"1.0\"", &row));
+    }
+
+    #[test]
+    fn globs() {
+        let row = row("2023-03-01", "x86_64-unknown-linux-gnu", "rust-std", true);
+        assert!(matches("target ~ \"x86_64-*-linux-*\"", &row));
+        assert!(!matches("target ~ \"aarch64-*\"", &row));
+        assert!(matches("package ~ \"rust-?td\"", &row));
+        assert!(!matches("version ~ \"*\"", &row));
+    }
+
+    #[test]
+    fn dates() {
+        let row = row("2023-03-01", "x86_64-unknown-linux-gnu", "miri", true);
+        assert!(matches("date == \"2023-03-01\"", &row));
+        assert!(matches(
+            "date > \"2023-02-28\" && date <= \"2023-03-01\"",
+            &row
+        ));
+        assert!(matches("date < \"2023-10-01\"", &row));
+        assert!(!matches("date >= \"2023-03-02\"", &row));
+    }
+
+    #[test]
+    fn errors() {
+        for expression in [
+            "",
+            "platform == \"x\"",
+            "package == \"miri",
+            "(available",
+            "available)",
+            "package",
+            "package ~ target",
+            "package ~ \"[\"",
+            "package == available",
+            "package = \"miri\"",
+            "available #",
+        ] {
+            assert!(
+                expression.parse::<Expr>().is_err(),
+                "{:?} is parsed",
+                expression
+            );
+        }
+    }
+
+    #[test]
+    fn renamed_packages_are_listed_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("query.db");
+        let date = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+        let mut targets = HashMap::new();
+        targets.insert(
+            "x86_64-unknown-linux-gnu".to_string(),
+            PackageInfo {
+                available: true,
+                ..Default::default()
+            },
+        );
+        let mut manifest = Manifest {
+            date,
+            packages: HashMap::new(),
+            renames: HashMap::new(),
+            profiles: HashMap::new(),
+        };
+        manifest.packages.insert(
+            "rls-preview".into(),
+            PackageTargets {
+                version: None,
+                git_commit_hash: None,
+                targets,
+            },
+        );
+        for old in ["rls", "rls-old"] {
+            manifest.renames.insert(
+                old.into(),
+                Rename {
+                    to: "rls-preview".into(),
+                },
+            );
+        }
+        let mut storage = Storage::open(&path).unwrap();
+        storage.store("nightly", &[manifest]).unwrap();
+        let rows = stored_rows(&storage, "nightly").unwrap();
+        assert_eq!(1, rows.len());
+        assert_eq!("rls", rows[0].package);
+    }
+}
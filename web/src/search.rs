@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::{
+    generators::GeneratorInput,
+    html::Pages,
+    opts,
+    output::{Output, Purpose},
+    target_pages, AdditionalData,
+};
+
+pub const TEMPLATE_NAME: &str = "search_page";
+
+/// The latest status of a package on a target.
+#[derive(Serialize)]
+struct Entry<'a> {
+    target: &'a str,
+    package: &'a str,
+    available: bool,
+    last_available: Option<NaiveDate>,
+}
+
+/// Everything a client-side search needs.
+#[derive(Serialize)]
+struct SearchIndex<'a> {
+    channel: &'a str,
+    /// The date of the latest manifest.
+    date: Option<NaiveDate>,
+    /// All the targets, sorted by name.
+    targets: Vec<&'a str>,
+    /// All the packages, sorted by name.
+    packages: Vec<&'a str>,
+    entries: Vec<Entry<'a>>,
+}
+
+/// Statuses of all the packages on a target, as shown on the fallback page.
+#[derive(Serialize)]
+struct TargetStatus<'a> {
+    target: &'a str,
+    available: Vec<&'a str>,
+    missing: Vec<&'a Entry<'a>>,
+}
+
+#[derive(Serialize)]
+struct SearchPageData<'a> {
+    date: Option<NaiveDate>,
+    targets: Vec<TargetStatus<'a>>,
+    /// Links to the pages of the targets relative to the page, keyed by target.
+    target_pages: BTreeMap<&'a str, String>,
+    additional: &'a AdditionalData<'a>,
+}
+
+/// Generates a JSON search index and, optionally, a page listing the same
+/// information for those who browse without JavaScript.
+///
+/// The page template is expected to be registered under the [`TEMPLATE_NAME`].
+pub fn generate_search_index(
+    pages: &Pages,
+    config: &opts::Search,
+    out: &mut dyn Output,
+) -> anyhow::Result<()> {
+    let GeneratorInput {
+        data,
+        dates,
+        channel,
+        ..
+    } = *pages.input;
+    let mut targets: Vec<_> = data.get_available_targets().into_iter().collect();
+    targets.sort_unstable();
+    let mut packages: Vec<_> = data.get_available_packages().into_iter().collect();
+    packages.sort_unstable();
+    let date = dates.first().copied();
+
+    let entries = targets
+        .iter()
+        .flat_map(|&target| {
+            packages.iter().filter_map(move |&package| {
+                let row = data.get_availability_row(target, package, dates)?;
+                Some(Entry {
+                    target,
+                    package,
+                    available: row.availability_list.first() == Some(&true),
+                    last_available: row.last_available,
+                })
+            })
+        })
+        .collect();
+    let index = SearchIndex {
+        channel: channel.name,
+        date,
+        targets,
+        packages,
+        entries,
+    };
+    let index_path = channel.relocate(&config.index_path);
+    log::info!("Writing search index to {:?}", index_path);
+    out.set_purpose(Purpose::SearchIndex);
+    out.write(&index_path, &serde_json::to_vec(&index)?)?;
+
+    let page = match &config.fallback_page {
+        Some(page) => page,
+        None => return Ok(()),
+    };
+    let targets = index
+        .targets
+        .iter()
+        .map(|&target| {
+            let entries = index.entries.iter().filter(|entry| entry.target == target);
+            TargetStatus {
+                target,
+                available: entries
+                    .clone()
+                    .filter(|entry| entry.available)
+                    .map(|entry| entry.package)
+                    .collect(),
+                missing: entries.filter(|entry| !entry.available).collect(),
+            }
+        })
+        .collect();
+    let output_path = channel.relocate(&page.output_path);
+    let target_pages = target_pages(
+        &pages.config.output_pattern,
+        index.targets.iter().copied(),
+        channel,
+        &output_path,
+    )?;
+    let rendered = pages
+        .templates
+        .render_page(
+            TEMPLATE_NAME,
+            &SearchPageData {
+                date,
+                targets,
+                target_pages,
+                additional: pages.additional,
+            },
+            &output_path,
+        )
+        .with_context(|| format!("Can't render [{:?}]", page.template_path))?;
+    log::info!("Writing search page to {:?}", output_path);
+    out.set_purpose(Purpose::SearchPage);
+    out.write(&output_path, rendered.as_bytes())
+}
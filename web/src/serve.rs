@@ -0,0 +1,192 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+    thread,
+    time::Duration,
+};
+
+use anyhow::Context;
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use percent_encoding::percent_decode_str;
+use structopt::StructOpt;
+
+use rustup_status::{
+    acquire_lock,
+    opts::Config,
+    output::{content_type, MemoryOutput},
+    platform_support, render_keeping_data, Caches, LoadedChannel,
+};
+
+use crate::{api, ConfigOpt};
+
+#[derive(StructOpt)]
+pub struct ServeOpt {
+    #[structopt(flatten)]
+    pub config: ConfigOpt,
+    #[structopt(
+        short = "a",
+        long = "address",
+        help = "Address to listen on",
+        default_value = "127.0.0.1:8080"
+    )]
+    address: SocketAddr,
+    #[structopt(
+        short = "i",
+        long = "interval",
+        help = "Re-rendering interval in seconds",
+        default_value = "3600"
+    )]
+    interval: u64,
+}
+
+/// A rendered site.
+struct Site {
+    /// Rendered files keyed by their paths relative to the file tree output.
+    files: HashMap<String, Vec<u8>>,
+    /// Metrics of the rendering, served at `/metrics`.
+    metrics: String,
+    /// The loaded data, served by the API.
+    channels: Vec<LoadedChannel>,
+}
+
+/// Renders the whole site into memory, holding the run lock meanwhile.
+fn render_site(config: &Config, caches: &Caches) -> anyhow::Result<Arc<Site>> {
+    let _lock = acquire_lock(config)?;
+    let mut out = MemoryOutput::default();
+    let (report, channels) = render_keeping_data(config, caches, &mut out)?;
+    Ok(Arc::new(Site {
+        files: out.into_tree(&config.file_tree_output),
+        metrics: report.metrics,
+        channels,
+    }))
+}
+
+/// Runs an HTTP server that serves the rendered pages and the file tree
+/// (everything that is rendered under `file_tree_output`) from memory, along
+/// with a JSON API over the loaded data.
+pub fn run(mut config: Config, opts: ServeOpt) -> anyhow::Result<()> {
+    // Nothing is kept between re-renderings, so every target has to be rendered
+    // every time.
+    if config.state_path.take().is_some() {
+        log::warn!("state_path is ignored in the serve mode");
+    }
+    // The file tree is served file by file.
+    if config.output.tree_archive.take().is_some() {
+        log::warn!("output.tree_archive is ignored in the serve mode");
+    }
+    // Nothing is written to the disk to be archived.
+    if config.output.archive.take().is_some() {
+        log::warn!("output.archive is ignored in the serve mode");
+    }
+    platform_support::apply(&mut config)?;
+    // Caches are kept between re-renderings to benefit from the in-memory tier.
+    let caches = {
+        let _lock = acquire_lock(&config)?;
+        Caches::new(&config)?
+    };
+    let site = Arc::new(RwLock::new(
+        render_site(&config, &caches).context("Initial rendering failed")?,
+    ));
+
+    let background_site = Arc::clone(&site);
+    let interval = Duration::from_secs(opts.interval);
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        match render_site(&config, &caches) {
+            Ok(new_site) => *background_site.write().unwrap() = new_site,
+            Err(e) => log::error!("Re-rendering failed, keeping the old pages: {:?}", e),
+        }
+    });
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .build()
+        .context("Can't start the async runtime")?;
+    runtime.block_on(async move {
+        let make_service = make_service_fn(move |_| {
+            let site = Arc::clone(&site);
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let current = Arc::clone(&site.read().unwrap());
+                    async move { Ok::<_, Infallible>(respond(&current, request)) }
+                }))
+            }
+        });
+        log::info!("Listening on http://{}", opts.address);
+        Server::try_bind(&opts.address)
+            .with_context(|| format!("Can't bind to {}", opts.address))?
+            .serve(make_service)
+            .await
+            .context("Server error")
+    })
+}
+
+fn respond(site: &Site, request: Request<Body>) -> Response<Body> {
+    if request.method() != Method::GET && request.method() != Method::HEAD {
+        return status(StatusCode::METHOD_NOT_ALLOWED);
+    }
+    if request.uri().path() == "/metrics" {
+        return Response::builder()
+            .header(CONTENT_TYPE, "text/plain; version=0.0.4")
+            .body(Body::from(site.metrics.clone()))
+            .expect("Valid response");
+    }
+    if let Some(path) = request.uri().path().strip_prefix(api::PREFIX) {
+        return api::respond(&site.channels, path, request.uri().query());
+    }
+    // Page names might contain any characters, e.g. the package pages.
+    let path = match percent_decode_str(request.uri().path()).decode_utf8() {
+        Ok(path) => path,
+        Err(_) => return status(StatusCode::NOT_FOUND),
+    };
+    let path = path.trim_start_matches('/');
+    let path = if path.is_empty() || path.ends_with('/') {
+        format!("{}index.html", path)
+    } else {
+        path.to_string()
+    };
+    match site.files.get(&path) {
+        Some(contents) => Response::builder()
+            .header(CONTENT_TYPE, content_type(&path))
+            .body(Body::from(contents.clone()))
+            .expect("Valid response"),
+        None => status(StatusCode::NOT_FOUND),
+    }
+}
+
+pub fn status(code: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(code)
+        .body(Body::from(code.to_string()))
+        .expect("Valid response")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_the_path() {
+        let site = Site {
+            files: vec![
+                ("index.html".to_string(), b"index".to_vec()),
+                ("a b/c+d.html".to_string(), b"page".to_vec()),
+            ]
+            .into_iter()
+            .collect(),
+            metrics: String::new(),
+            channels: Vec::new(),
+        };
+        let get = |uri: &str| respond(&site, Request::get(uri).body(Body::empty()).unwrap());
+        assert_eq!(StatusCode::OK, get("/").status());
+        assert_eq!(StatusCode::OK, get("/a%20b/c+d.html").status());
+        assert_eq!(StatusCode::OK, get("/a%20b/c%2Bd.html").status());
+        assert_eq!(StatusCode::NOT_FOUND, get("/a%20b/c%FF.html").status());
+    }
+}
@@ -0,0 +1,286 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    env, fs,
+    io::{Read, Write},
+    iter,
+    net::TcpStream,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use rustup_available_packages::verify::sha256;
+use rustup_status::opts::SftpDeploy;
+use ssh2::{CheckResult, FileStat, KnownHostFileKind, RenameFlags, Session, Sftp};
+
+fn check_host_key(session: &Session, config: &SftpDeploy) -> anyhow::Result<()> {
+    let path = match &config.known_hosts {
+        Some(path) => path.clone(),
+        None => env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".ssh/known_hosts"))
+            .context("No home directory to find known_hosts in, set `known_hosts`")?,
+    };
+    let mut known_hosts = session.known_hosts()?;
+    known_hosts
+        .read_file(&path, KnownHostFileKind::OpenSSH)
+        .with_context(|| format!("Can't read {}", path.display()))?;
+    let (key, _) = session
+        .host_key()
+        .with_context(|| format!("{} hasn't sent its key", config.host))?;
+    match known_hosts.check_port(&config.host, config.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::NotFound => anyhow::bail!(
+            "The key of {} isn't listed in {}",
+            config.host,
+            path.display()
+        ),
+        CheckResult::Mismatch => anyhow::bail!(
+            "The key of {} doesn't match the one listed in {}",
+            config.host,
+            path.display()
+        ),
+        CheckResult::Failure => anyhow::bail!("Can't check the key of {}", config.host),
+    }
+}
+
+fn connect(config: &SftpDeploy) -> anyhow::Result<Session> {
+    let tcp = TcpStream::connect((config.host.as_str(), config.port))
+        .with_context(|| format!("Can't connect to {}:{}", config.host, config.port))?;
+    let mut session = Session::new()?;
+    session.set_tcp_stream(tcp);
+    session
+        .handshake()
+        .with_context(|| format!("SSH handshake with {} has failed", config.host))?;
+    check_host_key(&session, config)?;
+    let passphrase = config
+        .passphrase
+        .clone()
+        .or_else(|| env::var("SFTP_PASSPHRASE").ok());
+    match &config.private_key {
+        Some(key) => session.userauth_pubkey_file(&config.user, None, key, passphrase.as_deref()),
+        None => session.userauth_agent(&config.user),
+    }
+    .with_context(|| format!("Can't log in to {} as {}", config.host, config.user))?;
+    Ok(session)
+}
+
+/// Lists the files and the directories under a remote directory, with their
+/// `/`-separated paths relative to it.
+fn list_remote(
+    sftp: &Sftp,
+    dir: &Path,
+    relative: &str,
+    files: &mut HashMap<String, FileStat>,
+    dirs: &mut HashSet<String>,
+) -> anyhow::Result<()> {
+    let entries = sftp
+        .readdir(dir)
+        .with_context(|| format!("Can't list {}", dir.display()))?;
+    for (path, stat) in entries {
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => format!("{}{}", relative, name),
+            None => continue,
+        };
+        if stat.is_dir() {
+            list_remote(sftp, &path, &format!("{}/", name), files, dirs)?;
+            dirs.insert(name);
+        } else {
+            files.insert(name, stat);
+        }
+    }
+    Ok(())
+}
+
+/// A file on the server with the SHA-256 of every uploaded file, so the
+/// unchanged files aren't uploaded again. The generated files are written anew
+/// on every run, so their modification times tell nothing.
+const CHECKSUMS: &str = ".checksums.json";
+
+/// Appended to the names of the files while they are being uploaded.
+const PARTIAL: &str = ".part";
+
+/// Reads the checksums of the files uploaded by the previous deployment. If
+/// they can't be read, everything is uploaded again.
+fn read_checksums(sftp: &Sftp, dir: &Path) -> BTreeMap<String, String> {
+    let path = dir.join(CHECKSUMS);
+    let mut contents = String::new();
+    let read = sftp
+        .open(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|mut file| Ok(file.read_to_string(&mut contents)?))
+        .and_then(|_| Ok(serde_json::from_str(&contents)?));
+    read.unwrap_or_else(|e| {
+        log::info!("Can't read {}, uploading everything: {}", path.display(), e);
+        BTreeMap::new()
+    })
+}
+
+/// Whether a file is on the server with the same contents.
+fn is_uploaded(
+    name: &str,
+    sha256: &str,
+    checksums: &BTreeMap<String, String>,
+    remote_files: &HashMap<String, FileStat>,
+) -> bool {
+    remote_files.contains_key(name) && checksums.get(name).is_some_and(|old| old == sha256)
+}
+
+/// Uploads a file under a temporary name and renames it into place, so
+/// nobody downloads a half-uploaded file.
+fn upload(sftp: &Sftp, contents: &[u8], remote_path: &Path) -> anyhow::Result<()> {
+    let mut partial = remote_path.as_os_str().to_owned();
+    partial.push(PARTIAL);
+    let partial = PathBuf::from(partial);
+    let mut remote_file = sftp
+        .create(&partial)
+        .with_context(|| format!("Can't create {}", partial.display()))?;
+    remote_file
+        .write_all(contents)
+        .with_context(|| format!("Can't upload {}", remote_path.display()))?;
+    drop(remote_file);
+    let flags = RenameFlags::OVERWRITE | RenameFlags::ATOMIC | RenameFlags::NATIVE;
+    if sftp.rename(&partial, remote_path, Some(flags)).is_err() {
+        // Servers of the SFTP version 3 don't replace files.
+        let _ = sftp.unlink(remote_path);
+        sftp.rename(&partial, remote_path, None)
+            .with_context(|| format!("Can't move {} in place", remote_path.display()))?;
+    }
+    Ok(())
+}
+
+/// Directories on the server that none of the `names` is in, the nested ones
+/// first, so the parents are empty by the time they are deleted.
+fn stale_dirs<'a>(names: &HashSet<&str>, remote_dirs: &'a HashSet<String>) -> Vec<&'a str> {
+    let parents: HashSet<_> = names
+        .iter()
+        .flat_map(|name| name.match_indices('/').map(move |(idx, _)| &name[..idx]))
+        .collect();
+    let mut stale: Vec<_> = remote_dirs
+        .iter()
+        .map(String::as_str)
+        .filter(|dir| !parents.contains(dir))
+        .collect();
+    stale.sort_unstable_by_key(|dir| (std::cmp::Reverse(dir.len()), *dir));
+    stale
+}
+
+/// Uploads the files of a directory whose contents differ from the ones on
+/// the server, and deletes the files that aren't there anymore afterwards if
+/// configured.
+pub fn sync(directory: &Path, config: &SftpDeploy) -> anyhow::Result<()> {
+    let mut files = Vec::new();
+    crate::deploy::list_files(directory, "", &mut files)?;
+    let session = connect(config)?;
+    let sftp = session.sftp().context("Can't start an SFTP session")?;
+    let mut remote_files = HashMap::new();
+    let mut remote_dirs = HashSet::new();
+    list_remote(&sftp, &config.path, "", &mut remote_files, &mut remote_dirs)?;
+    let old_checksums = read_checksums(&sftp, &config.path);
+
+    let mut checksums = BTreeMap::new();
+    let mut uploaded = 0;
+    for (name, path) in &files {
+        let contents = fs::read(path).with_context(|| format!("Can't read {}", path.display()))?;
+        let sha256 = sha256(&contents);
+        if !is_uploaded(name, &sha256, &old_checksums, &remote_files) {
+            for (idx, _) in name.match_indices('/') {
+                let parent = &name[..idx];
+                if remote_dirs.insert(parent.to_string()) {
+                    sftp.mkdir(&config.path.join(parent), 0o755)
+                        .with_context(|| format!("Can't create {}", parent))?;
+                }
+            }
+            upload(&sftp, &contents, &config.path.join(name))?;
+            uploaded += 1;
+        }
+        checksums.insert(name.clone(), sha256);
+    }
+    upload(
+        &sftp,
+        &serde_json::to_vec_pretty(&checksums)?,
+        &config.path.join(CHECKSUMS),
+    )?;
+
+    let mut deleted = 0;
+    if config.delete {
+        let names: HashSet<_> = files
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .chain(iter::once(CHECKSUMS))
+            .collect();
+        for name in remote_files
+            .keys()
+            .filter(|name| !names.contains(name.as_str()))
+        {
+            sftp.unlink(&config.path.join(name))
+                .with_context(|| format!("Can't delete {}", name))?;
+            deleted += 1;
+        }
+        for dir in stale_dirs(&names, &remote_dirs) {
+            sftp.rmdir(&config.path.join(dir))
+                .with_context(|| format!("Can't delete {}", dir))?;
+        }
+    }
+    log::info!(
+        "Uploaded {} of {} files to {}:{}, deleted {} files",
+        uploaded,
+        files.len(),
+        config.host,
+        config.path.display(),
+        deleted
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stat(size: u64) -> FileStat {
+        FileStat {
+            size: Some(size),
+            uid: None,
+            gid: None,
+            perm: None,
+            atime: None,
+            mtime: None,
+        }
+    }
+
+    #[test]
+    fn changed_files_are_uploaded() {
+        let checksums: BTreeMap<_, _> = vec![
+            ("a.html".to_string(), sha256(b"a")),
+            ("gone.html".to_string(), sha256(b"gone")),
+        ]
+        .into_iter()
+        .collect();
+        let remote: HashMap<_, _> = vec![("a.html".to_string(), stat(1))].into_iter().collect();
+        assert!(is_uploaded("a.html", &sha256(b"a"), &checksums, &remote));
+        assert!(!is_uploaded("a.html", &sha256(b"b"), &checksums, &remote));
+        // Deleted on the server behind our back.
+        assert!(!is_uploaded(
+            "gone.html",
+            &sha256(b"gone"),
+            &checksums,
+            &remote
+        ));
+        assert!(!is_uploaded(
+            "new.html",
+            &sha256(b"new"),
+            &checksums,
+            &remote
+        ));
+    }
+
+    #[test]
+    fn stale_dirs_go_nested_first() {
+        let names: HashSet<_> = vec!["x/a.html", "y/z/b.html", CHECKSUMS]
+            .into_iter()
+            .collect();
+        let remote: HashSet<_> = vec!["x", "y", "y/z", "old", "old/nested", "x/old"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        assert_eq!(stale_dirs(&names, &remote), ["old/nested", "x/old", "old"]);
+    }
+}
@@ -0,0 +1,217 @@
+//! A machine-readable list of all the generated files.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use rustup_available_packages::verify::sha256;
+use serde::{Deserialize, Serialize};
+
+use crate::output::{Output, Purpose};
+
+/// A generated file.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    purpose: Purpose,
+    /// Lowercase hex SHA-256 of the contents, unless the file is a link.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sha256: Option<String>,
+    #[serde(default)]
+    size: usize,
+    /// Where the link points to, if the file is one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    link: Option<PathBuf>,
+    /// When the file has been written for the last time.
+    generated_at: DateTime<Utc>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct SiteManifest {
+    generated_at: Option<DateTime<Utc>>,
+    /// Keyed by the `/`-separated paths relative to the manifest's directory.
+    files: BTreeMap<String, Entry>,
+}
+
+/// Keeps track of every file written to another output and writes a
+/// `site-manifest.json` listing them when everything has been written.
+pub struct SiteManifestOutput<'a> {
+    inner: &'a mut dyn Output,
+    path: PathBuf,
+    purpose: Purpose,
+    started_at: DateTime<Utc>,
+    manifest: SiteManifest,
+}
+
+impl<'a> SiteManifestOutput<'a> {
+    /// Starts a manifest at a given `path`. If `incremental`, only some of the
+    /// files are rendered every time, so the rest of them are carried over
+    /// from the previous manifest, unless they are gone from the disk.
+    pub fn new(
+        inner: &'a mut dyn Output,
+        path: &Path,
+        incremental: bool,
+        started_at: DateTime<Utc>,
+    ) -> Self {
+        let manifest = if incremental && path.exists() {
+            let mut manifest: SiteManifest = fs::read(path)
+                .map_err(anyhow::Error::from)
+                .and_then(|data| Ok(serde_json::from_slice(&data)?))
+                .unwrap_or_else(|e| {
+                    log::warn!("Can't read the site manifest {}: {}", path.display(), e);
+                    SiteManifest::default()
+                });
+            let dir = path.parent().unwrap_or_else(|| Path::new(""));
+            manifest
+                .files
+                .retain(|file, _| dir.join(file).symlink_metadata().is_ok());
+            manifest
+        } else {
+            SiteManifest::default()
+        };
+        SiteManifestOutput {
+            inner,
+            path: path.into(),
+            purpose: Purpose::Other,
+            started_at,
+            manifest,
+        }
+    }
+
+    /// Makes a path relative to the manifest's directory, if it's there.
+    fn key(&self, path: &Path) -> String {
+        let dir = self.path.parent().unwrap_or_else(|| Path::new(""));
+        let relative = path.strip_prefix(dir).unwrap_or(path);
+        relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+impl Output for SiteManifestOutput<'_> {
+    fn write(&mut self, path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+        self.inner.write(path, contents)?;
+        let entry = Entry {
+            purpose: self.purpose,
+            sha256: Some(sha256(contents)),
+            size: contents.len(),
+            link: None,
+            generated_at: self.started_at,
+        };
+        self.manifest.files.insert(self.key(path), entry);
+        Ok(())
+    }
+
+    fn symlink(&mut self, target: &Path, link: &Path) -> anyhow::Result<()> {
+        self.inner.symlink(target, link)?;
+        let entry = Entry {
+            purpose: self.purpose,
+            sha256: None,
+            size: 0,
+            link: Some(target.into()),
+            generated_at: self.started_at,
+        };
+        self.manifest.files.insert(self.key(link), entry);
+        Ok(())
+    }
+
+    fn list_dir(&self, path: &Path) -> anyhow::Result<Vec<String>> {
+        self.inner.list_dir(path)
+    }
+
+    fn remove_dir(&mut self, path: &Path) -> anyhow::Result<()> {
+        self.inner.remove_dir(path)?;
+        let dir = self.key(path);
+        self.manifest
+            .files
+            .retain(|file, _| !Path::new(file).starts_with(&dir));
+        Ok(())
+    }
+
+    fn remove_file(&mut self, path: &Path) -> anyhow::Result<()> {
+        self.inner.remove_file(path)?;
+        self.manifest.files.remove(&self.key(path));
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.manifest.generated_at = Some(self.started_at);
+        log::info!("Writing the site manifest to {:?}", self.path);
+        let manifest = serde_json::to_vec_pretty(&self.manifest)?;
+        self.inner.write(&self.path, &manifest)?;
+        self.inner.finish()
+    }
+
+    fn set_purpose(&mut self, purpose: Purpose) {
+        self.purpose = purpose;
+        self.inner.set_purpose(purpose)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::MemoryOutput;
+
+    fn read(out: MemoryOutput, root: &Path) -> serde_json::Value {
+        let tree = out.into_tree(root);
+        serde_json::from_slice(&tree["site-manifest.json"]).unwrap()
+    }
+
+    #[test]
+    fn lists_the_written_files() {
+        let root = Path::new("site");
+        let mut out = MemoryOutput::default();
+        let mut manifest = SiteManifestOutput::new(
+            &mut out,
+            &root.join("site-manifest.json"),
+            false,
+            Utc::now(),
+        );
+        manifest.set_purpose(Purpose::TargetPage);
+        manifest.write(&root.join("a.html"), b"abc").unwrap();
+        manifest.write(&root.join("gone/b.html"), b"b").unwrap();
+        manifest.remove_dir(&root.join("gone")).unwrap();
+        manifest
+            .symlink(Path::new("a.html"), &root.join("latest.html"))
+            .unwrap();
+        manifest.finish().unwrap();
+
+        let files = &read(out, root)["files"];
+        assert_eq!(files["a.html"]["purpose"], "target_page");
+        assert_eq!(files["a.html"]["sha256"], sha256(b"abc"));
+        assert_eq!(files["a.html"]["size"], 3);
+        assert_eq!(files["latest.html"]["link"], "a.html");
+        assert!(files.get("gone/b.html").is_none());
+    }
+
+    #[test]
+    fn incremental_manifest_drops_deleted_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let path = root.join("site-manifest.json");
+        let started_at = Utc::now();
+
+        let mut out = crate::output::FsOutput::default();
+        let mut manifest = SiteManifestOutput::new(&mut out, &path, true, started_at);
+        manifest.write(&root.join("kept.html"), b"kept").unwrap();
+        manifest
+            .write(&root.join("deleted.html"), b"deleted")
+            .unwrap();
+        manifest.finish().unwrap();
+        fs::remove_file(root.join("deleted.html")).unwrap();
+
+        let mut out = MemoryOutput::default();
+        let mut manifest = SiteManifestOutput::new(&mut out, &path, true, started_at);
+        manifest.write(&root.join("new.html"), b"new").unwrap();
+        manifest.finish().unwrap();
+        let files = read(out, root)["files"].clone();
+        let names: Vec<_> = files.as_object().unwrap().keys().cloned().collect();
+        // The manifest itself is written after it's been listed.
+        assert_eq!(names, ["kept.html", "new.html"]);
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use chrono::Utc;
+use handlebars::Handlebars;
+use rustup_available_packages::AvailabilityData;
+
+use crate::{
+    escape_xml, index, opts,
+    output::{Output, Purpose},
+    ChannelInfo, PathRenderData,
+};
+
+/// Lists all the HTML pages of a channel, including the ones that haven't been
+/// re-rendered during this run.
+pub fn channel_pages(
+    data: &AvailabilityData,
+    html: &opts::Html,
+    channel: ChannelInfo,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let handlebars = Handlebars::new();
+    let render = |pattern: &str, data: &PathRenderData| {
+        handlebars
+            .render_template(pattern, data)
+            .map(|path| channel.relocate(Path::new(&path)))
+            .with_context(|| format!("Invalid output pattern: {}", pattern))
+    };
+    let mut pages = Vec::new();
+    if html.index_template.is_some() {
+        pages.push(index::output_path(&html.output_pattern, channel)?);
+    }
+    let mut targets: Vec<_> = data.get_available_targets().into_iter().collect();
+    targets.sort_unstable();
+    for target in targets {
+        pages.push(render(
+            &html.output_pattern,
+            &PathRenderData::target(target),
+        )?);
+    }
+    if let Some(package_pages) = &html.package_pages {
+        let mut packages: Vec<_> = data.get_available_packages().into_iter().collect();
+        packages.sort_unstable();
+        for package in packages {
+            pages.push(render(
+                &package_pages.output_pattern,
+                &PathRenderData {
+                    package: Some(package),
+                    ..PathRenderData::target("")
+                },
+            )?);
+        }
+    }
+    if let Some(page) = html
+        .search
+        .as_ref()
+        .and_then(|search| search.fallback_page.as_ref())
+    {
+        pages.push(channel.relocate(&page.output_path));
+    }
+    if let Some(changes) = &html.changes {
+        pages.push(channel.relocate(&changes.output_path));
+    }
+    Ok(pages)
+}
+
+/// Generates a sitemap of the given pages. Since all the pages are re-rendered
+/// or confirmed to be up to date on every run, the render date is used as their
+/// modification date.
+pub fn generate_sitemap(
+    pages: &[PathBuf],
+    config: &opts::Sitemap,
+    out: &mut dyn Output,
+) -> anyhow::Result<()> {
+    let root = config.path.parent().unwrap_or_else(|| Path::new(""));
+    let base_url = config.base_url.trim_end_matches('/');
+    let lastmod = Utc::now().format("%Y-%m-%d");
+
+    let mut sitemap = String::new();
+    writeln!(sitemap, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        sitemap,
+        r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#
+    )?;
+    for page in pages {
+        let relative = match page.strip_prefix(root) {
+            Ok(relative) => relative,
+            Err(_) => {
+                log::warn!(
+                    "{} is outside of the sitemap's directory {}",
+                    page.display(),
+                    root.display()
+                );
+                continue;
+            }
+        };
+        let relative = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        writeln!(sitemap, "  <url>")?;
+        writeln!(
+            sitemap,
+            "    <loc>{}</loc>",
+            escape_xml(&format!("{}/{}", base_url, relative))
+        )?;
+        writeln!(sitemap, "    <lastmod>{}</lastmod>", lastmod)?;
+        writeln!(sitemap, "  </url>")?;
+    }
+    writeln!(sitemap, "</urlset>")?;
+    log::info!("Writing sitemap to {:?}", config.path);
+    out.set_purpose(Purpose::Sitemap);
+    out.write(&config.path, sitemap.as_bytes())
+}
@@ -0,0 +1,115 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context;
+use chrono::NaiveDate;
+use resvg::{tiny_skia, usvg};
+use rustup_available_packages::AvailabilityData;
+use serde::Serialize;
+
+use crate::{index::count_missing, opts::Tier, templates::Templates, AdditionalData};
+
+pub const TEMPLATE_NAME: &str = "social_card";
+
+/// A target's summary as shown on its card.
+#[derive(Serialize)]
+struct CardData<'a> {
+    target: &'a str,
+    tier: Tier,
+    /// How many packages that have been available within the rendered dates
+    /// are missing in the latest manifest.
+    missing: usize,
+    /// The date of the latest manifest.
+    date: Option<NaiveDate>,
+    additional: &'a AdditionalData<'a>,
+}
+
+/// Returns the path of a target's card, which is put next to its page.
+pub fn output_path(page: &Path) -> PathBuf {
+    page.with_extension("png")
+}
+
+/// Returns the absolute URL of a card, given the URL the `site_root` (the
+/// working directory if there's none) is published at.
+pub fn url(base_url: &str, site_root: Option<&Path>, card: &Path) -> anyhow::Result<String> {
+    let relative = match site_root {
+        Some(root) => card
+            .strip_prefix(root)
+            .with_context(|| format!("{:?} is outside of the site root {:?}", card, root))?,
+        None => card,
+    };
+    let relative = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    Ok(format!("{}/{}", base_url.trim_end_matches('/'), relative))
+}
+
+/// Loads the fonts the text of the cards is drawn with.
+pub fn load_fonts() -> Arc<usvg::fontdb::Database> {
+    let mut fonts = usvg::fontdb::Database::new();
+    fonts.load_system_fonts();
+    Arc::new(fonts)
+}
+
+/// Renders a social preview card of a target, summarizing its status, as a
+/// PNG image: link previews don't show SVG ones.
+///
+/// The card template is expected to be registered under the [`TEMPLATE_NAME`].
+pub fn render_card(
+    templates: &Templates,
+    fonts: &Arc<usvg::fontdb::Database>,
+    data: &AvailabilityData,
+    dates: &[NaiveDate],
+    tiers: &HashMap<Tier, Vec<String>>,
+    target: &str,
+    additional: &AdditionalData,
+) -> anyhow::Result<Vec<u8>> {
+    let tier = tiers
+        .iter()
+        .find(|(_, targets)| targets.iter().any(|t| t == target))
+        .map_or(Tier::UnknownTier, |(&tier, _)| tier);
+    let svg = templates.render(
+        TEMPLATE_NAME,
+        &CardData {
+            target,
+            tier,
+            missing: count_missing(data, target, dates),
+            date: dates.first().copied(),
+            additional,
+        },
+    )?;
+    let options = usvg::Options {
+        fontdb: Arc::clone(fonts),
+        ..Default::default()
+    };
+    let tree = usvg::Tree::from_str(&svg, &options).context("The card isn't a valid SVG")?;
+    let size = tree.size().to_int_size();
+    let mut pixmap =
+        tiny_skia::Pixmap::new(size.width(), size.height()).context("The card is empty")?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    pixmap.encode_png().context("Can't encode the card")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn absolute_urls() {
+        let card = Path::new("site/beta/x86_64-unknown-linux-gnu.png");
+        assert_eq!(
+            url("https://example.org/status/", Some(Path::new("site")), card).unwrap(),
+            "https://example.org/status/beta/x86_64-unknown-linux-gnu.png"
+        );
+        assert_eq!(
+            url("https://example.org", None, card).unwrap(),
+            "https://example.org/site/beta/x86_64-unknown-linux-gnu.png"
+        );
+        assert!(url("https://example.org", Some(Path::new("other")), card).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs::{self, File},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use chrono::NaiveDate;
+use rustup_available_packages::{availability::AvailabilityRow, AvailabilityData};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    opts::Tier,
+    tiers_table::{self, TierChange},
+};
+
+/// FNV-1a, which unlike the std hashers is guaranteed to stay the same between
+/// runs and compiler versions.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Everything a target's pages are rendered from.
+#[derive(Serialize)]
+struct TargetData<'a> {
+    dates: &'a [NaiveDate],
+    targets: &'a [&'a str],
+    rows: Vec<AvailabilityRow<'a>>,
+}
+
+/// Packages available on every target.
+pub type Available = BTreeMap<String, BTreeSet<String>>;
+
+/// Collects the packages available on every target on the `latest` date.
+pub fn snapshot(data: &AvailabilityData, latest: NaiveDate) -> Available {
+    let mut available = Available::new();
+    for target in data.get_available_targets() {
+        for package in data.get_available_packages() {
+            if data.last_available(target, package) == Some(latest) {
+                available
+                    .entry(target.to_string())
+                    .or_default()
+                    .insert(package.to_string());
+            }
+        }
+    }
+    available
+}
+
+/// What is persisted between runs.
+#[derive(Default, Serialize, Deserialize)]
+struct StateFile {
+    /// Hashes of the data the targets have been rendered from.
+    #[serde(default)]
+    hashes: BTreeMap<String, String>,
+    /// Packages available in the latest manifest of the previous run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    available: Option<Available>,
+    /// Tiers of the targets during the previous run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tiers: Option<BTreeMap<String, Tier>>,
+    /// All the tier changes noticed so far.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tier_changes: Vec<TierChange>,
+    /// A fingerprint of the assets the pages link to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    assets: Option<String>,
+}
+
+/// Older versions only kept the hashes.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Stored {
+    Hashes(BTreeMap<String, String>),
+    State(StateFile),
+}
+
+/// The state of the previous run, persisted between runs: hashes of the data
+/// the targets have been rendered from and a snapshot of the available
+/// packages.
+pub struct RenderState {
+    path: Option<PathBuf>,
+    state: StateFile,
+}
+
+impl RenderState {
+    /// Loads the state from a given path. If no path is provided every target
+    /// is considered changed.
+    pub fn load(path: Option<PathBuf>) -> anyhow::Result<Self> {
+        let state = match &path {
+            Some(path) if path.exists() => {
+                let f = File::open(path)?;
+                match serde_json::from_reader(f)
+                    .with_context(|| format!("Can't parse state file {}", path.display()))?
+                {
+                    Stored::Hashes(hashes) => StateFile {
+                        hashes,
+                        ..Default::default()
+                    },
+                    Stored::State(state) => state,
+                }
+            }
+            _ => StateFile::default(),
+        };
+        Ok(RenderState { path, state })
+    }
+
+    /// Checks whether the state is kept between runs at all.
+    pub fn is_persistent(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Returns the packages that were available during the previous run, if
+    /// it has been recorded.
+    pub fn available(&self) -> Option<&Available> {
+        self.state.available.as_ref()
+    }
+
+    /// Records the packages available during this run.
+    pub fn set_available(&mut self, available: Available) {
+        self.state.available = Some(available);
+    }
+
+    /// Compares the tiers with the ones of the previous run, recording the
+    /// changes as of the `date`.
+    pub fn update_tiers(&mut self, tiers: &HashMap<Tier, Vec<String>>, date: NaiveDate) {
+        let current = tiers_table::tiers_of(tiers);
+        if let Some(previous) = &self.state.tiers {
+            let changes = tiers_table::tier_changes(date, previous, &current);
+            // Every page lists the tiers.
+            if !changes.is_empty() {
+                self.state.hashes.clear();
+            }
+            for change in changes {
+                log::info!(
+                    "{} has moved from {:?} to {:?}",
+                    change.target,
+                    change.from,
+                    change.to
+                );
+                self.state.tier_changes.push(change);
+            }
+        }
+        self.state.tiers = Some(current);
+    }
+
+    /// Compares the assets with the ones of the previous run. The previous
+    /// copies of changed assets are removed, so every page has to link the
+    /// new ones.
+    pub fn update_assets(&mut self, fingerprint: String) {
+        if self.state.assets.as_ref() != Some(&fingerprint) {
+            self.state.hashes.clear();
+        }
+        self.state.assets = Some(fingerprint);
+    }
+
+    /// Forgets the tier changes that happened before a date, which are no
+    /// longer rendered.
+    pub fn prune_tier_changes(&mut self, since: NaiveDate) {
+        self.state
+            .tier_changes
+            .retain(|change| change.date >= since);
+    }
+
+    /// Returns all the tier changes recorded so far, the oldest first.
+    pub fn tier_changes(&self) -> &[TierChange] {
+        &self.state.tier_changes
+    }
+
+    /// Returns the targets whose data has changed since the previous run, and
+    /// remembers the new hashes.
+    pub fn changed_targets<'a>(
+        &mut self,
+        data: &'a AvailabilityData,
+        dates: &[NaiveDate],
+    ) -> anyhow::Result<HashSet<&'a str>> {
+        let all_targets = data.get_available_targets();
+        if self.path.is_none() {
+            return Ok(all_targets);
+        }
+
+        let mut targets: Vec<_> = all_targets.iter().copied().collect();
+        targets.sort_unstable();
+        let mut packages: Vec<_> = data.get_available_packages().into_iter().collect();
+        packages.sort_unstable();
+
+        let mut changed = HashSet::new();
+        for &target in &targets {
+            let target_data = TargetData {
+                dates,
+                targets: &targets,
+                rows: packages
+                    .iter()
+                    .filter_map(|pkg| data.get_availability_row(target, pkg, dates))
+                    .collect(),
+            };
+            let hash = format!("{:016x}", fnv1a(&serde_json::to_vec(&target_data)?));
+            if self.state.hashes.get(target) == Some(&hash) {
+                log::info!("Target {} hasn't changed", target);
+            } else {
+                changed.insert(target);
+                self.state.hashes.insert(target.to_string(), hash);
+            }
+        }
+        // Forget targets that are gone.
+        self.state
+            .hashes
+            .retain(|target, _| all_targets.contains(target.as_str()));
+        Ok(changed)
+    }
+
+    /// Forgets the hash of a target, so it is considered changed next time.
+    pub fn forget(&mut self, target: &str) {
+        self.state.hashes.remove(target);
+    }
+
+    /// Persists the state.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(&self.state)?)
+            .with_context(|| format!("Can't write state file {}", path.display()))
+    }
+}
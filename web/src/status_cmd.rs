@@ -0,0 +1,127 @@
+use std::{
+    env,
+    io::{self, IsTerminal, Write},
+};
+
+use anyhow::Context;
+use rustup_available_packages::{availability::TargetLifecycle, table::Table, AvailabilityData};
+use rustup_status::{channel_cache, downloader};
+use structopt::StructOpt;
+
+use crate::ConfigOpt;
+
+#[derive(StructOpt)]
+pub struct StatusOpt {
+    #[structopt(flatten)]
+    config: ConfigOpt,
+    #[structopt(short = "t", long = "target", help = "A target to show")]
+    target: String,
+    #[structopt(long = "days", help = "How many days to show", default_value = "14")]
+    days: usize,
+    #[structopt(long = "no-color", help = "Don't color the table")]
+    no_color: bool,
+}
+
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// Prints a table of the recent availability of every package on a target.
+pub fn run(opts: &StatusOpt) -> anyhow::Result<()> {
+    let (config, channel) = opts.config.load_with_channel()?;
+    let manifests = downloader(&config, &channel, opts.days)?
+        .set_cache(channel_cache(&config, &channel)?)
+        .get_last_manifests(opts.days)
+        .with_context(|| format!("Can't fetch the {} manifests", channel))?;
+    let mut dates: Vec<_> = manifests.iter().map(|manifest| manifest.date).collect();
+    dates.sort_unstable_by(|a, b| b.cmp(a));
+    let mut data = AvailabilityData::default();
+    data.add_manifests(manifests);
+    anyhow::ensure!(
+        data.get_available_targets().contains(opts.target.as_str()),
+        "{} isn't in the {} manifests",
+        opts.target,
+        channel
+    );
+
+    let table = Table::builder(&data, &opts.target).dates(dates).build();
+    let color = !opts.no_color && env::var_os("NO_COLOR").is_none() && io::stdout().is_terminal();
+    let paint = |text: &str, code: &str| {
+        if color {
+            format!("{}{}{}", code, text, RESET)
+        } else {
+            text.to_string()
+        }
+    };
+
+    // The target-independent packages follow the target's own ones.
+    let rows: Vec<_> = table
+        .packages_availability
+        .iter()
+        .map(|row| (row, row.package_name.to_string(), true))
+        .chain(
+            table
+                .target_independent
+                .iter()
+                .map(|row| (row, format!("{} (any target)", row.package_name), false)),
+        )
+        .collect();
+    let width = rows
+        .iter()
+        .map(|(_, name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("package".len());
+    let mut out = io::stdout().lock();
+    match table.lifecycle {
+        TargetLifecycle::New { since } => {
+            writeln!(out, "{} has been added on {}\n", opts.target, since)?
+        }
+        TargetLifecycle::Retired { until } => writeln!(
+            out,
+            "{} has been removed, it was last listed on {}\n",
+            opts.target, until
+        )?,
+        _ => {}
+    }
+    write!(out, "{:width$}", "package", width = width)?;
+    for date in &table.dates {
+        write!(out, "  {}", date.format("%m-%d"))?;
+    }
+    writeln!(out, "  last available")?;
+    let mut available = 0;
+    for (row, name, own) in &rows {
+        write!(out, "{:width$}", name, width = width)?;
+        for (&status, &unlisted) in row.availability_list.iter().zip(&table.target_missing) {
+            // The days the target isn't listed on are left blank.
+            let cell = if *own && unlisted {
+                "     ".to_string()
+            } else if status {
+                paint("  ✓  ", GREEN)
+            } else {
+                paint("  ✗  ", RED)
+            };
+            write!(out, "  {}", cell)?;
+        }
+        let last_available = row
+            .last_available
+            .map(|date| date.to_string())
+            .unwrap_or_else(|| "N/A".into());
+        writeln!(out, "  {}", last_available)?;
+        if row.availability_list.first() == Some(&true) {
+            available += 1;
+        }
+    }
+    if let Some(latest) = table.dates.first() {
+        writeln!(
+            out,
+            "\n{} of {} packages are available on {} in the {} {} manifest",
+            available,
+            rows.len(),
+            opts.target,
+            latest,
+            channel
+        )?;
+    }
+    Ok(())
+}
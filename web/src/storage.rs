@@ -0,0 +1,272 @@
+//! A SQLite database with the availability history.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use anyhow::Context;
+use chrono::{Duration, NaiveDate};
+use rusqlite::{params, Connection};
+use rustup_available_packages::manifest::{Manifest, PackageInfo, PackageTargets, Rename};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS manifests (
+    channel TEXT NOT NULL,
+    date TEXT NOT NULL,
+    rustc_commit TEXT,
+    PRIMARY KEY (channel, date)
+);
+CREATE TABLE IF NOT EXISTS availability (
+    channel TEXT NOT NULL,
+    date TEXT NOT NULL,
+    target TEXT NOT NULL,
+    package TEXT NOT NULL,
+    available INTEGER NOT NULL,
+    version TEXT,
+    PRIMARY KEY (channel, date, target, package)
+);
+CREATE TABLE IF NOT EXISTS renames (
+    channel TEXT NOT NULL,
+    date TEXT NOT NULL,
+    old TEXT NOT NULL,
+    new TEXT NOT NULL,
+    PRIMARY KEY (channel, date, old)
+);
+";
+
+/// The availability history of all the channels.
+pub struct Storage {
+    connection: Connection,
+}
+
+impl Storage {
+    /// Opens a database, creating it if it doesn't exist.
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let connection =
+            Connection::open(path).with_context(|| format!("Can't open {}", path.display()))?;
+        connection
+            .execute_batch(SCHEMA)
+            .with_context(|| format!("Can't create the tables in {}", path.display()))?;
+        Ok(Storage { connection })
+    }
+
+    /// Gives access to the database, e.g. to query it.
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// Stores the manifests of a channel, replacing the days that are
+    /// already stored, e.g. because their manifests have been re-published.
+    pub fn store(&mut self, channel: &str, manifests: &[Manifest]) -> anyhow::Result<()> {
+        let transaction = self.connection.transaction()?;
+        {
+            let mut manifest_stmt = transaction.prepare(
+                "INSERT OR REPLACE INTO manifests (channel, date, rustc_commit) VALUES (?, ?, ?)",
+            )?;
+            let mut availability_stmt = transaction.prepare(
+                "INSERT OR REPLACE INTO availability
+                 (channel, date, target, package, available, version)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+            )?;
+            let mut rename_stmt = transaction.prepare(
+                "INSERT OR REPLACE INTO renames (channel, date, old, new) VALUES (?, ?, ?, ?)",
+            )?;
+            // Packages and renames a re-published manifest no longer has
+            // mustn't stay behind.
+            let mut clear_availability_stmt =
+                transaction.prepare("DELETE FROM availability WHERE channel = ? AND date = ?")?;
+            let mut clear_renames_stmt =
+                transaction.prepare("DELETE FROM renames WHERE channel = ? AND date = ?")?;
+            for manifest in manifests {
+                clear_availability_stmt.execute(params![channel, manifest.date])?;
+                clear_renames_stmt.execute(params![channel, manifest.date])?;
+                let rustc_commit = manifest
+                    .packages
+                    .get("rustc")
+                    .and_then(|info| info.git_commit_hash.as_deref());
+                manifest_stmt.execute(params![channel, manifest.date, rustc_commit])?;
+                for (package, info) in &manifest.packages {
+                    for (target, target_info) in &info.targets {
+                        availability_stmt.execute(params![
+                            channel,
+                            manifest.date,
+                            target,
+                            package,
+                            target_info.available,
+                            info.version
+                        ])?;
+                    }
+                }
+                for (old, rename) in &manifest.renames {
+                    rename_stmt.execute(params![channel, manifest.date, old, rename.to])?;
+                }
+            }
+        }
+        transaction.commit()?;
+        log::info!(
+            channel = channel;
+            "Stored {} manifests of {}",
+            manifests.len(),
+            channel
+        );
+        Ok(())
+    }
+
+    /// Returns the dates of a channel's stored manifests, the latest first.
+    pub fn dates(&self, channel: &str) -> anyhow::Result<Vec<NaiveDate>> {
+        let mut stmt = self
+            .connection
+            .prepare("SELECT date FROM manifests WHERE channel = ? ORDER BY date DESC")?;
+        let dates = stmt
+            .query_map([channel], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(dates)
+    }
+
+    /// Reads a stored manifest back. Only the availability, the versions, the
+    /// renames and the commit of `rustc` are stored.
+    pub fn manifest(&self, channel: &str, date: NaiveDate) -> anyhow::Result<Option<Manifest>> {
+        Ok(self.read(channel, date, date + Duration::days(1))?.pop())
+    }
+
+    /// Reads the stored manifests of a channel that are older than `before`,
+    /// up to `days` back, the latest first.
+    pub fn history(
+        &self,
+        channel: &str,
+        before: NaiveDate,
+        days: usize,
+    ) -> anyhow::Result<Vec<Manifest>> {
+        self.read(channel, before - Duration::days(days as i64), before)
+    }
+
+    /// Reads the stored manifests of a channel from `since` up to `until`
+    /// (exclusive), the latest first.
+    fn read(
+        &self,
+        channel: &str,
+        since: NaiveDate,
+        until: NaiveDate,
+    ) -> anyhow::Result<Vec<Manifest>> {
+        let mut manifests = BTreeMap::new();
+        let mut rustc_commits = HashMap::new();
+        let mut stmt = self.connection.prepare_cached(
+            "SELECT date, rustc_commit FROM manifests
+             WHERE channel = ? AND date >= ? AND date < ?",
+        )?;
+        let mut rows = stmt.query(params![channel, since, until])?;
+        while let Some(row) = rows.next()? {
+            let date: NaiveDate = row.get(0)?;
+            rustc_commits.insert(date, row.get::<_, Option<String>>(1)?);
+            manifests.insert(
+                date,
+                Manifest {
+                    date,
+                    packages: HashMap::new(),
+                    renames: HashMap::new(),
+                    profiles: HashMap::new(),
+                },
+            );
+        }
+        let mut stmt = self.connection.prepare_cached(
+            "SELECT date, target, package, available, version FROM availability
+             WHERE channel = ? AND date >= ? AND date < ?",
+        )?;
+        let mut rows = stmt.query(params![channel, since, until])?;
+        while let Some(row) = rows.next()? {
+            let manifest = match manifests.get_mut(&row.get::<_, NaiveDate>(0)?) {
+                Some(manifest) => manifest,
+                None => continue,
+            };
+            let package: String = row.get(2)?;
+            let info = manifest
+                .packages
+                .entry(package)
+                .or_insert_with(|| PackageTargets {
+                    version: None,
+                    git_commit_hash: None,
+                    targets: HashMap::new(),
+                });
+            info.version = row.get(4)?;
+            info.targets.insert(
+                row.get(1)?,
+                PackageInfo {
+                    available: row.get(3)?,
+                    ..Default::default()
+                },
+            );
+        }
+        let mut stmt = self.connection.prepare_cached(
+            "SELECT date, old, new FROM renames WHERE channel = ? AND date >= ? AND date < ?",
+        )?;
+        let mut rows = stmt.query(params![channel, since, until])?;
+        while let Some(row) = rows.next()? {
+            if let Some(manifest) = manifests.get_mut(&row.get::<_, NaiveDate>(0)?) {
+                manifest
+                    .renames
+                    .insert(row.get(1)?, Rename { to: row.get(2)? });
+            }
+        }
+        for (date, rustc_commit) in rustc_commits {
+            if let Some(rustc) = manifests
+                .get_mut(&date)
+                .and_then(|manifest| manifest.packages.get_mut("rustc"))
+            {
+                rustc.git_commit_hash = rustc_commit;
+            }
+        }
+        Ok(manifests.into_values().rev().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fake_data, opts::FakeData};
+
+    /// Made-up manifests with only what's stored.
+    fn manifests(end: NaiveDate, days: usize) -> Vec<Manifest> {
+        let mut manifests = fake_data::manifests(&FakeData::default(), "nightly", end, days);
+        for manifest in &mut manifests {
+            manifest.profiles.clear();
+            for (package, info) in &mut manifest.packages {
+                if package != "rustc" {
+                    info.git_commit_hash = None;
+                }
+            }
+            manifest.renames.insert(
+                "rls".into(),
+                Rename {
+                    to: "rls-preview".into(),
+                },
+            );
+        }
+        manifests
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("storage.db");
+        let end = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+        let manifests = manifests(end, 5);
+        let mut storage = Storage::open(&path).unwrap();
+        storage.store("nightly", &manifests).unwrap();
+        // Re-published.
+        storage.store("nightly", &manifests[..1]).unwrap();
+
+        let dates: Vec<_> = manifests.iter().map(|manifest| manifest.date).collect();
+        assert_eq!(dates, storage.dates("nightly").unwrap());
+        assert_eq!(
+            Some(&manifests[2]),
+            storage.manifest("nightly", dates[2]).unwrap().as_ref()
+        );
+        assert_eq!(None, storage.manifest("beta", dates[2]).unwrap());
+        // Older than the latest day, two days back.
+        assert_eq!(
+            manifests[1..3].to_vec(),
+            storage.history("nightly", end, 2).unwrap()
+        );
+    }
+}
@@ -0,0 +1,44 @@
+use crate::{opts, publishes_daily, Regression, Report};
+
+/// Fails if the report of a run shows any of the problems the policy doesn't
+/// tolerate, listing all of them.
+pub fn check(config: &opts::Strictness, report: &Report) -> anyhow::Result<()> {
+    let mut problems = Vec::new();
+    for channel in &report.channels {
+        let max_skipped_days = config
+            .max_skipped_days
+            .filter(|_| publishes_daily(&channel.channel));
+        if let Some(max) = max_skipped_days {
+            let skipped = channel.manifests.skipped_days;
+            if skipped > max {
+                problems.push(format!(
+                    "{} days of {} skipped, {} allowed",
+                    skipped, channel.channel, max
+                ));
+            }
+        }
+        if let Some(regressions) = &config.regressions {
+            let watched = |regression: &&Regression| {
+                (regressions.targets.is_empty() || regressions.targets.contains(&regression.target))
+                    && (regressions.packages.is_empty()
+                        || regressions.packages.contains(&regression.package))
+            };
+            for regression in channel.regressions.iter().filter(watched) {
+                problems.push(format!(
+                    "{} has gone missing on {} in {}",
+                    regression.package, regression.target, channel.channel
+                ));
+            }
+        }
+    }
+    if config.fail_on_warnings && !report.warnings.is_empty() {
+        problems.push(format!("{} warnings logged", report.warnings.len()));
+    }
+    if problems.is_empty() {
+        return Ok(());
+    }
+    anyhow::bail!(
+        "The run doesn't meet the strictness policy: {}",
+        problems.join("; ")
+    )
+}
@@ -0,0 +1,464 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context as _;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use handlebars::{Context, Handlebars, Helper, HelperDef, RenderContext, RenderError, ScopedJson};
+use serde::Serialize;
+use serde_json::Value;
+use tera::Tera;
+
+use crate::{assets::Assets, heatmap::relative_link, i18n::Catalog, opts::Engine};
+
+/// A helper available in the templates of every engine. It takes positional
+/// arguments in Handlebars, e.g. `{{format_date date "%d %b"}}`, and named
+/// ones in Tera, e.g. `{{ format_date(date=date, format="%d %b") }}`.
+#[derive(Clone, Copy)]
+struct Function {
+    name: &'static str,
+    args: &'static [&'static str],
+    call: fn(&[&Value]) -> Result<Value, String>,
+}
+
+const FUNCTIONS: &[Function] = &[
+    Function {
+        name: "streq",
+        args: &["x", "y"],
+        call: streq,
+    },
+    // Unlike `lookup`, doesn't fail in the strict mode if the item is `null`.
+    Function {
+        name: "nth",
+        args: &["list", "idx"],
+        call: nth,
+    },
+    Function {
+        name: "format_date",
+        args: &["date", "format"],
+        call: format_date,
+    },
+    Function {
+        name: "days_ago",
+        args: &["date"],
+        call: days_ago,
+    },
+    Function {
+        name: "iso8601",
+        args: &["datetime"],
+        call: iso8601,
+    },
+];
+
+fn as_str<'a>(value: &'a Value, what: &str) -> Result<&'a str, String> {
+    value
+        .as_str()
+        .ok_or_else(|| format!("`{}` must be a string, got {}", what, value))
+}
+
+/// Parses a date, which is taken as midnight UTC, or an RFC 3339 datetime.
+fn parse_datetime(value: &Value) -> Result<DateTime<Utc>, String> {
+    let s = as_str(value, "date")?;
+    if let Ok(date) = s.parse::<NaiveDate>() {
+        let midnight = date.and_hms_opt(0, 0, 0).expect("Valid time");
+        return Ok(Utc.from_utc_datetime(&midnight));
+    }
+    DateTime::parse_from_rfc3339(s)
+        .map(|datetime| datetime.with_timezone(&Utc))
+        .map_err(|e| format!("Invalid date {:?}: {}", s, e))
+}
+
+fn streq(args: &[&Value]) -> Result<Value, String> {
+    Ok(Value::Bool(as_str(args[0], "x")? == as_str(args[1], "y")?))
+}
+
+fn nth(args: &[&Value]) -> Result<Value, String> {
+    let list = args[0]
+        .as_array()
+        .ok_or_else(|| format!("`list` must be an array, got {}", args[0]))?;
+    let idx = args[1]
+        .as_u64()
+        .ok_or_else(|| format!("`idx` must be an index, got {}", args[1]))?;
+    Ok(list.get(idx as usize).cloned().unwrap_or_default())
+}
+
+fn format_date(args: &[&Value]) -> Result<Value, String> {
+    let datetime = parse_datetime(args[0])?;
+    let format = as_str(args[1], "format")?;
+    let mut formatted = String::new();
+    std::fmt::Write::write_fmt(&mut formatted, format_args!("{}", datetime.format(format)))
+        .map_err(|_| format!("Invalid date format {:?}", format))?;
+    Ok(Value::String(formatted))
+}
+
+/// Counts the days until the page is rendered, not until it is viewed.
+fn days_ago(args: &[&Value]) -> Result<Value, String> {
+    let date = parse_datetime(args[0])?.date_naive();
+    Ok((Utc::now().date_naive() - date).num_days().into())
+}
+
+fn iso8601(args: &[&Value]) -> Result<Value, String> {
+    Ok(Value::String(parse_datetime(args[0])?.to_rfc3339()))
+}
+
+impl HelperDef for Function {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+        let args = self
+            .args
+            .iter()
+            .enumerate()
+            .map(|(idx, arg)| {
+                h.param(idx).map(|param| param.value()).ok_or_else(|| {
+                    RenderError::new(format!("`{}` misses argument `{}`", self.name, arg))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let value = (self.call)(&args)
+            .map_err(|e| RenderError::new(format!("`{}` failed: {}", self.name, e)))?;
+        Ok(ScopedJson::Derived(value))
+    }
+}
+
+impl tera::Function for Function {
+    fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let args = self
+            .args
+            .iter()
+            .map(|arg| {
+                args.get(*arg).ok_or_else(|| {
+                    tera::Error::msg(format!("`{}` misses argument `{}`", self.name, arg))
+                })
+            })
+            .collect::<tera::Result<Vec<_>>>()?;
+        (self.call)(&args).map_err(|e| tera::Error::msg(format!("`{}` failed: {}", self.name, e)))
+    }
+}
+
+thread_local! {
+    /// The output path of the page being rendered on this thread, see
+    /// [`Templates::render_page`].
+    static PAGE: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// The `asset` helper, which resolves a path of an asset to the URL of its
+/// hashed copy, e.g. `{{asset "style.css"}}` in Handlebars and
+/// `{{ asset(name="style.css") }}` in Tera.
+#[derive(Clone)]
+struct AssetHelper {
+    links: Arc<BTreeMap<String, String>>,
+    /// Whether the `links` are paths in the output, which are linked relative
+    /// to the page being rendered.
+    relative: bool,
+}
+
+impl AssetHelper {
+    fn resolve(&self, name: &Value) -> Result<Value, String> {
+        let name = as_str(name, "name")?;
+        let link = self
+            .links
+            .get(name)
+            .ok_or_else(|| format!("Unknown asset {:?}", name))?;
+        if !self.relative {
+            return Ok(Value::String(link.clone()));
+        }
+        let link = PAGE.with(|page| match &*page.borrow() {
+            Some(page) => relative_link(page.parent().unwrap_or(Path::new("")), Path::new(link)),
+            None => link.clone(),
+        });
+        Ok(Value::String(link))
+    }
+}
+
+impl HelperDef for AssetHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+        let name = h
+            .param(0)
+            .ok_or_else(|| RenderError::new("`asset` misses argument `name`"))?;
+        let url = self
+            .resolve(name.value())
+            .map_err(|e| RenderError::new(format!("`asset` failed: {}", e)))?;
+        Ok(ScopedJson::Derived(url))
+    }
+}
+
+impl tera::Function for AssetHelper {
+    fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let name = args
+            .get("name")
+            .ok_or_else(|| tera::Error::msg("`asset` misses argument `name`"))?;
+        self.resolve(name)
+            .map_err(|e| tera::Error::msg(format!("`asset` failed: {}", e)))
+    }
+}
+
+/// The `t` helper, which translates a message of the [`Catalog`] into the
+/// page's language, e.g. `{{t "missing-packages" count=3}}` in Handlebars and
+/// `{{ t(key="missing-packages", lang=additional.lang, count=3) }}` in Tera.
+/// Handlebars takes the language from `additional.lang` unless it's passed
+/// as `lang`.
+#[derive(Clone)]
+struct TranslateHelper {
+    catalog: Arc<Catalog>,
+}
+
+impl TranslateHelper {
+    fn translate(
+        &self,
+        key: &Value,
+        lang: Option<&Value>,
+        args: HashMap<&str, &Value>,
+    ) -> Result<Value, String> {
+        let key = as_str(key, "key")?;
+        let lang = lang.map(|lang| as_str(lang, "lang")).transpose()?;
+        self.catalog.translate(lang, key, &args).map(Value::String)
+    }
+}
+
+impl HelperDef for TranslateHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'reg, 'rc>, RenderError> {
+        let key = h
+            .param(0)
+            .ok_or_else(|| RenderError::new("`t` misses argument `key`"))?;
+        let lang = h
+            .hash_get("lang")
+            .map(|lang| lang.value())
+            .or_else(|| ctx.data().pointer("/additional/lang"))
+            .filter(|lang| !lang.is_null());
+        let args = h
+            .hash()
+            .iter()
+            .filter(|(name, _)| **name != "lang")
+            .map(|(name, value)| (*name, value.value()))
+            .collect();
+        let translated = self
+            .translate(key.value(), lang, args)
+            .map_err(|e| RenderError::new(format!("`t` failed: {}", e)))?;
+        Ok(ScopedJson::Derived(translated))
+    }
+}
+
+impl tera::Function for TranslateHelper {
+    fn call(&self, args: &HashMap<String, Value>) -> tera::Result<Value> {
+        let key = args
+            .get("key")
+            .ok_or_else(|| tera::Error::msg("`t` misses argument `key`"))?;
+        let lang = args.get("lang").filter(|lang| !lang.is_null());
+        let args = args
+            .iter()
+            .filter(|(name, _)| *name != "key" && *name != "lang")
+            .map(|(name, value)| (name.as_str(), value))
+            .collect();
+        self.translate(key, lang, args)
+            .map_err(|e| tera::Error::msg(format!("`t` failed: {}", e)))
+    }
+}
+
+/// Recursively collects the files with a given extension.
+fn find_files(dir: &Path, extension: &str, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Can't read {:?}", dir))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_files(&path, extension, files)?;
+        } else if path.extension().is_some_and(|ext| ext == extension) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Templates of the pages, compiled by one of the supported engines.
+pub enum Templates {
+    Handlebars(Handlebars<'static>),
+    Tera(Tera),
+}
+
+impl Templates {
+    /// Creates an empty set of templates with all the [`FUNCTIONS`] registered.
+    pub fn new(engine: Engine) -> Self {
+        match engine {
+            Engine::Handlebars => {
+                let mut handlebars = Handlebars::new();
+                for &function in FUNCTIONS {
+                    handlebars.register_helper(function.name, Box::new(function));
+                }
+                handlebars.set_strict_mode(true);
+                Templates::Handlebars(handlebars)
+            }
+            Engine::Tera => {
+                let mut tera = Tera::default();
+                // Tera only escapes templates with HTML-like names by default,
+                // while Handlebars escapes everything.
+                tera.autoescape_on(vec![""]);
+                for &function in FUNCTIONS {
+                    tera.register_function(function.name, function);
+                }
+                Templates::Tera(tera)
+            }
+        }
+    }
+
+    /// Compiles a template file and registers it under a given name.
+    pub fn register_file(&mut self, name: &str, path: &Path) -> anyhow::Result<()> {
+        match self {
+            Templates::Handlebars(handlebars) => handlebars
+                .register_template_file(name, path)
+                .map_err(anyhow::Error::new),
+            Templates::Tera(tera) => tera
+                .add_template_file(path, Some(name))
+                .map_err(anyhow::Error::new),
+        }
+        .with_context(|| format!("File path: {:?}", path))
+    }
+
+    /// Registers a Handlebars helper written in [Rhai](https://rhai.rs).
+    pub fn register_script_helper(&mut self, name: &str, path: &Path) -> anyhow::Result<()> {
+        match self {
+            Templates::Handlebars(handlebars) => handlebars
+                .register_script_helper_file(name, path)
+                .with_context(|| format!("Invalid script helper {:?}", path)),
+            Templates::Tera(_) => anyhow::bail!("Script helpers are only supported by Handlebars"),
+        }
+    }
+
+    /// Registers the `asset` helper resolving the given assets.
+    pub fn register_assets(&mut self, assets: &Assets) {
+        let helper = AssetHelper {
+            links: Arc::new(assets.links()),
+            relative: assets.relative(),
+        };
+        match self {
+            Templates::Handlebars(handlebars) => {
+                handlebars.register_helper("asset", Box::new(helper))
+            }
+            Templates::Tera(tera) => tera.register_function("asset", helper),
+        }
+    }
+
+    /// Registers the `t` helper translating the messages of a catalog.
+    pub fn register_catalog(&mut self, catalog: Arc<Catalog>) {
+        let helper = TranslateHelper { catalog };
+        match self {
+            Templates::Handlebars(handlebars) => handlebars.register_helper("t", Box::new(helper)),
+            Templates::Tera(tera) => tera.register_function("t", helper),
+        }
+    }
+
+    /// Registers every template of a directory, i.e. the `*.hbs` files for
+    /// Handlebars and the `*.tera` ones for Tera, under its path relative to
+    /// the directory without the extension, e.g. `partials/header`.
+    pub fn register_dir(&mut self, dir: &Path) -> anyhow::Result<()> {
+        let extension = match self {
+            Templates::Handlebars(_) => "hbs",
+            Templates::Tera(_) => "tera",
+        };
+        let mut files = Vec::new();
+        find_files(dir, extension, &mut files)?;
+        files.sort_unstable();
+        let named = files.into_iter().map(|path| {
+            let name = path
+                .strip_prefix(dir)
+                .expect("Found under the directory")
+                .with_extension("")
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            (path, name)
+        });
+        match self {
+            Templates::Handlebars(handlebars) => {
+                for (path, name) in named {
+                    handlebars
+                        .register_template_file(&name, &path)
+                        .with_context(|| format!("File path: {:?}", path))?;
+                }
+            }
+            // Added at once, so the templates can extend each other regardless
+            // of the order.
+            Templates::Tera(tera) => tera
+                .add_template_files(named.map(|(path, name)| (path, Some(name))))
+                .with_context(|| format!("Invalid templates in {:?}", dir))?,
+        }
+        Ok(())
+    }
+
+    /// Renders a registered template of a page written to the `path`, which
+    /// the links of the assets are relative to.
+    pub fn render_page(
+        &self,
+        name: &str,
+        data: &impl Serialize,
+        path: &Path,
+    ) -> anyhow::Result<String> {
+        PAGE.with(|page| *page.borrow_mut() = Some(path.into()));
+        let rendered = self.render(name, data);
+        PAGE.with(|page| *page.borrow_mut() = None);
+        rendered
+    }
+
+    /// Renders a registered template.
+    pub fn render(&self, name: &str, data: &impl Serialize) -> anyhow::Result<String> {
+        Ok(match self {
+            Templates::Handlebars(handlebars) => handlebars.render(name, data)?,
+            Templates::Tera(tera) => tera.render(name, &tera::Context::from_serialize(data)?)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn both_engines_have_the_functions() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let data = json!({ "channel": "nightly", "date": "2024-05-03" });
+        let cases = [
+            (
+                Engine::Handlebars,
+                "page.hbs",
+                r#"{{#if (streq channel "nightly")}}{{format_date date "%d %b"}} {{iso8601 date}}{{/if}}"#,
+            ),
+            (
+                Engine::Tera,
+                "page.tera",
+                r#"{% if streq(x=channel, y="nightly") %}{{ format_date(date=date, format="%d %b") }} {{ iso8601(datetime=date) }}{% endif %}"#,
+            ),
+        ];
+        for &(engine, file, template) in &cases {
+            let path = dir.join(file);
+            fs::write(&path, template).unwrap();
+            let mut templates = Templates::new(engine);
+            templates.register_file("page", &path).unwrap();
+            assert_eq!(
+                templates.render("page", &data).unwrap(),
+                "03 May 2024-05-03T00:00:00+00:00"
+            );
+        }
+    }
+}
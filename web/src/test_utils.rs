@@ -0,0 +1,17 @@
+//! Helpers shared by the tests.
+
+use chrono::NaiveDate;
+use rustup_available_packages::manifest::Manifest;
+
+/// Makes a manifest of the `date` listing the `(package, target, available)`
+/// triples.
+pub(crate) fn manifest(date: NaiveDate, packages: &[(&str, &str, bool)]) -> Manifest {
+    let mut data = format!("date = \"{}\"\n", date);
+    for &(package, target, available) in packages {
+        data += &format!(
+            "[pkg.\"{}\".target.\"{}\"]\navailable = {}\n",
+            package, target, available
+        );
+    }
+    toml::from_str(&data).unwrap()
+}
@@ -0,0 +1,91 @@
+//! The theme as the templates get it, with every color resolved.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+use crate::opts::{Palette, Theme, Tier};
+
+/// Colors of the default template, which come from Bootstrap.
+const DEFAULT: Colors<'static> = Colors {
+    background: "#ffffff",
+    text: "#212529",
+    available: "#b8daff",
+    missing: "#ffeeba",
+    no_manifest: "#d6d8db",
+    tiers: BTreeMap::new(),
+};
+
+fn default_tier_color(tier: Tier) -> &'static str {
+    match tier {
+        Tier::Tier1 => "#28a745",
+        Tier::Tier2 => "#007bff",
+        Tier::Tier25 => "#17a2b8",
+        Tier::Tier3 => "#6c757d",
+        Tier::UnknownTier => "#343a40",
+    }
+}
+
+/// A [`Palette`] with every color set, including the color of every tier.
+#[derive(Serialize)]
+pub struct Colors<'a> {
+    background: &'a str,
+    text: &'a str,
+    available: &'a str,
+    missing: &'a str,
+    no_manifest: &'a str,
+    tiers: BTreeMap<Tier, &'a str>,
+}
+
+impl<'a> Colors<'a> {
+    /// Takes the colors that aren't set in the `palette` from the `fallback`.
+    fn new(palette: &'a Palette, fallback: &Colors<'a>) -> Self {
+        let pick = |color: &'a Option<String>, default| color.as_deref().unwrap_or(default);
+        let tiers = Tier::iter()
+            .map(|tier| {
+                let color = palette.tiers.get(&tier).map(String::as_str);
+                let default = fallback.tiers.get(&tier).copied();
+                (
+                    tier,
+                    color
+                        .or(default)
+                        .unwrap_or_else(|| default_tier_color(tier)),
+                )
+            })
+            .collect();
+        Colors {
+            background: pick(&palette.background, fallback.background),
+            text: pick(&palette.text, fallback.text),
+            available: pick(&palette.available, fallback.available),
+            missing: pick(&palette.missing, fallback.missing),
+            no_manifest: pick(&palette.no_manifest, fallback.no_manifest),
+            tiers,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ThemeData<'a> {
+    brand_name: Option<&'a str>,
+    brand_logo: Option<&'a str>,
+    light: Colors<'a>,
+    /// `null` unless a dark theme is configured.
+    dark: Option<Colors<'a>>,
+}
+
+impl<'a> ThemeData<'a> {
+    pub fn new(theme: &'a Theme) -> Self {
+        let light = Colors::new(&theme.light, &DEFAULT);
+        let dark = theme
+            .dark
+            .as_ref()
+            .map(|palette| Colors::new(palette, &light));
+        ThemeData {
+            brand_name: theme.brand_name.as_deref(),
+            brand_logo: theme.brand_logo.as_deref(),
+            light,
+            dark,
+        }
+    }
+}
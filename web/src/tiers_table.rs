@@ -1,16 +1,78 @@
 use crate::opts::Tier;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
 use std::{
     borrow::Cow,
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
 };
 
+/// A target of a tier, whether it is available and its recent tier change.
+type TierTarget = (String, bool, Option<TierChange>);
+
 #[derive(serde::Serialize)]
 pub struct TiersTable<'a> {
     /// A list of tier names and their targets.
-    tiers_and_targets: Vec<(Tier, Vec<(String, bool)>)>,
+    tiers_and_targets: Vec<(Tier, Vec<TierTarget>)>,
     unknown_tier: Vec<Cow<'a, str>>,
 }
 
+/// A target moving to another tier. Targets that aren't listed in any tier are
+/// in the [`Tier::UnknownTier`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TierChange {
+    /// The date of the latest manifest when the change was noticed.
+    pub date: NaiveDate,
+    pub target: String,
+    pub from: Tier,
+    pub to: Tier,
+    /// Whether the target has moved to a higher tier.
+    pub promoted: bool,
+}
+
+impl TierChange {
+    pub fn new(date: NaiveDate, target: String, from: Tier, to: Tier) -> Self {
+        TierChange {
+            date,
+            target,
+            from,
+            to,
+            // Higher tiers go first.
+            promoted: to < from,
+        }
+    }
+}
+
+/// Finds the targets whose tiers differ between two assignments, sorted by
+/// target.
+pub fn tier_changes(
+    date: NaiveDate,
+    previous: &BTreeMap<String, Tier>,
+    current: &BTreeMap<String, Tier>,
+) -> Vec<TierChange> {
+    let tier = |map: &BTreeMap<String, Tier>, target: &str| {
+        map.get(target).copied().unwrap_or(Tier::UnknownTier)
+    };
+    let mut targets: Vec<_> = previous.keys().chain(current.keys()).collect();
+    targets.sort_unstable();
+    targets.dedup();
+    targets
+        .into_iter()
+        .filter_map(|target| {
+            let (from, to) = (tier(previous, target), tier(current, target));
+            (from != to).then(|| TierChange::new(date, target.clone(), from, to))
+        })
+        .collect()
+}
+
+/// Maps every listed target to its tier.
+pub fn tiers_of(tiers: &HashMap<Tier, Vec<String>>) -> BTreeMap<String, Tier> {
+    tiers
+        .iter()
+        .filter(|(tier, _)| **tier != Tier::UnknownTier)
+        .flat_map(|(tier, targets)| targets.iter().map(move |target| (target.clone(), *tier)))
+        .collect()
+}
+
 fn inverse_tiers_map(map: &HashMap<Tier, Vec<String>>) -> HashMap<&str, Tier> {
     map.iter()
         .flat_map(|(tier, targets)| targets.iter().map(move |target| (target as &str, *tier)))
@@ -21,7 +83,7 @@ fn find_unknown<'a>(
     tiers: &HashMap<Tier, Vec<String>>,
     targets: &HashSet<&'a str>,
 ) -> Vec<Cow<'a, str>> {
-    let inversed_tiers = inverse_tiers_map(&tiers);
+    let inversed_tiers = inverse_tiers_map(tiers);
     let not_listed = targets.iter().filter_map(|&target| {
         let tier = inversed_tiers
             .get(target)
@@ -45,7 +107,12 @@ fn find_unknown<'a>(
 }
 
 impl<'a> TiersTable<'a> {
-    pub fn new(tiers: HashMap<Tier, Vec<String>>, targets: &HashSet<&'a str>) -> Self {
+    /// The `changes` mark the targets they are about, the latest change wins.
+    pub fn new(
+        tiers: HashMap<Tier, Vec<String>>,
+        targets: &HashSet<&'a str>,
+        changes: &[TierChange],
+    ) -> Self {
         let unknown = find_unknown(&tiers, targets);
         let mut v: Vec<_> = tiers
             .into_iter()
@@ -56,16 +123,113 @@ impl<'a> TiersTable<'a> {
                     .into_iter()
                     .map(|target| {
                         let contains = targets.contains(&target as &str);
-                        (target, contains)
+                        let change = changes
+                            .iter()
+                            .filter(|change| change.target == target)
+                            .max_by_key(|change| change.date)
+                            .cloned();
+                        (target, contains, change)
                     })
                     .collect();
                 (tier, tier_targets)
             })
             .collect();
-        v.sort_unstable();
+        v.sort_unstable_by_key(|(tier, _)| *tier);
         TiersTable {
             tiers_and_targets: v,
             unknown_tier: unknown,
         }
     }
+
+    /// Targets that aren't listed in any tier.
+    pub fn unknown_targets(&self) -> &[Cow<'a, str>] {
+        &self.unknown_tier
+    }
+
+    /// All the targets of the table, the ones of the tiers first.
+    pub fn targets(&self) -> impl Iterator<Item = &str> {
+        self.tiers_and_targets
+            .iter()
+            .flat_map(|(_, targets)| targets.iter().map(|(target, _, _)| target.as_str()))
+            .chain(self.unknown_tier.iter().map(|target| &**target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, day).unwrap()
+    }
+
+    fn tiers(list: &[(Tier, &[&str])]) -> HashMap<Tier, Vec<String>> {
+        list.iter()
+            .map(|(tier, targets)| (*tier, targets.iter().map(|t| t.to_string()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn new_sorts_and_marks_targets() {
+        let tiers = tiers(&[
+            (Tier::Tier2, &["b", "a"]),
+            (Tier::Tier1, &["c"]),
+            (Tier::UnknownTier, &["z"]),
+        ]);
+        let targets: HashSet<_> = ["a", "c", "x"].iter().copied().collect();
+        let changes = [
+            TierChange::new(date(1), "a".into(), Tier::Tier3, Tier::Tier2),
+            TierChange::new(date(2), "a".into(), Tier::Tier1, Tier::Tier2),
+        ];
+        let table = TiersTable::new(tiers, &targets, &changes);
+
+        let tiers: Vec<_> = table.tiers_and_targets.iter().map(|(t, _)| *t).collect();
+        assert_eq!(tiers, [Tier::Tier1, Tier::Tier2]);
+        let tier2 = &table.tiers_and_targets[1].1;
+        assert_eq!(tier2[0].0, "a");
+        assert!(tier2[0].1);
+        assert_eq!(tier2[0].2.as_ref().map(|c| c.date), Some(date(2)));
+        assert_eq!(tier2[1], ("b".to_string(), false, None));
+        assert_eq!(table.unknown_targets(), ["x", "z"]);
+        assert_eq!(
+            table.targets().collect::<Vec<_>>(),
+            ["c", "a", "b", "x", "z"]
+        );
+    }
+
+    #[test]
+    fn changes_between_assignments() {
+        let previous = tiers_of(&tiers(&[(Tier::Tier2, &["a", "b"]), (Tier::Tier3, &["c"])]));
+        let current = tiers_of(&tiers(&[
+            (Tier::Tier1, &["a"]),
+            (Tier::Tier3, &["b", "d"]),
+            (Tier::UnknownTier, &["c"]),
+        ]));
+        assert_eq!(
+            tier_changes(date(3), &previous, &current),
+            [
+                TierChange::new(date(3), "a".into(), Tier::Tier2, Tier::Tier1),
+                TierChange::new(date(3), "b".into(), Tier::Tier2, Tier::Tier3),
+                TierChange::new(date(3), "c".into(), Tier::Tier3, Tier::UnknownTier),
+                TierChange::new(date(3), "d".into(), Tier::UnknownTier, Tier::Tier3),
+            ]
+        );
+        let promotions: Vec<_> = tier_changes(date(3), &previous, &current)
+            .iter()
+            .map(|change| change.promoted)
+            .collect();
+        assert_eq!(promotions, [true, false, false, true]);
+    }
+
+    #[test]
+    fn tiers_of_skips_the_unknown_tier() {
+        let map = tiers_of(&tiers(&[
+            (Tier::Tier1, &["a"]),
+            (Tier::UnknownTier, &["b"]),
+        ]));
+        assert_eq!(
+            map.into_iter().collect::<Vec<_>>(),
+            [("a".to_string(), Tier::Tier1)]
+        );
+    }
 }
@@ -0,0 +1,462 @@
+//! Explanations of missing packages by the
+//! [toolstate](https://github.com/rust-lang-nursery/rust-toolstate) history,
+//! which records whether every tool built and passed its tests on every
+//! commit of `rust-lang/rust`.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    env,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use chrono::NaiveDate;
+use rustup_available_packages::AvailabilityData;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{http::Http, opts};
+
+const RUST_REPO: &str = "https://github.com/rust-lang/rust";
+
+/// The commit a tool broke at.
+#[derive(Debug, Clone, Serialize)]
+pub struct Culprit {
+    pub commit: String,
+    /// The tool's status since the commit, e.g. `build-fail`.
+    pub status: String,
+    /// The pull request merged by the commit, if it's known.
+    pub pull_request: Option<u64>,
+    /// A link to the pull request, or to the commit if the pull request
+    /// isn't known.
+    pub url: String,
+}
+
+/// Why a package went missing on a target.
+#[derive(Debug, Clone, Serialize)]
+pub struct Explanation {
+    pub last_available: NaiveDate,
+    pub first_missing: NaiveDate,
+    /// The commits `rustc` was built from on those days.
+    pub last_available_commit: Option<String>,
+    pub first_missing_commit: Option<String>,
+    /// A link to the changes between the commits.
+    pub compare_url: Option<String>,
+    /// The first commit in between on which the tool failed, if the toolstate
+    /// history knows both of the commits.
+    pub broken_by: Option<Culprit>,
+}
+
+/// A line of a toolstate history: a commit and the statuses of the tools on it.
+#[derive(Debug, PartialEq)]
+struct Entry {
+    commit: String,
+    statuses: HashMap<String, String>,
+}
+
+/// Returns the OS whose toolstate history applies to a target.
+fn os(target: &str) -> &'static str {
+    if target.contains("windows") {
+        "windows"
+    } else {
+        "linux"
+    }
+}
+
+/// Returns the toolstate name of a package, e.g. `clippy-driver` for
+/// `clippy-preview`.
+fn tool_name(package: &str) -> &str {
+    match package.trim_end_matches("-preview") {
+        "clippy" => "clippy-driver",
+        tool => tool,
+    }
+}
+
+/// Parses a history, which is a line per commit with the commit and a JSON
+/// object of the tools' statuses, separated by a tab.
+fn parse_history(text: &str) -> Vec<Entry> {
+    text.lines()
+        .filter_map(|line| {
+            let (commit, statuses) = line.split_once('\t')?;
+            Some(Entry {
+                commit: commit.trim().to_string(),
+                statuses: serde_json::from_str(statuses).ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Returns the entries of a history after `good` and up to `bad`, the oldest
+/// first, if the history lists both of the commits.
+fn range<'h>(history: &'h [Entry], good: &str, bad: &str) -> Option<Vec<&'h Entry>> {
+    let position = |commit: &str| history.iter().position(|entry| entry.commit == commit);
+    let (good, bad) = (position(good)?, position(bad)?);
+    // The histories might list the commits either way.
+    Some(if good < bad {
+        history[good + 1..=bad].iter().collect()
+    } else {
+        history[bad..good].iter().rev().collect()
+    })
+}
+
+/// Returns the entries of a history of the given commits, in their order.
+/// The commits the history doesn't list are skipped.
+fn entries_of<'h>(history: &'h [Entry], commits: &[&str]) -> Vec<&'h Entry> {
+    let by_commit: HashMap<_, _> = history
+        .iter()
+        .map(|entry| (entry.commit.as_str(), entry))
+        .collect();
+    commits
+        .iter()
+        .filter_map(|commit| by_commit.get(commit).copied())
+        .collect()
+}
+
+/// Finds the first of the entries on which a tool wasn't passing its tests,
+/// along with its status.
+fn first_failure<'h>(entries: &[&'h Entry], tool: &str) -> Option<(&'h str, &'h str)> {
+    entries.iter().find_map(|entry| {
+        let status = entry.statuses.get(tool)?;
+        (status != "test-pass").then_some((entry.commit.as_str(), status.as_str()))
+    })
+}
+
+#[derive(Deserialize)]
+struct CommitMessage {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct Commit {
+    #[serde(default)]
+    sha: String,
+    commit: CommitMessage,
+}
+
+#[derive(Deserialize)]
+struct Comparison {
+    commits: Vec<Commit>,
+}
+
+/// Finds the pull request number in a merge commit's message, e.g. `Auto
+/// merge of #12345 - ...`.
+fn pull_request(message: &str) -> Option<u64> {
+    let number = message.split_once("merge of #")?.1;
+    let end = number
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(number.len());
+    number[..end].parse().ok()
+}
+
+/// A history URL, a tool and the `good` and `bad` commits.
+type CulpritKey = (String, String, String, String);
+
+/// What the explainers have fetched, which the next ones reuse for a while,
+/// e.g. the other channels of a run or the re-renderings of `serve` and
+/// `watch`.
+struct Fetched {
+    since: Instant,
+    histories: HashMap<String, Arc<Vec<Entry>>>,
+    culprits: HashMap<CulpritKey, Option<Culprit>>,
+}
+
+static FETCHED: Mutex<Option<Fetched>> = Mutex::new(None);
+
+/// For how long the fetched histories and culprits are reused.
+const REUSE_FOR: Duration = Duration::from_secs(60 * 60);
+
+fn with_fetched<T>(f: impl FnOnce(&mut Fetched) -> T) -> T {
+    let mut fetched = FETCHED.lock().unwrap_or_else(PoisonError::into_inner);
+    let fetched = match &mut *fetched {
+        Some(fetched) if fetched.since.elapsed() < REUSE_FOR => fetched,
+        stale => stale.insert(Fetched {
+            since: Instant::now(),
+            histories: HashMap::new(),
+            culprits: HashMap::new(),
+        }),
+    };
+    f(fetched)
+}
+
+/// Explains missing packages, fetching the histories and the pull requests
+/// once.
+pub struct Explainer<'a> {
+    config: &'a opts::Toolstate,
+    http: &'a Http,
+    token: Option<String>,
+}
+
+impl<'a> Explainer<'a> {
+    pub fn new(config: &'a opts::Toolstate, http: &'a Http) -> Self {
+        Explainer {
+            config,
+            http,
+            token: config
+                .token
+                .clone()
+                .or_else(|| env::var("GITHUB_TOKEN").ok()),
+        }
+    }
+
+    fn history(&self, url: &str) -> anyhow::Result<Arc<Vec<Entry>>> {
+        if let Some(history) = with_fetched(|fetched| fetched.histories.get(url).cloned()) {
+            return Ok(history);
+        }
+        log::info!("Fetching the toolstate history from {}", url);
+        let text = self
+            .http
+            .send(self.http.client().get(url))
+            .and_then(|response| Ok(response.error_for_status()?.text()?))
+            .with_context(|| format!("Can't fetch the toolstate history {}", url))?;
+        let history = Arc::new(parse_history(&text));
+        with_fetched(|fetched| {
+            fetched
+                .histories
+                .insert(url.to_string(), Arc::clone(&history))
+        });
+        Ok(history)
+    }
+
+    /// Sends a request to the GitHub API.
+    fn github<T: DeserializeOwned>(&self, path: &str) -> anyhow::Result<T> {
+        let url = format!("{}/{}", self.config.api_url.trim_end_matches('/'), path);
+        let mut request = self
+            .http
+            .client()
+            .get(url)
+            .header("Accept", "application/vnd.github+json");
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        Ok(self.http.send(request)?.error_for_status()?.json()?)
+    }
+
+    /// Looks up the pull request merged by a commit.
+    fn pull_request(&self, commit: &str) -> anyhow::Result<Option<u64>> {
+        let commit: Commit = self.github(&format!("repos/rust-lang/rust/commits/{}", commit))?;
+        Ok(pull_request(&commit.commit.message))
+    }
+
+    /// Lists the commits after `good` and up to `bad`, the oldest first.
+    fn commits_between(&self, good: &str, bad: &str) -> anyhow::Result<Vec<Commit>> {
+        let comparison: Comparison =
+            self.github(&format!("repos/rust-lang/rust/compare/{}...{}", good, bad))?;
+        Ok(comparison.commits)
+    }
+
+    /// Finds the first commit after `good` and up to `bad` on which a tool
+    /// wasn't passing its tests.
+    fn culprit(
+        &self,
+        os: &'static str,
+        tool: &str,
+        good: &str,
+        bad: &str,
+    ) -> anyhow::Result<Option<Culprit>> {
+        let url = self.config.history_url.replace("{{os}}", os);
+        let key = (url, tool.to_string(), good.to_string(), bad.to_string());
+        if let Some(culprit) = with_fetched(|fetched| fetched.culprits.get(&key).cloned()) {
+            return Ok(culprit);
+        }
+        let history = self.history(&key.0)?;
+        let mut message = None;
+        let failed = match range(&history, good, bad) {
+            Some(entries) => first_failure(&entries, tool),
+            // The history doesn't list every commit a nightly is built from,
+            // so the range is bracketed by the ones in between it does list.
+            None if !self.config.skip_pull_requests => {
+                let commits = self.commits_between(good, bad).unwrap_or_else(|e| {
+                    log::warn!("Can't list the commits from {} to {}: {:#}", good, bad, e);
+                    Vec::new()
+                });
+                let shas: Vec<_> = commits.iter().map(|commit| commit.sha.as_str()).collect();
+                let failed = first_failure(&entries_of(&history, &shas), tool);
+                message = failed.and_then(|(failed, _)| {
+                    let commit = commits.iter().find(|commit| commit.sha == failed)?;
+                    Some(commit.commit.message.clone())
+                });
+                failed
+            }
+            None => None,
+        };
+        let culprit = failed.map(|(commit, status)| {
+            let pull_request = match &message {
+                Some(message) => pull_request(message),
+                None if self.config.skip_pull_requests => None,
+                // The explanation is still useful without a pull request.
+                None => self.pull_request(commit).unwrap_or_else(|e| {
+                    log::warn!("Can't find the pull request of {}: {:#}", commit, e);
+                    None
+                }),
+            };
+            let url = match pull_request {
+                Some(number) => format!("{}/pull/{}", RUST_REPO, number),
+                None => format!("{}/commit/{}", RUST_REPO, commit),
+            };
+            Culprit {
+                commit: commit.to_string(),
+                status: status.to_string(),
+                pull_request,
+                url,
+            }
+        });
+        with_fetched(|fetched| fetched.culprits.insert(key, culprit.clone()));
+        Ok(culprit)
+    }
+
+    /// Explains why a package is missing on a target on the latest of the
+    /// `dates`, which are sorted in descending order. Nothing is explained if
+    /// the package is available or hasn't been available on any of the dates.
+    pub fn explain(
+        &self,
+        data: &AvailabilityData,
+        dates: &[NaiveDate],
+        target: &str,
+        package: &str,
+    ) -> anyhow::Result<Option<Explanation>> {
+        let row = match data.get_availability_row(target, package, dates) {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+        let known: Vec<_> = dates
+            .iter()
+            .zip(&row.availability_list)
+            .filter(|(&date, _)| {
+                !data.is_manifest_missing(date) && data.is_target_listed(target, date)
+            })
+            .collect();
+        let (first_missing, last_available) = match known.first() {
+            Some((_, true)) | None => return Ok(None),
+            Some(_) => match known.windows(2).find(|pair| *pair[1].1) {
+                Some(pair) => (*pair[0].0, *pair[1].0),
+                None => return Ok(None),
+            },
+        };
+        let good = data.rustc_commit(last_available);
+        let bad = data.rustc_commit(first_missing);
+        let compare_url = good
+            .zip(bad)
+            .map(|(good, bad)| format!("{}/compare/{}...{}", RUST_REPO, good, bad));
+        let broken_by = match good.zip(bad) {
+            Some((good, bad)) => self.culprit(os(target), tool_name(package), good, bad)?,
+            None => None,
+        };
+        Ok(Some(Explanation {
+            last_available,
+            first_missing,
+            last_available_commit: good.map(String::from),
+            first_missing_commit: bad.map(String::from),
+            compare_url,
+            broken_by,
+        }))
+    }
+}
+
+/// Explains the packages missing on the latest date on the given targets,
+/// keyed by target and package. Failures are only logged, since the pages are
+/// useful without the explanations.
+pub fn explain_missing<'a>(
+    data: &'a AvailabilityData,
+    dates: &[NaiveDate],
+    targets: &HashSet<&'a str>,
+    config: &opts::Toolstate,
+    http: &Http,
+) -> HashMap<&'a str, BTreeMap<&'a str, Explanation>> {
+    let mut targets: Vec<_> = targets.iter().copied().collect();
+    targets.sort_unstable();
+    let mut packages: Vec<_> = data.get_available_packages().into_iter().collect();
+    packages.sort_unstable();
+    let explainer = Explainer::new(config, http);
+    let mut explanations = HashMap::new();
+    for target in targets {
+        for &package in &packages {
+            match explainer.explain(data, dates, target, package) {
+                Ok(Some(explanation)) => {
+                    explanations
+                        .entry(target)
+                        .or_insert_with(BTreeMap::new)
+                        .insert(package, explanation);
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    log::error!("Can't explain the missing packages: {:#}", e);
+                    return explanations;
+                }
+            }
+        }
+    }
+    explanations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HISTORY: &str = "\
+c1\t{\"miri\":\"test-pass\",\"clippy-driver\":\"test-pass\"}
+c2\t{\"miri\":\"test-pass\",\"clippy-driver\":\"build-fail\"}
+not a line
+c3\t{\"miri\":\"test-fail\",\"clippy-driver\":\"build-fail\"}
+c4\t{\"miri\":\"test-fail\"}
+";
+
+    fn commits(entries: &[&Entry]) -> Vec<String> {
+        entries.iter().map(|entry| entry.commit.clone()).collect()
+    }
+
+    #[test]
+    fn parses_histories() {
+        let history = parse_history(HISTORY);
+        assert_eq!(4, history.len());
+        assert_eq!("c2", history[1].commit);
+        assert_eq!(
+            Some("build-fail"),
+            history[1].statuses["clippy-driver"].get(..)
+        );
+        assert!(!history[3].statuses.contains_key("clippy-driver"));
+    }
+
+    #[test]
+    fn finds_pull_requests() {
+        assert_eq!(
+            Some(12345),
+            pull_request("Auto merge of #12345 - user:branch, r=reviewer")
+        );
+        assert_eq!(Some(7), pull_request("Rollup merge of #7"));
+        assert_eq!(None, pull_request("Update cargo"));
+        assert_eq!(None, pull_request("Auto merge of #abc"));
+    }
+
+    #[test]
+    fn names_tools() {
+        assert_eq!("clippy-driver", tool_name("clippy-preview"));
+        assert_eq!("clippy-driver", tool_name("clippy"));
+        assert_eq!("miri", tool_name("miri-preview"));
+        assert_eq!("rls", tool_name("rls"));
+    }
+
+    #[test]
+    fn ranges() {
+        let history = parse_history(HISTORY);
+        let forward = range(&history, "c1", "c3").unwrap();
+        assert_eq!(vec!["c2", "c3"], commits(&forward));
+        assert_eq!(
+            Some(("c2", "build-fail")),
+            first_failure(&forward, "clippy-driver")
+        );
+        assert_eq!(Some(("c3", "test-fail")), first_failure(&forward, "miri"));
+        assert_eq!(None, first_failure(&forward, "rls"));
+
+        // Newest first.
+        let mut reversed = parse_history(HISTORY);
+        reversed.reverse();
+        let backward = range(&reversed, "c1", "c3").unwrap();
+        assert_eq!(vec!["c2", "c3"], commits(&backward));
+
+        assert_eq!(None, range(&history, "c0", "c3"));
+        // Bracketed by the commits the history lists.
+        let bracketed = entries_of(&history, &["c0", "c2", "c5", "c4"]);
+        assert_eq!(vec!["c2", "c4"], commits(&bracketed));
+        assert_eq!(Some(("c4", "test-fail")), first_failure(&bracketed, "miri"));
+    }
+}
@@ -0,0 +1,168 @@
+use std::path::Path;
+
+use anyhow::Context;
+use chrono::NaiveDate;
+use handlebars::Handlebars;
+use rustup_available_packages::{aggregate::PeriodAvailability, AvailabilityData};
+use serde::Serialize;
+
+use crate::{
+    heatmap::relative_link,
+    opts::{self, Period},
+    output::{Output, Purpose},
+    target_output_path,
+    templates::Templates,
+    AdditionalData, ChannelInfo, PathRenderData, Shared,
+};
+
+const TEMPLATE_NAME: &str = "trends";
+
+/// A column of the trends table.
+#[derive(Serialize)]
+struct PeriodColumn {
+    /// The first day of the period.
+    start: NaiveDate,
+    /// A short name of the period, e.g. `2024-W18` or `2024-05`.
+    label: String,
+}
+
+/// Availability of a package over the periods.
+#[derive(Serialize)]
+struct TrendRow<'a> {
+    package_name: &'a str,
+    /// The availability per period, whose `percentage` is `null` if there
+    /// isn't a single manifest within the period.
+    availability_list: Vec<PeriodAvailability>,
+}
+
+/// The same target's trends on a channel.
+#[derive(Serialize)]
+struct ChannelTrends<'a> {
+    name: &'a str,
+    /// A link relative to the page.
+    link: String,
+    current: bool,
+}
+
+/// A ready-to-render table of a target's availability per period.
+#[derive(Serialize)]
+struct TrendsTable<'a> {
+    current_target: &'a str,
+    /// A link to the target's page relative to the trends page.
+    target_page: String,
+    /// The target's trends on every configured channel.
+    channels: Vec<ChannelTrends<'a>>,
+    period: Period,
+    periods: Vec<PeriodColumn>,
+    packages_availability: Vec<TrendRow<'a>>,
+    additional: &'a AdditionalData<'a>,
+}
+
+fn label(period: Period, start: NaiveDate) -> String {
+    match period {
+        Period::Week => start.format("%G-W%V").to_string(),
+        Period::Month => start.format("%Y-%m").to_string(),
+    }
+}
+
+/// Renders a page per target with the availability of every package rolled up
+/// into periods.
+///
+/// `dates` are all the known dates in descending order, not only the rendered
+/// ones.
+pub fn generate_trends(
+    data: &AvailabilityData,
+    dates: &[NaiveDate],
+    config: &opts::Trends,
+    html: &opts::Html,
+    channel: ChannelInfo,
+    shared: &Shared,
+    out: &mut dyn Output,
+) -> anyhow::Result<()> {
+    let mut templates = Templates::new(html.engine);
+    for dir in &html.template_dirs {
+        templates.register_dir(dir)?;
+    }
+    shared.register(&mut templates);
+    templates.register_file(TEMPLATE_NAME, &config.template_path)?;
+    let targets = data.get_available_targets();
+    let additional = AdditionalData::new(&html.tiers, &targets, &[], &html.theme, channel);
+    let mut packages: Vec<_> = data.get_available_packages().into_iter().collect();
+    packages.sort_unstable();
+    let mut targets: Vec<_> = targets.into_iter().collect();
+    targets.sort_unstable();
+
+    let handlebars = Handlebars::new();
+    for target in targets {
+        log::info!(target_triple = target; "Generating trends for {}", target);
+        let packages_availability: Vec<_> = packages
+            .iter()
+            .filter_map(|pkg| {
+                Some(TrendRow {
+                    package_name: pkg,
+                    availability_list: data.aggregate(
+                        target,
+                        pkg,
+                        dates,
+                        config.period.into(),
+                        config.periods,
+                    )?,
+                })
+            })
+            .collect();
+        let periods = packages_availability
+            .first()
+            .map(|row| {
+                row.availability_list
+                    .iter()
+                    .map(|period| PeriodColumn {
+                        start: period.start,
+                        label: label(config.period, period.start),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let output_path = Handlebars::new()
+            .render_template(&config.output_pattern, &PathRenderData::target(target))
+            .with_context(|| format!("Invalid trends output pattern: {}", config.output_pattern))?;
+        let output_path = Path::new(&output_path);
+        let dir = channel
+            .relocate(output_path)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let channels = channel
+            .all
+            .iter()
+            .map(|name| ChannelTrends {
+                name,
+                link: relative_link(
+                    &dir,
+                    &ChannelInfo::new(name, channel.all).relocate(output_path),
+                ),
+                current: name == channel.name,
+            })
+            .collect();
+        let target_page = target_output_path(&handlebars, &html.output_pattern, target, channel)?;
+        let table = TrendsTable {
+            current_target: target,
+            target_page: relative_link(&dir, &target_page),
+            channels,
+            period: config.period,
+            periods,
+            packages_availability,
+            additional: &additional,
+        };
+
+        let output_path = channel.relocate(output_path);
+        let rendered = templates
+            .render_page(TEMPLATE_NAME, &table, &output_path)
+            .with_context(|| {
+                format!("Can't render [{:?}] for [{}]", config.template_path, target)
+            })?;
+        log::info!("Writing trends of {} to {:?}", target, output_path);
+        out.set_purpose(Purpose::Trends);
+        out.write(&output_path, rendered.as_bytes())?;
+    }
+    Ok(())
+}
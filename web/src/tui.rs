@@ -0,0 +1,518 @@
+//! An interactive terminal viewer of the stored or cached statuses.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::{self, IsTerminal},
+};
+
+use chrono::NaiveDate;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Cell, List, ListItem, ListState, Paragraph, Row, Table, TableState},
+    DefaultTerminal, Frame,
+};
+use structopt::StructOpt;
+
+use crate::{query, ConfigOpt};
+
+#[derive(StructOpt)]
+pub struct TuiOpt {
+    #[structopt(flatten)]
+    config: ConfigOpt,
+}
+
+/// The status of a package on a target on a day.
+struct Status {
+    available: bool,
+    version: Option<String>,
+}
+
+/// Everything known about a channel.
+struct Channel {
+    name: String,
+    /// The latest date first.
+    dates: Vec<NaiveDate>,
+    targets: Vec<String>,
+    /// Keyed by target, package and date.
+    statuses: HashMap<String, HashMap<String, HashMap<NaiveDate, Status>>>,
+}
+
+impl Channel {
+    fn status(&self, target: &str, package: &str, date: NaiveDate) -> Option<&Status> {
+        self.statuses.get(target)?.get(package)?.get(&date)
+    }
+
+    /// Packages ever seen on a target, sorted by name.
+    fn packages(&self, target: &str) -> Vec<&str> {
+        let mut packages: Vec<_> = self
+            .statuses
+            .get(target)
+            .into_iter()
+            .flat_map(|packages| packages.keys().map(String::as_str))
+            .collect();
+        packages.sort_unstable();
+        packages
+    }
+}
+
+/// Groups the rows by channel, keeping the configured order of the channels.
+fn channels(rows: Vec<query::Row>, names: &[String]) -> Vec<Channel> {
+    let mut channels: Vec<_> = names
+        .iter()
+        .map(|name| Channel {
+            name: name.clone(),
+            dates: Vec::new(),
+            targets: Vec::new(),
+            statuses: HashMap::new(),
+        })
+        .collect();
+    for row in rows {
+        let channel = match channels.iter_mut().find(|c| c.name == row.channel) {
+            Some(channel) => channel,
+            None => continue,
+        };
+        let status = Status {
+            available: row.available,
+            version: row.version,
+        };
+        channel
+            .statuses
+            .entry(row.target)
+            .or_default()
+            .entry(row.package)
+            .or_default()
+            .insert(row.date, status);
+    }
+    for channel in &mut channels {
+        let dates: BTreeSet<_> = channel
+            .statuses
+            .values()
+            .flat_map(|packages| packages.values())
+            .flat_map(|days| days.keys().copied())
+            .collect();
+        channel.dates = dates.into_iter().rev().collect();
+        channel.targets = channel.statuses.keys().cloned().collect();
+        channel.targets.sort_unstable();
+    }
+    channels
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Focus {
+    Targets,
+    Packages,
+}
+
+struct App {
+    channels: Vec<Channel>,
+    channel: usize,
+    focus: Focus,
+    targets: ListState,
+    packages: TableState,
+    target_filter: String,
+    package_filter: String,
+    /// Whether the focused pane's filter is being typed.
+    editing: bool,
+    /// Shows only the packages missing on the latest date.
+    only_missing: bool,
+    /// How many of the latest dates are scrolled past.
+    date_offset: usize,
+}
+
+impl App {
+    fn new(channels: Vec<Channel>) -> Self {
+        let mut app = App {
+            channels,
+            channel: 0,
+            focus: Focus::Targets,
+            targets: ListState::default(),
+            packages: TableState::default(),
+            target_filter: String::new(),
+            package_filter: String::new(),
+            editing: false,
+            only_missing: false,
+            date_offset: 0,
+        };
+        app.clamp();
+        app
+    }
+
+    fn channel(&self) -> &Channel {
+        &self.channels[self.channel]
+    }
+
+    fn visible_targets(&self) -> Vec<&str> {
+        self.channel()
+            .targets
+            .iter()
+            .map(String::as_str)
+            .filter(|target| target.contains(self.target_filter.as_str()))
+            .collect()
+    }
+
+    fn selected_target(&self) -> Option<&str> {
+        let targets = self.visible_targets();
+        self.targets
+            .selected()
+            .and_then(|idx| targets.get(idx).copied())
+    }
+
+    fn visible_packages(&self) -> Vec<&str> {
+        let channel = self.channel();
+        let target = match self.selected_target() {
+            Some(target) => target,
+            None => return Vec::new(),
+        };
+        let latest = channel.dates.first().copied();
+        channel
+            .packages(target)
+            .into_iter()
+            .filter(|package| package.contains(self.package_filter.as_str()))
+            .filter(|package| {
+                !self.only_missing
+                    || latest.is_none_or(|date| {
+                        channel
+                            .status(target, package, date)
+                            .is_none_or(|status| !status.available)
+                    })
+            })
+            .collect()
+    }
+
+    fn filter_mut(&mut self) -> &mut String {
+        match self.focus {
+            Focus::Targets => &mut self.target_filter,
+            Focus::Packages => &mut self.package_filter,
+        }
+    }
+
+    /// Keeps the selections within the filtered lists.
+    fn clamp(&mut self) {
+        let targets = self.visible_targets().len();
+        self.targets.select(Some(
+            self.targets
+                .selected()
+                .unwrap_or(0)
+                .min(targets.saturating_sub(1)),
+        ));
+        let packages = self.visible_packages().len();
+        self.packages.select(Some(
+            self.packages
+                .selected()
+                .unwrap_or(0)
+                .min(packages.saturating_sub(1)),
+        ));
+        self.date_offset = self
+            .date_offset
+            .min(self.channel().dates.len().saturating_sub(1));
+    }
+
+    fn move_selection(&mut self, down: bool) {
+        match self.focus {
+            Focus::Targets => {
+                if down {
+                    self.targets.select_next()
+                } else {
+                    self.targets.select_previous()
+                }
+            }
+            Focus::Packages => {
+                if down {
+                    self.packages.select_next()
+                } else {
+                    self.packages.select_previous()
+                }
+            }
+        }
+    }
+
+    /// Handles a key, returns `false` to quit.
+    fn handle(&mut self, code: KeyCode) -> bool {
+        if self.editing {
+            match code {
+                KeyCode::Enter | KeyCode::Esc => self.editing = false,
+                KeyCode::Backspace => {
+                    self.filter_mut().pop();
+                }
+                KeyCode::Char(c) => self.filter_mut().push(c),
+                _ => {}
+            }
+        } else {
+            match code {
+                KeyCode::Char('q') | KeyCode::Esc => return false,
+                KeyCode::Char('/') => self.editing = true,
+                KeyCode::Tab => {
+                    self.focus = match self.focus {
+                        Focus::Targets => Focus::Packages,
+                        Focus::Packages => Focus::Targets,
+                    }
+                }
+                KeyCode::Char('m') => self.only_missing = !self.only_missing,
+                KeyCode::Char('c') => {
+                    self.channel = (self.channel + 1) % self.channels.len();
+                    self.date_offset = 0;
+                }
+                KeyCode::Down | KeyCode::Char('j') => self.move_selection(true),
+                KeyCode::Up | KeyCode::Char('k') => self.move_selection(false),
+                KeyCode::Right | KeyCode::Char('l') => self.date_offset += 1,
+                KeyCode::Left | KeyCode::Char('h') => {
+                    self.date_offset = self.date_offset.saturating_sub(1)
+                }
+                _ => {}
+            }
+        }
+        self.clamp();
+        true
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status_bar] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(2)]).areas(frame.area());
+        let [targets_area, packages_area] =
+            Layout::horizontal([Constraint::Length(36), Constraint::Min(0)]).areas(main);
+        let focus = self.focus;
+        let focused = move |pane| {
+            if focus == pane {
+                Style::default().fg(Color::Yellow)
+            } else {
+                Style::default()
+            }
+        };
+        let highlight = Style::default().add_modifier(Modifier::REVERSED);
+
+        let title = format!(" {} targets /{} ", self.channel().name, self.target_filter);
+        let targets: Vec<_> = self
+            .visible_targets()
+            .into_iter()
+            .map(|target| ListItem::new(target.to_string()))
+            .collect();
+        let targets = List::new(targets)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(focused(Focus::Targets))
+                    .title(title),
+            )
+            .highlight_style(highlight);
+        frame.render_stateful_widget(targets, targets_area, &mut self.targets);
+
+        let channel = self.channel();
+        let target = self.selected_target().unwrap_or_default();
+        // A column per date that fits, after the package names.
+        let name_width = 28;
+        let fitting = usize::from(packages_area.width.saturating_sub(name_width + 2)) / 6;
+        let dates: Vec<_> = channel
+            .dates
+            .iter()
+            .skip(self.date_offset)
+            .take(fitting)
+            .copied()
+            .collect();
+        let header = std::iter::once(Cell::from("package"))
+            .chain(
+                dates
+                    .iter()
+                    .map(|date| Cell::from(date.format("%m-%d").to_string())),
+            )
+            .collect::<Row>()
+            .style(Style::default().add_modifier(Modifier::BOLD));
+        let rows: Vec<_> = self
+            .visible_packages()
+            .into_iter()
+            .map(|package| {
+                let cells = dates
+                    .iter()
+                    .map(|&date| match channel.status(target, package, date) {
+                        Some(status) if status.available => {
+                            Cell::from("  ✓").style(Style::default().fg(Color::Green))
+                        }
+                        Some(_) => Cell::from("  ✗").style(Style::default().fg(Color::Red)),
+                        None => Cell::from("  ·").style(Style::default().fg(Color::DarkGray)),
+                    });
+                std::iter::once(Cell::from(package.to_string()))
+                    .chain(cells)
+                    .collect::<Row>()
+            })
+            .collect();
+        let widths = std::iter::once(Constraint::Length(name_width))
+            .chain(dates.iter().map(|_| Constraint::Length(5)));
+        let title = format!(
+            " {}{} /{} ",
+            target,
+            if self.only_missing { " (missing)" } else { "" },
+            self.package_filter
+        );
+        let selected = self
+            .packages
+            .selected()
+            .and_then(|idx| self.visible_packages().get(idx).copied());
+        let details = match (selected, dates.first()) {
+            (Some(package), Some(&date)) => match channel.status(target, package, date) {
+                Some(status) => format!(
+                    "{} on {}: {} {}",
+                    package,
+                    date,
+                    if status.available {
+                        "available"
+                    } else {
+                        "missing"
+                    },
+                    status.version.as_deref().unwrap_or_default()
+                ),
+                None => format!("{} on {}: unknown", package, date),
+            },
+            _ => String::new(),
+        };
+        let packages = Table::new(rows, widths)
+            .header(header)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(focused(Focus::Packages))
+                    .title(title),
+            )
+            .row_highlight_style(highlight);
+        frame.render_stateful_widget(packages, packages_area, &mut self.packages);
+
+        let help = if self.editing {
+            "Type to filter, Enter to finish"
+        } else {
+            "q quit  Tab switch pane  / filter  m only missing  c channel  ←/→ dates  ↑/↓ select"
+        };
+        let status = Paragraph::new(vec![Line::from(details), Line::from(help)]);
+        frame.render_widget(status, status_bar);
+    }
+}
+
+fn run_app(terminal: &mut DefaultTerminal, mut app: App) -> anyhow::Result<()> {
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && !app.handle(key.code) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Browses the statuses the `query` command would print, a table of packages
+/// and dates per target.
+pub fn run(opts: &TuiOpt) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        io::stdout().is_terminal(),
+        "The viewer needs an interactive terminal"
+    );
+    let config = opts.config.load()?;
+    let rows = query::load_rows(&config)?;
+    let channels = channels(rows, &config.channels);
+    anyhow::ensure!(!channels.is_empty(), "No channels are configured");
+    let app = App::new(channels);
+    ratatui::run(|terminal| run_app(terminal, app))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(channel: &str, day: u32, target: &str, package: &str, available: bool) -> query::Row {
+        query::Row {
+            channel: channel.into(),
+            date: NaiveDate::from_ymd_opt(2024, 1, day).unwrap(),
+            target: target.into(),
+            package: package.into(),
+            available,
+            version: None,
+        }
+    }
+
+    fn app() -> App {
+        let rows = vec![
+            row("nightly", 1, "x86_64-unknown-linux-gnu", "rls", true),
+            row("nightly", 2, "x86_64-unknown-linux-gnu", "rls", false),
+            row("nightly", 2, "x86_64-unknown-linux-gnu", "cargo", true),
+            row("nightly", 2, "aarch64-apple-darwin", "cargo", true),
+            row("beta", 1, "x86_64-pc-windows-msvc", "cargo", true),
+            row("stable", 1, "x86_64-pc-windows-msvc", "cargo", true),
+        ];
+        App::new(channels(rows, &["nightly".into(), "beta".into()]))
+    }
+
+    fn keys(app: &mut App, keys: &str) {
+        for c in keys.chars() {
+            app.handle(KeyCode::Char(c));
+        }
+    }
+
+    #[test]
+    fn groups_rows_by_channel() {
+        let app = app();
+        let names: Vec<_> = app.channels.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["nightly", "beta"]);
+        let nightly = &app.channels[0];
+        assert_eq!(
+            nightly.dates,
+            [
+                NaiveDate::from_ymd_opt(2024, 1, 2).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            ]
+        );
+        assert_eq!(
+            nightly.targets,
+            ["aarch64-apple-darwin", "x86_64-unknown-linux-gnu"]
+        );
+        assert_eq!(
+            nightly.packages("x86_64-unknown-linux-gnu"),
+            ["cargo", "rls"]
+        );
+    }
+
+    #[test]
+    fn filters_and_clamps_the_selection() {
+        let mut app = app();
+        assert_eq!(app.selected_target(), Some("aarch64-apple-darwin"));
+        keys(&mut app, "jjj");
+        assert_eq!(app.selected_target(), Some("x86_64-unknown-linux-gnu"));
+
+        keys(&mut app, "/aarch");
+        assert_eq!(app.visible_targets(), ["aarch64-apple-darwin"]);
+        assert_eq!(app.selected_target(), Some("aarch64-apple-darwin"));
+        keys(&mut app, "z");
+        assert!(app.visible_targets().is_empty());
+        assert_eq!(app.selected_target(), None);
+        assert!(app.visible_packages().is_empty());
+        for _ in 0.."aarchz".len() {
+            app.handle(KeyCode::Backspace);
+        }
+        app.handle(KeyCode::Enter);
+        assert_eq!(app.visible_targets().len(), 2);
+    }
+
+    #[test]
+    fn shows_only_the_missing_packages() {
+        let mut app = app();
+        keys(&mut app, "j");
+        assert_eq!(app.visible_packages(), ["cargo", "rls"]);
+        app.handle(KeyCode::Tab);
+        keys(&mut app, "jj");
+        assert_eq!(app.packages.selected(), Some(1));
+        keys(&mut app, "m");
+        assert_eq!(app.visible_packages(), ["rls"]);
+        assert_eq!(app.packages.selected(), Some(0));
+    }
+
+    #[test]
+    fn switches_channels() {
+        let mut app = app();
+        keys(&mut app, "llll");
+        assert_eq!(app.date_offset, 1);
+        keys(&mut app, "c");
+        assert_eq!(app.channel().name, "beta");
+        assert_eq!(app.date_offset, 0);
+        assert_eq!(app.selected_target(), Some("x86_64-pc-windows-msvc"));
+        keys(&mut app, "c");
+        assert_eq!(app.channel().name, "nightly");
+    }
+}
@@ -0,0 +1,21 @@
+//! Warnings of a run, collected for its report.
+
+use std::sync::Mutex;
+
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Records a warning, meant to be called by the logger for every warning or
+/// error it gets.
+pub fn record(message: String) {
+    if let Ok(mut warnings) = WARNINGS.lock() {
+        warnings.push(message);
+    }
+}
+
+/// Takes all the warnings recorded so far.
+pub(crate) fn take() -> Vec<String> {
+    WARNINGS
+        .lock()
+        .map(|mut warnings| std::mem::take(&mut *warnings))
+        .unwrap_or_default()
+}
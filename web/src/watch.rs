@@ -0,0 +1,132 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+
+use anyhow::Context;
+use notify::{Event, RecursiveMode, Watcher};
+
+use rustup_status::{
+    acquire_lock, load_channels, opts::Config, output::FsOutput, render_html, Caches,
+};
+
+/// For how long to wait for more changes before re-rendering, since editors
+/// tend to write a file in several steps.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Template files and directories to watch.
+struct Watched {
+    files: Vec<PathBuf>,
+    dirs: Vec<PathBuf>,
+}
+
+impl Watched {
+    fn new(config: &Config) -> anyhow::Result<Self> {
+        let html = &config.html;
+        let files = std::iter::once(&html.template_path)
+            .chain(html.package_pages.iter().map(|pages| &pages.template_path))
+            .chain(
+                html.search
+                    .iter()
+                    .flat_map(|search| &search.fallback_page)
+                    .map(|page| &page.template_path),
+            )
+            .chain(html.changes.iter().map(|changes| &changes.template_path))
+            .chain(config.trends.iter().map(|trends| &trends.template_path))
+            .chain(&html.index_template)
+            .chain(&html.social_card_template)
+            .chain(html.script_helpers.values())
+            .map(|path| absolute(path))
+            .collect::<anyhow::Result<_>>()?;
+        let dirs = html
+            .template_dirs
+            .iter()
+            .chain(html.assets.iter().flat_map(|assets| &assets.dirs))
+            .chain(html.i18n.iter().map(|i18n| &i18n.catalog_dir))
+            .map(|dir| absolute(dir))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Watched { files, dirs })
+    }
+
+    /// Starts watching the directories of the templates. Editors often replace
+    /// a file rather than write into it, which a watch of the file itself
+    /// wouldn't survive.
+    fn watch(&self, watcher: &mut impl Watcher) -> anyhow::Result<()> {
+        let mut parents: Vec<_> = self.files.iter().filter_map(|f| f.parent()).collect();
+        parents.sort_unstable();
+        parents.dedup();
+        for dir in parents {
+            watcher
+                .watch(dir, RecursiveMode::NonRecursive)
+                .with_context(|| format!("Can't watch {}", dir.display()))?;
+        }
+        for dir in &self.dirs {
+            watcher
+                .watch(dir, RecursiveMode::Recursive)
+                .with_context(|| format!("Can't watch {}", dir.display()))?;
+        }
+        Ok(())
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        self.files.iter().any(|file| file == path)
+            || self.dirs.iter().any(|dir| path.starts_with(dir))
+    }
+
+    /// Checks if an event changes any of the templates. Reading them, e.g. by
+    /// the rendering itself, doesn't.
+    fn is_changed_by(&self, event: notify::Result<Event>) -> bool {
+        event.is_ok_and(|event| {
+            !event.kind.is_access() && event.paths.iter().any(|path| self.contains(path))
+        })
+    }
+}
+
+/// Makes a path absolute, since the watcher reports absolute paths.
+fn absolute(path: &Path) -> anyhow::Result<PathBuf> {
+    let path = std::env::current_dir()?.join(path);
+    // Only the directory has to exist, the file might be in the middle of
+    // being replaced.
+    match (path.parent(), path.file_name()) {
+        (Some(dir), Some(name)) => Ok(dir
+            .canonicalize()
+            .with_context(|| format!("Can't find {}", dir.display()))?
+            .join(name)),
+        _ => Ok(path),
+    }
+}
+
+/// Renders the HTML pages and renders them again whenever a template changes.
+/// The manifests are loaded only once.
+pub fn run(config: Config) -> anyhow::Result<()> {
+    let watched = Watched::new(&config)?;
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).context("Can't watch the templates")?;
+    watched.watch(&mut watcher)?;
+
+    let channels = {
+        let _lock = acquire_lock(&config)?;
+        load_channels(&config, &Caches::new(&config)?)?
+    };
+    let render = || match acquire_lock(&config)
+        .and_then(|_lock| render_html(&config, &channels, &mut FsOutput::default()))
+    {
+        Ok(()) => log::info!("Rendered the pages"),
+        // Keep watching, the template is likely being fixed.
+        Err(e) => log::error!("{:#}", e),
+    };
+    render();
+    log::info!("Watching the templates for changes");
+    loop {
+        let event = rx.recv().context("The watcher has stopped")?;
+        let mut changed = watched.is_changed_by(event);
+        // Collect the rest of the burst.
+        while let Ok(event) = rx.recv_timeout(DEBOUNCE) {
+            changed |= watched.is_changed_by(event);
+        }
+        if changed {
+            render();
+        }
+    }
+}
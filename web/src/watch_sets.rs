@@ -0,0 +1,174 @@
+use chrono::NaiveDate;
+use rustup_available_packages::AvailabilityData;
+use serde::Serialize;
+
+use crate::opts::WatchSet;
+
+/// A watch set as shown on the landing page, e.g. "CI toolchains: 3/4 green".
+#[derive(Serialize)]
+pub struct WatchSetSummary<'a> {
+    name: &'a str,
+    /// How many of the targets have all the packages on the latest date with
+    /// a manifest.
+    green: usize,
+    total: usize,
+    all_green: bool,
+    targets: Vec<WatchedTarget<'a>>,
+    /// Packages that have been available within the rendered dates but are
+    /// missing on the latest one.
+    regressions: Vec<Regression<'a>>,
+}
+
+#[derive(Serialize)]
+struct WatchedTarget<'a> {
+    name: &'a str,
+    green: bool,
+    missing: Vec<&'a str>,
+}
+
+#[derive(Serialize)]
+struct Regression<'a> {
+    target: &'a str,
+    package: &'a str,
+    last_available: Option<NaiveDate>,
+}
+
+impl<'a> WatchSetSummary<'a> {
+    pub fn new(data: &AvailabilityData, dates: &[NaiveDate], set: &'a WatchSet) -> Self {
+        let latest = dates
+            .iter()
+            .position(|&date| !data.is_manifest_missing(date));
+        let available_targets = data.get_available_targets();
+        let mut targets = Vec::new();
+        let mut regressions = Vec::new();
+        for target in &set.targets {
+            if !available_targets.contains(target.as_str()) {
+                log::warn!("Watched target {} isn't in the manifests", target);
+            }
+            let mut missing = Vec::new();
+            for package in &set.packages {
+                // Target-independent packages are only listed under "*".
+                let row = data
+                    .get_availability_row(target, package, dates)
+                    .or_else(|| data.get_availability_row("*", package, dates));
+                let available =
+                    |index| row.as_ref().is_some_and(|row| row.availability_list[index]);
+                if latest.is_some_and(available) {
+                    continue;
+                }
+                missing.push(package.as_str());
+                if let Some(latest) = latest {
+                    if (latest + 1..dates.len()).any(available) {
+                        regressions.push(Regression {
+                            target,
+                            package,
+                            last_available: row.and_then(|row| row.last_available),
+                        });
+                    }
+                }
+            }
+            targets.push(WatchedTarget {
+                name: target,
+                green: missing.is_empty(),
+                missing,
+            });
+        }
+        let green = targets.iter().filter(|target| target.green).count();
+        WatchSetSummary {
+            name: &set.name,
+            green,
+            total: targets.len(),
+            all_green: green == targets.len(),
+            targets,
+            regressions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+    use rustup_available_packages::manifest::Manifest;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, day).unwrap()
+    }
+
+    fn manifest(day: u32, available: &[(&str, &str)]) -> Manifest {
+        let packages: Vec<_> = available
+            .iter()
+            .map(|&(package, target)| (package, target, true))
+            .collect();
+        test_utils::manifest(date(day), &packages)
+    }
+
+    #[test]
+    fn summarizes_the_latest_manifest() {
+        let mut data = AvailabilityData::default();
+        data.add_manifests(vec![
+            manifest(1, &[("rustc", "t1"), ("rustc", "t2"), ("rust-src", "*")]),
+            manifest(
+                2,
+                &[
+                    ("rustc", "t1"),
+                    ("cargo", "t1"),
+                    ("cargo", "t2"),
+                    ("rust-src", "*"),
+                ],
+            ),
+            manifest(3, &[("rustc", "t1"), ("cargo", "t1"), ("rust-src", "*")]),
+        ]);
+        // The latest date has no manifest, so the one before it counts.
+        data.add_missing_manifest(date(4));
+        let dates = [date(4), date(3), date(2), date(1)];
+        let set = WatchSet {
+            name: "CI".into(),
+            targets: vec!["t1".into(), "t2".into(), "t3".into()],
+            packages: vec!["rustc".into(), "cargo".into(), "rust-src".into()],
+        };
+        let summary = WatchSetSummary::new(&data, &dates, &set);
+
+        assert_eq!((summary.green, summary.total), (1, 3));
+        assert!(!summary.all_green);
+        let missing: Vec<_> = summary
+            .targets
+            .iter()
+            .map(|target| (target.name, target.green, target.missing.clone()))
+            .collect();
+        assert_eq!(
+            missing,
+            [
+                ("t1", true, vec![]),
+                ("t2", false, vec!["rustc", "cargo"]),
+                ("t3", false, vec!["rustc", "cargo"]),
+            ]
+        );
+        let regressions: Vec<_> = summary
+            .regressions
+            .iter()
+            .map(|r| (r.target, r.package, r.last_available))
+            .collect();
+        assert_eq!(
+            regressions,
+            [
+                ("t2", "rustc", Some(date(1))),
+                ("t2", "cargo", Some(date(2))),
+            ]
+        );
+    }
+
+    #[test]
+    fn all_green() {
+        let mut data = AvailabilityData::default();
+        data.add_manifests(vec![manifest(1, &[("rustc", "t1")])]);
+        let set = WatchSet {
+            name: "CI".into(),
+            targets: vec!["t1".into()],
+            packages: vec!["rustc".into()],
+        };
+        let summary = WatchSetSummary::new(&data, &[date(1)], &set);
+        assert!(summary.all_green);
+        assert!(summary.regressions.is_empty());
+    }
+}